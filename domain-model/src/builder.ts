@@ -1,6 +1,6 @@
 // DSL Core: Type-safe expression builder for physics simulation
 
-import type { Expression } from './types.js';
+import type { Expression, ForceLaw, StencilColumns, StencilRadius } from './types.js';
 
 // Type-safe operation builders
 export const ops = {
@@ -8,6 +8,8 @@ export const ops = {
   param: (id: string, group: string): Expression => ({ op: 'ref_param', id, group }),
   aux: (id: string): Expression => ({ op: 'ref_aux', id }),
   const: (value: number): Expression => ({ op: 'const', value }),
+  // Timestep passed to update_dynamics at runtime
+  dt: (): Expression => ({ op: 'ref_dt' }),
 
   add: (left: Expression, right: Expression): Expression => ({ op: 'add', left, right }),
   sub: (left: Expression, right: Expression): Expression => ({ op: 'sub', left, right }),
@@ -36,11 +38,22 @@ export const ops = {
     x,
     y,
   }),
+  // `within` is a [center, neighbor] 0/1 mask of the pairs inside each center's
+  // `radius`; only available when `radius` is given.
   stencil: (
     value: Expression,
     range: number,
-    kernel: (center: Expression, neighbor: Expression) => Expression
-  ): Expression => ({ op: 'stencil', value, range, kernel }),
+    kernel: (center: Expression, neighbor: Expression, within: Expression) => Expression,
+    radius?: StencilRadius
+  ): Expression => ({ op: 'stencil', value, range, kernel, ...(radius ? { radius } : {}) }),
+  // Sums `law` between every pair of slots within `range` cells, giving each
+  // slot's [fx, fy]: [H, W, Cap, 2].
+  stencil_force: (
+    value: Expression,
+    range: number,
+    law: ForceLaw,
+    columns: StencilColumns
+  ): Expression => ({ op: 'stencil_force', value, range, law, columns }),
   grid_gather: (value: Expression, x: Expression, y: Expression): Expression => ({
     op: 'grid_gather',
     value,
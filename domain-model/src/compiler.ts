@@ -12,6 +12,7 @@ import type {
   ParameterGroups,
   BoundaryCondition,
   GridConfig,
+  LabelMeta,
 } from './types.js';
 
 const __filename = fileURLToPath(import.meta.url);
@@ -85,6 +86,16 @@ function compileExpression(expr: Expression, ctx: CompilerContext): string {
       ctx.varMap.set(exprKey, resultVar);
       return resultVar;
 
+    case 'ref_dt':
+      resultVar = getTempVar(ctx);
+      ctx.operations.push({
+        target: resultVar,
+        op: 'ref_dt',
+        args: [],
+      });
+      ctx.varMap.set(exprKey, resultVar);
+      return resultVar;
+
     case 'add':
     case 'sub':
     case 'mul':
@@ -205,13 +216,19 @@ function compileExpression(expr: Expression, ctx: CompilerContext): string {
       // These will be provided by the runtime/generator as available variables in the kernel scope
       const centerExpr: Expression = { op: 'ref_aux', id: 'center' };
       const neighborExpr: Expression = { op: 'ref_aux', id: 'neighbor' };
+      const withinExpr: Expression = { op: 'ref_aux', id: 'within' };
 
       // Generate kernel expression tree
-      const kernelResultExpr = expr.kernel(centerExpr, neighborExpr);
+      const kernelResultExpr = expr.kernel(centerExpr, neighborExpr, withinExpr);
 
       // Compile kernel to operations
       const kernelResultVar = compileExpression(kernelResultExpr, kernelCtx);
 
+      // The generator only defines `within` when there is a radius to mask by.
+      if (!expr.radius && kernelCtx.varMap.has(JSON.stringify(withinExpr))) {
+        throw new Error('Stencil kernel uses `within` but the stencil has no radius.');
+      }
+
       // We need to ensure the result is explicitly marked if it's just a reference
       // But for now, we assume the generator takes the last operation's target or we can add a specific return op?
       // Let's add a 'kernel_return' op to be explicit, or just rely on convention.
@@ -237,12 +254,28 @@ function compileExpression(expr: Expression, ctx: CompilerContext): string {
         op: 'stencil',
         args: [val],
         stencil_range: expr.range,
+        ...(expr.radius ? { stencil_radius: expr.radius } : {}),
         kernel_operations: kernelCtx.operations,
       });
       ctx.varMap.set(exprKey, resultVar);
       return resultVar;
     }
 
+    case 'stencil_force': {
+      const val = compileExpression(expr.value, ctx);
+      resultVar = getTempVar(ctx);
+      ctx.operations.push({
+        target: resultVar,
+        op: 'stencil',
+        args: [val],
+        stencil_range: expr.range,
+        force_law: expr.law,
+        stencil_columns: expr.columns,
+      });
+      ctx.varMap.set(exprKey, resultVar);
+      return resultVar;
+    }
+
     case 'grid_gather': {
       const val = compileExpression(expr.value, ctx);
       const x = compileExpression(expr.x, ctx);
@@ -300,7 +333,9 @@ function compileRules(
   parameterGroups: ParameterGroups,
   boundaryConditions: BoundaryCondition[],
   initialization: InitializationIR,
-  gridConfig?: GridConfig
+  gridConfig?: GridConfig,
+  labelMeta?: Record<string, LabelMeta>,
+  fitness?: string
 ): OutputIR {
   const ctx: CompilerContext = {
     tempVarCounter: 0,
@@ -317,7 +352,12 @@ function compileRules(
       stateVarSet.add(expr.id);
       return;
     }
-    if (expr.op === 'ref_param' || expr.op === 'ref_aux' || expr.op === 'const') {
+    if (
+      expr.op === 'ref_param' ||
+      expr.op === 'ref_aux' ||
+      expr.op === 'const' ||
+      expr.op === 'ref_dt'
+    ) {
       return;
     }
     if ('left' in expr && 'right' in expr) {
@@ -378,6 +418,7 @@ function compileRules(
       case 'ref_aux':
       case 'ref_state':
       case 'const':
+      case 'ref_dt':
         break;
       case 'add':
       case 'sub':
@@ -398,6 +439,7 @@ function compileRules(
       case 'relu':
       case 'neg':
       case 'stencil':
+      case 'stencil_force':
       case 'transpose':
       case 'sum':
       case 'slice':
@@ -406,7 +448,8 @@ function compileRules(
           // Collect params from kernel
           const centerExpr: Expression = { op: 'ref_aux', id: 'center' };
           const neighborExpr: Expression = { op: 'ref_aux', id: 'neighbor' };
-          const kernelExpr = expr.kernel(centerExpr, neighborExpr);
+          const withinExpr: Expression = { op: 'ref_aux', id: 'within' };
+          const kernelExpr = expr.kernel(centerExpr, neighborExpr, withinExpr);
           collectParams(kernelExpr);
         }
         break;
@@ -463,8 +506,20 @@ function compileRules(
     }
   }
 
+  for (const name of Object.keys(labelMeta ?? {})) {
+    if (!stateVars.includes(name)) {
+      throw new Error(`LABEL_META references unknown state var: ${name}`);
+    }
+  }
+
+  if (fitness !== undefined && !stateVars.includes(fitness)) {
+    throw new Error(`FITNESS references unknown state var: ${fitness}`);
+  }
+
   return {
     state_vars: stateVars,
+    ...(labelMeta ? { label_meta: labelMeta } : {}),
+    ...(fitness !== undefined ? { fitness } : {}),
     constants: simConstants,
     groups,
     boundary_conditions: boundaryConditions,
@@ -497,6 +552,8 @@ async function main() {
       VISUAL_MAPPING,
       SIM_CONSTANTS,
       GRID_CONFIG,
+      LABEL_META,
+      FITNESS,
     } = mod;
 
     const ir = compileRules(
@@ -506,7 +563,9 @@ async function main() {
       PARAMETER_GROUPS,
       BOUNDARY_CONDITIONS,
       INITIALIZATION,
-      GRID_CONFIG
+      GRID_CONFIG,
+      LABEL_META,
+      FITNESS
     );
 
     const outputDir = join(__dirname, '../_gen', name);
@@ -61,7 +61,8 @@ export const BOUNDARY_CONDITIONS: BoundaryCondition[] = [
 ];
 
 const CONSTANTS = {
-  dt: ops.const(0.1),
+  // Timestep passed to update_dynamics
+  dt: ops.dt(),
   zero: ops.const(0.0),
   one: ops.const(1.0),
   threshold: ops.const(0.0),
@@ -42,6 +42,9 @@ export const STATE_VAR_ORDER: (keyof typeof STATE_VARS)[] = [
   'size',
 ];
 
+// 適応度: 世代の終わりのサイズ (捕食で大きくなった個体ほど次世代の親に選ばれやすい)
+export const FITNESS = 'size';
+
 export const INITIALIZATION: InitializationIR = {
   state: {
     pos_x: { kind: 'uniform', low: -WORLD_SIZE / 2, high: WORLD_SIZE / 2 },
@@ -61,6 +64,7 @@ export const BOUNDARY_CONDITIONS: BoundaryCondition[] = [
 const CONSTANTS = {
   zero: ops.const(0.0),
   one: ops.const(1.0),
+  dt: ops.dt(),
 };
 
 const INTERACTION_RANGE = 50.0; // 相互作用する距離
@@ -71,11 +75,11 @@ export const DYNAMICS_RULES: DynamicsRule[] = [
   // -------------------------------------------------
   {
     target_state: 'pos_x',
-    expr: ops.add(STATE_VARS.pos_x, STATE_VARS.vel_x),
+    expr: ops.add(STATE_VARS.pos_x, ops.mul(STATE_VARS.vel_x, CONSTANTS.dt)),
   },
   {
     target_state: 'pos_y',
-    expr: ops.add(STATE_VARS.pos_y, STATE_VARS.vel_y),
+    expr: ops.add(STATE_VARS.pos_y, ops.mul(STATE_VARS.vel_y, CONSTANTS.dt)),
   },
   {
     target_state: 'vel_x',
@@ -0,0 +1,186 @@
+// Example: Charged particles in a walled box, on a reflective grid
+// Like charges push each other apart; the walls bounce particles back and hold
+// mirror images of those near them, so particles are pushed off the walls too.
+// Each particle only feels those within its own reach.
+
+import { ops } from '../builder.js';
+import type {
+  BoundaryCondition,
+  DynamicsRule,
+  Expression,
+  GroupConfig,
+  InitializationIR,
+  ParameterGroups,
+  VisualMapping,
+  GridConfig,
+} from '../types.js';
+
+export const SIM_CONSTANTS = {
+  n_agents: 100,
+  gene_len: 10,
+  hidden_len: 10,
+};
+
+const WORLD_SIZE = 1000.0;
+
+// 1000 / 50 = 20 cells a side
+export const GRID_CONFIG: GridConfig = {
+  width: 20,
+  height: 20,
+  capacity: 8,
+  cell_size: [50.0, 50.0],
+  origin: [-WORLD_SIZE / 2, -WORLD_SIZE / 2],
+  boundary: 'reflective',
+};
+
+export const PARAMETER_GROUPS: ParameterGroups = {
+  ATTR: { name: 'attributes', activation: 'softmax' } satisfies GroupConfig,
+  PHYS: { name: 'physics', activation: 'tanh' } satisfies GroupConfig,
+};
+
+const GENETIC_PARAMS = {
+  // Keep at least one param per group so the phenotype engine stays well-formed.
+  dummy_attr: ops.param('dummy_attr', PARAMETER_GROUPS.ATTR.name),
+  dummy_phys: ops.param('dummy_phys', PARAMETER_GROUPS.PHYS.name),
+} as const;
+
+const STATE_VARS = {
+  pos_x: ops.state('pos_x'),
+  pos_y: ops.state('pos_y'),
+  vel_x: ops.state('vel_x'),
+  vel_y: ops.state('vel_y'),
+  charge: ops.state('charge'),
+  // Interaction radius; at most a cell (50) so the range-1 stencil covers it
+  reach: ops.state('reach'),
+} as const;
+
+export const STATE_VAR_ORDER: (keyof typeof STATE_VARS)[] = [
+  'pos_x',
+  'pos_y',
+  'vel_x',
+  'vel_y',
+  'charge',
+  'reach',
+];
+
+const CONSTANTS = {
+  dt: ops.dt(),
+  zero: ops.const(0.0),
+} as const;
+
+// Repulsion between each pair: k * q_j * (c - n) / (|d|^2 + softening).
+const REPULSION_PARAMS = {
+  softening: 1.0,
+  k: 100.0,
+} as const;
+
+export const INITIALIZATION: InitializationIR = {
+  state: {
+    pos_x: { kind: 'uniform', low: -450.0, high: 450.0 },
+    pos_y: { kind: 'uniform', low: -450.0, high: 450.0 },
+    vel_x: { kind: 'normal', mean: 0.0, std: 20.0 },
+    vel_y: { kind: 'normal', mean: 0.0, std: 20.0 },
+    charge: { kind: 'uniform', low: 0.5, high: 1.5 },
+    reach: { kind: 'uniform', low: 20.0, high: 50.0 },
+  },
+  genes: { kind: 'normal', mean: 0.0, std: 1.0 },
+};
+
+export const BOUNDARY_CONDITIONS: BoundaryCondition[] = [
+  {
+    target_state: 'pos_x',
+    kind: 'reflective',
+    range: [-WORLD_SIZE / 2, WORLD_SIZE / 2],
+    velocity: 'vel_x',
+  },
+  {
+    target_state: 'pos_y',
+    kind: 'reflective',
+    range: [-WORLD_SIZE / 2, WORLD_SIZE / 2],
+    velocity: 'vel_y',
+  },
+];
+
+// Per-cell repulsion on every slot, in the vel columns (2 and 3): [H, W, Cap, 6]
+const repulsion = (): Expression => {
+  const state_vec = ops.cat(
+    [
+      STATE_VARS.pos_x,
+      STATE_VARS.pos_y,
+      STATE_VARS.vel_x,
+      STATE_VARS.vel_y,
+      STATE_VARS.charge,
+      STATE_VARS.reach,
+    ],
+    1
+  );
+  const grid_state = ops.grid_scatter(state_vec, STATE_VARS.pos_x, STATE_VARS.pos_y);
+  const radius = { x: 0, y: 1, radius: 5 };
+  return ops.stencil(grid_state, 1, (center, neighbor, within) => {
+    const c_px = ops.slice(center, 1, 0, 1);
+    const c_py = ops.slice(center, 1, 1, 1);
+
+    const n_px_T = ops.transpose(ops.slice(neighbor, 1, 0, 1), 0, 1);
+    const n_py_T = ops.transpose(ops.slice(neighbor, 1, 1, 1), 0, 1);
+    const n_q_T = ops.transpose(ops.slice(neighbor, 1, 4, 1), 0, 1);
+
+    // Away from the neighbor; empty slots carry no charge.
+    const dx = ops.sub(c_px, n_px_T);
+    const dy = ops.sub(c_py, n_py_T);
+
+    const d2 = ops.add(
+      ops.add(ops.mul(dx, dx), ops.mul(dy, dy)),
+      ops.const(REPULSION_PARAMS.softening)
+    );
+    // Neighbors past the center's reach are masked out.
+    const scale = ops.mul(
+      ops.div(ops.mul(ops.const(REPULSION_PARAMS.k), n_q_T), d2),
+      within
+    );
+
+    const fx_sum = ops.sum(ops.mul(dx, scale), 1, true);
+    const fy_sum = ops.sum(ops.mul(dy, scale), 1, true);
+    const zeros = ops.mul(c_px, ops.const(0.0));
+
+    return ops.cat([zeros, zeros, fx_sum, fy_sum, zeros, zeros], 1);
+  }, radius);
+};
+
+// Keep params alive to ensure PhenotypeEngine produces valid tensors
+const keep_params = ops.add(
+  ops.mul(GENETIC_PARAMS.dummy_phys, CONSTANTS.zero),
+  ops.mul(GENETIC_PARAMS.dummy_attr, CONSTANTS.zero)
+);
+
+// vel += q * f * dt, with f gathered from column `column` of the repulsion grid
+const accelerate = (vel: Expression, column: number): Expression => {
+  const force_vec = ops.grid_gather(repulsion(), STATE_VARS.pos_x, STATE_VARS.pos_y);
+  const f = ops.mul(STATE_VARS.charge, ops.slice(force_vec, 1, column, 1));
+  return ops.add(vel, ops.mul(ops.add(f, keep_params), CONSTANTS.dt));
+};
+
+export const DYNAMICS_RULES: DynamicsRule[] = [
+  // pos += vel * dt, bounced back into the box by the boundary conditions
+  {
+    target_state: 'pos_x',
+    expr: ops.add(STATE_VARS.pos_x, ops.mul(STATE_VARS.vel_x, CONSTANTS.dt)),
+  },
+  {
+    target_state: 'pos_y',
+    expr: ops.add(STATE_VARS.pos_y, ops.mul(STATE_VARS.vel_y, CONSTANTS.dt)),
+  },
+  { target_state: 'vel_x', expr: accelerate(STATE_VARS.vel_x, 2) },
+  { target_state: 'vel_y', expr: accelerate(STATE_VARS.vel_y, 3) },
+  { target_state: 'charge', expr: STATE_VARS.charge },
+  { target_state: 'reach', expr: STATE_VARS.reach },
+];
+
+export const VISUAL_MAPPING: VisualMapping = {
+  position: { x: 'pos_x', y: 'pos_y' },
+  size: {
+    source: 'charge',
+    valueRange: [0.5, 1.5],
+    range: [4, 12],
+    scale: 'linear',
+  },
+};
@@ -6,6 +6,7 @@ import type {
   DynamicsRule,
   GroupConfig,
   InitializationIR,
+  LabelMeta,
   ParameterGroups,
   VisualMapping,
 } from '../types.js';
@@ -44,6 +45,7 @@ const CONSTANTS = {
   one: ops.const(1.0),
   eps: ops.const(1e-4),
   zero: ops.const(0.0),
+  dt: ops.dt(),
 } as const;
 
 // Canonical state ordering used for the simulator state tensor.
@@ -56,6 +58,15 @@ export const STATE_VAR_ORDER: (keyof typeof STATE_VARS)[] = [
   'size',
 ];
 
+// Display names for HUD/captions (falls back to the raw label when absent).
+export const LABEL_META: Record<string, LabelMeta> = {
+  pos_x: { display: 'Position X' },
+  pos_y: { display: 'Position Y' },
+  vel_x: { display: 'Velocity X', unit: 'per second' },
+  vel_y: { display: 'Velocity Y', unit: 'per second' },
+  size: { display: 'Mass' },
+};
+
 // 2.5. Initialization configuration (initial distributions + hyperparameters)
 // Keep this as the single source of truth for simulator initial conditions.
 export const INITIALIZATION: InitializationIR = {
@@ -90,12 +101,12 @@ export const DYNAMICS_RULES: DynamicsRule[] = [
   {
     target_state: 'pos_x',
     // Position update: x += v_x * dt
-    expr: ops.add(STATE_VARS.pos_x, STATE_VARS.vel_x),
+    expr: ops.add(STATE_VARS.pos_x, ops.mul(STATE_VARS.vel_x, CONSTANTS.dt)),
   },
   {
     target_state: 'pos_y',
     // Position update: y += v_y * dt
-    expr: ops.add(STATE_VARS.pos_y, STATE_VARS.vel_y),
+    expr: ops.add(STATE_VARS.pos_y, ops.mul(STATE_VARS.vel_y, CONSTANTS.dt)),
   },
   {
     target_state: 'vel_x',
@@ -130,7 +141,7 @@ export const DYNAMICS_RULES: DynamicsRule[] = [
       );
       const g = ops.add(ops.const(GRAVITY_CONST), _keep_params);
 
-      const dv = ops.mul(g, ax_grav);
+      const dv = ops.mul(ops.mul(g, ax_grav), CONSTANTS.dt);
       return ops.add(vx, dv);
     })(),
   },
@@ -163,7 +174,7 @@ export const DYNAMICS_RULES: DynamicsRule[] = [
         ops.mul(GENETIC_PARAMS.dummy_attr, CONSTANTS.zero)
       );
       const g = ops.add(ops.const(GRAVITY_CONST), _keep_params);
-      const dv = ops.mul(g, ay_grav);
+      const dv = ops.mul(ops.mul(g, ay_grav), CONSTANTS.dt);
       return ops.add(vy, dv);
     })(),
   },
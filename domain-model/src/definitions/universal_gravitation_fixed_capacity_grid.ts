@@ -31,6 +31,8 @@ export const GRID_CONFIG: GridConfig = {
   height: 64,
   capacity: 8,
   cell_size: [128.0, 125.0],
+  // The grid covers the torus below, so cells across its seam are neighbors.
+  origin: [-WORLD_SIZE_X / 2, -WORLD_SIZE_Y / 2],
 };
 
 // 1. Parameter group definitions (Phenotype Engine output structure)
@@ -57,6 +59,14 @@ const GENETIC_PARAMS = {
 const CONSTANTS = {
   one: ops.const(1.0),
   zero: ops.const(0.0),
+  dt: ops.dt(),
+} as const;
+
+// Gravity between each pair: g * m_j * d / (|d|^2 + softening). The softening
+// keeps close pairs finite; g scales every force.
+const GRAVITY_PARAMS = {
+  softening: 0.01,
+  g: 1.0,
 } as const;
 
 // Canonical state ordering used for the simulator state tensor.
@@ -94,77 +104,57 @@ export const BOUNDARY_CONDITIONS: BoundaryCondition[] = [
   },
 ];
 
+// Gravity on every agent from those within one cell → [N, 2]
+const gravity = () => {
+  // 1. Prepare state tensor for grid: [pos_x, pos_y, vel_x, vel_y, size]
+  const state_vec = ops.cat(
+    [STATE_VARS.pos_x, STATE_VARS.pos_y, STATE_VARS.vel_x, STATE_VARS.vel_y, STATE_VARS.size],
+    1
+  );
+
+  // 2. Scatter particles to Grid → [H, W, Cap, 5]
+  const grid_state = ops.grid_scatter(state_vec, STATE_VARS.pos_x, STATE_VARS.pos_y);
+
+  // 3. Compute Stencil Interactions (Gravity) → [H, W, Cap, 2]
+  // Uses 3x3 neighborhood (stencil_range = 1), with size as each agent's mass
+  const force_grid = ops.stencil_force(
+    grid_state,
+    1,
+    { kind: 'inverse_square', ...GRAVITY_PARAMS },
+    { x: 0, y: 1, mass: 4 }
+  );
+
+  // 4. Gather forces back to particles → [N, 2]
+  return ops.grid_gather(force_grid, STATE_VARS.pos_x, STATE_VARS.pos_y);
+};
+
+// Keep params alive to ensure PhenotypeEngine produces valid tensors
+const keep_params = ops.add(
+  ops.mul(GENETIC_PARAMS.grav_g, CONSTANTS.zero),
+  ops.mul(GENETIC_PARAMS.dummy_attr, CONSTANTS.zero)
+);
+
 // 5. Dynamics rules using Grid Stencil computation
 export const DYNAMICS_RULES: DynamicsRule[] = [
   // Position update: pos += vel * dt
   {
     target_state: 'pos_x',
-    expr: ops.add(STATE_VARS.pos_x, STATE_VARS.vel_x),
+    expr: ops.add(STATE_VARS.pos_x, ops.mul(STATE_VARS.vel_x, CONSTANTS.dt)),
   },
   {
     target_state: 'pos_y',
-    expr: ops.add(STATE_VARS.pos_y, STATE_VARS.vel_y),
+    expr: ops.add(STATE_VARS.pos_y, ops.mul(STATE_VARS.vel_y, CONSTANTS.dt)),
   },
 
   // Velocity X update using Grid Stencil
   {
     target_state: 'vel_x',
     expr: (() => {
-      // 1. Prepare state tensor for grid: [pos_x, pos_y, vel_x, vel_y, size]
-      const state_vec = ops.cat(
-        [STATE_VARS.pos_x, STATE_VARS.pos_y, STATE_VARS.vel_x, STATE_VARS.vel_y, STATE_VARS.size],
-        1
-      );
-
-      // 2. Scatter particles to Grid → [H, W, Cap, 5]
-      const grid_state = ops.grid_scatter(state_vec, STATE_VARS.pos_x, STATE_VARS.pos_y);
-
-      // 3. Compute Stencil Interactions (Gravity)
-      // Uses 3x3 neighborhood (stencil_range = 1)
-      // Returns grid with forces in vel slots (indices 2 and 3)
-      const force_grid = ops.stencil(grid_state, 1, (center, neighbor) => {
-        const c_px = ops.slice(center, 1, 0, 1);
-        const c_py = ops.slice(center, 1, 1, 1);
-
-        const n_px = ops.slice(neighbor, 1, 0, 1);
-        const n_py = ops.slice(neighbor, 1, 1, 1);
-        const n_m = ops.slice(neighbor, 1, 4, 1);
-
-        const n_px_T = ops.transpose(n_px, 0, 1);
-        const n_py_T = ops.transpose(n_py, 0, 1);
-        const n_m_T = ops.transpose(n_m, 0, 1);
-
-        const dx = ops.sub(n_px_T, c_px);
-        const dy = ops.sub(n_py_T, c_py);
-
-        const d2 = ops.add(ops.add(ops.mul(dx, dx), ops.mul(dy, dy)), ops.const(0.01));
-        const inv_d2 = ops.div(ops.const(1.0), d2);
-
-        const fx = ops.mul(ops.mul(n_m_T, dx), inv_d2);
-        const fy = ops.mul(ops.mul(n_m_T, dy), inv_d2);
-
-        const fx_sum = ops.sum(fx, 1, true);
-        const fy_sum = ops.sum(fy, 1, true);
-
-        const zeros = ops.mul(c_px, ops.const(0.0));
-
-        return ops.cat([zeros, zeros, fx_sum, fy_sum, zeros], 1);
-      });
-
-      // 4. Gather forces back to particles → [N, 5]
-      const force_vec = ops.grid_gather(force_grid, STATE_VARS.pos_x, STATE_VARS.pos_y);
-
-      // 5. Extract Force X (index 2)
-      const fx = ops.slice(force_vec, 1, 2, 1);
-
-      // Keep params alive to ensure PhenotypeEngine produces valid tensors
-      const _keep_params = ops.add(
-        ops.mul(GENETIC_PARAMS.grav_g, CONSTANTS.zero),
-        ops.mul(GENETIC_PARAMS.dummy_attr, CONSTANTS.zero)
-      );
+      // Extract Force X (column 0)
+      const fx = ops.slice(gravity(), 1, 0, 1);
 
       // Update vel_x: vel_x += fx * dt
-      return ops.add(STATE_VARS.vel_x, ops.add(fx, _keep_params));
+      return ops.add(STATE_VARS.vel_x, ops.mul(ops.add(fx, keep_params), CONSTANTS.dt));
     })(),
   },
 
@@ -172,54 +162,12 @@ export const DYNAMICS_RULES: DynamicsRule[] = [
   {
     target_state: 'vel_y',
     expr: (() => {
-      // Same grid computation (compiler should optimize if possible)
-      const state_vec = ops.cat(
-        [STATE_VARS.pos_x, STATE_VARS.pos_y, STATE_VARS.vel_x, STATE_VARS.vel_y, STATE_VARS.size],
-        1
-      );
-
-      const grid_state = ops.grid_scatter(state_vec, STATE_VARS.pos_x, STATE_VARS.pos_y);
-      const force_grid = ops.stencil(grid_state, 1, (center, neighbor) => {
-        const c_px = ops.slice(center, 1, 0, 1);
-        const c_py = ops.slice(center, 1, 1, 1);
-
-        const n_px = ops.slice(neighbor, 1, 0, 1);
-        const n_py = ops.slice(neighbor, 1, 1, 1);
-        const n_m = ops.slice(neighbor, 1, 4, 1);
-
-        const n_px_T = ops.transpose(n_px, 0, 1);
-        const n_py_T = ops.transpose(n_py, 0, 1);
-        const n_m_T = ops.transpose(n_m, 0, 1);
-
-        const dx = ops.sub(n_px_T, c_px);
-        const dy = ops.sub(n_py_T, c_py);
-
-        const d2 = ops.add(ops.add(ops.mul(dx, dx), ops.mul(dy, dy)), ops.const(0.01));
-        const inv_d2 = ops.div(ops.const(1.0), d2);
-
-        const fx = ops.mul(ops.mul(n_m_T, dx), inv_d2);
-        const fy = ops.mul(ops.mul(n_m_T, dy), inv_d2);
-
-        const fx_sum = ops.sum(fx, 1, true);
-        const fy_sum = ops.sum(fy, 1, true);
-
-        const zeros = ops.mul(c_px, ops.const(0.0));
-
-        return ops.cat([zeros, zeros, fx_sum, fy_sum, zeros], 1);
-      });
-      const force_vec = ops.grid_gather(force_grid, STATE_VARS.pos_x, STATE_VARS.pos_y);
-
-      // Extract Force Y (index 3)
-      const fy = ops.slice(force_vec, 1, 3, 1);
-
-      // Keep params alive
-      const _keep_params = ops.add(
-        ops.mul(GENETIC_PARAMS.grav_g, CONSTANTS.zero),
-        ops.mul(GENETIC_PARAMS.dummy_attr, CONSTANTS.zero)
-      );
+      // Same grid computation (the compiler reuses it)
+      // Extract Force Y (column 1)
+      const fy = ops.slice(gravity(), 1, 1, 1);
 
       // Update vel_y: vel_y += fy * dt
-      return ops.add(STATE_VARS.vel_y, ops.add(fy, _keep_params));
+      return ops.add(STATE_VARS.vel_y, ops.mul(ops.add(fy, keep_params), CONSTANTS.dt));
     })(),
   },
 
@@ -20,6 +20,7 @@ export type Expression =
   | { op: 'ref_param'; id: string; group: string }
   | { op: 'ref_aux'; id: string }
   | { op: 'const'; value: number }
+  | { op: 'ref_dt' }
   | { op: 'add'; left: Expression; right: Expression }
   | { op: 'sub'; left: Expression; right: Expression }
   | { op: 'mul'; left: Expression; right: Expression }
@@ -34,8 +35,10 @@ export type Expression =
       op: 'stencil';
       value: Expression;
       range: number;
-      kernel: (center: Expression, neighbor: Expression) => Expression;
+      kernel: (center: Expression, neighbor: Expression, within: Expression) => Expression;
+      radius?: StencilRadius;
     }
+  | { op: 'stencil_force'; value: Expression; range: number; law: ForceLaw; columns: StencilColumns }
   | { op: 'grid_gather'; value: Expression; x: Expression; y: Expression }
   | { op: 'cat'; values: Expression[]; dim: number }
   | { op: 'slice'; value: Expression; dim: number; start: number; len: number }
@@ -44,6 +47,34 @@ export type Expression =
   | { op: 'ge'; left: Expression; right: Expression }
   | { op: 'where'; cond: Expression; trueVal: Expression; falseVal: Expression };
 
+// Per-agent stencil cutoff: grid-state columns holding the position each
+// distance is measured between and each center's own interaction radius.
+// `range` must still be wide enough (in cells) for the largest radius.
+export interface StencilRadius {
+  x: number;
+  y: number;
+  radius: number;
+}
+
+// Pairwise force a `stencil_force` sums over each neighborhood, emitted as the
+// simulator's `grid::ForceLaw`.
+export type ForceLaw =
+  // g * mass_j * d / (|d|^2 + softening) towards each neighbor
+  | { kind: 'inverse_square'; softening: number; g: number }
+  // k * (|d| - rest) towards each neighbor, pushing apart when closer than rest
+  | { kind: 'spring'; k: number; rest: number }
+  // Lennard-Jones: repulsive inside 2^(1/6) * sigma, attractive beyond it
+  | { kind: 'lennard_jones'; eps: number; sigma: number };
+
+// Grid-state columns a `stencil_force` reads: positions, the neighbor mass an
+// inverse-square force scales by, and optionally each center's interaction radius.
+export interface StencilColumns {
+  x: number;
+  y: number;
+  mass: number;
+  radius?: number;
+}
+
 // Internal dynamics rule definition
 export interface DynamicsRule {
   target_state: string;
@@ -64,7 +95,7 @@ export interface InitializationIR {
 }
 
 // Boundary condition definitions.
-export type BoundaryType = 'torus' | 'clamp' | 'none';
+export type BoundaryType = 'torus' | 'reflective' | 'clamp' | 'none';
 
 export interface BoundaryCondition {
   // State var name to apply boundary to (e.g. pos_x, pos_y)
@@ -72,6 +103,14 @@ export interface BoundaryCondition {
   kind: BoundaryType;
   // World range [min, max]. For torus, this defines the period width (max - min).
   range: [number, number];
+  // Reflective only: state var whose sign flips on each bounce (e.g. vel_x)
+  velocity?: string;
+}
+
+// Human-friendly name and unit for a state var, shown in place of the raw label.
+export interface LabelMeta {
+  display: string;
+  unit?: string;
 }
 
 export interface GridConfig {
@@ -79,11 +118,18 @@ export interface GridConfig {
   height: number;
   capacity: number;
   cell_size: [number, number];
+  // World position of the corner of cell (0, 0) (default: [0, 0])
+  origin?: [number, number];
+  // What lies past the grid's edges (default: torus)
+  boundary?: 'torus' | 'reflective' | 'open';
 }
 
 // IR (Intermediate Representation) types for JSON output
 export interface OutputIR {
   state_vars: string[];
+  label_meta?: Record<string, LabelMeta>;
+  // State var whose value at the end of a generation is each agent's fitness
+  fitness?: string;
   constants: {
     n_agents: number;
     gene_len: number;
@@ -115,6 +161,7 @@ export interface Operation {
     | 'relu'
     | 'neg'
     | 'const'
+    | 'ref_dt'
     | 'ref_state'
     | 'ref_param'
     | 'ref_aux'
@@ -138,14 +185,26 @@ export interface Operation {
   dim1?: number;
   // Grid ops
   stencil_range?: number;
+  stencil_radius?: StencilRadius;
+  force_law?: ForceLaw;
+  stencil_columns?: StencilColumns;
   start?: number;
   len?: number;
 }
 
 // Visual mapping types for simulator output visualization
-export type ColorMap = 'viridis' | 'plasma' | 'heat' | 'cool';
+export type ColorMap =
+  | 'viridis'
+  | 'plasma'
+  | 'heat'
+  | 'cool'
+  | 'grayscale'
+  // Diverging: blue below `midpoint`, red above
+  | 'rdbu'
+  | 'coolwarm';
 export type SizeScale = 'linear' | 'sqrt' | 'log';
 export type BlendMode = 'add' | 'average' | 'max' | 'min';
+export type BivariatePalette = 'bluered' | 'greenblue';
 
 // Single or multiple sources with optional weights
 export type VisualSource =
@@ -156,36 +215,69 @@ export type VisualSource =
       blend?: BlendMode; // How to combine multiple sources
     };
 
+// Raw value range a source is normalized against: fixed, or "auto" to follow the
+// values in the recording.
+export type ValueRange = [number, number] | "auto";
+
 export interface VisualMapping {
   // Position mapping (required, single source per axis)
   position: {
     x: string; // State variable name
     y: string; // State variable name
+    // [width, height] of a torus centered on the origin that x and y wrap around;
+    // overrides the recording's torus ranges
+    worldSize?: [number, number];
   };
 
   // Size mapping (optional, supports multi-source)
   size?: {
     source: VisualSource;
     // Input value range used to normalize the (possibly blended) source into [0, 1].
-    // If omitted, the source is assumed to already be normalized.
-    valueRange?: [number, number];
+    // If omitted, the source is assumed to already be normalized; "auto" follows the
+    // recorded values (see the visualizer's --auto-range).
+    valueRange?: ValueRange;
     range: [number, number]; // [min_radius, max_radius] in pixels
     scale?: SizeScale;
   };
 
   // Color mapping (optional, supports multi-source)
-  color?: {
-    source: VisualSource;
-    colormap: ColorMap;
-    range?: [number, number]; // Data value range for mapping
-  };
+  color?:
+    | {
+        source: VisualSource;
+        colormap?: ColorMap; // Required unless `stops` is given
+        // Custom piecewise-linear gradient [[t, [r, g, b]], ...] (0-255 channels), sorted
+        // by t from 0 to 1; takes precedence over `colormap`
+        stops?: [number, [number, number, number]][];
+        range?: ValueRange; // Data value range for mapping
+        midpoint?: number; // Value at the center of the colormap; each side gets half of it
+      }
+    | {
+        // Bivariate color: sources[0] drives the palette's x axis, sources[1] its y axis.
+        sources: [VisualSource, VisualSource];
+        palette: BivariatePalette;
+        ranges?: [ValueRange | null, ValueRange | null]; // Per-axis data ranges
+      }
+    | {
+        solid: string; // Constant "#rrggbb" color for every agent
+      };
 
   // Opacity mapping (optional, supports multi-source)
   opacity?: {
     source: VisualSource;
     // Input value range used to normalize the (possibly blended) source into [0, 1].
-    // If omitted, the source is assumed to already be normalized.
-    valueRange?: [number, number];
+    // If omitted, the source is assumed to already be normalized; "auto" follows the
+    // recorded values (see the visualizer's --auto-range).
+    valueRange?: ValueRange;
     range: [number, number]; // [0.0, 1.0]
   };
+
+  // Velocity arrows (optional): a line from each agent along its velocity
+  velocity?: {
+    x: string; // State variable name
+    y: string; // State variable name
+    z?: string; // For the xz / yz projections
+    scale?: number; // World units of arrow per unit of speed (default 1)
+    maxLength?: number; // Longest arrow in world units (default 20)
+    minSpeed?: number; // No arrow at or below this speed (default 1e-6)
+  };
 }
@@ -5,6 +5,8 @@ use std::path::Path;
 use std::process::Command;
 
 fn main() {
+    emit_candle_version();
+
     println!("cargo:rerun-if-changed=../domain-model/_gen/");
     println!("cargo:rerun-if-changed=scripts/generators/generate-phenotype-physics.rs");
 
@@ -16,9 +18,14 @@ fn main() {
 
     let mut definitions = Vec::new();
 
-    for entry in fs::read_dir(gen_dir).unwrap() {
-        let entry = entry.unwrap();
-        let path = entry.path();
+    // Sort so the generated mod.rs is stable across filesystems.
+    let mut paths: Vec<_> = fs::read_dir(gen_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+
+    for path in paths {
         if path.is_dir() {
             let def_name = path.file_name().unwrap().to_str().unwrap().to_string();
             let json_path = path.join("dynamics_ir.json");
@@ -29,7 +36,7 @@ fn main() {
 
                 // Run the phenotype/physics code generator
                 let status = Command::new("cargo")
-                    .args(&[
+                    .args([
                         "run",
                         "--manifest-path",
                         "scripts/generators/Cargo.toml",
@@ -56,18 +63,56 @@ fn main() {
         mod_rs.push_str(&format!("pub mod {};\n", def));
     }
 
-    // Generate a macro to select the definition
+    // Names of the generated definitions, for listing the choices
+    mod_rs.push_str(&format!(
+        "\npub const DEFINITIONS: [&str; {}] = [\n",
+        definitions.len()
+    ));
+    for def in &definitions {
+        mod_rs.push_str(&format!("    \"{}\",\n", def));
+    }
+    mod_rs.push_str("];\n");
+
+    // Generate a macro to select the definition; `$unknown` is evaluated for
+    // any other name.
     mod_rs.push_str("\n#[macro_export]\n");
     mod_rs.push_str("macro_rules! with_definition {\n");
-    mod_rs.push_str("    ($name:expr, $callback:path) => {\n");
+    mod_rs.push_str("    ($name:expr, $callback:path, $unknown:expr) => {\n");
     mod_rs.push_str("        match $name.as_str() {\n");
     for def in &definitions {
-        mod_rs.push_str(&format!("            \"{}\" => {{ use $crate::_gen::{} as def; $callback!(def) }},\n", def, def));
+        mod_rs.push_str(&format!(
+            "            \"{}\" => {{ use $crate::_gen::{} as def; $callback!(def) }},\n",
+            def, def
+        ));
     }
-    mod_rs.push_str("            _ => panic!(\"Unknown definition: {}\", $name),\n");
+    mod_rs.push_str("            _ => $unknown,\n");
     mod_rs.push_str("        }\n");
-    mod_rs.push_str("    }\n");
+    mod_rs.push_str("    };\n");
     mod_rs.push_str("}\n");
 
     fs::write("src/_gen/mod.rs", mod_rs).expect("Failed to write mod.rs");
 }
+
+/// Exposes the locked candle-core version as `CANDLE_VERSION` for the headers'
+/// runtime block. candle-core sets no `links` key, so there is no `DEP_` variable
+/// to read it from, and `cargo metadata` would resolve the workspace again from
+/// inside the build; the lockfile is read instead. Warns and records "unknown"
+/// when it has no candle-core entry (e.g. built without this crate's lockfile).
+fn emit_candle_version() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    let lock = fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let version = lock
+        .split("[[package]]")
+        .find(|package| package.lines().any(|line| line == "name = \"candle-core\""))
+        .and_then(|package| {
+            package
+                .lines()
+                .find_map(|line| line.strip_prefix("version = "))
+        })
+        .map(|version| version.trim_matches('"').to_string());
+    let version = version.unwrap_or_else(|| {
+        println!("cargo:warning=candle-core not found in Cargo.lock; its version is unknown");
+        "unknown".to_string()
+    });
+    println!("cargo:rustc-env=CANDLE_VERSION={version}");
+}
@@ -1,6 +1,6 @@
 // Code generator: Generate phenotype.rs and dynamics.rs from JSON IR
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -19,11 +19,32 @@ struct GridConfig {
     height: usize,
     capacity: usize,
     cell_size: (f64, f64),
+    /// World position of the corner of cell (0, 0).
+    #[serde(default)]
+    origin: (f64, f64),
+    #[serde(default)]
+    boundary: GridBoundary,
+}
+
+/// What lies past the grid's edges; emitted as `crate::grid::Boundary`.
+#[derive(Deserialize, Debug, Default)]
+enum GridBoundary {
+    #[default]
+    #[serde(rename = "torus")]
+    Torus,
+    #[serde(rename = "reflective")]
+    Reflective,
+    #[serde(rename = "open")]
+    Open,
 }
 
 #[derive(Deserialize, Debug)]
 struct ConfigIR {
     state_vars: Vec<String>,
+    #[serde(default)]
+    label_meta: BTreeMap<String, LabelMeta>,
+    #[serde(default)]
+    fitness: Option<String>,
     constants: Option<Constants>,
     grid_config: Option<GridConfig>,
     groups: HashMap<String, GroupConfig>,
@@ -34,11 +55,21 @@ struct ConfigIR {
     operations: Vec<Operation>,
 }
 
+#[derive(Deserialize, Debug)]
+struct LabelMeta {
+    display: String,
+    #[serde(default)]
+    unit: Option<String>,
+}
+
 #[derive(Deserialize, Debug)]
 struct BoundaryCondition {
     target_state: String,
     kind: String,
     range: (f64, f64),
+    /// State var whose sign flips when a reflective wall bounces `target_state`.
+    #[serde(default)]
+    velocity: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -107,6 +138,12 @@ struct Operation {
     #[serde(default)]
     stencil_range: Option<i32>,
     #[serde(default)]
+    stencil_radius: Option<StencilRadius>,
+    #[serde(default)]
+    force_law: Option<ForceLaw>,
+    #[serde(default)]
+    stencil_columns: Option<StencilColumns>,
+    #[serde(default)]
     kernel_operations: Option<Vec<Operation>>,
     #[serde(default)]
     start: Option<usize>,
@@ -114,6 +151,56 @@ struct Operation {
     len: Option<usize>,
 }
 
+/// Grid-state columns a stencil reads each center's interaction radius from, and
+/// the position columns the distance to each neighbor is measured between.
+#[derive(Deserialize, Debug)]
+struct StencilRadius {
+    x: usize,
+    y: usize,
+    radius: usize,
+}
+
+/// Pairwise force of a kernel-less stencil; emitted as `crate::grid::ForceLaw`.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "kind")]
+enum ForceLaw {
+    #[serde(rename = "inverse_square")]
+    InverseSquare { softening: f64, g: f64 },
+    #[serde(rename = "spring")]
+    Spring { k: f64, rest: f64 },
+    #[serde(rename = "lennard_jones")]
+    LennardJones { eps: f64, sigma: f64 },
+}
+
+impl ForceLaw {
+    fn to_rust(&self) -> String {
+        match self {
+            Self::InverseSquare { softening, g } => format!(
+                "crate::grid::ForceLaw::InverseSquare(crate::grid::GravityParams {{ softening: {:?}, g: {:?} }})",
+                softening, g
+            ),
+            Self::Spring { k, rest } => {
+                format!("crate::grid::ForceLaw::Spring {{ k: {:?}, rest: {:?} }}", k, rest)
+            }
+            Self::LennardJones { eps, sigma } => format!(
+                "crate::grid::ForceLaw::LennardJones {{ eps: {:?}, sigma: {:?} }}",
+                eps, sigma
+            ),
+        }
+    }
+}
+
+/// Grid-state columns a kernel-less stencil reads; emitted as
+/// `crate::grid::StencilColumns`.
+#[derive(Deserialize, Debug)]
+struct StencilColumns {
+    x: usize,
+    y: usize,
+    mass: usize,
+    #[serde(default)]
+    radius: Option<usize>,
+}
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 3 {
@@ -237,9 +324,12 @@ fn generate_phenotype(ir: &ConfigIR, out_dir: &Path) {
 
     // Gene initialization helper.
     code.push_str("\n#[allow(dead_code)]\n");
+    // `rng` goes unused when every value is constant.
+    code.push_str("#[allow(unused_variables)]\n");
     code.push_str("pub fn init_genes(\n");
     code.push_str("    n_agents: usize,\n");
     code.push_str("    gene_len: usize,\n");
+    code.push_str("    rng: &mut rand::rngs::StdRng,\n");
     code.push_str("    device: &candle_core::Device,\n");
     code.push_str(") -> candle_core::Result<candle_core::Tensor> {\n");
 
@@ -248,7 +338,7 @@ fn generate_phenotype(ir: &ConfigIR, out_dir: &Path) {
     match genes_dist {
         None => {
             code.push_str(
-                "    candle_core::Tensor::randn(0.0f32, 1.0f32, (n_agents, gene_len), device)\n",
+                "    crate::sampling::normal(rng, 0.0f32, 1.0f32, (n_agents, gene_len), device)\n",
             );
         }
         Some(genes_dist) => match genes_dist {
@@ -261,13 +351,13 @@ fn generate_phenotype(ir: &ConfigIR, out_dir: &Path) {
         }
         Distribution::Uniform { low, high } => {
             code.push_str(&format!(
-                "    candle_core::Tensor::rand({}f32, {}f32, (n_agents, gene_len), device)\n",
+                "    crate::sampling::uniform(rng, {}f32, {}f32, (n_agents, gene_len), device)\n",
                 *low as f32, *high as f32
             ));
         }
         Distribution::Normal { mean, std } => {
             code.push_str(&format!(
-                "    candle_core::Tensor::randn({}f32, {}f32, (n_agents, gene_len), device)\n",
+                "    crate::sampling::normal(rng, {}f32, {}f32, (n_agents, gene_len), device)\n",
                 *mean as f32, *std as f32
             ));
         }
@@ -285,6 +375,73 @@ fn generate_phenotype(ir: &ConfigIR, out_dir: &Path) {
     fs::write(out_dir.join("phenotype.rs"), code).expect("Failed to write phenotype.rs");
 }
 
+/// Grid-state columns of the x and y positions a stencil's input was scattered
+/// by: the stencil must read a `grid_scatter` of a `cat` that includes both.
+fn stencil_position_columns(ir: &ConfigIR, stencil: &Operation) -> (usize, usize) {
+    let find = |target: &str| ir.operations.iter().find(|o| o.target == target);
+    let scatter = find(&stencil.args[0])
+        .filter(|o| o.op == "grid_scatter" && o.args.len() == 3)
+        .unwrap_or_else(|| panic!("stencil {} does not read a grid_scatter", stencil.target));
+    let columns: Vec<&String> = match find(&scatter.args[0]) {
+        Some(cat) if cat.op == "cat" => cat.args.iter().collect(),
+        _ => vec![&scatter.args[0]],
+    };
+    let column = |arg: &String| {
+        columns.iter().position(|c| *c == arg).unwrap_or_else(|| {
+            panic!(
+                "grid_scatter {} does not carry its position {} for stencil {}",
+                scatter.target, arg, stencil.target
+            )
+        })
+    };
+    (column(&scatter.args[1]), column(&scatter.args[2]))
+}
+
+/// Folds `bc.target_state` back between reflective walls at `bc.range`, as if it
+/// had bounced off them, and flips the sign of `bc.velocity` for an odd number of
+/// bounces.
+fn push_reflective_boundary(code: &mut String, bc: &BoundaryCondition) {
+    let (min, max) = bc.range;
+    let target = &bc.target_state;
+    code.push_str(&format!(
+        "    // reflective walls: {} in [{:.6},{:.6}]\n",
+        target, min, max
+    ));
+    match &bc.velocity {
+        Some(velocity) => code.push_str(&format!("    let ({}, {}) = {{\n", target, velocity)),
+        None => code.push_str(&format!("    let {} = {{\n", target)),
+    }
+    code.push_str(&format!(
+        "        let min = candle_core::Tensor::new(&[{:.6}f32], state.device())?;\n",
+        min
+    ));
+    code.push_str(&format!(
+        "        let width = candle_core::Tensor::new(&[{:.6}f32], state.device())?;\n",
+        max - min
+    ));
+    code.push_str(&format!(
+        "        let period = candle_core::Tensor::new(&[{:.6}f32], state.device())?;\n",
+        2.0 * (max - min)
+    ));
+    // Unfolded, a bouncing coordinate repeats every two widths: forward, then mirrored.
+    code.push_str(&format!("        let norm = {}.broadcast_sub(&min)?;\n", target));
+    code.push_str("        let floor = norm.broadcast_div(&period)?.floor()?;\n");
+    code.push_str("        let rem = norm.broadcast_sub(&floor.broadcast_mul(&period)?)?;\n");
+    code.push_str("        let mirrored = rem.broadcast_gt(&width)?.to_dtype(candle_core::DType::F32)?;\n");
+    code.push_str("        let folded = mirrored.broadcast_mul(&period.broadcast_sub(&rem)?.broadcast_sub(&rem)?)?.broadcast_add(&rem)?;\n");
+    match &bc.velocity {
+        Some(velocity) => {
+            code.push_str("        let sign = mirrored.affine(-2.0, 1.0)?;\n");
+            code.push_str(&format!(
+                "        (folded.broadcast_add(&min)?, {}.broadcast_mul(&sign)?)\n",
+                velocity
+            ));
+        }
+        None => code.push_str("        folded.broadcast_add(&min)?\n"),
+    }
+    code.push_str("    };\n");
+}
+
 fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
     let mut code = String::new();
     let group_names = ordered_group_names(ir);
@@ -295,7 +452,7 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
         code.push_str(&format!("pub const N_AGENTS: usize = {};\n", constants.n_agents));
         code.push_str(&format!("pub const GENE_LEN: usize = {};\n", constants.gene_len));
         code.push_str(&format!("pub const HIDDEN_LEN: usize = {};\n", constants.hidden_len));
-        code.push_str("\n");
+        code.push('\n');
     }
 
     if let Some(grid) = &ir.grid_config {
@@ -305,9 +462,27 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
         code.push_str(&format!("    height: {},\n", grid.height));
         code.push_str(&format!("    capacity: {},\n", grid.capacity));
         code.push_str(&format!("    cell_size: ({:.6}, {:.6}),\n", grid.cell_size.0, grid.cell_size.1));
+        code.push_str(&format!("    origin: ({:.6}, {:.6}),\n", grid.origin.0, grid.origin.1));
+        code.push_str(&format!("    boundary: crate::grid::Boundary::{:?},\n", grid.boundary));
         code.push_str("};\n\n");
     }
 
+    // Grid and farthest stencil reach (in cells), so the simulator can check that
+    // agents do not move past what the stencil sees in one update.
+    let stencil_range = ir
+        .operations
+        .iter()
+        .filter(|op| op.op == "stencil")
+        .map(|op| op.stencil_range.unwrap_or(1))
+        .max();
+    match (&ir.grid_config, stencil_range) {
+        (Some(_), Some(range)) => code.push_str(&format!(
+            "pub const STENCIL: Option<(crate::grid::SpatialGrid, usize)> = Some((GRID_CONFIG, {}));\n\n",
+            range.max(0)
+        )),
+        _ => code.push_str("pub const STENCIL: Option<(crate::grid::SpatialGrid, usize)> = None;\n\n"),
+    }
+
     // Export state metadata for the simulator.
     code.push_str(&format!("pub const STATE_DIMS: usize = {};\n", ir.state_vars.len()));
     code.push_str(&format!("pub const STATE_VARS: [&str; {}] = [\n", ir.state_vars.len()));
@@ -316,10 +491,60 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
     }
     code.push_str("];\n\n");
 
+    // Display metadata as (state var, display name, unit).
+    code.push_str(&format!(
+        "pub const STATE_LABEL_META: [(&str, &str, Option<&str>); {}] = [\n",
+        ir.label_meta.len()
+    ));
+    for (name, meta) in &ir.label_meta {
+        let unit = match &meta.unit {
+            Some(unit) => format!("Some({:?})", unit),
+            None => "None".to_string(),
+        };
+        code.push_str(&format!("    (\"{}\", {:?}, {}),\n", name, meta.display, unit));
+    }
+    code.push_str("];\n\n");
+
+    // State var whose value at the end of a generation is each agent's fitness.
+    match &ir.fitness {
+        Some(label) => {
+            assert!(
+                ir.state_vars.contains(label),
+                "fitness references unknown state var: {}",
+                label
+            );
+            code.push_str(&format!(
+                "pub const FITNESS: Option<&str> = Some({:?});\n\n",
+                label
+            ));
+        }
+        None => code.push_str("pub const FITNESS: Option<&str> = None;\n\n"),
+    }
+
+    // Torus boundaries as (state var, min, max), recorded so readers can unwrap motion.
+    let torus: Vec<&BoundaryCondition> = ir
+        .boundary_conditions
+        .iter()
+        .filter(|bc| bc.kind == "torus")
+        .collect();
+    code.push_str(&format!(
+        "pub const TORUS_RANGES: [(&str, f32, f32); {}] = [\n",
+        torus.len()
+    ));
+    for bc in &torus {
+        code.push_str(&format!(
+            "    (\"{}\", {:.6}, {:.6}),\n",
+            bc.target_state, bc.range.0, bc.range.1
+        ));
+    }
+    code.push_str("];\n\n");
+
     // State initialization helper.
     code.push_str("#[allow(dead_code)]\n");
+    code.push_str("#[allow(unused_variables)]\n");
     code.push_str("pub fn init_state(\n");
     code.push_str("    n_agents: usize,\n");
+    code.push_str("    rng: &mut rand::rngs::StdRng,\n");
     code.push_str("    device: &candle_core::Device,\n");
     code.push_str(") -> candle_core::Result<candle_core::Tensor> {\n");
 
@@ -341,7 +566,7 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
                 }
                 Distribution::Uniform { low, high } => {
                     code.push_str(&format!(
-                        "    let {} = candle_core::Tensor::rand({}f32, {}f32, (n_agents, 1), device)?;\n",
+                        "    let {} = crate::sampling::uniform(rng, {}f32, {}f32, (n_agents, 1), device)?;\n",
                         var,
                         *low as f32,
                         *high as f32
@@ -349,7 +574,7 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
                 }
                 Distribution::Normal { mean, std } => {
                     code.push_str(&format!(
-                        "    let {} = candle_core::Tensor::randn({}f32, {}f32, (n_agents, 1), device)?;\n",
+                        "    let {} = crate::sampling::normal(rng, {}f32, {}f32, (n_agents, 1), device)?;\n",
                         var,
                         *mean as f32,
                         *std as f32
@@ -387,7 +612,7 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
                 ));
             } else if name.starts_with("pos_") {
                 code.push_str(&format!(
-                    "    let {} = candle_core::Tensor::rand(-200.0f32, 200.0f32, (n_agents, 1), device)?;\n",
+                    "    let {} = crate::sampling::uniform(rng, -200.0f32, 200.0f32, (n_agents, 1), device)?;\n",
                     var
                 ));
             } else {
@@ -415,6 +640,9 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
     for name in &group_names {
         code.push_str(&format!("    p_{}: &candle_core::Tensor,\n", name));
     }
+    code.push_str("    dt: f32,\n");
+    // Adds up the particles grid scatters leave out of full cells.
+    code.push_str("    grid_overflow: &mut u64,\n");
     code.push_str(") -> candle_core::Result<candle_core::Tensor> {\n");
     // Decompose state variables
     code.push_str("    // State variable decomposition\n");
@@ -442,16 +670,27 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
     code.push('\n');
 
     // Declare indices variables for grid_scatter operations (to be reused by grid_gather)
-    let has_grid_scatter = ir.operations.iter().any(|op| op.op == "grid_scatter");
-    if has_grid_scatter {
-        code.push_str("    #[allow(unused_assignments)]\n");
-    }
+    // Occupancy masks are only kept for the scatters a force stencil reads.
+    let masked: Vec<&String> = ir
+        .operations
+        .iter()
+        .filter(|op| op.op == "stencil" && op.force_law.is_some())
+        .map(|op| &op.args[0])
+        .collect();
     for op in &ir.operations {
         if op.op == "grid_scatter" {
+            code.push_str("    #[allow(unused_assignments)]\n");
             code.push_str(&format!(
                 "    let mut {}_indices: candle_core::Tensor = candle_core::Tensor::zeros(1, candle_core::DType::U32, state.device())?;\n",
                 op.target
             ));
+            if masked.contains(&&op.target) {
+                code.push_str("    #[allow(unused_assignments)]\n");
+                code.push_str(&format!(
+                    "    let mut {}_mask: candle_core::Tensor = candle_core::Tensor::zeros(1, candle_core::DType::F32, state.device())?;\n",
+                    op.target
+                ));
+            }
         }
     }
     code.push('\n');
@@ -467,6 +706,7 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
                     "candle_core::Tensor::new(&[0f32], state.device())?".to_string()
                 }
             }
+            "ref_dt" => "candle_core::Tensor::new(&[dt], state.device())?".to_string(),
             "ref_param" => {
                 // Already decomposed above
                 continue;
@@ -509,11 +749,18 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
             "grid_scatter" if op.args.len() == 3 => {
                 // args: [value, x, y]
                 // Generate both grid and indices, storing indices for later reuse by grid_gather
+                // (and the mask for force stencils)
+                let (mask, keep_mask) = if masked.contains(&&op.target) {
+                    ("mask", format!("\n                    {}_mask = mask;", op.target))
+                } else {
+                    ("_mask", String::new())
+                };
                 format!("{{
-                    let (grid, _mask, indices) = particles_to_grid(&{}, &{}, &{}, &GRID_CONFIG)?;
-                    {}_indices = indices;
+                    let (grid, {}, indices, overflowed) = particles_to_grid(&{}, &{}, &{}, &GRID_CONFIG)?;
+                    *grid_overflow += overflowed;
+                    {}_indices = indices;{}
                     grid
-                }}", op.args[1], op.args[2], op.args[0], op.target)
+                }}", mask, op.args[1], op.args[2], op.args[0], op.target, keep_mask)
             }
             "grid_gather" if op.args.len() == 4 => {
                 // args: [grid_value, x, y, scatter_target_for_indices]
@@ -534,7 +781,7 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
                 } else {
                     // Fallback: recalculate indices (legacy behavior)
                     format!("{{
-                    let (_, _, indices) = particles_to_grid(&{}, &{}, state, &GRID_CONFIG)?;
+                    let (_, _, indices, _) = particles_to_grid(&{}, &{}, state, &GRID_CONFIG)?;
                     grid_to_particles(&{}, &indices)?
                 }}", op.args[1], op.args[2], op.args[0])
                 }
@@ -551,6 +798,35 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
                 let len = op.len.unwrap_or(1);
                 format!("{}.narrow({}, {}, {})?", op.args[0], dim, start, len)
             }
+            "stencil" if op.args.len() == 1 && op.force_law.is_some() => {
+                // args: [grid]; sums the law over each neighborhood → [H, W, Cap, 2]
+                let law = op.force_law.as_ref().unwrap();
+                let columns = op
+                    .stencil_columns
+                    .as_ref()
+                    .unwrap_or_else(|| panic!("stencil {} has a force law but no columns", op.target));
+                assert_eq!(
+                    stencil_position_columns(ir, op),
+                    (columns.x, columns.y),
+                    "stencil {} measures distances between other columns than its grid was scattered by",
+                    op.target
+                );
+                let radius = match columns.radius {
+                    Some(radius) => format!("Some({})", radius),
+                    None => "None".to_string(),
+                };
+                format!(
+                    "crate::grid::solve_stencil(&{}, &{}_mask, &GRID_CONFIG, {}, {}, &crate::grid::StencilColumns {{ pos_x: {}, pos_y: {}, mass: {}, radius: {} }})?",
+                    op.args[0],
+                    op.args[0],
+                    op.stencil_range.unwrap_or(1).max(0),
+                    law.to_rust(),
+                    columns.x,
+                    columns.y,
+                    columns.mass,
+                    radius
+                )
+            }
             "stencil" if op.args.len() == 1 => {
                 let range = op.stencil_range.unwrap_or(1);
                 if let Some(kernel_ops) = &op.kernel_operations {
@@ -561,7 +837,10 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
                     block.push_str(&format!("        let range = {};\n", range));
                     block.push_str("        let (h, w, cap, d) = grid.dims4()?;\n");
                     block.push_str("        let pad = range as usize;\n");
-                    block.push_str("        let padded = crate::grid::create_torus_padded_grid(grid, pad)?;\n");
+                    // Padding cells hold their particles where the boundary puts them, so
+                    // distances across an edge match those inside.
+                    let (x, y) = stencil_position_columns(ir, op);
+                    block.push_str(&format!("        let padded = crate::grid::create_ghost_padded_grid(grid, pad, &GRID_CONFIG, {}, {})?;\n", x, y));
                     block.push_str("        let mut acc = grid.zeros_like()?;\n");
 
                     block.push_str("        for dy in -range..=range {\n");
@@ -570,6 +849,16 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
                     block.push_str("                let offset_x = (pad as i32 + dx) as usize;\n");
                     block.push_str("                let neighbor = padded.narrow(0, offset_y, h)?.narrow(1, offset_x, w)?;\n");
                     block.push_str("                let center = grid;\n"); // Alias for clarity
+                    if let Some(r) = &op.stencil_radius {
+                        // [H, W, Cap, Cap]: 1.0 where the neighbor slot lies within the
+                        // center slot's own radius, for the kernel to mask with before summing.
+                        block.push_str("                let within = {\n");
+                        block.push_str(&format!("                    let dx = neighbor.narrow(3, {x}, 1)?.transpose(2, 3)?.broadcast_sub(&center.narrow(3, {x}, 1)?)?;\n", x = r.x));
+                        block.push_str(&format!("                    let dy = neighbor.narrow(3, {y}, 1)?.transpose(2, 3)?.broadcast_sub(&center.narrow(3, {y}, 1)?)?;\n", y = r.y));
+                        block.push_str(&format!("                    let r2 = center.narrow(3, {}, 1)?.sqr()?;\n", r.radius));
+                        block.push_str("                    dx.sqr()?.add(&dy.sqr()?)?.broadcast_le(&r2)?.to_dtype(candle_core::DType::F32)?\n");
+                        block.push_str("                };\n");
+                    }
 
                     // Generate kernel operations
                     for k_op in kernel_ops {
@@ -585,6 +874,7 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
                                     "candle_core::Tensor::new(&[0f32], state.device())?".to_string()
                                 }
                             }
+                            "ref_dt" => "candle_core::Tensor::new(&[dt], state.device())?".to_string(),
                             "add" | "sub" | "mul" | "div" => {
                                 format!("{}.broadcast_{}(&{})?", k_op.args[0], k_op.op, k_op.args[1])
                             }
@@ -632,15 +922,9 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
                     block.push_str("    }");
                     block
                 } else {
-                    // Legacy fallback
-                    format!("crate::grid::solve_gravity_stencil(&{}, {})?", op.args[0], range)
+                    panic!("stencil {} has neither a kernel nor a force law", op.target)
                 }
             }
-            "stencil" if op.args.len() == 1 => {
-                // args: [grid]
-                let range = op.stencil_range.unwrap_or(1);
-                format!("crate::grid::solve_gravity_stencil(&{}, {})?", op.args[0], range)
-            }
             "add" if op.args.len() == 1 => {
                 // Assignment operation (final state update)
                 op.args[0].to_string()
@@ -686,6 +970,10 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
     if !ir.boundary_conditions.is_empty() {
         code.push_str("\n    // Boundary conditions\n");
         for bc in &ir.boundary_conditions {
+            if bc.kind == "reflective" {
+                push_reflective_boundary(&mut code, bc);
+                continue;
+            }
             if bc.kind != "torus" {
                 continue;
             }
@@ -740,10 +1028,11 @@ fn generate_dynamics(ir: &ConfigIR, out_dir: &Path) {
         shim.push_str(&format!("    p_{}: &candle_core::Tensor,\n", name));
     }
     shim.push_str(") -> candle_core::Result<candle_core::Tensor> {\n");
+    // Older callers stepped by one implicit unit of time.
     shim.push_str("    update_dynamics(state");
     for name in &group_names {
         shim.push_str(&format!(", p_{}", name));
     }
-    shim.push_str(")\n}\n");
+    shim.push_str(", 1.0, &mut 0)\n}\n");
     fs::write(out_dir.join("physics.rs"), shim).expect("Failed to write physics.rs");
 }
@@ -4,6 +4,8 @@ pub const N_AGENTS: usize = 100;
 pub const GENE_LEN: usize = 10;
 pub const HIDDEN_LEN: usize = 10;
 
+pub const STENCIL: Option<(crate::grid::SpatialGrid, usize)> = None;
+
 pub const STATE_DIMS: usize = 5;
 pub const STATE_VARS: [&str; 5] = [
     "pos_x",
@@ -13,16 +15,28 @@ pub const STATE_VARS: [&str; 5] = [
     "size",
 ];
 
+pub const STATE_LABEL_META: [(&str, &str, Option<&str>); 0] = [
+];
+
+pub const FITNESS: Option<&str> = None;
+
+pub const TORUS_RANGES: [(&str, f32, f32); 2] = [
+    ("pos_x", -500.000000, 500.000000),
+    ("pos_y", -500.000000, 500.000000),
+];
+
 #[allow(dead_code)]
+#[allow(unused_variables)]
 pub fn init_state(
     n_agents: usize,
+    rng: &mut rand::rngs::StdRng,
     device: &candle_core::Device,
 ) -> candle_core::Result<candle_core::Tensor> {
-    let init_pos_x = candle_core::Tensor::rand(-500f32, 500f32, (n_agents, 1), device)?;
-    let init_pos_y = candle_core::Tensor::rand(-500f32, 500f32, (n_agents, 1), device)?;
-    let init_vel_x = candle_core::Tensor::randn(0f32, 10f32, (n_agents, 1), device)?;
-    let init_vel_y = candle_core::Tensor::randn(0f32, 10f32, (n_agents, 1), device)?;
-    let init_size = candle_core::Tensor::rand(1f32, 10f32, (n_agents, 1), device)?;
+    let init_pos_x = crate::sampling::uniform(rng, -500f32, 500f32, (n_agents, 1), device)?;
+    let init_pos_y = crate::sampling::uniform(rng, -500f32, 500f32, (n_agents, 1), device)?;
+    let init_vel_x = crate::sampling::normal(rng, 0f32, 10f32, (n_agents, 1), device)?;
+    let init_vel_y = crate::sampling::normal(rng, 0f32, 10f32, (n_agents, 1), device)?;
+    let init_size = crate::sampling::uniform(rng, 1f32, 10f32, (n_agents, 1), device)?;
 
     candle_core::Tensor::cat(&[
         &init_pos_x,
@@ -39,6 +53,8 @@ pub fn update_dynamics(
     state: &candle_core::Tensor,
     p_physics: &candle_core::Tensor,
     p_attributes: &candle_core::Tensor,
+    dt: f32,
+    grid_overflow: &mut u64,
 ) -> candle_core::Result<candle_core::Tensor> {
     // State variable decomposition
     let s_pos_x = state.narrow(1, 0, 1)?;
@@ -53,7 +69,7 @@ pub fn update_dynamics(
 
 
     // Internal dynamics operations
-    let temp_0 = candle_core::Tensor::new(&[0.1f32], state.device())?;
+    let temp_0 = candle_core::Tensor::new(&[dt], state.device())?;
     let temp_1 = s_vel_x.broadcast_mul(&temp_0)?;
     let temp_2 = s_pos_x.broadcast_add(&temp_1)?;
     let pos_x = temp_2;
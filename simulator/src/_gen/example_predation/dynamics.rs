@@ -4,6 +4,8 @@ pub const N_AGENTS: usize = 100;
 pub const GENE_LEN: usize = 10;
 pub const HIDDEN_LEN: usize = 10;
 
+pub const STENCIL: Option<(crate::grid::SpatialGrid, usize)> = None;
+
 pub const STATE_DIMS: usize = 5;
 pub const STATE_VARS: [&str; 5] = [
     "pos_x",
@@ -13,16 +15,28 @@ pub const STATE_VARS: [&str; 5] = [
     "size",
 ];
 
+pub const STATE_LABEL_META: [(&str, &str, Option<&str>); 0] = [
+];
+
+pub const FITNESS: Option<&str> = Some("size");
+
+pub const TORUS_RANGES: [(&str, f32, f32); 2] = [
+    ("pos_x", -500.000000, 500.000000),
+    ("pos_y", -500.000000, 500.000000),
+];
+
 #[allow(dead_code)]
+#[allow(unused_variables)]
 pub fn init_state(
     n_agents: usize,
+    rng: &mut rand::rngs::StdRng,
     device: &candle_core::Device,
 ) -> candle_core::Result<candle_core::Tensor> {
-    let init_pos_x = candle_core::Tensor::rand(-500f32, 500f32, (n_agents, 1), device)?;
-    let init_pos_y = candle_core::Tensor::rand(-500f32, 500f32, (n_agents, 1), device)?;
-    let init_vel_x = candle_core::Tensor::randn(0f32, 2f32, (n_agents, 1), device)?;
-    let init_vel_y = candle_core::Tensor::randn(0f32, 2f32, (n_agents, 1), device)?;
-    let init_size = candle_core::Tensor::rand(1f32, 10f32, (n_agents, 1), device)?;
+    let init_pos_x = crate::sampling::uniform(rng, -500f32, 500f32, (n_agents, 1), device)?;
+    let init_pos_y = crate::sampling::uniform(rng, -500f32, 500f32, (n_agents, 1), device)?;
+    let init_vel_x = crate::sampling::normal(rng, 0f32, 2f32, (n_agents, 1), device)?;
+    let init_vel_y = crate::sampling::normal(rng, 0f32, 2f32, (n_agents, 1), device)?;
+    let init_size = crate::sampling::uniform(rng, 1f32, 10f32, (n_agents, 1), device)?;
 
     candle_core::Tensor::cat(&[
         &init_pos_x,
@@ -39,6 +53,8 @@ pub fn update_dynamics(
     state: &candle_core::Tensor,
     p_physics: &candle_core::Tensor,
     p_attributes: &candle_core::Tensor,
+    dt: f32,
+    grid_overflow: &mut u64,
 ) -> candle_core::Result<candle_core::Tensor> {
     // State variable decomposition
     let s_pos_x = state.narrow(1, 0, 1)?;
@@ -53,51 +69,54 @@ pub fn update_dynamics(
 
 
     // Internal dynamics operations
-    let temp_0 = s_pos_x.broadcast_add(&s_vel_x)?;
-    let pos_x = temp_0;
-    let temp_1 = s_pos_y.broadcast_add(&s_vel_y)?;
-    let pos_y = temp_1;
-    let temp_2 = candle_core::Tensor::new(&[0f32], state.device())?;
-    let temp_3 = p_dummy_phys.broadcast_mul(&temp_2)?;
-    let temp_4 = s_vel_x.broadcast_add(&temp_3)?;
-    let vel_x = temp_4;
-    let temp_5 = p_dummy_attr.broadcast_mul(&temp_2)?;
-    let temp_6 = s_vel_y.broadcast_add(&temp_5)?;
-    let vel_y = temp_6;
-    let temp_7 = s_pos_x.transpose(0, 1)?;
-    let temp_8 = s_pos_x.broadcast_sub(&temp_7)?;
-    let temp_9 = temp_8.broadcast_mul(&temp_8)?;
-    let temp_10 = s_pos_y.transpose(0, 1)?;
-    let temp_11 = s_pos_y.broadcast_sub(&temp_10)?;
+    let temp_0 = candle_core::Tensor::new(&[dt], state.device())?;
+    let temp_1 = s_vel_x.broadcast_mul(&temp_0)?;
+    let temp_2 = s_pos_x.broadcast_add(&temp_1)?;
+    let pos_x = temp_2;
+    let temp_3 = s_vel_y.broadcast_mul(&temp_0)?;
+    let temp_4 = s_pos_y.broadcast_add(&temp_3)?;
+    let pos_y = temp_4;
+    let temp_5 = candle_core::Tensor::new(&[0f32], state.device())?;
+    let temp_6 = p_dummy_phys.broadcast_mul(&temp_5)?;
+    let temp_7 = s_vel_x.broadcast_add(&temp_6)?;
+    let vel_x = temp_7;
+    let temp_8 = p_dummy_attr.broadcast_mul(&temp_5)?;
+    let temp_9 = s_vel_y.broadcast_add(&temp_8)?;
+    let vel_y = temp_9;
+    let temp_10 = s_pos_x.transpose(0, 1)?;
+    let temp_11 = s_pos_x.broadcast_sub(&temp_10)?;
     let temp_12 = temp_11.broadcast_mul(&temp_11)?;
-    let temp_13 = temp_9.broadcast_add(&temp_12)?;
-    let temp_14 = candle_core::Tensor::new(&[2500f32], state.device())?;
-    let temp_15 = {
-                    let diff = temp_13.broadcast_sub(&temp_14)?;
+    let temp_13 = s_pos_y.transpose(0, 1)?;
+    let temp_14 = s_pos_y.broadcast_sub(&temp_13)?;
+    let temp_15 = temp_14.broadcast_mul(&temp_14)?;
+    let temp_16 = temp_12.broadcast_add(&temp_15)?;
+    let temp_17 = candle_core::Tensor::new(&[2500f32], state.device())?;
+    let temp_18 = {
+                    let diff = temp_16.broadcast_sub(&temp_17)?;
                     let zeros = diff.zeros_like()?;
                     diff.lt(&zeros)?.to_dtype(candle_core::DType::F32)?
                 };
-    let temp_16 = candle_core::Tensor::new(&[1f32], state.device())?;
-    let temp_17 = temp_15.broadcast_mul(&temp_16.broadcast_sub(&temp_2)?)?.broadcast_add(&temp_2)?;
-    let temp_18 = s_size.transpose(0, 1)?;
-    let temp_19 = candle_core::Tensor::new(&[2f32], state.device())?;
-    let temp_20 = temp_18.broadcast_mul(&temp_19)?;
-    let temp_21 = {
-                    let diff = s_size.broadcast_sub(&temp_20)?;
+    let temp_19 = candle_core::Tensor::new(&[1f32], state.device())?;
+    let temp_20 = temp_18.broadcast_mul(&temp_19.broadcast_sub(&temp_5)?)?.broadcast_add(&temp_5)?;
+    let temp_21 = s_size.transpose(0, 1)?;
+    let temp_22 = candle_core::Tensor::new(&[2f32], state.device())?;
+    let temp_23 = temp_21.broadcast_mul(&temp_22)?;
+    let temp_24 = {
+                    let diff = s_size.broadcast_sub(&temp_23)?;
                     let zeros = diff.zeros_like()?;
                     diff.ge(&zeros)?.to_dtype(candle_core::DType::F32)?
                 };
-    let temp_22 = temp_21.broadcast_mul(&temp_16.broadcast_sub(&temp_2)?)?.broadcast_add(&temp_2)?;
-    let temp_23 = temp_17.broadcast_mul(&temp_22)?;
-    let temp_24 = candle_core::Tensor::new(&[0.5f32], state.device())?;
-    let temp_25 = temp_18.broadcast_mul(&temp_24)?;
-    let temp_26 = temp_23.broadcast_mul(&temp_25)?;
-    let temp_27 = temp_26.sum_keepdim(1)?;
-    let temp_28 = s_size.broadcast_add(&temp_27)?;
-    let temp_29 = temp_26.transpose(0, 1)?;
+    let temp_25 = temp_24.broadcast_mul(&temp_19.broadcast_sub(&temp_5)?)?.broadcast_add(&temp_5)?;
+    let temp_26 = temp_20.broadcast_mul(&temp_25)?;
+    let temp_27 = candle_core::Tensor::new(&[0.5f32], state.device())?;
+    let temp_28 = temp_21.broadcast_mul(&temp_27)?;
+    let temp_29 = temp_26.broadcast_mul(&temp_28)?;
     let temp_30 = temp_29.sum_keepdim(1)?;
-    let temp_31 = temp_28.broadcast_sub(&temp_30)?;
-    let size = temp_31;
+    let temp_31 = s_size.broadcast_add(&temp_30)?;
+    let temp_32 = temp_29.transpose(0, 1)?;
+    let temp_33 = temp_32.sum_keepdim(1)?;
+    let temp_34 = temp_31.broadcast_sub(&temp_33)?;
+    let size = temp_34;
 
     // Boundary conditions
     // torus wrap: pos_x in [-500.000000,500.000000]
@@ -47,10 +47,12 @@ impl PhenotypeEngine {
 }
 
 #[allow(dead_code)]
+#[allow(unused_variables)]
 pub fn init_genes(
     n_agents: usize,
     gene_len: usize,
+    rng: &mut rand::rngs::StdRng,
     device: &candle_core::Device,
 ) -> candle_core::Result<candle_core::Tensor> {
-    candle_core::Tensor::randn(0f32, 1f32, (n_agents, gene_len), device)
+    crate::sampling::normal(rng, 0f32, 1f32, (n_agents, gene_len), device)
 }
@@ -0,0 +1,210 @@
+// AUTO-GENERATED by generate-phenotype-physics.rs - DO NOT EDIT
+
+pub const N_AGENTS: usize = 100;
+pub const GENE_LEN: usize = 10;
+pub const HIDDEN_LEN: usize = 10;
+
+use crate::grid::{SpatialGrid, particles_to_grid, grid_to_particles};
+
+pub const GRID_CONFIG: SpatialGrid = SpatialGrid {
+    width: 20,
+    height: 20,
+    capacity: 8,
+    cell_size: (50.000000, 50.000000),
+    origin: (-500.000000, -500.000000),
+    boundary: crate::grid::Boundary::Reflective,
+};
+
+pub const STENCIL: Option<(crate::grid::SpatialGrid, usize)> = Some((GRID_CONFIG, 1));
+
+pub const STATE_DIMS: usize = 6;
+pub const STATE_VARS: [&str; 6] = [
+    "pos_x",
+    "pos_y",
+    "vel_x",
+    "vel_y",
+    "charge",
+    "reach",
+];
+
+pub const STATE_LABEL_META: [(&str, &str, Option<&str>); 0] = [
+];
+
+pub const FITNESS: Option<&str> = None;
+
+pub const TORUS_RANGES: [(&str, f32, f32); 0] = [
+];
+
+#[allow(dead_code)]
+#[allow(unused_variables)]
+pub fn init_state(
+    n_agents: usize,
+    rng: &mut rand::rngs::StdRng,
+    device: &candle_core::Device,
+) -> candle_core::Result<candle_core::Tensor> {
+    let init_pos_x = crate::sampling::uniform(rng, -450f32, 450f32, (n_agents, 1), device)?;
+    let init_pos_y = crate::sampling::uniform(rng, -450f32, 450f32, (n_agents, 1), device)?;
+    let init_vel_x = crate::sampling::normal(rng, 0f32, 20f32, (n_agents, 1), device)?;
+    let init_vel_y = crate::sampling::normal(rng, 0f32, 20f32, (n_agents, 1), device)?;
+    let init_charge = crate::sampling::uniform(rng, 0.5f32, 1.5f32, (n_agents, 1), device)?;
+    let init_reach = crate::sampling::uniform(rng, 20f32, 50f32, (n_agents, 1), device)?;
+
+    candle_core::Tensor::cat(&[
+        &init_pos_x,
+        &init_pos_y,
+        &init_vel_x,
+        &init_vel_y,
+        &init_charge,
+        &init_reach,
+    ], 1)
+}
+
+#[allow(dead_code)]
+#[allow(unused_variables)]
+pub fn update_dynamics(
+    state: &candle_core::Tensor,
+    p_physics: &candle_core::Tensor,
+    p_attributes: &candle_core::Tensor,
+    dt: f32,
+    grid_overflow: &mut u64,
+) -> candle_core::Result<candle_core::Tensor> {
+    // State variable decomposition
+    let s_pos_x = state.narrow(1, 0, 1)?;
+    let s_pos_y = state.narrow(1, 1, 1)?;
+    let s_vel_x = state.narrow(1, 2, 1)?;
+    let s_vel_y = state.narrow(1, 3, 1)?;
+    let s_charge = state.narrow(1, 4, 1)?;
+    let s_reach = state.narrow(1, 5, 1)?;
+
+    // Parameter decomposition
+    let p_dummy_phys = p_physics.narrow(1, 0, 1)?;
+    let p_dummy_attr = p_attributes.narrow(1, 0, 1)?;
+
+    #[allow(unused_assignments)]
+    let mut temp_6_indices: candle_core::Tensor = candle_core::Tensor::zeros(1, candle_core::DType::U32, state.device())?;
+
+    // Internal dynamics operations
+    let temp_0 = candle_core::Tensor::new(&[dt], state.device())?;
+    let temp_1 = s_vel_x.broadcast_mul(&temp_0)?;
+    let temp_2 = s_pos_x.broadcast_add(&temp_1)?;
+    let pos_x = temp_2;
+    let temp_3 = s_vel_y.broadcast_mul(&temp_0)?;
+    let temp_4 = s_pos_y.broadcast_add(&temp_3)?;
+    let pos_y = temp_4;
+    let temp_5 = candle_core::Tensor::cat(&[&s_pos_x, &s_pos_y, &s_vel_x, &s_vel_y, &s_charge, &s_reach], 1)?;
+    let temp_6 = {
+                    let (grid, _mask, indices, overflowed) = particles_to_grid(&s_pos_x, &s_pos_y, &temp_5, &GRID_CONFIG)?;
+                    *grid_overflow += overflowed;
+                    temp_6_indices = indices;
+                    grid
+                };
+    let temp_7 = {
+        let grid = &temp_6;
+        let range = 1;
+        let (h, w, cap, d) = grid.dims4()?;
+        let pad = range as usize;
+        let padded = crate::grid::create_ghost_padded_grid(grid, pad, &GRID_CONFIG, 0, 1)?;
+        let mut acc = grid.zeros_like()?;
+        for dy in -range..=range {
+            for dx in -range..=range {
+                let offset_y = (pad as i32 + dy) as usize;
+                let offset_x = (pad as i32 + dx) as usize;
+                let neighbor = padded.narrow(0, offset_y, h)?.narrow(1, offset_x, w)?;
+                let center = grid;
+                let within = {
+                    let dx = neighbor.narrow(3, 0, 1)?.transpose(2, 3)?.broadcast_sub(&center.narrow(3, 0, 1)?)?;
+                    let dy = neighbor.narrow(3, 1, 1)?.transpose(2, 3)?.broadcast_sub(&center.narrow(3, 1, 1)?)?;
+                    let r2 = center.narrow(3, 5, 1)?.sqr()?;
+                    dx.sqr()?.add(&dy.sqr()?)?.broadcast_le(&r2)?.to_dtype(candle_core::DType::F32)?
+                };
+                let temp_0 = center.narrow(3, 0, 1)?;
+                let temp_1 = candle_core::Tensor::new(&[0f32], state.device())?;
+                let temp_2 = temp_0.broadcast_mul(&temp_1)?;
+                let temp_3 = neighbor.narrow(3, 0, 1)?;
+                let temp_4 = temp_3.transpose(2, 3)?;
+                let temp_5 = temp_0.broadcast_sub(&temp_4)?;
+                let temp_6 = candle_core::Tensor::new(&[100f32], state.device())?;
+                let temp_7 = neighbor.narrow(3, 4, 1)?;
+                let temp_8 = temp_7.transpose(2, 3)?;
+                let temp_9 = temp_6.broadcast_mul(&temp_8)?;
+                let temp_10 = temp_5.broadcast_mul(&temp_5)?;
+                let temp_11 = center.narrow(3, 1, 1)?;
+                let temp_12 = neighbor.narrow(3, 1, 1)?;
+                let temp_13 = temp_12.transpose(2, 3)?;
+                let temp_14 = temp_11.broadcast_sub(&temp_13)?;
+                let temp_15 = temp_14.broadcast_mul(&temp_14)?;
+                let temp_16 = temp_10.broadcast_add(&temp_15)?;
+                let temp_17 = candle_core::Tensor::new(&[1f32], state.device())?;
+                let temp_18 = temp_16.broadcast_add(&temp_17)?;
+                let temp_19 = temp_9.broadcast_div(&temp_18)?;
+                let temp_20 = temp_19.broadcast_mul(&within)?;
+                let temp_21 = temp_5.broadcast_mul(&temp_20)?;
+                let temp_22 = temp_21.sum_keepdim(3)?;
+                let temp_23 = temp_14.broadcast_mul(&temp_20)?;
+                let temp_24 = temp_23.sum_keepdim(3)?;
+                let temp_25 = candle_core::Tensor::cat(&[&temp_2, &temp_2, &temp_22, &temp_24, &temp_2, &temp_2], 3)?;
+                let kernel_output = temp_25;
+                acc = acc.add(&kernel_output)?;
+            }
+        }
+        acc
+    };
+    let temp_8 = grid_to_particles(&temp_7, &temp_6_indices)?;
+    let temp_9 = temp_8.narrow(1, 2, 1)?;
+    let temp_10 = s_charge.broadcast_mul(&temp_9)?;
+    let temp_11 = candle_core::Tensor::new(&[0f32], state.device())?;
+    let temp_12 = p_dummy_phys.broadcast_mul(&temp_11)?;
+    let temp_13 = p_dummy_attr.broadcast_mul(&temp_11)?;
+    let temp_14 = temp_12.broadcast_add(&temp_13)?;
+    let temp_15 = temp_10.broadcast_add(&temp_14)?;
+    let temp_16 = temp_15.broadcast_mul(&temp_0)?;
+    let temp_17 = s_vel_x.broadcast_add(&temp_16)?;
+    let vel_x = temp_17;
+    let temp_18 = temp_8.narrow(1, 3, 1)?;
+    let temp_19 = s_charge.broadcast_mul(&temp_18)?;
+    let temp_20 = temp_19.broadcast_add(&temp_14)?;
+    let temp_21 = temp_20.broadcast_mul(&temp_0)?;
+    let temp_22 = s_vel_y.broadcast_add(&temp_21)?;
+    let vel_y = temp_22;
+    let charge = s_charge;
+    let reach = s_reach;
+
+    // Boundary conditions
+    // reflective walls: pos_x in [-500.000000,500.000000]
+    let (pos_x, vel_x) = {
+        let min = candle_core::Tensor::new(&[-500.000000f32], state.device())?;
+        let width = candle_core::Tensor::new(&[1000.000000f32], state.device())?;
+        let period = candle_core::Tensor::new(&[2000.000000f32], state.device())?;
+        let norm = pos_x.broadcast_sub(&min)?;
+        let floor = norm.broadcast_div(&period)?.floor()?;
+        let rem = norm.broadcast_sub(&floor.broadcast_mul(&period)?)?;
+        let mirrored = rem.broadcast_gt(&width)?.to_dtype(candle_core::DType::F32)?;
+        let folded = mirrored.broadcast_mul(&period.broadcast_sub(&rem)?.broadcast_sub(&rem)?)?.broadcast_add(&rem)?;
+        let sign = mirrored.affine(-2.0, 1.0)?;
+        (folded.broadcast_add(&min)?, vel_x.broadcast_mul(&sign)?)
+    };
+    // reflective walls: pos_y in [-500.000000,500.000000]
+    let (pos_y, vel_y) = {
+        let min = candle_core::Tensor::new(&[-500.000000f32], state.device())?;
+        let width = candle_core::Tensor::new(&[1000.000000f32], state.device())?;
+        let period = candle_core::Tensor::new(&[2000.000000f32], state.device())?;
+        let norm = pos_y.broadcast_sub(&min)?;
+        let floor = norm.broadcast_div(&period)?.floor()?;
+        let rem = norm.broadcast_sub(&floor.broadcast_mul(&period)?)?;
+        let mirrored = rem.broadcast_gt(&width)?.to_dtype(candle_core::DType::F32)?;
+        let folded = mirrored.broadcast_mul(&period.broadcast_sub(&rem)?.broadcast_sub(&rem)?)?.broadcast_add(&rem)?;
+        let sign = mirrored.affine(-2.0, 1.0)?;
+        (folded.broadcast_add(&min)?, vel_y.broadcast_mul(&sign)?)
+    };
+
+    // Concatenate updated state
+    let n_agents = state.dim(0)?;
+    candle_core::Tensor::cat(&[
+        &pos_x.broadcast_as((n_agents, 1))?,
+        &pos_y.broadcast_as((n_agents, 1))?,
+        &vel_x.broadcast_as((n_agents, 1))?,
+        &vel_y.broadcast_as((n_agents, 1))?,
+        &charge.broadcast_as((n_agents, 1))?,
+        &reach.broadcast_as((n_agents, 1))?,
+    ], 1)
+}
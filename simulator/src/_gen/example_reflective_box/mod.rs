@@ -0,0 +1,2 @@
+pub mod phenotype;
+pub mod dynamics;
@@ -1,17 +1,27 @@
+pub mod example_conditional;
+pub mod example_predation;
+pub mod example_reflective_box;
 pub mod universal_gravitation;
 pub mod universal_gravitation_fixed_capacity_grid;
-pub mod example_predation;
-pub mod example_conditional;
+
+pub const DEFINITIONS: [&str; 5] = [
+    "example_conditional",
+    "example_predation",
+    "example_reflective_box",
+    "universal_gravitation",
+    "universal_gravitation_fixed_capacity_grid",
+];
 
 #[macro_export]
 macro_rules! with_definition {
-    ($name:expr, $callback:path) => {
+    ($name:expr, $callback:path, $unknown:expr) => {
         match $name.as_str() {
+            "example_conditional" => { use $crate::_gen::example_conditional as def; $callback!(def) },
+            "example_predation" => { use $crate::_gen::example_predation as def; $callback!(def) },
+            "example_reflective_box" => { use $crate::_gen::example_reflective_box as def; $callback!(def) },
             "universal_gravitation" => { use $crate::_gen::universal_gravitation as def; $callback!(def) },
             "universal_gravitation_fixed_capacity_grid" => { use $crate::_gen::universal_gravitation_fixed_capacity_grid as def; $callback!(def) },
-            "example_predation" => { use $crate::_gen::example_predation as def; $callback!(def) },
-            "example_conditional" => { use $crate::_gen::example_conditional as def; $callback!(def) },
-            _ => panic!("Unknown definition: {}", $name),
+            _ => $unknown,
         }
-    }
+    };
 }
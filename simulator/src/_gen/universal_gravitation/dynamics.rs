@@ -4,6 +4,8 @@ pub const N_AGENTS: usize = 10000;
 pub const GENE_LEN: usize = 32;
 pub const HIDDEN_LEN: usize = 64;
 
+pub const STENCIL: Option<(crate::grid::SpatialGrid, usize)> = None;
+
 pub const STATE_DIMS: usize = 5;
 pub const STATE_VARS: [&str; 5] = [
     "pos_x",
@@ -13,16 +15,33 @@ pub const STATE_VARS: [&str; 5] = [
     "size",
 ];
 
+pub const STATE_LABEL_META: [(&str, &str, Option<&str>); 5] = [
+    ("pos_x", "Position X", None),
+    ("pos_y", "Position Y", None),
+    ("size", "Mass", None),
+    ("vel_x", "Velocity X", Some("per second")),
+    ("vel_y", "Velocity Y", Some("per second")),
+];
+
+pub const FITNESS: Option<&str> = None;
+
+pub const TORUS_RANGES: [(&str, f32, f32); 2] = [
+    ("pos_x", -5120.000000, 5120.000000),
+    ("pos_y", -4000.000000, 4000.000000),
+];
+
 #[allow(dead_code)]
+#[allow(unused_variables)]
 pub fn init_state(
     n_agents: usize,
+    rng: &mut rand::rngs::StdRng,
     device: &candle_core::Device,
 ) -> candle_core::Result<candle_core::Tensor> {
-    let init_pos_x = candle_core::Tensor::rand(-200f32, 200f32, (n_agents, 1), device)?;
-    let init_pos_y = candle_core::Tensor::rand(-200f32, 200f32, (n_agents, 1), device)?;
-    let init_vel_x = candle_core::Tensor::randn(0f32, 10f32, (n_agents, 1), device)?;
-    let init_vel_y = candle_core::Tensor::randn(0f32, 10f32, (n_agents, 1), device)?;
-    let init_size = candle_core::Tensor::rand(1f32, 10f32, (n_agents, 1), device)?;
+    let init_pos_x = crate::sampling::uniform(rng, -200f32, 200f32, (n_agents, 1), device)?;
+    let init_pos_y = crate::sampling::uniform(rng, -200f32, 200f32, (n_agents, 1), device)?;
+    let init_vel_x = crate::sampling::normal(rng, 0f32, 10f32, (n_agents, 1), device)?;
+    let init_vel_y = crate::sampling::normal(rng, 0f32, 10f32, (n_agents, 1), device)?;
+    let init_size = crate::sampling::uniform(rng, 1f32, 10f32, (n_agents, 1), device)?;
 
     candle_core::Tensor::cat(&[
         &init_pos_x,
@@ -39,6 +58,8 @@ pub fn update_dynamics(
     state: &candle_core::Tensor,
     p_physics: &candle_core::Tensor,
     p_attributes: &candle_core::Tensor,
+    dt: f32,
+    grid_overflow: &mut u64,
 ) -> candle_core::Result<candle_core::Tensor> {
     // State variable decomposition
     let s_pos_x = state.narrow(1, 0, 1)?;
@@ -53,39 +74,44 @@ pub fn update_dynamics(
 
 
     // Internal dynamics operations
-    let temp_0 = s_pos_x.broadcast_add(&s_vel_x)?;
-    let pos_x = temp_0;
-    let temp_1 = s_pos_y.broadcast_add(&s_vel_y)?;
-    let pos_y = temp_1;
-    let temp_2 = candle_core::Tensor::new(&[1f32], state.device())?;
-    let temp_3 = candle_core::Tensor::new(&[0f32], state.device())?;
-    let temp_4 = p_grav_g.broadcast_mul(&temp_3)?;
-    let temp_5 = p_dummy_attr.broadcast_mul(&temp_3)?;
-    let temp_6 = temp_4.broadcast_add(&temp_5)?;
-    let temp_7 = temp_2.broadcast_add(&temp_6)?;
-    let temp_8 = s_size.transpose(0, 1)?;
-    let temp_9 = s_pos_x.transpose(0, 1)?;
-    let temp_10 = temp_9.broadcast_sub(&s_pos_x)?;
-    let temp_11 = temp_8.broadcast_mul(&temp_10)?;
-    let temp_12 = temp_10.broadcast_mul(&temp_10)?;
-    let temp_13 = s_pos_y.transpose(0, 1)?;
-    let temp_14 = temp_13.broadcast_sub(&s_pos_y)?;
-    let temp_15 = temp_14.broadcast_mul(&temp_14)?;
-    let temp_16 = temp_12.broadcast_add(&temp_15)?;
-    let temp_17 = candle_core::Tensor::new(&[0.0001f32], state.device())?;
-    let temp_18 = temp_16.broadcast_add(&temp_17)?;
-    let temp_19 = temp_2.broadcast_div(&temp_18)?;
-    let temp_20 = temp_11.broadcast_mul(&temp_19)?;
-    let temp_21 = temp_20.sum_keepdim(1)?;
-    let temp_22 = temp_7.broadcast_mul(&temp_21)?;
-    let temp_23 = s_vel_x.broadcast_add(&temp_22)?;
-    let vel_x = temp_23;
-    let temp_24 = temp_8.broadcast_mul(&temp_14)?;
-    let temp_25 = temp_24.broadcast_mul(&temp_19)?;
-    let temp_26 = temp_25.sum_keepdim(1)?;
-    let temp_27 = temp_7.broadcast_mul(&temp_26)?;
-    let temp_28 = s_vel_y.broadcast_add(&temp_27)?;
-    let vel_y = temp_28;
+    let temp_0 = candle_core::Tensor::new(&[dt], state.device())?;
+    let temp_1 = s_vel_x.broadcast_mul(&temp_0)?;
+    let temp_2 = s_pos_x.broadcast_add(&temp_1)?;
+    let pos_x = temp_2;
+    let temp_3 = s_vel_y.broadcast_mul(&temp_0)?;
+    let temp_4 = s_pos_y.broadcast_add(&temp_3)?;
+    let pos_y = temp_4;
+    let temp_5 = candle_core::Tensor::new(&[1f32], state.device())?;
+    let temp_6 = candle_core::Tensor::new(&[0f32], state.device())?;
+    let temp_7 = p_grav_g.broadcast_mul(&temp_6)?;
+    let temp_8 = p_dummy_attr.broadcast_mul(&temp_6)?;
+    let temp_9 = temp_7.broadcast_add(&temp_8)?;
+    let temp_10 = temp_5.broadcast_add(&temp_9)?;
+    let temp_11 = s_size.transpose(0, 1)?;
+    let temp_12 = s_pos_x.transpose(0, 1)?;
+    let temp_13 = temp_12.broadcast_sub(&s_pos_x)?;
+    let temp_14 = temp_11.broadcast_mul(&temp_13)?;
+    let temp_15 = temp_13.broadcast_mul(&temp_13)?;
+    let temp_16 = s_pos_y.transpose(0, 1)?;
+    let temp_17 = temp_16.broadcast_sub(&s_pos_y)?;
+    let temp_18 = temp_17.broadcast_mul(&temp_17)?;
+    let temp_19 = temp_15.broadcast_add(&temp_18)?;
+    let temp_20 = candle_core::Tensor::new(&[0.0001f32], state.device())?;
+    let temp_21 = temp_19.broadcast_add(&temp_20)?;
+    let temp_22 = temp_5.broadcast_div(&temp_21)?;
+    let temp_23 = temp_14.broadcast_mul(&temp_22)?;
+    let temp_24 = temp_23.sum_keepdim(1)?;
+    let temp_25 = temp_10.broadcast_mul(&temp_24)?;
+    let temp_26 = temp_25.broadcast_mul(&temp_0)?;
+    let temp_27 = s_vel_x.broadcast_add(&temp_26)?;
+    let vel_x = temp_27;
+    let temp_28 = temp_11.broadcast_mul(&temp_17)?;
+    let temp_29 = temp_28.broadcast_mul(&temp_22)?;
+    let temp_30 = temp_29.sum_keepdim(1)?;
+    let temp_31 = temp_10.broadcast_mul(&temp_30)?;
+    let temp_32 = temp_31.broadcast_mul(&temp_0)?;
+    let temp_33 = s_vel_y.broadcast_add(&temp_32)?;
+    let vel_y = temp_33;
     let size = s_size;
 
     // Boundary conditions
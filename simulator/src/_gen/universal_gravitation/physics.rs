@@ -8,5 +8,5 @@ pub fn update_physics(
     p_physics: &candle_core::Tensor,
     p_attributes: &candle_core::Tensor,
 ) -> candle_core::Result<candle_core::Tensor> {
-    update_dynamics(state, p_physics, p_attributes)
+    update_dynamics(state, p_physics, p_attributes, 1.0, &mut 0)
 }
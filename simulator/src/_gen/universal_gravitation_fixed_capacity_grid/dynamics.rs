@@ -11,8 +11,12 @@ pub const GRID_CONFIG: SpatialGrid = SpatialGrid {
     height: 64,
     capacity: 8,
     cell_size: (128.000000, 125.000000),
+    origin: (-5120.000000, -4000.000000),
+    boundary: crate::grid::Boundary::Torus,
 };
 
+pub const STENCIL: Option<(crate::grid::SpatialGrid, usize)> = Some((GRID_CONFIG, 1));
+
 pub const STATE_DIMS: usize = 5;
 pub const STATE_VARS: [&str; 5] = [
     "pos_x",
@@ -22,16 +26,28 @@ pub const STATE_VARS: [&str; 5] = [
     "size",
 ];
 
+pub const STATE_LABEL_META: [(&str, &str, Option<&str>); 0] = [
+];
+
+pub const FITNESS: Option<&str> = None;
+
+pub const TORUS_RANGES: [(&str, f32, f32); 2] = [
+    ("pos_x", -5120.000000, 5120.000000),
+    ("pos_y", -4000.000000, 4000.000000),
+];
+
 #[allow(dead_code)]
+#[allow(unused_variables)]
 pub fn init_state(
     n_agents: usize,
+    rng: &mut rand::rngs::StdRng,
     device: &candle_core::Device,
 ) -> candle_core::Result<candle_core::Tensor> {
-    let init_pos_x = candle_core::Tensor::rand(-200f32, 200f32, (n_agents, 1), device)?;
-    let init_pos_y = candle_core::Tensor::rand(-200f32, 200f32, (n_agents, 1), device)?;
-    let init_vel_x = candle_core::Tensor::randn(0f32, 10f32, (n_agents, 1), device)?;
-    let init_vel_y = candle_core::Tensor::randn(0f32, 10f32, (n_agents, 1), device)?;
-    let init_size = candle_core::Tensor::rand(1f32, 10f32, (n_agents, 1), device)?;
+    let init_pos_x = crate::sampling::uniform(rng, -200f32, 200f32, (n_agents, 1), device)?;
+    let init_pos_y = crate::sampling::uniform(rng, -200f32, 200f32, (n_agents, 1), device)?;
+    let init_vel_x = crate::sampling::normal(rng, 0f32, 10f32, (n_agents, 1), device)?;
+    let init_vel_y = crate::sampling::normal(rng, 0f32, 10f32, (n_agents, 1), device)?;
+    let init_size = crate::sampling::uniform(rng, 1f32, 10f32, (n_agents, 1), device)?;
 
     candle_core::Tensor::cat(&[
         &init_pos_x,
@@ -48,6 +64,8 @@ pub fn update_dynamics(
     state: &candle_core::Tensor,
     p_physics: &candle_core::Tensor,
     p_attributes: &candle_core::Tensor,
+    dt: f32,
+    grid_overflow: &mut u64,
 ) -> candle_core::Result<candle_core::Tensor> {
     // State variable decomposition
     let s_pos_x = state.narrow(1, 0, 1)?;
@@ -61,77 +79,42 @@ pub fn update_dynamics(
     let p_dummy_attr = p_attributes.narrow(1, 0, 1)?;
 
     #[allow(unused_assignments)]
-    let mut temp_3_indices: candle_core::Tensor = candle_core::Tensor::zeros(1, candle_core::DType::U32, state.device())?;
+    let mut temp_6_indices: candle_core::Tensor = candle_core::Tensor::zeros(1, candle_core::DType::U32, state.device())?;
+    #[allow(unused_assignments)]
+    let mut temp_6_mask: candle_core::Tensor = candle_core::Tensor::zeros(1, candle_core::DType::F32, state.device())?;
 
     // Internal dynamics operations
-    let temp_0 = s_pos_x.broadcast_add(&s_vel_x)?;
-    let pos_x = temp_0;
-    let temp_1 = s_pos_y.broadcast_add(&s_vel_y)?;
-    let pos_y = temp_1;
-    let temp_2 = candle_core::Tensor::cat(&[&s_pos_x, &s_pos_y, &s_vel_x, &s_vel_y, &s_size], 1)?;
-    let temp_3 = {
-                    let (grid, _mask, indices) = particles_to_grid(&s_pos_x, &s_pos_y, &temp_2, &GRID_CONFIG)?;
-                    temp_3_indices = indices;
+    let temp_0 = candle_core::Tensor::new(&[dt], state.device())?;
+    let temp_1 = s_vel_x.broadcast_mul(&temp_0)?;
+    let temp_2 = s_pos_x.broadcast_add(&temp_1)?;
+    let pos_x = temp_2;
+    let temp_3 = s_vel_y.broadcast_mul(&temp_0)?;
+    let temp_4 = s_pos_y.broadcast_add(&temp_3)?;
+    let pos_y = temp_4;
+    let temp_5 = candle_core::Tensor::cat(&[&s_pos_x, &s_pos_y, &s_vel_x, &s_vel_y, &s_size], 1)?;
+    let temp_6 = {
+                    let (grid, mask, indices, overflowed) = particles_to_grid(&s_pos_x, &s_pos_y, &temp_5, &GRID_CONFIG)?;
+                    *grid_overflow += overflowed;
+                    temp_6_indices = indices;
+                    temp_6_mask = mask;
                     grid
                 };
-    let temp_4 = {
-        let grid = &temp_3;
-        let range = 1;
-        let (h, w, cap, d) = grid.dims4()?;
-        let pad = range as usize;
-        let padded = crate::grid::create_torus_padded_grid(grid, pad)?;
-        let mut acc = grid.zeros_like()?;
-        for dy in -range..=range {
-            for dx in -range..=range {
-                let offset_y = (pad as i32 + dy) as usize;
-                let offset_x = (pad as i32 + dx) as usize;
-                let neighbor = padded.narrow(0, offset_y, h)?.narrow(1, offset_x, w)?;
-                let center = grid;
-                let temp_0 = center.narrow(3, 0, 1)?;
-                let temp_1 = candle_core::Tensor::new(&[0f32], state.device())?;
-                let temp_2 = temp_0.broadcast_mul(&temp_1)?;
-                let temp_3 = neighbor.narrow(3, 4, 1)?;
-                let temp_4 = temp_3.transpose(2, 3)?;
-                let temp_5 = neighbor.narrow(3, 0, 1)?;
-                let temp_6 = temp_5.transpose(2, 3)?;
-                let temp_7 = temp_6.broadcast_sub(&temp_0)?;
-                let temp_8 = temp_4.broadcast_mul(&temp_7)?;
-                let temp_9 = candle_core::Tensor::new(&[1f32], state.device())?;
-                let temp_10 = temp_7.broadcast_mul(&temp_7)?;
-                let temp_11 = neighbor.narrow(3, 1, 1)?;
-                let temp_12 = temp_11.transpose(2, 3)?;
-                let temp_13 = center.narrow(3, 1, 1)?;
-                let temp_14 = temp_12.broadcast_sub(&temp_13)?;
-                let temp_15 = temp_14.broadcast_mul(&temp_14)?;
-                let temp_16 = temp_10.broadcast_add(&temp_15)?;
-                let temp_17 = candle_core::Tensor::new(&[0.01f32], state.device())?;
-                let temp_18 = temp_16.broadcast_add(&temp_17)?;
-                let temp_19 = temp_9.broadcast_div(&temp_18)?;
-                let temp_20 = temp_8.broadcast_mul(&temp_19)?;
-                let temp_21 = temp_20.sum_keepdim(3)?;
-                let temp_22 = temp_4.broadcast_mul(&temp_14)?;
-                let temp_23 = temp_22.broadcast_mul(&temp_19)?;
-                let temp_24 = temp_23.sum_keepdim(3)?;
-                let temp_25 = candle_core::Tensor::cat(&[&temp_2, &temp_2, &temp_21, &temp_24, &temp_2], 3)?;
-                let kernel_output = temp_25;
-                acc = acc.add(&kernel_output)?;
-            }
-        }
-        acc
-    };
-    let temp_5 = grid_to_particles(&temp_4, &temp_3_indices)?;
-    let temp_6 = temp_5.narrow(1, 2, 1)?;
-    let temp_7 = candle_core::Tensor::new(&[0f32], state.device())?;
-    let temp_8 = p_grav_g.broadcast_mul(&temp_7)?;
-    let temp_9 = p_dummy_attr.broadcast_mul(&temp_7)?;
-    let temp_10 = temp_8.broadcast_add(&temp_9)?;
-    let temp_11 = temp_6.broadcast_add(&temp_10)?;
-    let temp_12 = s_vel_x.broadcast_add(&temp_11)?;
-    let vel_x = temp_12;
-    let temp_13 = temp_5.narrow(1, 3, 1)?;
-    let temp_14 = temp_13.broadcast_add(&temp_10)?;
-    let temp_15 = s_vel_y.broadcast_add(&temp_14)?;
-    let vel_y = temp_15;
+    let temp_7 = crate::grid::solve_stencil(&temp_6, &temp_6_mask, &GRID_CONFIG, 1, crate::grid::ForceLaw::InverseSquare(crate::grid::GravityParams { softening: 0.01, g: 1.0 }), &crate::grid::StencilColumns { pos_x: 0, pos_y: 1, mass: 4, radius: None })?;
+    let temp_8 = grid_to_particles(&temp_7, &temp_6_indices)?;
+    let temp_9 = temp_8.narrow(1, 0, 1)?;
+    let temp_10 = candle_core::Tensor::new(&[0f32], state.device())?;
+    let temp_11 = p_grav_g.broadcast_mul(&temp_10)?;
+    let temp_12 = p_dummy_attr.broadcast_mul(&temp_10)?;
+    let temp_13 = temp_11.broadcast_add(&temp_12)?;
+    let temp_14 = temp_9.broadcast_add(&temp_13)?;
+    let temp_15 = temp_14.broadcast_mul(&temp_0)?;
+    let temp_16 = s_vel_x.broadcast_add(&temp_15)?;
+    let vel_x = temp_16;
+    let temp_17 = temp_8.narrow(1, 1, 1)?;
+    let temp_18 = temp_17.broadcast_add(&temp_13)?;
+    let temp_19 = temp_18.broadcast_mul(&temp_0)?;
+    let temp_20 = s_vel_y.broadcast_add(&temp_19)?;
+    let vel_y = temp_20;
     let size = s_size;
 
     // Boundary conditions
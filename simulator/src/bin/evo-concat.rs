@@ -0,0 +1,330 @@
+// Concatenates .evo recordings of one run (e.g. resumed segments) into a single timeline
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use evolimo_simulator::reader::EvoReader;
+use evolimo_simulator::recorder::{EvoHeader, EvoRecorder, FORMAT_VERSION};
+
+#[derive(Debug, Parser)]
+#[command(name = "evo-concat")]
+struct Args {
+    /// Output .evo file
+    output: PathBuf,
+
+    /// Input .evo files, in timeline order
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+}
+
+/// Fields that must agree for two recordings to form one timeline, described as
+/// `field: a vs b` for each mismatch. Files written before the definition was
+/// recorded match any definition.
+fn header_diff(a: &EvoHeader, b: &EvoHeader) -> Vec<String> {
+    let mut diff = Vec::new();
+    let mut check = |field: &str, a: String, b: String| {
+        if a != b {
+            diff.push(format!("{field}: {a} vs {b}"));
+        }
+    };
+    if !a.def_name.is_empty() && !b.def_name.is_empty() {
+        check("def_name", a.def_name.clone(), b.def_name.clone());
+    }
+    check(
+        "n_agents",
+        a.config.n_agents.to_string(),
+        b.config.n_agents.to_string(),
+    );
+    check(
+        "state_dims",
+        a.config.state_dims.to_string(),
+        b.config.state_dims.to_string(),
+    );
+    check(
+        "state_labels",
+        format!("{:?}", a.config.state_labels),
+        format!("{:?}", b.config.state_labels),
+    );
+    check(
+        "torus_ranges",
+        format!("{:?}", a.config.torus_ranges),
+        format!("{:?}", b.config.torus_ranges),
+    );
+    check(
+        "clamps",
+        format!("{:?}", a.config.clamps),
+        format!("{:?}", b.config.clamps),
+    );
+    check(
+        "layout",
+        format!("{:?}", a.layout),
+        format!("{:?}", b.layout),
+    );
+    check("dtype", format!("{:?}", a.dtype), format!("{:?}", b.dtype));
+    check("dt", a.playback.dt.to_string(), b.playback.dt.to_string());
+    check(
+        "substeps",
+        a.playback.substeps.to_string(),
+        b.playback.substeps.to_string(),
+    );
+    check(
+        "save_interval",
+        a.playback.save_interval.to_string(),
+        b.playback.save_interval.to_string(),
+    );
+    diff
+}
+
+/// Writes the frames of `inputs`, in order, to `output` and returns how many
+/// there are.
+fn concat(output: &Path, inputs: &[PathBuf]) -> Result<u64> {
+    if let Ok(output_path) = output.canonicalize() {
+        if inputs
+            .iter()
+            .any(|p| p.canonicalize().ok().as_ref() == Some(&output_path))
+        {
+            bail!("output {:?} is also an input", output);
+        }
+    }
+
+    let mut readers = inputs
+        .iter()
+        .map(|path| EvoReader::open(path).with_context(|| format!("failed to read {:?}", path)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let base = readers[0].header.clone();
+    for (path, reader) in inputs.iter().zip(&readers).skip(1) {
+        let diff = header_diff(&base, &reader.header);
+        if !diff.is_empty() {
+            bail!(
+                "{:?} is incompatible with {:?}:\n  {}",
+                path,
+                inputs[0],
+                diff.join("\n  ")
+            );
+        }
+    }
+    // Older files are read as they were written; the output is rewritten in the
+    // current format either way.
+    if readers.iter().any(|r| r.header.version != base.version) {
+        eprintln!("ℹ️  Inputs mix format versions; writing version {FORMAT_VERSION}:");
+        for (path, reader) in inputs.iter().zip(&readers) {
+            eprintln!("   {:?}: version {}", path, reader.header.version);
+        }
+    }
+    // Frames from different backends can differ slightly; allowed, but flagged.
+    let mixed_runtimes = readers
+        .iter()
+        .any(|r| r.header.runtime != readers[0].header.runtime);
+    if mixed_runtimes {
+        eprintln!("⚠️  Inputs were recorded on different backends:");
+        for (path, reader) in inputs.iter().zip(&readers) {
+            match &reader.header.runtime {
+                Some(runtime) => eprintln!(
+                    "   {:?}: {} (candle {})",
+                    path, runtime.device, runtime.backend_version
+                ),
+                None => eprintln!("   {:?}: unknown", path),
+            }
+        }
+    }
+
+    let total_frames: u64 = readers.iter().map(EvoReader::total_frames).sum();
+    let mut header = base;
+    header.playback.total_frames = Some(total_frames);
+    // Concatenated shards make a standalone file.
+    header.shard = None;
+    // Frames are rewritten, so the output is in the current format.
+    header.version = FORMAT_VERSION;
+    // Older inputs have no definition; any other input's names the timeline.
+    if let Some(named) = readers.iter().find(|r| !r.header.def_name.is_empty()) {
+        header.def_name = named.header.def_name.clone();
+    }
+    if mixed_runtimes {
+        header.runtime = None;
+    }
+    // No one seed reproduces runs started from different ones.
+    if readers.iter().any(|r| r.header.seed != header.seed) {
+        header.seed = None;
+    }
+
+    // Keep the timing track only if every input has a complete one.
+    let compute_times: Option<Vec<Vec<f32>>> = readers
+        .iter()
+        .map(|r| {
+            r.compute_times()
+                .filter(|t| t.len() as u64 == r.total_frames())
+        })
+        .collect();
+    if compute_times.is_none() && readers.iter().any(|r| r.compute_times().is_some()) {
+        eprintln!("⚠️  Not all inputs have complete compute times; dropping the timing track");
+    }
+    // Likewise the generation track, which only evolving runs have.
+    let generations: Option<Vec<Vec<u64>>> = readers
+        .iter()
+        .map(|r| {
+            r.generations()
+                .filter(|g| g.len() as u64 == r.total_frames())
+        })
+        .collect();
+    if generations.is_none() && readers.iter().any(|r| r.generations().is_some()) {
+        eprintln!("⚠️  Not all inputs have complete generations; dropping the generation track");
+    }
+
+    let mut recorder = EvoRecorder::create(output, header)?;
+    for reader in &mut readers {
+        let frames = 0..reader.total_frames();
+        reader.copy_frames(frames, &mut recorder)?;
+    }
+    for seconds in compute_times.into_iter().flatten().flatten() {
+        recorder.record_compute_time(seconds);
+    }
+    for generation in generations.into_iter().flatten().flatten() {
+        recorder.record_generation(generation);
+    }
+    // Non-uniform spacing in any input needs a step track for the whole timeline;
+    // each input continues one save interval after the previous one's last frame.
+    if readers.iter().any(|r| r.sim_steps().is_some()) {
+        let save_interval = readers[0].header.playback.save_interval;
+        let mut offset = 0;
+        for reader in &readers {
+            let steps = reader.frame_steps();
+            for &step in &steps {
+                recorder.record_sim_step(offset + step);
+            }
+            if let Some(last) = steps.last() {
+                offset += last + save_interval;
+            }
+        }
+    }
+    recorder.finish()?;
+    Ok(recorder.frames_written())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let frames = concat(&args.output, &args.inputs)?;
+    eprintln!(
+        "✅ Concatenated {} files ({frames} frames) into {}",
+        args.inputs.len(),
+        args.output.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    use evolimo_simulator::recorder::{EvoConfig, FrameCompression, PlaybackMeta};
+
+    /// A `"test"` header of `n_agents` agents with state `labels`, one frame per
+    /// unit step and no frame count.
+    fn test_header(labels: &[&str], n_agents: usize) -> EvoHeader {
+        let config = EvoConfig {
+            n_agents,
+            state_dims: labels.len(),
+            state_labels: labels.iter().map(|s| s.to_string()).collect(),
+            torus_ranges: BTreeMap::new(),
+            label_meta: BTreeMap::new(),
+            clamps: BTreeMap::new(),
+        };
+        EvoHeader::new("test", config, PlaybackMeta::legacy(Some(1.0)))
+    }
+
+    /// A version 1 file as written before the frame index, `def_name` and
+    /// `playback`, with `dt` in its config (like the checked-in `sim_output.evo`):
+    /// the header, then raw frames.
+    fn write_v1(path: &Path, dt: f64, frames: &[[f32; 2]]) -> Result<()> {
+        let header = format!(
+            r#"{{"version":1,"timestamp":"2025-12-16T12:03:24.777723+00:00","config":{{"n_agents":1,"state_dims":2,"state_labels":["pos_x","pos_y"],"dt":{dt}}}}}"#
+        );
+        let mut bytes = b"EVO1".to_vec();
+        bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        for value in frames.iter().flatten() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    fn write_v2(path: &Path, state_labels: &[&str], frames: &[[f32; 2]]) -> Result<()> {
+        let mut header = test_header(state_labels, 1);
+        header.def_name = "universal_gravitation".to_string();
+        header.playback.dt = 0.1;
+        header.compression = FrameCompression::Deflate;
+        let mut recorder = EvoRecorder::create(path, header)?;
+        for frame in frames {
+            recorder.write_frame_f32(frame)?;
+        }
+        recorder.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn joins_files_of_different_format_versions() -> Result<()> {
+        let dir = std::env::temp_dir().join("evo_concat_test");
+        std::fs::create_dir_all(&dir)?;
+        let (old, new, output) = (dir.join("0.evo"), dir.join("1.evo"), dir.join("out.evo"));
+        write_v1(&old, 0.1, &[[0.0, 1.0], [2.0, 3.0]])?;
+        write_v2(&new, &["pos_x", "pos_y"], &[[4.0, 5.0]])?;
+
+        assert_eq!(concat(&output, &[old.clone(), new.clone()])?, 3);
+        let mut reader = EvoReader::open(&output)?;
+        assert_eq!(reader.header.version, FORMAT_VERSION);
+        assert_eq!(reader.header.def_name, "universal_gravitation");
+        // Playback comes from the first input's legacy `config.dt`.
+        let playback = PlaybackMeta {
+            total_frames: Some(3),
+            ..PlaybackMeta::legacy(Some(0.1))
+        };
+        assert_eq!(reader.header.playback, playback);
+        assert!(reader.has_index());
+        assert!(reader.verify_checksum()?);
+        let mut buf = Vec::new();
+        for (i, first) in [0.0f32, 2.0, 4.0].into_iter().enumerate() {
+            reader.read_frame_bytes(i as u64, &mut buf)?;
+            assert_eq!(buf[..4], first.to_le_bytes(), "frame {i}");
+        }
+
+        write_v2(&new, &["pos_x", "energy"], &[[4.0, 5.0]])?;
+        let err = concat(&output, &[old.clone(), new.clone()])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("state_labels"), "{err}");
+
+        // A legacy file's `dt` is checked like any other.
+        write_v1(&old, 0.2, &[[0.0, 1.0]])?;
+        write_v2(&new, &["pos_x", "pos_y"], &[[4.0, 5.0]])?;
+        let err = concat(&output, &[old, new]).unwrap_err().to_string();
+        assert!(err.contains("dt: 0.2 vs 0.1"), "{err}");
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn joins_recordings_written_before_playback_was_recorded() -> Result<()> {
+        // The checked-in recording has no `playback`, and `dt` in its config.
+        let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("../sim_output.evo");
+        let output = std::env::temp_dir().join("evo_concat_legacy_out.evo");
+        assert_eq!(concat(&output, &[input.clone(), input.clone()])?, 20);
+
+        let mut legacy = EvoReader::open(&input)?;
+        let mut reader = EvoReader::open(&output)?;
+        assert_eq!(reader.header.playback.dt, 0.1);
+        assert_eq!(reader.header.playback.total_frames, Some(20));
+        let (mut expected, mut buf) = (Vec::new(), Vec::new());
+        for frame in 0..20 {
+            legacy.read_frame_bytes(frame % 10, &mut expected)?;
+            reader.read_frame_bytes(frame, &mut buf)?;
+            assert_eq!(buf, expected, "frame {frame}");
+        }
+
+        std::fs::remove_file(&output)?;
+        Ok(())
+    }
+}
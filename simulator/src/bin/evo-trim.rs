@@ -0,0 +1,159 @@
+// Copies a frame range of an .evo file into a new file without decoding it
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use evolimo_simulator::reader::EvoReader;
+use evolimo_simulator::recorder::{EvoRecorder, FORMAT_VERSION};
+
+#[derive(Debug, Parser)]
+#[command(name = "evo-trim")]
+struct Args {
+    /// First frame to keep
+    #[arg(long, default_value_t = 0)]
+    from: u64,
+
+    /// Frame to stop before (exclusive); defaults to the end of the input
+    #[arg(long)]
+    to: Option<u64>,
+
+    /// Input .evo file
+    input: PathBuf,
+
+    /// Output .evo file
+    output: PathBuf,
+}
+
+/// Writes frames `from..to` of `input` to `output`, returning the frame count.
+fn trim(input: &Path, output: &Path, from: u64, to: Option<u64>) -> Result<u64> {
+    let mut reader =
+        EvoReader::open(input).with_context(|| format!("failed to read {:?}", input))?;
+    let total = reader.total_frames();
+    let to = to.unwrap_or(total);
+    if from >= to || to > total {
+        bail!(
+            "invalid frame range {}..{} for {:?} with {} frames",
+            from,
+            to,
+            input,
+            total
+        );
+    }
+
+    let mut header = reader.header.clone();
+    header.playback.total_frames = Some(to - from);
+    header.shard = None;
+    // Frames are rewritten, so the output is in the current format.
+    header.version = FORMAT_VERSION;
+
+    let mut recorder = EvoRecorder::create(output, header)?;
+    reader.copy_frames(from..to, &mut recorder)?;
+    if let Some(times) = reader.compute_times() {
+        for &seconds in times.get(from as usize..to as usize).unwrap_or_default() {
+            recorder.record_compute_time(seconds);
+        }
+    }
+    // Rebase so the trimmed timeline starts at step 0, like uniformly spaced files.
+    if let Some(steps) = reader.sim_steps() {
+        let steps = steps.get(from as usize..to as usize).unwrap_or_default();
+        for &step in steps {
+            recorder.record_sim_step(step - steps[0]);
+        }
+    }
+    if let Some(generations) = reader.generations() {
+        let generations = generations
+            .get(from as usize..to as usize)
+            .unwrap_or_default();
+        for &generation in generations {
+            recorder.record_generation(generation);
+        }
+    }
+    recorder.finish()?;
+    Ok(recorder.frames_written())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    if let Ok(output) = args.output.canonicalize() {
+        if args.input.canonicalize().ok() == Some(output) {
+            bail!("output {:?} is the input", args.output);
+        }
+    }
+    let frames = trim(&args.input, &args.output, args.from, args.to)?;
+    eprintln!("✅ Wrote {} frames to {}", frames, args.output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    use evolimo_simulator::recorder::{EvoConfig, EvoHeader, PlaybackMeta};
+
+    /// A `"test"` header of `n_agents` agents with state `labels`, one frame per
+    /// unit step and no frame count.
+    fn test_header(labels: &[&str], n_agents: usize) -> EvoHeader {
+        let config = EvoConfig {
+            n_agents,
+            state_dims: labels.len(),
+            state_labels: labels.iter().map(|s| s.to_string()).collect(),
+            torus_ranges: BTreeMap::new(),
+            label_meta: BTreeMap::new(),
+            clamps: BTreeMap::new(),
+        };
+        EvoHeader::new("test", config, PlaybackMeta::legacy(Some(1.0)))
+    }
+
+    #[test]
+    fn keeps_only_the_selected_frames() -> Result<()> {
+        let dir = std::env::temp_dir();
+        let (input, output) = (dir.join("evo_trim_in.evo"), dir.join("evo_trim_out.evo"));
+        let header = test_header(&["pos_x"], 1);
+        let mut recorder = EvoRecorder::create(&input, header)?;
+        for i in 0..10 {
+            recorder.write_frame_f32(&[i as f32])?;
+            recorder.record_compute_time(i as f32);
+        }
+        recorder.finish()?;
+        drop(recorder);
+
+        assert_eq!(trim(&input, &output, 5, Some(8))?, 3);
+        let mut reader = EvoReader::open(&output)?;
+        assert_eq!(reader.header.playback.total_frames, Some(3));
+        assert_eq!(reader.compute_times(), Some(vec![5.0, 6.0, 7.0]));
+        let mut buf = Vec::new();
+        reader.read_frame_bytes(0, &mut buf)?;
+        assert_eq!(buf, 5.0f32.to_le_bytes());
+
+        assert!(trim(&input, &output, 8, Some(5)).is_err());
+        assert!(trim(&input, &output, 0, Some(11)).is_err());
+
+        std::fs::remove_file(&input)?;
+        std::fs::remove_file(&output)?;
+        Ok(())
+    }
+
+    #[test]
+    fn trims_files_written_before_playback_was_recorded() -> Result<()> {
+        // The checked-in recording has no `playback`, and `dt` in its config.
+        let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("../sim_output.evo");
+        let output = std::env::temp_dir().join("evo_trim_legacy_out.evo");
+        assert_eq!(trim(&input, &output, 0, Some(2))?, 2);
+
+        let mut legacy = EvoReader::open(&input)?;
+        let mut reader = EvoReader::open(&output)?;
+        assert_eq!(reader.header.playback.dt, 0.1);
+        assert_eq!(reader.header.playback.total_frames, Some(2));
+        let (mut expected, mut buf) = (Vec::new(), Vec::new());
+        for frame in 0..2 {
+            legacy.read_frame_bytes(frame, &mut expected)?;
+            reader.read_frame_bytes(frame, &mut buf)?;
+            assert_eq!(buf, expected);
+        }
+
+        std::fs::remove_file(&output)?;
+        Ok(())
+    }
+}
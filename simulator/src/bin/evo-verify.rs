@@ -0,0 +1,234 @@
+// Checks .evo files for truncation and inconsistent headers by reading every frame
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use evolimo_simulator::reader::EvoReader;
+
+#[derive(Debug, Parser)]
+#[command(name = "evo-verify")]
+struct Args {
+    /// .evo files to check
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+}
+
+/// What [`verify`] found in one file.
+#[derive(Debug, Default)]
+struct Report {
+    /// Everything found wrong, empty when the file reads back cleanly.
+    problems: Vec<String>,
+    /// Why the file could only be checked in part, for version 1 files written
+    /// before headers declared a frame count.
+    legacy: Option<String>,
+}
+
+/// Checks `path` by reading every frame.
+///
+/// This catches files that end early, headers that disagree with the body,
+/// trailers that do not cover every frame and, in finished recordings, any
+/// stored byte that no longer matches the header's body checksum.
+fn verify(path: &Path) -> Report {
+    let mut reader = match EvoReader::open(path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            return Report {
+                problems: vec![format!("unreadable: {e}")],
+                legacy: None,
+            }
+        }
+    };
+    let mut problems = Vec::new();
+    let mut legacy = None;
+    let frames = reader.total_frames();
+
+    let config = &reader.header.config;
+    if config.state_labels.len() != config.state_dims {
+        problems.push(format!(
+            "header has {} state labels for {} state dims",
+            config.state_labels.len(),
+            config.state_dims
+        ));
+    }
+    match reader.header.playback.total_frames {
+        None if reader.header.version == 1 => {
+            legacy = Some(format!(
+                "legacy file with no frame count; the body holds {frames} whole frames"
+            ))
+        }
+        None => problems.push("header has no frame count (the recording never finished)".into()),
+        Some(declared) if declared != frames => problems.push(format!(
+            "header declares {declared} frames but the body holds {frames}"
+        )),
+        Some(_) => {}
+    }
+    if reader.header.version >= 2 && !reader.has_index() {
+        problems.push("no frame index at the end of the file (cut off, or never finished)".into());
+    }
+    let partial = reader.trailing_partial_bytes();
+    if partial > 0 {
+        problems.push(format!(
+            "body ends {partial} bytes into a frame after frame {frames} (truncated, or a damaged trailer)"
+        ));
+    }
+
+    if let Err(e) = reader.verify_checksum() {
+        problems.push(e.to_string());
+    }
+
+    if let Some(times) = reader.compute_times() {
+        if times.len() as u64 != frames {
+            problems.push(format!(
+                "trailer has compute times for {} of {frames} frames",
+                times.len()
+            ));
+        }
+    }
+    if let Some(steps) = reader.sim_steps() {
+        if steps.len() as u64 != frames {
+            problems.push(format!(
+                "trailer has sim steps for {} of {frames} frames",
+                steps.len()
+            ));
+        }
+        if let Some(i) = steps.windows(2).position(|w| w[1] < w[0]) {
+            problems.push(format!("sim steps go backwards after frame {i}"));
+        }
+    }
+    if let Some(generations) = reader.generations() {
+        if generations.len() as u64 != frames {
+            problems.push(format!(
+                "trailer has generations for {} of {frames} frames",
+                generations.len()
+            ));
+        }
+        if let Some(i) = generations.windows(2).position(|w| w[1] < w[0]) {
+            problems.push(format!("generations go backwards after frame {i}"));
+        }
+    }
+
+    let mut buf = Vec::new();
+    for frame_index in 0..frames {
+        if let Err(e) = reader.read_frame_bytes(frame_index, &mut buf) {
+            problems.push(format!("frame {frame_index} is unreadable: {e}"));
+            break;
+        }
+    }
+    Report { problems, legacy }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut corrupt = 0;
+    for input in &args.inputs {
+        let report = verify(input);
+        if !report.problems.is_empty() {
+            corrupt += 1;
+            println!("CORRUPT  {}", input.display());
+            for problem in report.problems {
+                println!("  - {problem}");
+            }
+        } else if let Some(legacy) = report.legacy {
+            println!("LEGACY   {}", input.display());
+            println!("  - {legacy}");
+        } else {
+            println!("OK       {}", input.display());
+        }
+    }
+    if corrupt > 0 {
+        bail!(
+            "{corrupt} of {} files failed verification",
+            args.inputs.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    use evolimo_simulator::recorder::{EvoConfig, EvoHeader, EvoRecorder, PlaybackMeta};
+
+    /// A `"test"` header of `n_agents` agents with state `labels`, one frame per
+    /// unit step and no frame count.
+    fn test_header(labels: &[&str], n_agents: usize) -> EvoHeader {
+        let config = EvoConfig {
+            n_agents,
+            state_dims: labels.len(),
+            state_labels: labels.iter().map(|s| s.to_string()).collect(),
+            torus_ranges: BTreeMap::new(),
+            label_meta: BTreeMap::new(),
+            clamps: BTreeMap::new(),
+        };
+        EvoHeader::new("test", config, PlaybackMeta::legacy(Some(1.0)))
+    }
+
+    /// Records 4 frames of 2 state dims, whatever `state_labels` holds.
+    fn record(path: &Path, state_labels: &[&str]) -> Result<()> {
+        let mut header = test_header(state_labels, 1);
+        header.config.state_dims = 2;
+        let mut recorder = EvoRecorder::create(path, header)?;
+        for i in 0..4 {
+            recorder.write_frame_f32(&[i as f32, 0.0])?;
+        }
+        recorder.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn flags_truncated_and_inconsistent_files() -> Result<()> {
+        let path = std::env::temp_dir().join("evo_verify_test.evo");
+
+        record(&path, &["pos_x", "pos_y"])?;
+        assert_eq!(verify(&path).problems, Vec::<String>::new());
+
+        // Cut off the frame index and the end of the last frame.
+        let len = std::fs::metadata(&path)?.len();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)?
+            .set_len(len - (4 * 8 + 8) - 3)?;
+        let problems = verify(&path).problems;
+        assert_eq!(problems.len(), 4, "{problems:?}");
+        assert!(problems[0].contains("declares 4 frames but the body holds 3"));
+        assert!(problems[1].contains("no frame index"));
+        assert!(problems[2].contains("5 bytes into a frame"));
+        assert!(problems[3].contains("checksum mismatch"));
+
+        // A flipped byte inside a frame leaves the structure intact.
+        record(&path, &["pos_x", "pos_y"])?;
+        let mut bytes = std::fs::read(&path)?;
+        let body_offset = 8 + u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        bytes[body_offset + 9] ^= 0x01;
+        std::fs::write(&path, &bytes)?;
+        let problems = verify(&path).problems;
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].starts_with("body checksum mismatch"));
+
+        record(&path, &["pos_x"])?;
+        assert_eq!(
+            verify(&path).problems,
+            ["header has 1 state labels for 2 state dims"]
+        );
+
+        // A version 1 file from before headers declared a frame count.
+        let mut bytes = b"EVO1".to_vec();
+        let header = br#"{"version":1,"timestamp":"t","config":{"n_agents":1,"state_dims":2,"state_labels":["pos_x","pos_y"],"dt":0.1}}"#;
+        bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(header);
+        bytes.extend_from_slice(&[0; 3 * 8]);
+        std::fs::write(&path, &bytes)?;
+        let report = verify(&path);
+        assert_eq!(report.problems, Vec::<String>::new());
+        assert!(report.legacy.unwrap().contains("3 whole frames"));
+
+        std::fs::write(&path, b"EVO1garbage")?;
+        assert!(verify(&path).problems[0].starts_with("unreadable"));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
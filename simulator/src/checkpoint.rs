@@ -0,0 +1,161 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use candle_core::{Device, Tensor};
+use serde::{Deserialize, Serialize};
+
+const MAGIC: &[u8; 4] = b"EVCK";
+
+/// A snapshot of a running simulation, written every `--checkpoint-every`
+/// frames and read back by `--resume`.
+///
+/// Layout: `EVCK`, a little-endian u32 metadata length, the metadata as JSON,
+/// then the state, physics, attribute and gene tensors as little-endian f32 in
+/// that order.
+pub struct Checkpoint {
+    pub def_name: String,
+    pub sim_frame: u64,
+    /// The seed the run was initialized from.
+    pub seed: u64,
+    /// Frames in the `.evo` recording when the checkpoint was taken; resuming
+    /// drops any recorded after it.
+    pub frames_written: u64,
+    /// Index of the generation the population belongs to.
+    pub generation: u64,
+    pub state: Tensor,
+    pub physics: Tensor,
+    pub attributes: Tensor,
+    /// `[n_agents, gene_len]`, which the next generation is bred from.
+    pub genes: Tensor,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CheckpointMeta {
+    def_name: String,
+    sim_frame: u64,
+    seed: u64,
+    frames_written: u64,
+    generation: u64,
+    /// Shapes of the state, physics, attribute and gene tensors.
+    shapes: [Vec<usize>; 4],
+}
+
+impl Checkpoint {
+    /// Where checkpoints of the recording at `evo_path` go: next to it, with a
+    /// `.ckpt` extension.
+    pub fn path_for(evo_path: &Path) -> PathBuf {
+        evo_path.with_extension("ckpt")
+    }
+
+    /// Writes the checkpoint to a temporary file and renames it over `path`, so
+    /// an interrupted save leaves the previous checkpoint intact.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tensors = [&self.state, &self.physics, &self.attributes, &self.genes];
+        let meta = CheckpointMeta {
+            def_name: self.def_name.clone(),
+            sim_frame: self.sim_frame,
+            seed: self.seed,
+            frames_written: self.frames_written,
+            generation: self.generation,
+            shapes: tensors.map(|t| t.dims().to_vec()),
+        };
+        let meta = serde_json::to_vec(&meta)?;
+
+        let tmp = path.with_extension("ckpt.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(meta.len() as u32).to_le_bytes())?;
+        writer.write_all(&meta)?;
+        for tensor in tensors {
+            for v in tensor.flatten_all()?.to_vec1::<f32>()? {
+                writer.write_all(&v.to_le_bytes())?;
+            }
+        }
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("failed to move checkpoint into {}", path.display()))
+    }
+
+    pub fn load(path: &Path, device: &Device) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open checkpoint {}", path.display()))?;
+        let mut reader = BufReader::new(file);
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("{} is not a checkpoint (expected EVCK)", path.display());
+        }
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len)?;
+        let mut meta = vec![0u8; u32::from_le_bytes(len) as usize];
+        reader.read_exact(&mut meta)?;
+        let meta: CheckpointMeta = serde_json::from_slice(&meta)?;
+
+        let mut tensors = Vec::with_capacity(meta.shapes.len());
+        for shape in &meta.shapes {
+            let mut bytes = vec![0u8; shape.iter().product::<usize>() * 4];
+            reader
+                .read_exact(&mut bytes)
+                .with_context(|| format!("checkpoint {} is truncated", path.display()))?;
+            let values: Vec<f32> = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            tensors.push(Tensor::from_vec(values, shape.as_slice(), device)?);
+        }
+        let [state, physics, attributes, genes]: [Tensor; 4] =
+            tensors.try_into().expect("one tensor per shape");
+        Ok(Self {
+            def_name: meta.def_name,
+            sim_frame: meta.sim_frame,
+            seed: meta.seed,
+            frames_written: meta.frames_written,
+            generation: meta.generation,
+            state,
+            physics,
+            attributes,
+            genes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{Simulation, SimulationOptions};
+
+    #[test]
+    fn resuming_from_a_checkpoint_matches_an_uninterrupted_run() -> Result<()> {
+        let device = Device::Cpu;
+        let options = SimulationOptions {
+            n_agents: Some(8),
+            dt: 0.1,
+            substeps: 2,
+            // Resuming needs the seed the phenotype network was drawn from.
+            seed: Some(7),
+            ..Default::default()
+        };
+        let def = "universal_gravitation";
+        let mut uninterrupted = Simulation::new(def, &device, options.clone())?;
+        uninterrupted.step()?;
+        let path = std::env::temp_dir().join("evolimo_checkpoint_test.ckpt");
+        uninterrupted.checkpoint(1).save(&path)?;
+        uninterrupted.step()?;
+        uninterrupted.step()?;
+
+        // A fresh simulation starts over at frame 0; the checkpoint puts it back.
+        let checkpoint = Checkpoint::load(&path, &device)?;
+        fs::remove_file(&path)?;
+        assert_eq!((checkpoint.sim_frame, checkpoint.frames_written), (1, 1));
+        let mut resumed = Simulation::new(def, &device, options)?;
+        resumed.restore(&checkpoint)?;
+        resumed.step()?;
+        resumed.step()?;
+
+        assert_eq!(resumed.sim_frame(), uninterrupted.sim_frame());
+        assert_eq!(resumed.state_f32()?, uninterrupted.state_f32()?);
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+use std::io;
+
+use thiserror::Error;
+
+/// Failures of the `.evo` reader and recorder.
+#[derive(Debug, Error)]
+pub enum EvoError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("invalid magic bytes (expected EVO1)")]
+    BadMagic,
+
+    #[error("unsupported format version {found} (expected {expected} or older)")]
+    UnsupportedVersion { found: u32, expected: u32 },
+
+    /// The file ends before `what` is complete.
+    #[error("file is truncated: {what}")]
+    Truncated { what: &'static str },
+
+    #[error("header too large ({len} bytes, max {max})")]
+    HeaderTooLarge { len: usize, max: usize },
+
+    #[error("invalid header JSON: {0}")]
+    InvalidHeader(#[from] serde_json::Error),
+
+    /// The header describes frames of zero bytes (no agents or no state).
+    #[error("invalid frame size (0)")]
+    EmptyFrame,
+
+    #[error("frame index out of range: {index} >= {total_frames}")]
+    FrameOutOfRange { index: u64, total_frames: u64 },
+
+    #[error("shape mismatch: expected {expected:?}, got {found:?}")]
+    ShapeMismatch {
+        expected: [usize; 2],
+        found: Vec<usize>,
+    },
+
+    /// A frame handed to the recorder has the wrong number of values or bytes.
+    #[error("frame length mismatch: expected {expected}, got {found}")]
+    FrameLength { expected: usize, found: usize },
+
+    /// A compressed frame does not inflate to the size the header describes.
+    #[error("compressed frame {index} holds {found} bytes (expected {expected})")]
+    CompressedFrameSize {
+        index: u64,
+        expected: u64,
+        found: u64,
+    },
+
+    /// The frame index at the end of the file points outside the body or out of order.
+    #[error("invalid frame index")]
+    BadIndex,
+
+    /// The body does not match the checksum the header recorded for it.
+    #[error(
+        "body checksum mismatch: header records {expected:#010x}, body hashes to {found:#010x}"
+    )]
+    ChecksumMismatch { expected: u32, found: u32 },
+
+    /// More frames than a frame index can list (its length is a u32 of bytes).
+    #[error("too many frames for the frame index: {frames}")]
+    IndexTooLarge { frames: u64 },
+
+    /// A recording to append to holds fewer frames than it is resumed after.
+    #[error("cannot resume after frame {frames}: the file holds only {total_frames}")]
+    MissingFrames { frames: u64, total_frames: u64 },
+
+    #[error(transparent)]
+    Tensor(#[from] candle_core::Error),
+}
+
+pub type Result<T, E = EvoError> = std::result::Result<T, E>;
@@ -6,98 +6,166 @@ pub struct SpatialGrid {
     pub height: usize,
     pub capacity: usize,
     pub cell_size: (f32, f32),
+    /// World position of the corner of cell (0, 0).
+    pub origin: (f32, f32),
+    pub boundary: Boundary,
+}
+
+impl SpatialGrid {
+    /// World size the grid covers along x and y.
+    pub fn extent(&self) -> (f32, f32) {
+        (
+            self.width as f32 * self.cell_size.0,
+            self.height as f32 * self.cell_size.1,
+        )
+    }
+}
+
+/// What lies past the edges of a [`SpatialGrid`], which covers
+/// `[origin.0, origin.0 + width * cell_size.0)` by
+/// `[origin.1, origin.1 + height * cell_size.1)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    /// Each edge meets the opposite one.
+    Torus,
+    /// Walls: what lies past an edge is the mirror image of what lies inside.
+    Reflective,
+    /// Nothing: particles that leave the grid are gone from it.
+    Open,
+}
+
+impl Boundary {
+    /// The cell of `n` along an axis that cell coordinate `g` falls in, or
+    /// `None` if it lies outside an open grid.
+    fn fold(self, g: i64, n: usize) -> Option<usize> {
+        let n = n as i64;
+        let g = match self {
+            Self::Torus => g.rem_euclid(n),
+            Self::Reflective => {
+                let m = g.rem_euclid(2 * n);
+                if m < n {
+                    m
+                } else {
+                    2 * n - 1 - m
+                }
+            }
+            Self::Open => (0..n).contains(&g).then_some(g)?,
+        };
+        Some(g as usize)
+    }
 }
 
 /// Maps particles to a fixed-capacity grid.
 ///
+/// Particles claim the free slots of their cell in particle order, one slot
+/// each. Overflow policy: once a cell's `capacity` slots are taken, further
+/// particles in it are dropped from the grid for this call. They exert no force
+/// through it, [`grid_to_particles`] gives them zeros, and the call returns how
+/// many were dropped.
+///
+/// Particles past the grid's edges go in the cell its boundary puts them in:
+/// the wrapped-around one on a torus, the mirrored one between walls. An open
+/// grid drops them the same way, without counting them as overflow.
+///
+/// Slots are handed out on the host, on purpose: first-come slots need a running
+/// count per cell, a sequential scan, and the exact overflow count returned is
+/// read on the host anyway. Each call therefore copies the `2 * N` cell
+/// coordinates from the device and `N` slot indices back, and waits for the
+/// queued work first, a sync per step on GPU backends. On the CPU backend the
+/// copies and the pass over them take ~25 µs of a ~1.8 ms call at 10,000
+/// agents, and ~0.26 ms of ~6 ms at 100,000 (`particles_to_grid_benchmark`);
+/// on a GPU the wait for queued work comes on top.
+///
 /// Returns a tuple:
 /// 1. `grid_state`: [Height, Width, Capacity, StateDims]
 /// 2. `grid_mask`: [Height, Width, Capacity, 1] (1.0 for valid particles, 0.0 for empty slots)
-/// 3. `sort_indices`: [N_AGENTS] (Indices to map back to original order)
+/// 3. `sort_indices`: [N_AGENTS] (Indices to map back to original order; one past the
+///    last slot for dropped particles)
+/// 4. `overflowed`: how many particles found their cell full
 pub fn particles_to_grid(
     pos_x: &Tensor, // [N, 1]
     pos_y: &Tensor, // [N, 1]
     state: &Tensor, // [N, D]
     config: &SpatialGrid,
-) -> Result<(Tensor, Tensor, Tensor)> {
+) -> Result<(Tensor, Tensor, Tensor, u64)> {
     let n_agents = state.dim(0)?;
     let device = state.device();
-    let (w, h) = (config.width as f32, config.height as f32);
     let (cw, ch) = (config.cell_size.0, config.cell_size.1);
-    let cap = config.capacity as f32;
+    let (ox, oy) = (config.origin.0 as f64, config.origin.1 as f64);
 
     // 1. Grid Coordinates (GPU)
-    // pos / cell_size (use F32 for Metal compatibility)
-    let gx = (pos_x / cw as f64)?.floor()?;
-    let gy = (pos_y / ch as f64)?.floor()?;
-
-    // Wrap (Torus) helper
-    let wrap = |x: &Tensor, max: f32| -> Result<Tensor> {
-        let max_t = Tensor::new(&[max], device)?;
-        let div = x.broadcast_div(&max_t)?.floor()?;
-        let sub = div.broadcast_mul(&max_t)?;
-        x.broadcast_sub(&sub)
-    };
-    
-    let gx = wrap(&gx, w)?;
-    let gy = wrap(&gy, h)?;
-
-    // 2. Cell Index
-    // idx = gy * w + gx
-    let w_t = Tensor::new(&[w], device)?;
-    let cell_idx = gy.broadcast_mul(&w_t)?.broadcast_add(&gx)?;
-
-    // 3. Slot Index (Hash based on Particle ID)
-    // We use a simple modulo hash: slot = particle_id % capacity
-    // This avoids CPU sync but allows collisions.
-    // Collisions are handled by averaging the state (center of mass).
-    let particle_ids = Tensor::arange(0u32, n_agents as u32, device)?
-        .reshape((n_agents, 1))?
-        .to_dtype(candle_core::DType::F32)?;
-        
-    let slot_idx = wrap(&particle_ids, cap)?; // particle_id % capacity
-
-    // 4. Flat Index
-    // flat = cell_idx * capacity + slot_idx
-    let cap_t = Tensor::new(&[cap], device)?;
-    let flat_idx = cell_idx.broadcast_mul(&cap_t)?.broadcast_add(&slot_idx)?;
-    let flat_idx = flat_idx.flatten_all()?.to_dtype(candle_core::DType::U32)?;
-
-    // 5. Scatter to Grid
+    // (pos - origin) / cell_size (use F32 for Metal compatibility)
+    let gx = ((pos_x - ox)? / cw as f64)?.floor()?;
+    let gy = ((pos_y - oy)? / ch as f64)?.floor()?;
+
+    // 2. Cell Index, with coordinates past the edges wrapped, folded back or
+    // left out per the boundary
+    let gx: Vec<f32> = gx.flatten_all()?.to_vec1()?;
+    let gy: Vec<f32> = gy.flatten_all()?.to_vec1()?;
+    let boundary = config.boundary;
+    let cells = gx.iter().zip(&gy).map(|(&x, &y)| {
+        let x = boundary.fold(x as i64, config.width)?;
+        let y = boundary.fold(y as i64, config.height)?;
+        Some(y * config.width + x)
+    });
+
+    // 3. Slot Index
+    // Each particle takes the next free slot of its cell, counted on the host;
+    // the sentinel `total_slots` marks particles that found none, or left an
+    // open grid.
     let total_slots = config.width * config.height * config.capacity;
+    let mut occupancy = vec![0usize; config.width * config.height];
+    let mut overflowed = 0u64;
+    let flat_idx: Vec<u32> = cells
+        .map(|cell| {
+            let Some(cell) = cell else {
+                return total_slots as u32;
+            };
+            let slot = occupancy[cell];
+            occupancy[cell] += 1;
+            if slot < config.capacity {
+                (cell * config.capacity + slot) as u32
+            } else {
+                overflowed += 1;
+                total_slots as u32
+            }
+        })
+        .collect();
+    let flat_idx = Tensor::from_vec(flat_idx, n_agents, device)?;
+
+    // 4. Scatter to Grid, with a spare row after the slots for dropped particles
     let state_dim = state.dim(1)?;
-    
-    // Initialize grid with zeros
-    let mut grid_flat = Tensor::zeros((total_slots, state_dim), state.dtype(), device)?;
-    
-    // Accumulate state into grid slots
+    let grid_flat = Tensor::zeros((total_slots + 1, state_dim), state.dtype(), device)?;
+
     // Ensure state is contiguous
     let state_cont = if state.is_contiguous() {
         state.clone()
     } else {
         state.contiguous()?
     };
-    grid_flat = grid_flat.index_add(&flat_idx, &state_cont, 0)?;
-    
-    // 6. Mask (Count)
-    let mut mask_flat = Tensor::zeros((total_slots, 1), state.dtype(), device)?;
+    let grid_flat = grid_flat.index_add(&flat_idx, &state_cont, 0)?;
+
+    // 5. Mask: 1.0 where a particle took the slot
+    let mask_flat = Tensor::zeros((total_slots + 1, 1), state.dtype(), device)?;
     let ones = Tensor::ones((n_agents, 1), state.dtype(), device)?;
-    mask_flat = mask_flat.index_add(&flat_idx, &ones, 0)?;
-    
-    // 7. Average colliding particles
-    // Avoid division by zero
-    let safe_mask = mask_flat.maximum(&Tensor::ones_like(&mask_flat)?)?;
-    grid_flat = grid_flat.broadcast_div(&safe_mask)?;
-    
-    // Clamp mask to 0.0/1.0 for validity
-    let valid_mask = mask_flat.minimum(&Tensor::ones_like(&mask_flat)?)?;
-    
+    let mask_flat = mask_flat.index_add(&flat_idx, &ones, 0)?;
+
     // Reshape
-    let grid = grid_flat.reshape((config.height, config.width, config.capacity, state_dim))?;
-    let mask = valid_mask.reshape((config.height, config.width, config.capacity, 1))?;
-    
+    let grid = grid_flat.narrow(0, 0, total_slots)?.reshape((
+        config.height,
+        config.width,
+        config.capacity,
+        state_dim,
+    ))?;
+    let mask = mask_flat.narrow(0, 0, total_slots)?.reshape((
+        config.height,
+        config.width,
+        config.capacity,
+        1,
+    ))?;
+
     // Return flat_idx as target_indices for gathering later
-    Ok((grid, mask, flat_idx))
+    Ok((grid, mask, flat_idx, overflowed))
 }
 
 /// Computes stencil (neighbor) interactions.
@@ -114,7 +182,7 @@ pub fn particles_to_grid(
 /// The `interaction_expr` expects `center` and `neighbor` inputs?
 ///
 /// Looking at the plan:
-/// ```rust
+/// ```text
 /// fn compute_neighbor_forces(grid, range) {
 ///    for dy... for dx...
 ///       shifted = shift_grid(grid, dx, dy)
@@ -137,9 +205,9 @@ pub fn shift_grid(
 ) -> Result<Tensor> {
     // Implement roll with wrapping (Torus)
     // grid is 4D. We roll on dim 0 (H) and 1 (W).
-    
+
     let (h, w) = (grid.dim(0)?, grid.dim(1)?);
-    
+
     // Helper for 1D roll
     let roll_dim = |t: &Tensor, shift: i32, dim: usize, size: usize| -> Result<Tensor> {
         if shift == 0 {
@@ -151,25 +219,28 @@ pub fn shift_grid(
         }
         // split at size - shift
         let split_idx = size - shift;
-        
+
         let part1 = t.narrow(dim, 0, split_idx)?;
         let part2 = t.narrow(dim, split_idx, size - split_idx)?;
         Tensor::cat(&[&part2, &part1], dim)
     };
-    
+
     let t = roll_dim(grid, dy, 0, h)?;
     let t = roll_dim(&t, dx, 1, w)?;
     Ok(t)
 }
 
-/// Maps grid values back to particles.
+/// Maps grid values back to particles; particles [`particles_to_grid`] dropped
+/// get zeros.
 pub fn grid_to_particles(
-    grid: &Tensor, // [H, W, Cap, D]
+    grid: &Tensor,           // [H, W, Cap, D]
     target_indices: &Tensor, // [N]
 ) -> Result<Tensor> {
     let (h, w, cap, d) = grid.dims4()?;
     let grid_flat = grid.reshape((h * w * cap, d))?;
-    
+    let dropped = Tensor::zeros((1, d), grid.dtype(), grid.device())?;
+    let grid_flat = Tensor::cat(&[&grid_flat, &dropped], 0)?;
+
     // gather: result[i] = grid_flat[target_indices[i]]
     // candle's index_select works on dim 0.
     grid_flat.index_select(target_indices, 0)
@@ -180,29 +251,708 @@ pub fn grid_to_particles(
 /// Returns a grid of shape [H + 2*pad, W + 2*pad, Cap, D]
 pub fn create_torus_padded_grid(grid: &Tensor, pad: usize) -> Result<Tensor> {
     let (h, w, _cap, _d) = grid.dims4()?;
-    
+
     if pad == 0 {
         return Ok(grid.clone());
     }
-    
+
     // Step 1: Pad height dimension (dim 0)
     // Top padding: last `pad` rows of grid
     // Bottom padding: first `pad` rows of grid
     let top_pad = grid.narrow(0, h - pad, pad)?;
     let bottom_pad = grid.narrow(0, 0, pad)?;
     let h_padded = Tensor::cat(&[&top_pad, grid, &bottom_pad], 0)?;
-    
-    // Step 2: Pad width dimension (dim 1) 
+
+    // Step 2: Pad width dimension (dim 1)
     // Left padding: last `pad` columns of h_padded
     // Right padding: first `pad` columns of h_padded
     let new_h = h + 2 * pad;
     let left_pad = h_padded.narrow(1, w - pad, pad)?;
     let right_pad = h_padded.narrow(1, 0, pad)?;
     let fully_padded = Tensor::cat(&[&left_pad, &h_padded, &right_pad], 1)?;
-    
+
     debug_assert_eq!(fully_padded.dim(0)?, new_h);
     debug_assert_eq!(fully_padded.dim(1)?, w + 2 * pad);
-    
+
     Ok(fully_padded)
 }
 
+/// Pads `grid` by `pad` cells on every side with what lies past its edges under
+/// `boundary`: the opposite edges on a torus, the edge rows and columns mirrored
+/// between walls, and empty cells on an open grid.
+/// Returns a grid of shape [H + 2*pad, W + 2*pad, Cap, D]
+pub fn create_padded_grid(grid: &Tensor, pad: usize, boundary: Boundary) -> Result<Tensor> {
+    if pad == 0 {
+        return Ok(grid.clone());
+    }
+    match boundary {
+        Boundary::Torus => create_torus_padded_grid(grid, pad),
+        Boundary::Reflective => {
+            // Cell -1 mirrors cell 0, -2 mirrors 1, and so on at either end.
+            let mirror = |t: &Tensor, dim: usize| -> Result<Tensor> {
+                let n = t.dim(dim)?;
+                let rows = |indices: Vec<u32>| -> Result<Tensor> {
+                    let indices = Tensor::from_vec(indices, pad, t.device())?;
+                    t.index_select(&indices, dim)
+                };
+                let before = rows((0..pad as u32).rev().collect())?;
+                let after = rows((n - pad..n).rev().map(|i| i as u32).collect())?;
+                Tensor::cat(&[&before, t, &after], dim)
+            };
+            mirror(&mirror(grid, 0)?, 1)
+        }
+        Boundary::Open => grid
+            .pad_with_zeros(0, pad, pad)?
+            .pad_with_zeros(1, pad, pad),
+    }
+}
+
+/// `padded_pos`, the positions along `axis` of a grid padded by `pad` around
+/// its `n` cells spanning `[origin, origin + extent)`, with each padding cell's
+/// moved to where its boundary puts the copy: a world `extent` over on a torus,
+/// mirrored in the wall between walls.
+fn ghost_positions(
+    padded_pos: &Tensor, // [H + 2*pad, W + 2*pad, Cap, 1]
+    axis: usize,
+    n: usize,
+    pad: usize,
+    (origin, extent): (f32, f32),
+    boundary: Boundary,
+) -> Result<Tensor> {
+    let (before, after) = match boundary {
+        Boundary::Torus => ((1.0, -extent), (1.0, extent)),
+        Boundary::Reflective => ((-1.0, 2.0 * origin), (-1.0, 2.0 * (origin + extent))),
+        Boundary::Open => ((1.0, 0.0), (1.0, 0.0)),
+    };
+    let (scale, offset): (Vec<f32>, Vec<f32>) = (0..n + 2 * pad)
+        .map(|i| match i {
+            i if i < pad => before,
+            i if i >= n + pad => after,
+            _ => (1.0, 0.0),
+        })
+        .unzip();
+    let mut shape = [1, 1, 1, 1];
+    shape[axis] = n + 2 * pad;
+    let scale = Tensor::from_vec(scale, &shape, padded_pos.device())?;
+    let offset = Tensor::from_vec(offset, &shape, padded_pos.device())?;
+    padded_pos.broadcast_mul(&scale)?.broadcast_add(&offset)
+}
+
+/// Pads `grid` by `pad` cells on every side like [`create_padded_grid`], with
+/// the `pos_x` and `pos_y` columns of the padding moved to where the `config`
+/// boundary puts each copy: a world width over on a torus, mirrored in the wall
+/// between walls. Distances to padding cells then come out as they do inside.
+pub fn create_ghost_padded_grid(
+    grid: &Tensor, // [H, W, Cap, D]
+    pad: usize,
+    config: &SpatialGrid,
+    pos_x: usize,
+    pos_y: usize,
+) -> Result<Tensor> {
+    let (h, w, _cap, d) = grid.dims4()?;
+    let boundary = config.boundary;
+    let padded = create_padded_grid(grid, pad, boundary)?;
+    let extent = config.extent();
+    let columns = (0..d)
+        .map(|column| {
+            let values = padded.narrow(3, column, 1)?;
+            if column == pos_x {
+                ghost_positions(&values, 1, w, pad, (config.origin.0, extent.0), boundary)
+            } else if column == pos_y {
+                ghost_positions(&values, 0, h, pad, (config.origin.1, extent.1), boundary)
+            } else {
+                Ok(values)
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Tensor::cat(&columns, 3)
+}
+
+/// Softening added to `|d|^2` and the gravitational constant the force is
+/// scaled by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GravityParams {
+    pub softening: f32,
+    pub g: f32,
+}
+
+/// How two particles `d` apart pull on (or push) each other in [`solve_stencil`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForceLaw {
+    /// `g * mass_j * d / (|d|^2 + softening)` towards the neighbor, the law the
+    /// generated `universal_gravitation_fixed_capacity_grid` stencil computes.
+    InverseSquare(GravityParams),
+    /// Hooke's law: `k * (|d| - rest)` towards the neighbor, pushing apart
+    /// when closer than `rest`.
+    Spring { k: f32, rest: f32 },
+    /// The derivative of `4 * eps * ((sigma / r)^12 - (sigma / r)^6)`: repulsive
+    /// inside `2^(1/6) * sigma`, attractive beyond it.
+    LennardJones { eps: f32, sigma: f32 },
+}
+
+impl ForceLaw {
+    /// What `d` is scaled by to give the force on the center slot, for squared
+    /// distances `d2` and the neighbors' `mass`.
+    fn coefficient(&self, d2: &Tensor, mass: &Tensor) -> Result<Tensor> {
+        match *self {
+            Self::InverseSquare(GravityParams { softening, g }) => {
+                let inv = ((d2 + softening as f64)?.recip()? * g as f64)?;
+                mass.broadcast_mul(&inv)
+            }
+            Self::Spring { k, rest } => {
+                let r = d2.sqrt()?;
+                ((&r - rest as f64)? / &r)? * k as f64
+            }
+            Self::LennardJones { eps, sigma } => {
+                let inv_d2 = d2.recip()?;
+                let s6 = (&inv_d2 * (sigma * sigma) as f64)?.powf(3.0)?;
+                let repulsion = ((s6.sqr()? * 2.0)? - &s6)?;
+                repulsion.mul(&inv_d2)? * (-24.0 * eps) as f64
+            }
+        }
+    }
+}
+
+/// State columns [`solve_stencil`] reads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StencilColumns {
+    pub pos_x: usize,
+    pub pos_y: usize,
+    /// The mass (or charge) [`ForceLaw::InverseSquare`] scales by.
+    pub mass: usize,
+    /// Each slot's interaction radius; neighbors farther away exert no force
+    /// on it. `None` lets the whole stencil interact.
+    pub radius: Option<usize>,
+}
+
+/// Sums `law` over every slot pair of the `range` neighborhood of each cell
+/// (its own cell included), on a copy of `grid` padded per the `config`
+/// boundary so each offset is one slice. Past the edges, particles are where
+/// the boundary puts them: wrapped around a torus, or the mirror images of
+/// those inside the walls. Empty slots, per `mask`, and a slot's pair with
+/// itself exert no force. Returns `[H, W, Cap, 2]` forces.
+pub fn solve_stencil(
+    grid: &Tensor, // [H, W, Cap, D]
+    mask: &Tensor, // [H, W, Cap, 1]
+    config: &SpatialGrid,
+    range: usize,
+    law: ForceLaw,
+    columns: &StencilColumns,
+) -> Result<Tensor> {
+    let (h, w, _cap, _d) = grid.dims4()?;
+    let padded = create_ghost_padded_grid(grid, range, config, columns.pos_x, columns.pos_y)?;
+    let padded_mask = create_padded_grid(mask, range, config.boundary)?;
+    let padded_x = padded.narrow(3, columns.pos_x, 1)?;
+    let padded_y = padded.narrow(3, columns.pos_y, 1)?;
+    let center_x = grid.narrow(3, columns.pos_x, 1)?;
+    let center_y = grid.narrow(3, columns.pos_y, 1)?;
+    let radius2 = match columns.radius {
+        Some(radius) => Some(grid.narrow(3, radius, 1)?.sqr()?),
+        None => None,
+    };
+    let mut acc = grid.narrow(3, 0, 2)?.zeros_like()?;
+    for offset_y in 0..=2 * range {
+        for offset_x in 0..=2 * range {
+            // [H, W, 1, Cap] against [H, W, Cap, 1]: every slot pair.
+            let neighbors = |t: &Tensor| -> Result<Tensor> {
+                t.narrow(0, offset_y, h)?
+                    .narrow(1, offset_x, w)?
+                    .transpose(2, 3)
+            };
+            let present = neighbors(&padded_mask)?;
+            let ddx = neighbors(&padded_x)?.broadcast_sub(&center_x)?;
+            let ddy = neighbors(&padded_y)?.broadcast_sub(&center_y)?;
+            let d2 = (ddx.sqr()? + ddy.sqr()?)?;
+            let mass = neighbors(&padded.narrow(3, columns.mass, 1)?)?;
+            let coefficient = law.coefficient(&d2, &mass)?;
+            // The self pair sits at zero distance, where springs and
+            // Lennard-Jones divide by zero.
+            let coefficient = d2.gt(0.0)?.where_cond(&coefficient, &d2.zeros_like()?)?;
+            let mut weight = coefficient.broadcast_mul(&present)?;
+            if let Some(radius2) = &radius2 {
+                let within = d2.broadcast_le(radius2)?.to_dtype(d2.dtype())?;
+                weight = weight.mul(&within)?;
+            }
+            let fx = weight.mul(&ddx)?.sum_keepdim(3)?;
+            let fy = weight.mul(&ddy)?.sum_keepdim(3)?;
+            acc = acc.add(&Tensor::cat(&[&fx, &fy], 3)?)?;
+        }
+    }
+    Ok(acc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::Device;
+
+    /// State layout of the fixture: `[pos_x, pos_y, vel_x, vel_y, size, radius]`.
+    const POS_X: usize = 0;
+    const POS_Y: usize = 1;
+    const SIZE: usize = 4;
+    const RADIUS: usize = 5;
+
+    /// The definition's `GRAVITY_PARAMS`.
+    const GRAVITY: GravityParams = GravityParams {
+        softening: 0.01,
+        g: 1.0,
+    };
+
+    const GRID: SpatialGrid = SpatialGrid {
+        width: 4,
+        height: 4,
+        capacity: 2,
+        cell_size: (10.0, 10.0),
+        origin: (0.0, 0.0),
+        boundary: Boundary::Torus,
+    };
+
+    /// Per-agent `(fx, fy)` for agents at `positions` with the given sizes,
+    /// cut off at each agent's radius when `radii` are given.
+    fn forces(
+        positions: &[(f32, f32)],
+        sizes: &[f32],
+        radii: Option<&[f32]>,
+    ) -> Result<Vec<Vec<f32>>> {
+        forces_with(positions, sizes, radii, ForceLaw::InverseSquare(GRAVITY))
+    }
+
+    fn forces_with(
+        positions: &[(f32, f32)],
+        sizes: &[f32],
+        radii: Option<&[f32]>,
+        law: ForceLaw,
+    ) -> Result<Vec<Vec<f32>>> {
+        forces_on(&GRID, positions, sizes, radii, law)
+    }
+
+    fn forces_on(
+        config: &SpatialGrid,
+        positions: &[(f32, f32)],
+        sizes: &[f32],
+        radii: Option<&[f32]>,
+        law: ForceLaw,
+    ) -> Result<Vec<Vec<f32>>> {
+        let rows: Vec<f32> = positions
+            .iter()
+            .zip(sizes)
+            .enumerate()
+            .flat_map(|(i, (&(x, y), &size))| [x, y, 0.0, 0.0, size, radii.map_or(0.0, |r| r[i])])
+            .collect();
+        let state = Tensor::from_vec(rows, (positions.len(), 6), &Device::Cpu)?;
+        let (grid, mask, indices, _overflowed) = particles_to_grid(
+            &state.narrow(1, POS_X, 1)?,
+            &state.narrow(1, POS_Y, 1)?,
+            &state,
+            config,
+        )?;
+        let columns = StencilColumns {
+            pos_x: POS_X,
+            pos_y: POS_Y,
+            mass: SIZE,
+            radius: radii.map(|_| RADIUS),
+        };
+        let cell_forces = solve_stencil(&grid, &mask, config, 1, law, &columns)?;
+        grid_to_particles(&cell_forces, &indices)?.to_vec2()
+    }
+
+    fn assert_close(actual: &[f32], expected: [f32; 2]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{actual:?} != {expected:?}");
+        }
+    }
+
+    /// Per-agent `(fx, fy)` the generated `universal_gravitation_fixed_capacity_grid`
+    /// dynamics give agents at rest at `positions` with the given sizes: their
+    /// velocities after one step of 1.
+    fn generated_forces(positions: &[(f32, f32)], sizes: &[f32]) -> Result<Vec<Vec<f32>>> {
+        use crate::_gen::universal_gravitation_fixed_capacity_grid::dynamics;
+        let rows: Vec<[f32; 5]> = positions
+            .iter()
+            .zip(sizes)
+            .map(|(&(x, y), &size)| [x, y, 0.0, 0.0, size])
+            .collect();
+        let next = step_generated(dynamics::update_dynamics, &rows, 1.0)?;
+        Ok(next.into_iter().map(|row| row[2..4].to_vec()).collect())
+    }
+
+    #[test]
+    fn equal_masses_one_cell_apart_pull_on_each_other() -> Result<()> {
+        // Neighboring 128-wide cells, 128 apart along x: each feels
+        // 2 * 128 / (128^2 + 0.01) towards the other and nothing along y.
+        let f = generated_forces(&[(64.0, 62.5), (192.0, 62.5)], &[2.0, 2.0])?;
+        let pull = 2.0 * 128.0 / (128.0 * 128.0 + GRAVITY.softening);
+        assert_close(&f[0], [pull, 0.0]);
+        assert_close(&f[1], [-pull, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn two_body_force_follows_softening_and_g() -> Result<()> {
+        // Masses 3 and 1, 6 apart along y in cells (0, 0) and (0, 1): each is
+        // pulled by g * m_other * 6 / (6^2 + softening) towards the other.
+        let params = GravityParams {
+            softening: 4.0,
+            g: 2.5,
+        };
+        let law = ForceLaw::InverseSquare(params);
+        let f = forces_with(&[(5.0, 5.0), (5.0, 11.0)], &[3.0, 1.0], None, law)?;
+        assert_close(&f[0], [0.0, 2.5 * 1.0 * 6.0 / 40.0]);
+        assert_close(&f[1], [0.0, -2.5 * 3.0 * 6.0 / 40.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn springs_pull_towards_their_rest_length() -> Result<()> {
+        // 10 apart with rest length 4: each is pulled by 2 * (10 - 4), whatever
+        // the sizes; the empty slot beside each agent, at the origin, pulls on
+        // neither.
+        let law = ForceLaw::Spring { k: 2.0, rest: 4.0 };
+        let f = forces_with(&[(5.0, 5.0), (15.0, 5.0)], &[1.0, 9.0], None, law)?;
+        assert_close(&f[0], [12.0, 0.0]);
+        assert_close(&f[1], [-12.0, 0.0]);
+
+        // Closer than rest, they push apart.
+        let f = forces_with(&[(5.0, 5.0), (5.0, 7.0)], &[1.0, 1.0], None, law)?;
+        assert_close(&f[0], [0.0, -4.0]);
+        assert_close(&f[1], [0.0, 4.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn lennard_jones_repels_at_sigma_and_balances_at_its_minimum() -> Result<()> {
+        // At r = sigma the force is 24 * eps / sigma, pushing apart.
+        let law = ForceLaw::LennardJones {
+            eps: 0.5,
+            sigma: 10.0,
+        };
+        let f = forces_with(&[(5.0, 5.0), (15.0, 5.0)], &[1.0, 1.0], None, law)?;
+        assert_close(&f[0], [-1.2, 0.0]);
+        assert_close(&f[1], [1.2, 0.0]);
+
+        // At r = 2^(1/6) * sigma, the bottom of the well, it vanishes.
+        let r = 2f32.powf(1.0 / 6.0) * 10.0;
+        let f = forces_with(&[(5.0, 5.0), (5.0 + r, 5.0)], &[1.0, 1.0], None, law)?;
+        assert_close(&f[0], [0.0, 0.0]);
+        assert_close(&f[1], [0.0, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn a_lone_agent_feels_no_force_from_itself() -> Result<()> {
+        // Its own slot is in its own cell's stencil; the zero offset must
+        // cancel it out, and empty slots carry no mass.
+        let f = generated_forces(&[(25.0, 35.0)], &[3.0])?;
+        assert_close(&f[0], [0.0, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn agents_beyond_the_stencil_do_not_interact() -> Result<()> {
+        // Two cells apart, outside a range-1 stencil.
+        let f = generated_forces(&[(64.0, 62.5), (320.0, 62.5)], &[2.0, 2.0])?;
+        assert_close(&f[0], [0.0, 0.0]);
+        assert_close(&f[1], [0.0, 0.0]);
+        Ok(())
+    }
+
+    fn with_boundary(boundary: Boundary) -> SpatialGrid {
+        SpatialGrid { boundary, ..GRID }
+    }
+
+    #[test]
+    fn a_torus_pulls_across_its_edges() -> Result<()> {
+        // Cells (0, 1) and (3, 1) meet across the wrap: the copy of each past
+        // the edge is 10 away, not 30.
+        let law = ForceLaw::InverseSquare(GRAVITY);
+        let f = forces_on(&GRID, &[(5.0, 15.0), (35.0, 15.0)], &[2.0, 2.0], None, law)?;
+        let pull = 2.0 * 10.0 / (100.0 + GRAVITY.softening);
+        assert_close(&f[0], [-pull, 0.0]);
+        assert_close(&f[1], [pull, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn reflective_walls_push_back_with_each_agents_mirror_image() -> Result<()> {
+        // Each agent is 5 from a wall, so its image is sigma = 10 away and
+        // repels it by 24 * eps / sigma; the two no longer meet across the edge.
+        let walls = with_boundary(Boundary::Reflective);
+        let law = ForceLaw::LennardJones {
+            eps: 0.5,
+            sigma: 10.0,
+        };
+        let f = forces_on(&walls, &[(5.0, 15.0), (35.0, 15.0)], &[1.0, 1.0], None, law)?;
+        assert_close(&f[0], [1.2, 0.0]);
+        assert_close(&f[1], [-1.2, 0.0]);
+
+        // An agent past the wall is binned in the cell it mirrors, (0, 1).
+        let state = Tensor::new(&[[-5f32, 15.0]], &Device::Cpu)?;
+        let (_grid, _mask, indices, _overflowed) = particles_to_grid(
+            &state.narrow(1, 0, 1)?,
+            &state.narrow(1, 1, 1)?,
+            &state,
+            &walls,
+        )?;
+        assert_eq!(indices.to_vec1::<u32>()?, [(4 * GRID.capacity) as u32]);
+        Ok(())
+    }
+
+    #[test]
+    fn open_edges_lose_what_crosses_them() -> Result<()> {
+        // Neither the pair at opposite edges nor the agent that left the grid,
+        // next to the first, interacts.
+        let open = with_boundary(Boundary::Open);
+        let law = ForceLaw::InverseSquare(GRAVITY);
+        let positions = [(5.0, 15.0), (35.0, 15.0), (-5.0, 15.0)];
+        let f = forces_on(&open, &positions, &[2.0; 3], None, law)?;
+        for force in &f {
+            assert_close(force, [0.0, 0.0]);
+        }
+
+        let state = Tensor::new(&[[-5f32, 15.0]], &Device::Cpu)?;
+        let (_grid, mask, indices, overflowed) = particles_to_grid(
+            &state.narrow(1, 0, 1)?,
+            &state.narrow(1, 1, 1)?,
+            &state,
+            &open,
+        )?;
+        assert_eq!(overflowed, 0);
+        assert_eq!(mask.sum_all()?.to_scalar::<f32>()?, 0.0);
+        let total_slots = GRID.width * GRID.height * GRID.capacity;
+        assert_eq!(indices.to_vec1::<u32>()?, [total_slots as u32]);
+        Ok(())
+    }
+
+    #[test]
+    fn anisotropic_cells_measure_distances_in_world_units() -> Result<()> {
+        // 5x3 cells of 8x20, a 40x60 world: the x and y distances between
+        // neighbors, and the wrap on each axis, follow the cell size.
+        let wide = SpatialGrid {
+            width: 5,
+            height: 3,
+            capacity: 2,
+            cell_size: (8.0, 20.0),
+            origin: (0.0, 0.0),
+            boundary: Boundary::Torus,
+        };
+        let law = ForceLaw::InverseSquare(GRAVITY);
+        let pull = |d: [f32; 2]| {
+            let inv = 2.0 / (d[0] * d[0] + d[1] * d[1] + GRAVITY.softening);
+            [d[0] * inv, d[1] * inv]
+        };
+        let f = forces_on(&wide, &[(4.0, 10.0), (12.0, 30.0)], &[2.0; 2], None, law)?;
+        assert_close(&f[0], pull([8.0, 20.0]));
+        let f = forces_on(&wide, &[(4.0, 10.0), (36.0, 10.0)], &[2.0; 2], None, law)?;
+        assert_close(&f[0], pull([-8.0, 0.0]));
+        let f = forces_on(&wide, &[(4.0, 10.0), (4.0, 50.0)], &[2.0; 2], None, law)?;
+        assert_close(&f[0], pull([0.0, -20.0]));
+        Ok(())
+    }
+
+    #[test]
+    fn padding_and_shifts_keep_rows_and_columns_apart() -> Result<()> {
+        // A 2x3 grid of one-slot cells holding 10 * row + column.
+        let values = [0f32, 1.0, 2.0, 10.0, 11.0, 12.0];
+        let grid = Tensor::new(&values, &Device::Cpu)?.reshape((2, 3, 1, 1))?;
+        let cells = |t: Tensor| -> Result<Vec<Vec<f32>>> { t.squeeze(3)?.squeeze(2)?.to_vec2() };
+        assert_eq!(
+            cells(create_torus_padded_grid(&grid, 1)?)?,
+            [
+                [12.0, 10.0, 11.0, 12.0, 10.0],
+                [2.0, 0.0, 1.0, 2.0, 0.0],
+                [12.0, 10.0, 11.0, 12.0, 10.0],
+                [2.0, 0.0, 1.0, 2.0, 0.0],
+            ]
+        );
+        assert_eq!(
+            cells(shift_grid(&grid, 1, 1)?)?,
+            [[12.0, 10.0, 11.0], [2.0, 0.0, 1.0]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn padding_mirrors_or_zeros_the_edges() -> Result<()> {
+        // A 3x3 grid of one-slot cells holding 1, 2, 3 along each row.
+        let grid = Tensor::new(&[1f32, 2.0, 3.0], &Device::Cpu)?
+            .reshape((1, 3, 1, 1))?
+            .repeat((3, 1, 1, 1))?;
+        let row = |boundary| -> Result<Vec<f32>> {
+            let padded = create_padded_grid(&grid, 2, boundary)?;
+            padded.get(2)?.flatten_all()?.to_vec1()
+        };
+        assert_eq!(row(Boundary::Torus)?, [2.0, 3.0, 1.0, 2.0, 3.0, 1.0, 2.0]);
+        assert_eq!(
+            row(Boundary::Reflective)?,
+            [2.0, 1.0, 1.0, 2.0, 3.0, 3.0, 2.0]
+        );
+        assert_eq!(row(Boundary::Open)?, [0.0, 0.0, 1.0, 2.0, 3.0, 0.0, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn particles_past_a_cells_capacity_are_dropped() -> Result<()> {
+        // Three agents in cell (0, 0), which holds two: the first two keep their
+        // own states instead of being averaged, and the third is left out.
+        let rows = [[1f32, 2.0], [3.0, 4.0], [5.0, 6.0]];
+        let state = Tensor::new(&rows, &Device::Cpu)?;
+        let (grid, mask, indices, overflowed) = particles_to_grid(
+            &state.narrow(1, 0, 1)?,
+            &state.narrow(1, 1, 1)?,
+            &state,
+            &GRID,
+        )?;
+        assert_eq!(overflowed, 1);
+        assert_eq!(mask.sum_all()?.to_scalar::<f32>()?, 2.0);
+        assert_eq!(
+            grid.get(0)?.get(0)?.to_vec2::<f32>()?,
+            [[1.0, 2.0], [3.0, 4.0]]
+        );
+        let back = grid_to_particles(&grid, &indices)?.to_vec2::<f32>()?;
+        assert_eq!(back, [[1.0, 2.0], [3.0, 4.0], [0.0, 0.0]]);
+
+        // The dropped agent pulls on no one, nor is it pulled.
+        let crowded = [(2.0, 2.0), (4.0, 4.0), (5.0, 5.0), (15.0, 5.0)];
+        let f = forces(&crowded, &[1.0; 4], None)?;
+        let alone = forces(&[(2.0, 2.0), (4.0, 4.0), (15.0, 5.0)], &[1.0; 3], None)?;
+        assert_close(&f[2], [0.0, 0.0]);
+        assert_close(&f[3], [alone[2][0], alone[2][1]]);
+        Ok(())
+    }
+
+    #[test]
+    fn each_agent_only_feels_neighbors_within_its_own_radius() -> Result<()> {
+        // Same pair as above, but only the second agent sees 10 away: it is
+        // still pulled, while the first, with radius 5, feels nothing.
+        let f = forces(&[(5.0, 5.0), (15.0, 5.0)], &[2.0, 2.0], Some(&[5.0, 12.0]))?;
+        let pull = 2.0 * 10.0 / (100.0 + GRAVITY.softening);
+        assert_close(&f[0], [0.0, 0.0]);
+        assert_close(&f[1], [-pull, 0.0]);
+        Ok(())
+    }
+
+    /// One step of a generated definition's `update_dynamics` from `rows`, each
+    /// `[pos_x, pos_y, vel_x, vel_y, ..]` in the definition's state order, with
+    /// zeroed genetic params.
+    fn step_generated<const D: usize>(
+        update: fn(&Tensor, &Tensor, &Tensor, f32, &mut u64) -> Result<Tensor>,
+        rows: &[[f32; D]],
+        dt: f32,
+    ) -> Result<Vec<Vec<f32>>> {
+        let device = Device::Cpu;
+        let state = Tensor::from_vec(rows.concat(), (rows.len(), D), &device)?;
+        let params = Tensor::zeros((rows.len(), 1), candle_core::DType::F32, &device)?;
+        update(&state, &params, &params, dt, &mut 0)?.to_vec2()
+    }
+
+    #[test]
+    fn generated_gravity_pulls_across_the_torus_seam() -> Result<()> {
+        // The grid covers the definition's torus, so agents 10 either side of
+        // its seam at x = +-5120 are 20 apart.
+        let f = generated_forces(&[(-5110.0, 0.0), (5110.0, 0.0)], &[1.0, 1.0])?;
+        let pull = 20.0 / (400.0 + GRAVITY.softening);
+        assert_close(&f[0], [-pull, 0.0]);
+        assert_close(&f[1], [pull, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn generated_gravity_matches_the_two_body_force() -> Result<()> {
+        // g * m_j * d / (|d|^2 + softening) with the definition's softening of
+        // 0.01 and g of 1: for a 3-4-5 pair, and for one close enough that the
+        // softening halves the pull.
+        let positions = [(0.0, 0.0), (30.0, 40.0), (1000.0, 1000.0), (1000.1, 1000.0)];
+        let f = generated_forces(&positions, &[2.0, 3.0, 1.0, 1.0])?;
+        let far = 2500.0 + GRAVITY.softening;
+        assert_close(&f[0], [3.0 * 30.0 / far, 3.0 * 40.0 / far]);
+        assert_close(&f[1], [-2.0 * 30.0 / far, -2.0 * 40.0 / far]);
+        let close = 0.1 / (0.01 + GRAVITY.softening);
+        assert!((f[2][0] - close).abs() < 0.05, "{:?}", f[2]);
+        assert!((f[3][0] + close).abs() < 0.05, "{:?}", f[3]);
+        Ok(())
+    }
+
+    #[test]
+    fn generated_walls_push_agents_off_with_their_mirror_images() -> Result<()> {
+        // 10 inside the wall at x = -500, the agent's image is 20 away and
+        // repels it by k * q * q * 20 / (20^2 + softening), with k = 100; with a
+        // reach of 15 it no longer feels it.
+        use crate::_gen::example_reflective_box::dynamics;
+        let rows = [[-490.0, 0.0, 0.0, 0.0, 1.0, 25.0]];
+        let next = step_generated(dynamics::update_dynamics, &rows, 1.0)?;
+        assert_close(&next[0][2..4], [100.0 * 20.0 / 401.0, 0.0]);
+        let rows = [[-490.0, 0.0, 0.0, 0.0, 1.0, 15.0]];
+        let next = step_generated(dynamics::update_dynamics, &rows, 1.0)?;
+        assert_close(&next[0][2..4], [0.0, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn generated_repulsion_only_reaches_as_far_as_each_agent_sees() -> Result<()> {
+        // 30 apart across a cell edge: the agent reaching 40 is pushed off by
+        // 100 * 30 / (30^2 + 1), the one reaching 20 is not.
+        use crate::_gen::example_reflective_box::dynamics;
+        let rows = [
+            [40.0, 10.0, 0.0, 0.0, 1.0, 40.0],
+            [70.0, 10.0, 0.0, 0.0, 1.0, 20.0],
+        ];
+        let next = step_generated(dynamics::update_dynamics, &rows, 1.0)?;
+        assert_close(&next[0][2..4], [-100.0 * 30.0 / 901.0, 0.0]);
+        assert_close(&next[1][2..4], [0.0, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn generated_walls_bounce_agents_back_in() -> Result<()> {
+        // Moving 6 left from 1 inside the wall, the agent ends 5 inside it,
+        // heading right.
+        use crate::_gen::example_reflective_box::dynamics;
+        let rows = [[-499.0, 0.0, -600.0, 0.0, 1.0, 20.0]];
+        let next = step_generated(dynamics::update_dynamics, &rows, 0.01)?;
+        assert!((next[0][0] + 495.0).abs() < 1e-3, "{:?}", next[0]);
+        assert!(next[0][2] > 590.0, "{:?}", next[0]);
+        Ok(())
+    }
+
+    /// Times `particles_to_grid` and, within it, the host round trip of the cell
+    /// coordinates and slot indices. Run with
+    /// `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn particles_to_grid_benchmark() -> Result<()> {
+        let grid = SpatialGrid {
+            width: 100,
+            height: 100,
+            capacity: 16,
+            cell_size: (10.0, 10.0),
+            origin: (0.0, 0.0),
+            boundary: Boundary::Torus,
+        };
+        let device = Device::Cpu;
+        for n_agents in [10_000, 100_000] {
+            let pos_x = Tensor::rand(0f32, 1000.0, (n_agents, 1), &device)?;
+            let pos_y = Tensor::rand(0f32, 1000.0, (n_agents, 1), &device)?;
+            let state = Tensor::cat(&[&pos_x, &pos_y, &pos_x, &pos_y, &pos_x], 1)?;
+            let runs = 20;
+
+            let start = std::time::Instant::now();
+            for _ in 0..runs {
+                particles_to_grid(&pos_x, &pos_y, &state, &grid)?;
+            }
+            let call = start.elapsed() / runs;
+
+            let start = std::time::Instant::now();
+            for _ in 0..runs {
+                let gx: Vec<f32> = pos_x.flatten_all()?.to_vec1()?;
+                let gy: Vec<f32> = pos_y.flatten_all()?.to_vec1()?;
+                let idx: Vec<u32> = gx.iter().zip(&gy).map(|(x, y)| (x + y) as u32).collect();
+                Tensor::from_vec(idx, n_agents, &device)?;
+            }
+            let round_trip = start.elapsed() / runs;
+            println!(
+                "{n_agents} agents: {call:?} per call, {round_trip:?} of it the host round trip"
+            );
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,16 @@
 // Library root
 
-pub mod grid;
 pub mod _gen;
+pub mod checkpoint;
+pub mod error;
+pub mod grid;
+pub mod lifecycle;
+pub mod mapping;
+pub mod reader;
+pub mod recorder;
+pub mod sampling;
+pub mod simulation;
+pub mod spatial_hash;
 
 // Compatibility/Legacy exports (optional, maybe remove if breaking changes are ok)
 // pub use _gen::phenotype::*;
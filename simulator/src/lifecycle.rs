@@ -0,0 +1,494 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use candle_core::Tensor;
+use rand::distr::{weighted::WeightedIndex, Distribution};
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::sampling;
+
+/// Draws selection may take to find a parent not already selected, per parent,
+/// before it settles for parents picked more than once.
+const MAX_DRAWS_PER_PARENT: usize = 1000;
+
+/// Agents in each tournament that picks the parents after the elites of
+/// [`SelectionStrategy::Elitist`].
+const TOURNAMENT_SIZE: usize = 3;
+
+/// Share of an offspring's gene values that mutate.
+const MUTATION_RATE: f32 = 0.1;
+
+/// Standard deviation of the Gaussian noise added to each mutated gene value.
+const MUTATION_SIGMA: f32 = 0.1;
+
+/// One generation of a population: its genes and its index in the run.
+pub struct Generation {
+    pub index: u64,
+    /// `[n_agents, gene_len]`
+    pub genes: Tensor,
+}
+
+impl Generation {
+    pub fn new(index: u64, genes: Tensor) -> Self {
+        Self { index, genes }
+    }
+
+    /// Fitness of each agent: its value of the state variable `fitness_label`
+    /// (e.g. `size`) at the end of the generation, with the columns of `state`
+    /// named by `state_labels`.
+    pub fn calculate_fitness(
+        &self,
+        state: &Tensor,
+        state_labels: &[String],
+        fitness_label: &str,
+    ) -> Result<Vec<f32>> {
+        let column = fitness_column(state_labels, fitness_label)?;
+        let state_dims = state.dim(1)?;
+        if state_dims != state_labels.len() {
+            bail!(
+                "state has {state_dims} columns for {} labels",
+                state_labels.len()
+            );
+        }
+        Ok(state
+            .narrow(1, column, 1)?
+            .flatten_all()?
+            .to_vec1::<f32>()?)
+    }
+
+    /// The generation bred from this one once it ends in `state`: half the
+    /// population is picked as parents by `strategy` on
+    /// [`Self::calculate_fitness`], and [`reproduce`] breeds a full population
+    /// from them. Elites of [`SelectionStrategy::Elitist`] are carried over
+    /// unchanged in place of offspring.
+    pub fn next(
+        &self,
+        state: &Tensor,
+        state_labels: &[String],
+        fitness_label: &str,
+        strategy: SelectionStrategy,
+        rng: &mut StdRng,
+    ) -> Result<Self> {
+        let fitness = self.calculate_fitness(state, state_labels, fitness_label)?;
+        let n_agents = fitness.len();
+        let n_parents = parent_count(n_agents);
+        let parents = select_parents(&fitness, n_parents, strategy, TieBreak::LowestIndex, rng)?;
+        let parents: Vec<u32> = parents.iter().map(|&p| p as u32).collect();
+        let parents = Tensor::new(parents.as_slice(), self.genes.device())?;
+        let parent_genes = self.genes.index_select(&parents, 0)?;
+        let genes = match strategy {
+            SelectionStrategy::Elitist { keep } => {
+                let offspring = reproduce(&parent_genes, n_agents - keep, rng)?;
+                Tensor::cat(&[&parent_genes.narrow(0, 0, keep)?, &offspring], 0)?
+            }
+            _ => reproduce(&parent_genes, n_agents, rng)?,
+        };
+        Ok(Self::new(self.index + 1, genes))
+    }
+}
+
+/// How [`select_parents`] picks each parent of the next generation, written
+/// `tournament:SIZE`, `roulette` or `elitist:KEEP`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    /// The fittest of `size` agents drawn at random (with replacement).
+    Tournament { size: usize },
+    /// An agent drawn with probability proportional to how much fitter it is
+    /// than the least fit agent; uniformly if they are all as fit.
+    RouletteWheel,
+    /// The `keep` fittest agents, then tournaments of [`TOURNAMENT_SIZE`] for
+    /// the rest.
+    Elitist { keep: usize },
+}
+
+impl SelectionStrategy {
+    /// Fails if the strategy cannot breed generations of `n_agents` agents: when
+    /// `elitist:KEEP` keeps more elites than [`parent_count`] picks.
+    pub fn check(self, n_agents: usize) -> Result<()> {
+        let n_parents = parent_count(n_agents);
+        match self {
+            Self::Elitist { keep } if keep > n_parents.min(n_agents) => bail!(
+                "elitist:{keep} keeps more elites than the {n_parents} parents picked from \
+                 {n_agents} agents"
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parents [`Generation::next`] picks from `n_agents` agents: half of them, but
+/// at least one.
+pub fn parent_count(n_agents: usize) -> usize {
+    (n_agents / 2).max(1)
+}
+
+impl FromStr for SelectionStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |v: &str| {
+            v.parse::<usize>()
+                .with_context(|| format!("invalid selection parameter {v:?}"))
+        };
+        Ok(match s.split_once(':') {
+            Some(("tournament", size)) => match parse(size)? {
+                0 => bail!("tournament size must be at least 1"),
+                size => Self::Tournament { size },
+            },
+            Some(("elitist", keep)) => Self::Elitist { keep: parse(keep)? },
+            None if s == "roulette" => Self::RouletteWheel,
+            _ => bail!("expected tournament:SIZE, roulette or elitist:KEEP, got {s:?}"),
+        })
+    }
+}
+
+/// The state column holding `fitness_label`; fails if no state variable has
+/// that label.
+pub fn fitness_column(state_labels: &[String], fitness_label: &str) -> Result<usize> {
+    match state_labels.iter().position(|l| l == fitness_label) {
+        Some(column) => Ok(column),
+        None => bail!(
+            "fitness {fitness_label:?} is not a state variable (have {})",
+            state_labels.join(", ")
+        ),
+    }
+}
+
+/// Uniform crossover of two `[n, gene_len]` gene tensors: row `i` of the
+/// offspring takes each gene value from row `i` of `parent_a` or of `parent_b`
+/// with equal chance.
+pub fn crossover(parent_a: &Tensor, parent_b: &Tensor, rng: &mut StdRng) -> Result<Tensor> {
+    if parent_a.dims() != parent_b.dims() {
+        bail!(
+            "cannot cross genes of shape {:?} with {:?}",
+            parent_a.dims(),
+            parent_b.dims()
+        );
+    }
+    let from_a = sampling::uniform(rng, 0.0, 1.0, parent_a.shape(), parent_a.device())?.lt(0.5)?;
+    Ok(from_a.where_cond(parent_a, parent_b)?)
+}
+
+/// `genes` with Gaussian noise of standard deviation `sigma` added to each value
+/// with probability `rate`; a rate of 0 leaves them unchanged.
+pub fn mutate(genes: &Tensor, rate: f32, sigma: f32, rng: &mut StdRng) -> Result<Tensor> {
+    if !(0.0..=1.0).contains(&rate) {
+        bail!("mutation rate {rate} is not between 0 and 1");
+    }
+    let mutated = sampling::uniform(rng, 0.0, 1.0, genes.shape(), genes.device())?
+        .lt(rate)?
+        .to_dtype(genes.dtype())?;
+    let noise = sampling::normal(rng, 0.0, sigma, genes.shape(), genes.device())?;
+    Ok((genes + (noise * mutated)?)?)
+}
+
+/// `pop_size` offspring of the `[n_parents, gene_len]` `parents`: each is the
+/// [`crossover`] of two parents drawn at random, then [`mutate`]d.
+pub fn reproduce(parents: &Tensor, pop_size: usize, rng: &mut StdRng) -> Result<Tensor> {
+    let n_parents = parents.dim(0)?;
+    if n_parents == 0 {
+        bail!("cannot breed {pop_size} offspring without parents");
+    }
+    let mut pick = || -> Result<Tensor> {
+        let picks: Vec<u32> = (0..pop_size)
+            .map(|_| rng.random_range(0..n_parents) as u32)
+            .collect();
+        Ok(parents.index_select(&Tensor::new(picks.as_slice(), parents.device())?, 0)?)
+    };
+    let (parent_a, parent_b) = (pick()?, pick()?);
+    let offspring = crossover(&parent_a, &parent_b, rng)?;
+    mutate(&offspring, MUTATION_RATE, MUTATION_SIGMA, rng)
+}
+
+/// How a tournament picks between contestants of equal fitness.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum TieBreak<'a> {
+    /// Keep whichever was drawn first, so ties follow the RNG's draw order.
+    #[default]
+    FirstDrawn,
+    /// Prefer the lowest agent index.
+    LowestIndex,
+    /// Prefer the higher value of a second fitness component (one per agent),
+    /// then the lowest agent index.
+    Secondary(&'a [f32]),
+}
+
+impl TieBreak<'_> {
+    /// Whether `challenger` beats `best` when their fitness is equal.
+    fn prefers(&self, challenger: usize, best: usize) -> bool {
+        match *self {
+            Self::FirstDrawn => false,
+            Self::LowestIndex => challenger < best,
+            Self::Secondary(secondary) => {
+                let (a, b) = (secondary[challenger], secondary[best]);
+                a > b || (a == b && challenger < best)
+            }
+        }
+    }
+}
+
+/// Picks `n_parents` agents by `strategy`, each a different agent while enough
+/// can be found: when low diversity has the same agents winning again and
+/// again, or there are fewer agents than parents, the rest are picked allowing
+/// repeats. Elites come first, fittest first.
+pub fn select_parents<R: Rng>(
+    fitness: &[f32],
+    n_parents: usize,
+    strategy: SelectionStrategy,
+    tie_break: TieBreak<'_>,
+    rng: &mut R,
+) -> Result<Vec<usize>> {
+    let n_agents = fitness.len();
+    if n_agents == 0 && n_parents > 0 {
+        bail!("cannot select {n_parents} parents from no agents");
+    }
+    let tournament_size = match strategy {
+        SelectionStrategy::Tournament { size: 0 } => {
+            bail!("tournament size must be at least 1")
+        }
+        SelectionStrategy::Tournament { size } => size,
+        SelectionStrategy::RouletteWheel => 0,
+        SelectionStrategy::Elitist { keep } => {
+            if keep > n_parents.min(n_agents) {
+                bail!("cannot keep {keep} elites among {n_parents} parents of {n_agents} agents");
+            }
+            TOURNAMENT_SIZE
+        }
+    };
+    if let TieBreak::Secondary(secondary) = tie_break {
+        if secondary.len() != n_agents {
+            bail!(
+                "secondary fitness has {} values for {n_agents} agents",
+                secondary.len()
+            );
+        }
+    }
+
+    let mut parents = Vec::with_capacity(n_parents);
+    if let SelectionStrategy::Elitist { keep } = strategy {
+        let mut ranked: Vec<usize> = (0..n_agents).collect();
+        ranked.sort_by(|&a, &b| {
+            let rank = |i: usize| match fitness[i] {
+                f if f.is_nan() => f32::NEG_INFINITY,
+                f => f,
+            };
+            rank(b).total_cmp(&rank(a)).then_with(|| {
+                if tie_break.prefers(a, b) {
+                    Ordering::Less
+                } else if tie_break.prefers(b, a) {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+        });
+        parents.extend_from_slice(&ranked[..keep]);
+    }
+    let wheel = match strategy {
+        SelectionStrategy::RouletteWheel => Some(roulette_wheel(fitness)?),
+        _ => None,
+    };
+
+    let mut chosen: HashSet<usize> = parents.iter().copied().collect();
+    let mut draws = 0;
+    while parents.len() < n_parents {
+        let parent = match &wheel {
+            Some(wheel) => wheel.sample(rng),
+            None => tournament(fitness, tournament_size, tie_break, rng),
+        };
+        draws += 1;
+        if chosen.insert(parent) || draws > n_parents * MAX_DRAWS_PER_PARENT {
+            parents.push(parent);
+        }
+    }
+    Ok(parents)
+}
+
+/// The fittest of `size` agents drawn at random (with replacement).
+fn tournament<R: Rng>(fitness: &[f32], size: usize, tie_break: TieBreak<'_>, rng: &mut R) -> usize {
+    let mut best = rng.random_range(0..fitness.len());
+    for _ in 1..size {
+        let challenger = rng.random_range(0..fitness.len());
+        if fitness[challenger] > fitness[best]
+            || (fitness[challenger] == fitness[best] && tie_break.prefers(challenger, best))
+        {
+            best = challenger;
+        }
+    }
+    best
+}
+
+/// Weights each agent by how much fitter it is than the least fit one, leaving
+/// NaN fitness out; all agents weigh the same if none is fitter.
+fn roulette_wheel(fitness: &[f32]) -> Result<WeightedIndex<f32>> {
+    let least = fitness.iter().copied().fold(f32::INFINITY, f32::min);
+    let mut weights: Vec<f32> = fitness
+        .iter()
+        .map(|&f| if f.is_nan() { 0.0 } else { f - least })
+        .collect();
+    if !weights.iter().any(|&w| w > 0.0) {
+        weights.fill(1.0);
+    }
+    WeightedIndex::new(&weights).context("fitness cannot weight a roulette wheel")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn ties_resolve_deterministically() -> Result<()> {
+        // With every agent in the tournament, the draw order alone decides
+        // between the tied agents 1, 3 and 4.
+        let fitness = [0.0, 2.0, 1.0, 2.0, 2.0];
+        let secondary = [9.0, 0.0, 9.0, 5.0, 5.0];
+        let all = SelectionStrategy::Tournament { size: 64 };
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let lowest = select_parents(&fitness, 1, all, TieBreak::LowestIndex, &mut rng)?;
+            assert_eq!(lowest, [1]);
+            let by_secondary =
+                select_parents(&fitness, 1, all, TieBreak::Secondary(&secondary), &mut rng)?;
+            assert_eq!(by_secondary, [3]);
+        }
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let pairs = SelectionStrategy::Tournament { size: 2 };
+        let parents = select_parents(&fitness, 5, pairs, TieBreak::LowestIndex, &mut rng)?;
+        assert_eq!(parents.iter().collect::<HashSet<_>>().len(), 5);
+        // Past the agents there are, parents repeat instead of failing.
+        let parents = select_parents(&fitness, 6, pairs, TieBreak::FirstDrawn, &mut rng)?;
+        assert_eq!(parents.len(), 6);
+        assert_eq!(parents.iter().collect::<HashSet<_>>().len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn tournaments_without_enough_distinct_winners_repeat_parents() -> Result<()> {
+        // Agent 2 wins every tournament that includes all the agents.
+        let fitness = [0.0, 1.0, 5.0];
+        let mut rng = StdRng::seed_from_u64(3);
+        let strategy = SelectionStrategy::Tournament { size: 64 };
+        let parents = select_parents(&fitness, 2, strategy, TieBreak::FirstDrawn, &mut rng)?;
+        assert_eq!(parents, [2, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn roulette_wheel_draws_in_proportion_to_fitness() -> Result<()> {
+        // Weights over the least fit agent: 0, 1 and 3.
+        let fitness = [-1.0, 0.0, 2.0];
+        let mut counts = [0; 3];
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..4000 {
+            let parents = select_parents(
+                &fitness,
+                1,
+                SelectionStrategy::RouletteWheel,
+                TieBreak::FirstDrawn,
+                &mut rng,
+            )?;
+            counts[parents[0]] += 1;
+        }
+        assert_eq!(counts[0], 0);
+        assert!((2800..3200).contains(&counts[2]), "{counts:?}");
+
+        // All equally fit agents are drawn alike.
+        let mut rng = StdRng::seed_from_u64(5);
+        let strategy = SelectionStrategy::RouletteWheel;
+        let parents = select_parents(&[1.0; 4], 4, strategy, TieBreak::FirstDrawn, &mut rng)?;
+        assert_eq!(parents.iter().collect::<HashSet<_>>().len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn elitism_keeps_the_fittest_unchanged() -> Result<()> {
+        let fitness = [0.5, 3.0, f32::NAN, 3.0, 1.0, 0.0];
+        let mut rng = StdRng::seed_from_u64(2);
+        let elitist = SelectionStrategy::Elitist { keep: 2 };
+        let parents = select_parents(&fitness, 3, elitist, TieBreak::LowestIndex, &mut rng)?;
+        assert_eq!(parents[..2], [1, 3]);
+        assert!(![1, 3].contains(&parents[2]));
+        let too_many = SelectionStrategy::Elitist { keep: 4 };
+        assert!(select_parents(&fitness, 3, too_many, TieBreak::LowestIndex, &mut rng).is_err());
+        // Caught up front too: 6 agents breed from 3 parents.
+        assert!(elitist.check(6).is_ok());
+        assert!(too_many.check(6).is_err());
+
+        // The elites' genes pass to the next generation as they are.
+        let device = candle_core::Device::Cpu;
+        let genes: Vec<f32> = (0..6).flat_map(|k| [k as f32; 2]).collect();
+        let generation = Generation::new(0, Tensor::from_vec(genes, (6, 2), &device)?);
+        let state = Tensor::from_slice(&fitness, (6, 1), &device)?;
+        let next = generation.next(&state, &["size".to_string()], "size", elitist, &mut rng)?;
+        let next = next.genes.to_vec2::<f32>()?;
+        assert_eq!(next.len(), 6);
+        assert_eq!(next[..2], [[1.0, 1.0], [3.0, 3.0]]);
+        Ok(())
+    }
+
+    #[test]
+    fn next_generation_breeds_from_selected_parents() -> Result<()> {
+        let device = candle_core::Device::Cpu;
+        // Agent k has genes [k, k]; fitness is `size`, highest for agent 3.
+        let genes: Vec<f32> = (0..4).flat_map(|k| [k as f32; 2]).collect();
+        let generation = Generation::new(0, Tensor::from_vec(genes, (4, 2), &device)?);
+        let state = Tensor::new(&[[0f32, 0.0], [0.0, 1.0], [0.0, 2.0], [0.0, 9.0]], &device)?;
+        let labels = ["pos_x".to_string(), "size".to_string()];
+        let strategy = SelectionStrategy::Tournament { size: 3 };
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let next = generation.next(&state, &labels, "size", strategy, &mut rng)?;
+        assert_eq!(next.index, 1);
+        assert_eq!(next.genes.dims(), [4, 2]);
+        // Every gene value comes from one of two distinct parents, maybe mutated.
+        let parents: HashSet<i32> = next
+            .genes
+            .flatten_all()?
+            .to_vec1::<f32>()?
+            .iter()
+            .map(|g| g.round() as i32)
+            .collect();
+        assert!(parents.len() <= 2 && parents.iter().all(|p| (0..4).contains(p)));
+        let mut rng = StdRng::seed_from_u64(1);
+        let again = generation.next(&state, &labels, "size", strategy, &mut rng)?;
+        assert_eq!(next.genes.to_vec2::<f32>()?, again.genes.to_vec2::<f32>()?);
+        // A definition without the label fails instead of reading another column.
+        let err = generation
+            .next(&state, &labels, "energy", strategy, &mut rng)
+            .err();
+        let message = err.unwrap().to_string();
+        assert!(message.contains("\"energy\" is not a state variable"));
+        Ok(())
+    }
+
+    #[test]
+    fn offspring_keep_the_gene_length() -> Result<()> {
+        let device = candle_core::Device::Cpu;
+        let mut rng = StdRng::seed_from_u64(0);
+        let a = Tensor::zeros((3, 5), candle_core::DType::F32, &device)?;
+        let b = Tensor::ones((3, 5), candle_core::DType::F32, &device)?;
+
+        let child = crossover(&a, &b, &mut rng)?;
+        assert_eq!(child.dims(), [3, 5]);
+        let values = child.flatten_all()?.to_vec1::<f32>()?;
+        assert!(values.iter().all(|&v| v == 0.0 || v == 1.0));
+        assert!(values.contains(&0.0) && values.contains(&1.0));
+        assert!(crossover(&a, &b.narrow(1, 0, 4)?, &mut rng).is_err());
+
+        assert_eq!(reproduce(&a, 7, &mut rng)?.dims(), [7, 5]);
+        assert_eq!(
+            mutate(&b, 0.0, 1.0, &mut rng)?.to_vec2::<f32>()?,
+            b.to_vec2::<f32>()?
+        );
+        assert_ne!(
+            mutate(&b, 1.0, 1.0, &mut rng)?.to_vec2::<f32>()?,
+            b.to_vec2::<f32>()?
+        );
+        Ok(())
+    }
+}
@@ -1,19 +1,27 @@
 // Main entry point for evolution simulator
 
-use anyhow::Result;
-use candle_core::Device;
-use candle_nn::VarBuilder;
+use anyhow::{bail, Context, Result};
+use candle_core::{Device, Tensor};
 use clap::Parser;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
 use std::time::Instant;
 
-mod recorder;
 // mod _gen; // Use library's _gen instead
 
-use recorder::{EvoConfig, EvoHeader, EvoRecorder};
+use evolimo_simulator::checkpoint::Checkpoint;
+use evolimo_simulator::lifecycle::{self, SelectionStrategy};
+use evolimo_simulator::mapping::default_visual_mapping;
+use evolimo_simulator::reader::EvoReader;
+use evolimo_simulator::recorder::{
+    EvoRecorder, FrameCompression, FrameDtype, FrameLayout, RuntimeMeta, ShardedRecorder,
+};
+use evolimo_simulator::simulation::{self, GeneInit, Simulation, SimulationOptions, StateClamp};
+use rand::{rngs::StdRng, SeedableRng};
 
 /// How often to flush the output file during an infinite run.
 const FLUSH_INTERVAL_FRAMES: u64 = 60;
@@ -28,13 +36,210 @@ struct Args {
     /// Definition to use
     #[arg(long, default_value = "universal_gravitation")]
     def: String,
+
+    /// Also record the final state of each generation to this file
+    #[arg(long)]
+    generation_snapshots: Option<PathBuf>,
+
+    /// Breed a new generation every N sim frames: parents are picked by fitness
+    /// and their offspring restart from fresh initial states
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    generation_length: Option<u64>,
+
+    /// State variable whose value at the end of a generation is each agent's
+    /// fitness; overrides the definition's `FITNESS`
+    #[arg(long, value_name = "LABEL", requires = "generation_length")]
+    fitness: Option<String>,
+
+    /// How parents are picked by fitness: `tournament:SIZE`, `roulette`
+    /// (fitness-proportional) or `elitist:KEEP` (the KEEP fittest carry over
+    /// unchanged, tournaments pick the rest)
+    #[arg(long, value_name = "STRATEGY", default_value = "tournament:3")]
+    selection: SelectionStrategy,
+
+    /// Simulated seconds per dynamics update, passed to `update_dynamics`
+    #[arg(long, default_value_t = simulation::DEFAULT_DT)]
+    dt: f64,
+
+    /// Dynamics updates per recorded sim frame
+    #[arg(long, default_value_t = 1)]
+    substeps: u32,
+
+    /// Record one sim frame in this many, starting with the first. Written to the
+    /// header so playback keeps real time
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    save_interval: u64,
+
+    /// Record each frame's wall-clock compute time into the output trailer
+    #[arg(long)]
+    record_compute_time: bool,
+
+    /// Only record frames whose positions moved more than this (L2 norm over all
+    /// agents) since the last recorded frame. Recorded steps go into the trailer.
+    #[arg(long, value_name = "EPS")]
+    record_on_change: Option<f32>,
+
+    /// Clamp a state variable to `[MIN, MAX]` in recorded frames (e.g.
+    /// `pos_x:-1000:1000`); may be repeated. The simulation itself is unaffected.
+    #[arg(long = "clamp", value_name = "LABEL:MIN:MAX", value_parser = parse_clamp)]
+    clamps: Vec<(String, [f32; 2])>,
+
+    /// Record only these state variables, in this order (e.g.
+    /// `pos_x,pos_y,energy`). The simulation itself still tracks them all
+    #[arg(long, value_name = "LABELS", value_delimiter = ',')]
+    record_vars: Option<Vec<String>>,
+
+    /// Draw initial genes from `uniform:LOW:HIGH` or `normal:MEAN:STD` instead of
+    /// the definition's initialization
+    #[arg(long, value_name = "DIST")]
+    gene_init: Option<GeneInit>,
+
+    /// Seed for the initial genes, phenotype weights and state. Random if omitted;
+    /// either way it is printed and recorded in the header, so passing it again
+    /// reproduces the run
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Split the recording into shards of at most N bytes each
+    /// (`<def>.000.evo`, `<def>.001.evo`, ...) listed in `<def>.shards.json`
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    shard_bytes: Option<u64>,
+
+    /// Order of values within each recorded frame: `aos` (agent by agent) or
+    /// `soa` (state variable by state variable, for fast column reads)
+    #[arg(long, default_value = "aos")]
+    layout: FrameLayout,
+
+    /// How recorded frames are stored: `none` (raw) or `deflate` (each frame
+    /// compressed on its own, trading CPU time for smaller files)
+    #[arg(long, default_value = "none")]
+    compression: FrameCompression,
+
+    /// Precision of recorded values: `f32`, or `f16` for files half the size
+    /// (about three significant digits, magnitudes up to 65504)
+    #[arg(long, default_value = "f32")]
+    dtype: FrameDtype,
+
+    /// Every N sim frames, save the state, phenotype parameters and sim frame to
+    /// a checkpoint next to the recording (`output/<def>.ckpt`) for --resume
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..),
+          conflicts_with = "shard_bytes")]
+    checkpoint_every: Option<u64>,
+
+    /// Continue the run saved in this checkpoint, appending to the recording next
+    /// to it. Its dt, substeps, save interval and clamps replace the flags'
+    #[arg(long, value_name = "CKPT", conflicts_with = "shard_bytes")]
+    resume: Option<PathBuf>,
+
+    /// Write a default visual mapping for --def to
+    /// `../domain-model/_gen/<def>/visual_mapping.json` and exit, without simulating
+    #[arg(long)]
+    emit_default_mapping: bool,
+}
+
+/// The sim frame recording: one file, or shards of a bounded size.
+// There is only ever one, so the variants' size difference does not matter.
+#[allow(clippy::large_enum_variant)]
+enum Output {
+    Single(EvoRecorder),
+    Sharded(ShardedRecorder),
+}
+
+impl Output {
+    fn write_frame(&mut self, state: &Tensor) -> Result<()> {
+        match self {
+            Self::Single(r) => r.write_frame(state)?,
+            Self::Sharded(r) => r.write_frame(state)?,
+        }
+        Ok(())
+    }
+
+    fn select_columns(&mut self, columns: &[usize]) {
+        match self {
+            Self::Single(r) => r.select_columns(columns),
+            Self::Sharded(r) => r.select_columns(columns),
+        }
+    }
+
+    fn record_compute_time(&mut self, seconds: f32) {
+        match self {
+            Self::Single(r) => r.record_compute_time(seconds),
+            Self::Sharded(r) => r.record_compute_time(seconds),
+        }
+    }
+
+    fn record_sim_step(&mut self, step: u64) {
+        match self {
+            Self::Single(r) => r.record_sim_step(step),
+            Self::Sharded(r) => r.record_sim_step(step),
+        }
+    }
+
+    fn record_generation(&mut self, generation: u64) {
+        match self {
+            Self::Single(r) => r.record_generation(generation),
+            Self::Sharded(r) => r.record_generation(generation),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Self::Single(r) => r.flush()?,
+            Self::Sharded(r) => r.flush()?,
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match self {
+            Self::Single(r) => r.finish()?,
+            Self::Sharded(r) => r.finish()?,
+        }
+        Ok(())
+    }
+
+    fn frames_written(&self) -> u64 {
+        match self {
+            Self::Single(r) => r.frames_written(),
+            Self::Sharded(r) => r.frames_written(),
+        }
+    }
+}
+
+fn parse_clamp(s: &str) -> Result<(String, [f32; 2]), String> {
+    let mut parts = s.rsplitn(3, ':');
+    let (Some(max), Some(min), Some(label)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(format!("expected LABEL:MIN:MAX, got {s:?}"));
+    };
+    let bound = |v: &str| {
+        v.parse::<f32>()
+            .map_err(|e| format!("invalid clamp bound {v:?}: {e}"))
+    };
+    Ok((label.to_string(), [bound(min)?, bound(max)?]))
 }
 
-fn env_or_default_usize(key: &str, default: usize) -> usize {
+/// The state columns `labels` name, in that order, for --record-vars.
+fn record_columns(state_labels: &[String], labels: &[String]) -> Result<Vec<usize>> {
+    let mut columns = Vec::with_capacity(labels.len());
+    for label in labels {
+        let Some(column) = state_labels.iter().position(|l| l == label) else {
+            bail!(
+                "cannot record unknown state variable {label:?} (available: {})",
+                state_labels.join(", ")
+            );
+        };
+        if columns.contains(&column) {
+            bail!("state variable {label:?} is listed twice");
+        }
+        columns.push(column);
+    }
+    Ok(columns)
+}
+
+fn env_usize(key: &str) -> Option<usize> {
     std::env::var(key)
         .ok()
         .and_then(|v| v.parse::<usize>().ok())
-        .unwrap_or(default)
 }
 
 #[cfg(feature = "cuda")]
@@ -52,124 +257,482 @@ fn select_device() -> Device {
     Device::Cpu
 }
 
-macro_rules! run_simulation {
-    ($module:path) => {
-        {
-            use $module as def;
-            use def::phenotype::PhenotypeEngine;
-            use def::dynamics::{update_dynamics, STATE_DIMS, STATE_VARS, N_AGENTS, GENE_LEN, HIDDEN_LEN, init_state};
-            use def::phenotype::init_genes;
-
-            // Access args from the outer scope
-            let args = Args::parse();
+/// Writes [`default_visual_mapping`] for `def` next to its generated IR, refusing
+/// to replace an existing (possibly hand-tuned) mapping.
+fn emit_default_mapping(def: &str) -> Result<()> {
+    let path = PathBuf::from(format!("../domain-model/_gen/{def}/visual_mapping.json"));
+    if path.exists() {
+        bail!(
+            "{} already exists; remove it first to regenerate it",
+            path.display()
+        );
+    }
+    let mapping = default_visual_mapping(&simulation::state_labels(def)?)?;
+    std::fs::write(&path, serde_json::to_string_pretty(&mapping)?)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    eprintln!(
+        "✅ Wrote the default mapping for {def} to {}",
+        path.display()
+    );
+    Ok(())
+}
 
-            println!("🧬 Evolimo - Evolution Simulator");
-            println!("================================\n");
+/// `--generation-length`: how the loop breeds each generation from the last.
+struct Evolution<'a> {
+    length: u64,
+    /// State variable holding each agent's fitness.
+    fitness: &'a str,
+    selection: SelectionStrategy,
+    /// `--generation-snapshots`, which each generation's final state goes to.
+    snapshots: Option<&'a mut EvoRecorder>,
+}
 
-            let device = select_device();
-            println!("📍 Device: {:?}\n", device);
+/// Records sim frames until `--max-sim-frames` of them have passed or `stop` is
+/// set, returning how many `--record-on-change` skipped. Sim frame 0 is the
+/// initial state; each later one is one step on from the last. With
+/// `--checkpoint-every`, checkpoints go to `checkpoint_path`, taken before the
+/// frame they resume from is recorded.
+///
+/// With `evolution`, each generation ends with its final state going to the
+/// snapshots and the next one bred from it, whose initial state is the frame
+/// recorded at that sim frame.
+fn run(
+    args: &Args,
+    sim: &mut Simulation,
+    recorder: &mut Output,
+    clamp: Option<&StateClamp>,
+    checkpoint_path: Option<&Path>,
+    mut evolution: Option<Evolution>,
+    stop: &AtomicBool,
+) -> Result<u64> {
+    // Columns compared by --record-on-change: positions, or the whole state.
+    let config = sim.config();
+    let label_index = |name: &str| config.state_labels.iter().position(|l| l == name);
+    let change_dims: Vec<u32> = match (label_index("pos_x"), label_index("pos_y")) {
+        (Some(x), Some(y)) => vec![x as u32, y as u32],
+        _ => (0..config.state_dims as u32).collect(),
+    };
+    let change_dims = Tensor::new(change_dims.as_slice(), sim.state().device())?;
+    let positions_of = |state: &Tensor| state.contiguous()?.index_select(&change_dims, 1);
+    let mut last_recorded_positions: Option<Tensor> = None;
+    let mut skipped_frames = 0u64;
+
+    let mut last_report_time = Instant::now();
+    let mut frames_since_last_report = 0u64;
+    // When work on the current sim frame began: the step that produced it.
+    let mut step_start = args.record_compute_time.then(Instant::now);
+
+    loop {
+        let sim_frame = sim.sim_frame();
+        let state = sim.state();
+        let is_last_frame = args.max_sim_frames.is_some_and(|n| sim_frame + 1 >= n);
+        let on_interval = sim_frame.is_multiple_of(args.save_interval);
+        let record = match (args.record_on_change, &last_recorded_positions) {
+            // Always keep the final state so the recording ends where the run did.
+            (Some(eps), Some(last)) if !is_last_frame => {
+                on_interval && {
+                    let positions = positions_of(state)?;
+                    let moved = (positions - last)?.sqr()?.sum_all()?.sqrt()?;
+                    moved.to_scalar::<f32>()? > eps
+                }
+            }
+            (Some(_), _) => true,
+            (None, _) => on_interval,
+        };
+        if record {
+            match clamp {
+                Some(clamp) => recorder.write_frame(&clamp.apply(state)?)?,
+                None => recorder.write_frame(state)?,
+            }
+            if let Some(step_start) = step_start {
+                recorder.record_compute_time(step_start.elapsed().as_secs_f32());
+            }
+            if args.record_on_change.is_some() {
+                recorder.record_sim_step(sim_frame);
+                last_recorded_positions = Some(positions_of(state)?);
+            }
+            if evolution.is_some() {
+                recorder.record_generation(sim.generation().index);
+            }
+        } else if on_interval {
+            skipped_frames += 1;
+        }
+        frames_since_last_report += 1;
 
-            let n_agents = env_or_default_usize("EVO_N_AGENTS", N_AGENTS);
+        if is_last_frame || stop.load(Ordering::SeqCst) {
+            break;
+        }
 
-            // Initialize phenotype engine
-            let varmap = candle_nn::VarMap::new();
-            let vs = VarBuilder::from_varmap(&varmap, candle_core::DType::F32, &device);
-            let phenotype_engine = PhenotypeEngine::new(vs, GENE_LEN, HIDDEN_LEN)?;
+        step_start = args.record_compute_time.then(Instant::now);
+        sim.step()?;
+        let sim_frame = sim.sim_frame();
 
-            // Initialize agents
-            let genes = init_genes(n_agents, GENE_LEN, &device)?;
-            let mut state = init_state(n_agents, &device)?;
+        if let Some(evolution) = evolution
+            .as_mut()
+            .filter(|e| sim_frame.is_multiple_of(e.length))
+        {
+            if let Some(snapshots) = evolution.snapshots.as_deref_mut() {
+                match clamp {
+                    Some(clamp) => snapshots.write_frame(&clamp.apply(sim.state())?)?,
+                    None => snapshots.write_frame(sim.state())?,
+                }
+            }
+            // Seeded per generation, so a resumed run breeds as this one would.
+            let generation = sim.generation();
+            let mut rng = StdRng::seed_from_u64(sim.seed().wrapping_add(generation.index));
+            let labels = &sim.config().state_labels;
+            let next = generation.next(
+                sim.state(),
+                labels,
+                evolution.fitness,
+                evolution.selection,
+                &mut rng,
+            )?;
+            sim.start_generation(next, &mut rng)?;
+        }
+        if sim_frame.is_multiple_of(FLUSH_INTERVAL_FRAMES) {
+            recorder.flush()?;
+        }
+        if let (Some(every), Some(path)) = (args.checkpoint_every, checkpoint_path) {
+            if sim_frame.is_multiple_of(every) {
+                // Every frame the checkpoint counts must be on disk.
+                recorder.flush()?;
+                sim.checkpoint(recorder.frames_written()).save(path)?;
+            }
+        }
+        if sim_frame.is_multiple_of(20) {
+            let elapsed = last_report_time.elapsed().as_secs_f64();
+            let fps = frames_since_last_report as f64 / elapsed;
+            eprintln!("  Sim frame {}: FPS = {:.1}", sim_frame, fps);
+            if let Some(reach) = sim.stencil_reach()? {
+                if reach.exceeded() {
+                    eprintln!(
+                        "⚠️  Agents move up to ({:.1}, {:.1}) per update but the stencil only reaches ({:.1}, {:.1}); \
+                         interactions are being missed. Use a smaller --dt or a larger stencil range.",
+                        reach.max_displacement[0],
+                        reach.max_displacement[1],
+                        reach.reach[0],
+                        reach.reach[1]
+                    );
+                }
+            }
 
-            println!("🔧 Initialized {} agents", n_agents);
-            println!("   Gene length: {}", GENE_LEN);
-            println!("   State variables: {}\n", STATE_DIMS);
+            last_report_time = Instant::now();
+            frames_since_last_report = 0;
+        }
+    }
 
-            // A. Phenotype expression (Genes -> Parameters)
-            let params = phenotype_engine.forward(&genes)?;
+    Ok(skipped_frames)
+}
 
-            let header = EvoHeader::new(EvoConfig {
-                n_agents,
-                state_dims: STATE_DIMS,
-                state_labels: STATE_VARS.iter().map(|s| (*s).to_string()).collect(),
-            });
+fn main() -> Result<()> {
+    let mut args = Args::parse();
+    if args.emit_default_mapping {
+        return emit_default_mapping(&args.def);
+    }
 
-            let output_path = format!("output/{}.evo", args.def);
-            // Ensure output directory exists
-            if let Some(parent) = std::path::Path::new(&output_path).parent() {
-                std::fs::create_dir_all(parent)?;
+    eprintln!("🧬 Evolimo - Evolution Simulator");
+    eprintln!("================================\n");
+
+    let device = select_device();
+    let runtime = RuntimeMeta::new(&device);
+    eprintln!(
+        "📍 Device: {:?} (candle {})\n",
+        device, runtime.backend_version
+    );
+
+    // A resumed run keeps the recording's settings, so its frames stay evenly timed.
+    let resume = match &args.resume {
+        Some(path) => {
+            let checkpoint = Checkpoint::load(path, &device)?;
+            let recording = path.with_extension("evo");
+            let header = EvoReader::open(&recording)
+                .with_context(|| format!("failed to open {}", recording.display()))?
+                .header;
+            // The recording's variables cannot change partway through it.
+            if let Some(labels) = &args.record_vars {
+                if *labels != header.config.state_labels {
+                    bail!(
+                        "--record-vars {} differs from the {} recorded in {}",
+                        labels.join(","),
+                        header.config.state_labels.join(","),
+                        recording.display()
+                    );
+                }
             }
+            args.dt = header.playback.dt;
+            args.substeps = header.playback.substeps;
+            args.save_interval = header.playback.save_interval;
+            args.clamps = header.config.clamps.clone().into_iter().collect();
+            Some((checkpoint, recording, header))
+        }
+        None => None,
+    };
+
+    let mut sim = Simulation::new(
+        &args.def,
+        &device,
+        SimulationOptions {
+            n_agents: match &resume {
+                Some((_, _, header)) => Some(header.config.n_agents),
+                None => env_usize("EVO_N_AGENTS"),
+            },
+            hidden_len: env_usize("EVO_HIDDEN_LEN"),
+            dt: args.dt,
+            substeps: args.substeps,
+            gene_init: args.gene_init,
+            // The checkpoint's seed draws the phenotype network it was taken with.
+            seed: match &resume {
+                Some((checkpoint, _, _)) => Some(checkpoint.seed),
+                None => args.seed,
+            },
+        },
+    )?;
+    let config = sim.config().clone();
+
+    eprintln!("🔧 Initialized {} agents", config.n_agents);
+    eprintln!("   Seed: {}", sim.seed());
+    eprintln!("   Gene length: {}", sim.gene_len());
+    eprintln!("   Phenotype hidden width: {}", sim.hidden_len());
+    eprintln!("   State variables: {}\n", config.state_dims);
+    if let Some((checkpoint, recording, _)) = &resume {
+        sim.restore(checkpoint)?;
+        eprintln!(
+            "⏩ Resuming at sim frame {} after {} of the frames in {}\n",
+            checkpoint.sim_frame,
+            checkpoint.frames_written,
+            recording.display()
+        );
+    }
 
-            let mut recorder = EvoRecorder::create(&output_path, header)?;
-            println!("💾 Recording sim frames to {output_path}\n");
-
-            match args.max_sim_frames {
-                Some(n) => println!("▶️  Running simulation until {n} sim frames are recorded...\n"),
-                None => println!("▶️  Running simulation indefinitely (Ctrl+C to stop)...\n"),
+    // Checked before any output is created.
+    let fitness = match args.generation_length {
+        Some(_) => {
+            let Some(label) = args.fitness.as_deref().or(sim.fitness_label()) else {
+                bail!(
+                    "--generation-length needs --fitness, as {} declares no FITNESS",
+                    args.def
+                );
+            };
+            lifecycle::fitness_column(&config.state_labels, label)
+                .with_context(|| format!("cannot score generations of {}", args.def))?;
+            args.selection
+                .check(config.n_agents)
+                .context("invalid --selection")?;
+            Some(label)
+        }
+        None => None,
+    };
+
+    let clamps: BTreeMap<String, [f32; 2]> = args.clamps.iter().cloned().collect();
+    let clamp = if clamps.is_empty() {
+        None
+    } else {
+        Some(StateClamp::new(&config, &clamps, &device)?)
+    };
+
+    // Checked before any output is created, like the fitness label.
+    let recorded_columns = match &args.record_vars {
+        Some(labels) => Some(record_columns(&config.state_labels, labels)?),
+        None => None,
+    };
+
+    let (header, output_path) = match &resume {
+        Some((_, recording, header)) => (header.clone(), recording.clone()),
+        None => {
+            let mut header = sim.header();
+            header.config.clamps = clamps;
+            header.runtime = Some(runtime);
+            header.layout = args.layout;
+            header.compression = args.compression;
+            header.dtype = args.dtype;
+            if let Some(columns) = &recorded_columns {
+                header.config = header.config.subset(columns);
             }
-
-            let stop = Arc::new(AtomicBool::new(false));
-            {
-                let stop = Arc::clone(&stop);
-                ctrlc::set_handler(move || {
-                    stop.store(true, Ordering::SeqCst);
-                })?;
+            header.playback.save_interval = args.save_interval;
+            // Skipped frames make the count unknowable up front.
+            if args.record_on_change.is_none() {
+                header.playback.total_frames = args
+                    .max_sim_frames
+                    .map(|n| header.playback.frames_for_sim_frames(n));
             }
-
-            let mut sim_frame = 0u64;
-            let mut last_report_time = Instant::now();
-            let mut frames_since_last_report = 0u64;
-
-            loop {
-                if stop.load(Ordering::SeqCst) {
-                    recorder.flush()?;
-                    println!(
-                        "✅ Recorded {} sim frames. Output: {}",
-                        recorder.frames_written(),
-                        output_path
-                    );
-                    return Ok(());
+            (header, PathBuf::from(format!("output/{}.evo", args.def)))
+        }
+    };
+    // Ensure output directory exists
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let checkpoint_path = args
+        .checkpoint_every
+        .map(|_| Checkpoint::path_for(&output_path));
+
+    // Generation snapshots share the config but record one frame per generation.
+    // Without --generation-length, the whole run is one generation.
+    let mut snapshots = match &args.generation_snapshots {
+        Some(path) => {
+            let mut snapshot_header = header.clone();
+            match args.generation_length {
+                Some(length) => {
+                    snapshot_header.playback.save_interval = length;
+                    snapshot_header.playback.total_frames = None;
                 }
-
-                // B. Internal dynamics update (State + Parameters -> New State)
-                let new_state = update_dynamics(&state, &params.physics, &params.attributes)?;
-                state = new_state;
-                recorder.write_frame(&state)?;
-                sim_frame += 1;
-                frames_since_last_report += 1;
-
-                if let Some(max_sim_frames) = args.max_sim_frames {
-                    if sim_frame >= max_sim_frames {
-                        recorder.flush()?;
-                        println!(
-                            "✅ Recorded {} sim frames. Output: {}",
-                            recorder.frames_written(),
-                            output_path
-                        );
-                        return Ok(());
-                    }
+                None => {
+                    snapshot_header.playback.save_interval =
+                        args.max_sim_frames.unwrap_or(1).max(1);
+                    snapshot_header.playback.total_frames = Some(1);
                 }
-
-                if sim_frame % FLUSH_INTERVAL_FRAMES == 0 {
-                    recorder.flush()?;
+            }
+            let recorder = EvoRecorder::create(path, snapshot_header)?;
+            eprintln!("💾 Recording generation snapshots to {}", path.display());
+            Some(recorder)
+        }
+        None => None,
+    };
+
+    let recorder_labels = header.config.state_labels.clone();
+    let mut recorder = match args.shard_bytes {
+        Some(shard_bytes) => {
+            let recorder = ShardedRecorder::create(&output_path, header, shard_bytes)?;
+            eprintln!(
+                "💾 Recording sim frames to shards of {} (up to {shard_bytes} bytes each)\n",
+                output_path.display()
+            );
+            Output::Sharded(recorder)
+        }
+        None => {
+            let recorder = match &resume {
+                Some((checkpoint, _, _)) => {
+                    EvoRecorder::append(&output_path, checkpoint.frames_written)?
                 }
+                None => EvoRecorder::create(&output_path, header)?,
+            };
+            eprintln!("💾 Recording sim frames to {}\n", output_path.display());
+            if let Some(path) = &checkpoint_path {
+                eprintln!("   Checkpointing to {}\n", path.display());
+            }
+            Output::Single(recorder)
+        }
+    };
+    // A resumed recording keeps the variables it was started with.
+    let columns = record_columns(&config.state_labels, &recorder_labels)?;
+    if columns.len() != config.state_dims || columns.iter().enumerate().any(|(i, &c)| i != c) {
+        eprintln!("   Recording only {}\n", recorder_labels.join(", "));
+        recorder.select_columns(&columns);
+        if let Some(snapshots) = &mut snapshots {
+            snapshots.select_columns(&columns);
+        }
+    }
 
-                if sim_frame % 20 == 0 {
-                    let elapsed = last_report_time.elapsed().as_secs_f64();
-                    let fps = frames_since_last_report as f64 / elapsed;
-                    println!(
-                        "  Sim frame {}: FPS = {:.1}",
-                        sim_frame, fps
-                    );
+    match args.max_sim_frames {
+        Some(n) => eprintln!("▶️  Running simulation for {n} sim frames...\n"),
+        None => eprintln!("▶️  Running simulation indefinitely (Ctrl+C to stop)...\n"),
+    }
 
-                    last_report_time = Instant::now();
-                    frames_since_last_report = 0;
-                }
-            }
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let stop = Arc::clone(&stop);
+        ctrlc::set_handler(move || {
+            stop.store(true, Ordering::SeqCst);
+        })?;
+    }
+
+    let evolution = args
+        .generation_length
+        .zip(fitness)
+        .map(|(length, fitness)| Evolution {
+            length,
+            fitness,
+            selection: args.selection,
+            snapshots: snapshots.as_mut(),
+        });
+    let skipped_frames = run(
+        &args,
+        &mut sim,
+        &mut recorder,
+        clamp.as_ref(),
+        checkpoint_path.as_deref(),
+        evolution,
+        &stop,
+    )?;
+
+    recorder.finish()?;
+    eprintln!(
+        "✅ Recorded {} of {} sim frames. Output: {}",
+        recorder.frames_written(),
+        sim.sim_frame() + 1,
+        output_path.display()
+    );
+    if let Output::Sharded(recorder) = &recorder {
+        eprintln!(
+            "   Split into {} shards, listed in {}",
+            recorder.shard_count(),
+            ShardedRecorder::manifest_path(&output_path).display()
+        );
+    }
+    if skipped_frames > 0 {
+        eprintln!("   Skipped {skipped_frames} sim frames that barely changed");
+    }
+    let overflowed = sim.grid_overflow();
+    if overflowed > 0 {
+        eprintln!(
+            "⚠️  {overflowed} agent placements found their grid cell full and were left out of that step's grid interactions; consider a larger grid capacity"
+        );
+    }
+
+    if let Some(snapshots) = snapshots.as_mut() {
+        match &clamp {
+            Some(clamp) => snapshots.write_frame(&clamp.apply(sim.state())?)?,
+            None => snapshots.write_frame(sim.state())?,
         }
+        snapshots.finish()?;
+        eprintln!(
+            "✅ Recorded {} generation snapshots",
+            snapshots.frames_written()
+        );
     }
+
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    evolimo_simulator::with_definition!(args.def, run_simulation)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evolimo_simulator::reader::EvoReader;
+
+    #[test]
+    fn first_recorded_frame_is_the_initial_state() -> Result<()> {
+        let path = std::env::temp_dir().join("evo_simulator_initial_state_test.evo");
+        let args = Args::parse_from(["evolimo-simulator", "--max-sim-frames", "3"]);
+        let options = SimulationOptions {
+            n_agents: Some(4),
+            dt: args.dt,
+            substeps: args.substeps,
+            ..Default::default()
+        };
+        let mut sim = Simulation::new(&args.def, &Device::Cpu, options)?;
+        let initial = sim.state_f32()?;
+        let mut recorder = Output::Single(EvoRecorder::create(&path, sim.header())?);
+        let stop = AtomicBool::new(false);
+        run(&args, &mut sim, &mut recorder, None, None, None, &stop)?;
+        recorder.finish()?;
+        // Three sim frames: the initial state and two steps.
+        assert_eq!(recorder.frames_written(), 3);
+        assert_eq!(sim.sim_frame(), 2);
+
+        let mut reader = EvoReader::open(&path)?;
+        let mut frame = |i| -> Result<Vec<f32>> {
+            let mut buf = Vec::new();
+            reader.read_frame_bytes(i, &mut buf)?;
+            Ok(buf
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+                .collect())
+        };
+        assert_eq!(frame(0)?, initial);
+        assert_ne!(frame(1)?, initial);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,60 @@
+use anyhow::{bail, Result};
+use serde_json::{json, Map, Value};
+
+/// Colormap for `energy` in a default mapping.
+const DEFAULT_COLORMAP: &str = "viridis";
+
+/// A starting `visual_mapping.json` for a definition with state `labels`:
+/// position from the first two `pos_*` labels (and a third as `z`), color from
+/// `energy` with viridis when there is one, and no size mapping, so the
+/// visualizer draws every agent at its constant default size.
+pub fn default_visual_mapping(labels: &[String]) -> Result<Value> {
+    let positions: Vec<&String> = labels.iter().filter(|l| l.starts_with("pos_")).collect();
+    let [x, y, rest @ ..] = positions.as_slice() else {
+        bail!("a default mapping needs two pos_* state labels, found {positions:?}");
+    };
+
+    let mut position = Map::new();
+    position.insert("x".into(), json!(x));
+    position.insert("y".into(), json!(y));
+    if let Some(z) = rest.first() {
+        position.insert("z".into(), json!(z));
+    }
+    let mut mapping = Map::new();
+    mapping.insert("position".into(), Value::Object(position));
+    if labels.iter().any(|l| l == "energy") {
+        mapping.insert(
+            "color".into(),
+            json!({ "source": "energy", "colormap": DEFAULT_COLORMAP }),
+        );
+    }
+    Ok(Value::Object(mapping))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_mapping_follows_the_state_labels() -> Result<()> {
+        let labels = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let mapping = default_visual_mapping(&labels(&["vel_x", "pos_x", "energy", "pos_y"]))?;
+        assert_eq!(
+            mapping,
+            json!({
+                "position": { "x": "pos_x", "y": "pos_y" },
+                "color": { "source": "energy", "colormap": "viridis" },
+            })
+        );
+
+        let mapping = default_visual_mapping(&labels(&["pos_a", "pos_b", "pos_c", "size"]))?;
+        assert_eq!(
+            mapping,
+            json!({ "position": { "x": "pos_a", "y": "pos_b", "z": "pos_c" } })
+        );
+
+        assert!(default_visual_mapping(&labels(&["pos_x", "energy"])).is_err());
+        Ok(())
+    }
+}
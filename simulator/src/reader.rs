@@ -0,0 +1,544 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
+    path::Path,
+};
+
+use flate2::read::DeflateDecoder;
+
+use crate::error::{EvoError, Result};
+use crate::recorder::{
+    EvoHeader, EvoRecorder, FrameCompression, COMPUTE_TIME_TAG, FORMAT_VERSION, GENERATION_TAG,
+    INDEX_MAGIC, MAGIC_BYTES, MIN_FORMAT_VERSION, SIM_STEP_TAG, TRAILER_MAGIC,
+};
+
+/// Payload of each trailer section, keyed by tag.
+type TrailerSections = HashMap<[u8; 4], Vec<u8>>;
+
+/// Streaming reader for `.evo` files, used by the file tools to copy frames
+/// without decoding them.
+pub struct EvoReader {
+    file: File,
+    pub header: EvoHeader,
+    body_offset: u64,
+    frame_bytes: u64,
+    body_end: u64,
+    total_frames: u64,
+    /// Byte range of each stored frame (after its length prefix, if compressed),
+    /// from the frame index or a walk of a compressed body. `None` for raw bodies
+    /// without an index, whose frames sit `frame_bytes` apart.
+    frames: Option<Vec<Range<u64>>>,
+    /// Whether the file ends with a well-formed frame index.
+    indexed: bool,
+    /// Where the last complete frame ends.
+    frames_end: u64,
+    trailer_sections: TrailerSections,
+}
+
+impl EvoReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+
+        if file_len < 8 {
+            return Err(EvoError::Truncated {
+                what: "magic and header length",
+            });
+        }
+        let mut prefix = [0u8; 8];
+        file.read_exact(&mut prefix)?;
+        if &prefix[..4] != MAGIC_BYTES {
+            return Err(EvoError::BadMagic);
+        }
+        let header_len = u32::from_le_bytes(prefix[4..8].try_into().unwrap()) as u64;
+        let body_offset = 8 + header_len;
+        if body_offset > file_len {
+            return Err(EvoError::Truncated { what: "header" });
+        }
+        let mut header_json = vec![0u8; header_len as usize];
+        file.read_exact(&mut header_json)?;
+        let header = EvoHeader::from_json(&header_json)?;
+        if !(MIN_FORMAT_VERSION..=FORMAT_VERSION).contains(&header.version) {
+            return Err(EvoError::UnsupportedVersion {
+                found: header.version,
+                expected: FORMAT_VERSION,
+            });
+        }
+
+        let frame_bytes = header.frame_bytes();
+        if frame_bytes == 0 {
+            return Err(EvoError::EmptyFrame);
+        }
+
+        let index = match header.version {
+            1 => None,
+            _ => read_index(&mut file, body_offset, file_len)?,
+        };
+        let (index_start, offsets) = match index {
+            Some((index_start, offsets)) => (index_start, Some(offsets)),
+            None => (file_len, None),
+        };
+        let (body_end, trailer_sections) = read_trailer(&mut file, body_offset, index_start)?
+            .unwrap_or_else(|| (index_start, HashMap::new()));
+
+        let compressed = header.compression == FrameCompression::Deflate;
+        let indexed = offsets.is_some();
+        let frames = match offsets {
+            Some(offsets) => Some(
+                frame_ranges(&offsets, body_offset, body_end, frame_bytes, compressed)
+                    .ok_or(EvoError::BadIndex)?,
+            ),
+            None if compressed => Some(walk_compressed_frames(&mut file, body_offset, body_end)?),
+            None => None,
+        };
+        let (total_frames, frames_end) = match &frames {
+            Some(frames) => (
+                frames.len() as u64,
+                frames.last().map_or(body_offset, |range| range.end),
+            ),
+            None => {
+                let total_frames = (body_end - body_offset) / frame_bytes;
+                (total_frames, body_offset + total_frames * frame_bytes)
+            }
+        };
+
+        Ok(Self {
+            file,
+            header,
+            body_offset,
+            frame_bytes,
+            body_end,
+            total_frames,
+            frames,
+            indexed,
+            frames_end,
+            trailer_sections,
+        })
+    }
+
+    /// Complete frames present in the body.
+    pub fn total_frames(&self) -> u64 {
+        self.total_frames
+    }
+
+    /// Where the body starts, right after the header.
+    pub(crate) fn body_offset(&self) -> u64 {
+        self.body_offset
+    }
+
+    /// File offset frame `frame_index` starts at, length prefix included; the end
+    /// of the last complete frame for `total_frames` or more.
+    pub(crate) fn frame_offset(&self, frame_index: u64) -> u64 {
+        if frame_index >= self.total_frames {
+            return self.frames_end;
+        }
+        match &self.frames {
+            Some(frames) if self.header.compression == FrameCompression::Deflate => {
+                frames[frame_index as usize].start - 4
+            }
+            Some(frames) => frames[frame_index as usize].start,
+            None => self.body_offset + frame_index * self.frame_bytes,
+        }
+    }
+
+    /// Bytes after the last complete frame, left by a truncated write. Nonzero
+    /// also when a damaged trailer was read as body.
+    pub fn trailing_partial_bytes(&self) -> u64 {
+        self.body_end - self.frames_end
+    }
+
+    /// A checksum of the body from its start to file offset `end`, ready to
+    /// continue hashing frames written after it.
+    pub(crate) fn body_hasher(&mut self, end: u64) -> Result<crc32fast::Hasher> {
+        let mut hasher = crc32fast::Hasher::new();
+        self.file.seek(SeekFrom::Start(self.body_offset))?;
+        let mut body = (&mut self.file).take(end.saturating_sub(self.body_offset));
+        let mut buf = vec![0u8; 1 << 16];
+        loop {
+            let n = body.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(hasher)
+    }
+
+    /// Hashes the whole body and compares it with the checksum a finished
+    /// recording's header holds. `Ok(false)` for files without one (unfinished or
+    /// older); [`EvoError::ChecksumMismatch`] if any stored byte changed.
+    pub fn verify_checksum(&mut self) -> Result<bool> {
+        let Some(expected) = self.header.body_crc32 else {
+            return Ok(false);
+        };
+        let found = self.body_hasher(self.body_end)?.finalize();
+        if found != expected {
+            return Err(EvoError::ChecksumMismatch { expected, found });
+        }
+        Ok(true)
+    }
+
+    /// Whether the file ends with a frame index, as every finished version 2
+    /// recording does.
+    pub fn has_index(&self) -> bool {
+        self.indexed
+    }
+
+    /// Reads the raw little-endian bytes of frame `frame_index` into `buf`,
+    /// decompressing them if need be.
+    pub fn read_frame_bytes(&mut self, frame_index: u64, buf: &mut Vec<u8>) -> Result<()> {
+        if frame_index >= self.total_frames {
+            return Err(EvoError::FrameOutOfRange {
+                index: frame_index,
+                total_frames: self.total_frames,
+            });
+        }
+        let range = match &self.frames {
+            Some(frames) => frames[frame_index as usize].clone(),
+            None => {
+                let start = self.body_offset + frame_index * self.frame_bytes;
+                start..start + self.frame_bytes
+            }
+        };
+        self.file.seek(SeekFrom::Start(range.start))?;
+        if self.header.compression == FrameCompression::Deflate {
+            let compressed = (&mut self.file).take(range.end - range.start);
+            buf.clear();
+            // One byte past a whole frame is enough to catch an oversized one.
+            DeflateDecoder::new(compressed)
+                .take(self.frame_bytes + 1)
+                .read_to_end(buf)?;
+            if buf.len() as u64 != self.frame_bytes {
+                return Err(EvoError::CompressedFrameSize {
+                    index: frame_index,
+                    expected: self.frame_bytes,
+                    found: buf.len() as u64,
+                });
+            }
+            return Ok(());
+        }
+        buf.resize(self.frame_bytes as usize, 0);
+        self.file.read_exact(buf)?;
+        Ok(())
+    }
+
+    /// Copies the raw bytes of `frames` into `recorder` without decoding them.
+    pub fn copy_frames(&mut self, frames: Range<u64>, recorder: &mut EvoRecorder) -> Result<()> {
+        let mut buf = Vec::new();
+        for frame_index in frames {
+            self.read_frame_bytes(frame_index, &mut buf)?;
+            recorder.write_frame_bytes(&buf)?;
+        }
+        Ok(())
+    }
+
+    /// Per-frame compute times from the trailer, if the file recorded them.
+    pub fn compute_times(&self) -> Option<Vec<f32>> {
+        let payload = self.trailer_sections.get(COMPUTE_TIME_TAG)?;
+        Some(
+            payload
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    /// Per-frame simulation steps from the trailer, if the file recorded them.
+    pub fn sim_steps(&self) -> Option<Vec<u64>> {
+        let payload = self.trailer_sections.get(SIM_STEP_TAG)?;
+        Some(
+            payload
+                .chunks_exact(8)
+                .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    /// The generation of each frame from the trailer, if the run evolved.
+    pub fn generations(&self) -> Option<Vec<u64>> {
+        let payload = self.trailer_sections.get(GENERATION_TAG)?;
+        Some(
+            payload
+                .chunks_exact(8)
+                .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    /// The simulation step of every frame: the recorded track if it is complete,
+    /// otherwise one frame every `save_interval` steps.
+    pub fn frame_steps(&self) -> Vec<u64> {
+        match self.sim_steps() {
+            Some(steps) if steps.len() as u64 == self.total_frames => steps,
+            _ => (0..self.total_frames)
+                .map(|i| i * self.header.playback.save_interval)
+                .collect(),
+        }
+    }
+}
+
+/// Walks the `[len: u32, bytes]` frames of a compressed body without an index,
+/// returning the range of each one's bytes. Stops at the first frame that runs
+/// past `body_end`, as a truncated write leaves.
+fn walk_compressed_frames(
+    file: &mut File,
+    body_offset: u64,
+    body_end: u64,
+) -> Result<Vec<Range<u64>>> {
+    let mut frames = Vec::new();
+    let mut pos = body_offset;
+    let mut prefix = [0u8; 4];
+    while pos + 4 <= body_end {
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut prefix)?;
+        let len = u32::from_le_bytes(prefix);
+        let start = pos + 4;
+        if start + len as u64 > body_end {
+            break;
+        }
+        frames.push(start..start + len as u64);
+        pos = start + len as u64;
+    }
+    Ok(frames)
+}
+
+/// Reads the frame offsets of the index at the end of the file, returning where
+/// the index starts. Files without one (e.g. an unfinished recording) yield `None`.
+fn read_index(file: &mut File, body_offset: u64, file_len: u64) -> Result<Option<(u64, Vec<u64>)>> {
+    let Some(footer_start) = file_len.checked_sub(8).filter(|&s| s >= body_offset) else {
+        return Ok(None);
+    };
+    let mut footer = [0u8; 8];
+    file.seek(SeekFrom::Start(footer_start))?;
+    file.read_exact(&mut footer)?;
+    if &footer[4..] != INDEX_MAGIC {
+        return Ok(None);
+    }
+    let index_len = u32::from_le_bytes(footer[..4].try_into().unwrap()) as u64;
+    let index_start = footer_start
+        .checked_sub(index_len)
+        .filter(|&s| s >= body_offset && index_len.is_multiple_of(8))
+        .ok_or(EvoError::BadIndex)?;
+
+    let mut index = vec![0u8; index_len as usize];
+    file.seek(SeekFrom::Start(index_start))?;
+    file.read_exact(&mut index)?;
+    let offsets = index
+        .chunks_exact(8)
+        .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    Ok(Some((index_start, offsets)))
+}
+
+/// The byte range of each frame given the `offsets` they start at, skipping the
+/// length prefix of compressed frames. `None` unless the frames are in order,
+/// inside the body and (uncompressed) exactly `frame_bytes` long.
+fn frame_ranges(
+    offsets: &[u64],
+    body_offset: u64,
+    body_end: u64,
+    frame_bytes: u64,
+    compressed: bool,
+) -> Option<Vec<Range<u64>>> {
+    let mut ranges = Vec::with_capacity(offsets.len());
+    let mut prev_end = body_offset;
+    for (i, &start) in offsets.iter().enumerate() {
+        let next = offsets.get(i + 1).copied().unwrap_or(body_end);
+        let range = if compressed {
+            start.checked_add(4)?..next
+        } else {
+            start..start.checked_add(frame_bytes)?
+        };
+        if start < prev_end || range.start > range.end || range.end > next.min(body_end) {
+            return None;
+        }
+        prev_end = range.end;
+        ranges.push(range);
+    }
+    Some(ranges)
+}
+
+/// Reads the trailer sections, returning where the body ends. Files without a
+/// well-formed trailer yield `None` and are read as all body.
+fn read_trailer(
+    file: &mut File,
+    body_offset: u64,
+    file_len: u64,
+) -> Result<Option<(u64, TrailerSections)>> {
+    let Some(footer_start) = file_len.checked_sub(12).filter(|&s| s >= body_offset) else {
+        return Ok(None);
+    };
+    let mut footer = [0u8; 12];
+    file.seek(SeekFrom::Start(footer_start))?;
+    file.read_exact(&mut footer)?;
+    if &footer[8..] != TRAILER_MAGIC {
+        return Ok(None);
+    }
+    let trailer_len = u64::from_le_bytes(footer[..8].try_into().unwrap());
+    let Some(trailer_start) = footer_start
+        .checked_sub(trailer_len)
+        .filter(|&s| s >= body_offset)
+    else {
+        return Ok(None);
+    };
+
+    let mut trailer = vec![0u8; trailer_len as usize];
+    file.seek(SeekFrom::Start(trailer_start))?;
+    file.read_exact(&mut trailer)?;
+
+    let mut sections = HashMap::new();
+    let mut rest = &trailer[..];
+    while !rest.is_empty() {
+        if rest.len() < 12 {
+            return Ok(None);
+        }
+        let tag: [u8; 4] = rest[..4].try_into().unwrap();
+        let len = u64::from_le_bytes(rest[4..12].try_into().unwrap());
+        let Some(payload) = usize::try_from(len)
+            .ok()
+            .and_then(|len| rest.get(12..12 + len))
+        else {
+            return Ok(None);
+        };
+        sections.insert(tag, payload.to_vec());
+        rest = &rest[12 + payload.len()..];
+    }
+    Ok(Some((trailer_start, sections)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::{test_header, EvoRecorder, PlaybackMeta};
+    use crate::simulation::DEFAULT_DT;
+
+    #[test]
+    fn reads_back_recorded_frames_and_trailer() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_reader_test.evo");
+        let mut header = test_header(&["pos_x", "pos_y"], 1);
+        header.playback.dt = 0.5;
+        let mut recorder = EvoRecorder::create(&tmp_path, header.clone())?;
+        for i in 0..3u64 {
+            recorder.write_frame_f32(&[i as f32, -(i as f32)])?;
+            recorder.record_compute_time(i as f32 * 0.1);
+            recorder.record_sim_step(i * 4);
+            recorder.record_generation(i / 2);
+        }
+        recorder.finish()?;
+        drop(recorder);
+
+        let mut reader = EvoReader::open(&tmp_path)?;
+        assert_eq!(reader.header.config, header.config);
+        assert_eq!(reader.header.playback.total_frames, Some(3));
+        // The trailer is not mistaken for frames.
+        assert_eq!(reader.total_frames(), 3);
+        assert_eq!(reader.compute_times(), Some(vec![0.0, 0.1, 0.2]));
+        assert_eq!(reader.sim_steps(), Some(vec![0, 4, 8]));
+        assert_eq!(reader.generations(), Some(vec![0, 0, 1]));
+        assert_eq!(reader.frame_steps(), vec![0, 4, 8]);
+
+        let mut buf = Vec::new();
+        reader.read_frame_bytes(2, &mut buf)?;
+        assert_eq!(
+            buf,
+            [2.0f32.to_le_bytes(), (-2.0f32).to_le_bytes()].concat()
+        );
+        assert!(reader.read_frame_bytes(3, &mut buf).is_err());
+
+        std::fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn seeks_through_the_index_to_frames_of_any_size() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_reader_index_test.evo");
+        let mut header = test_header(&["pos_x"], 64);
+        header.compression = FrameCompression::Deflate;
+        // Constant frames deflate to a few bytes, scrambled ones hardly at all.
+        let frame = |i: u32| -> Vec<f32> {
+            (0..64u32)
+                .map(|k| match i % 2 {
+                    0 => i as f32,
+                    _ => (k.wrapping_mul(2_654_435_761) ^ i) as f32,
+                })
+                .collect()
+        };
+        let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+        for i in 0..8 {
+            recorder.write_frame_f32(&frame(i))?;
+        }
+        recorder.finish()?;
+        drop(recorder);
+
+        let mut reader = EvoReader::open(&tmp_path)?;
+        assert!(reader.has_index());
+        let sizes: Vec<u64> = reader
+            .frames
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|range| range.end - range.start)
+            .collect();
+        assert!(sizes[0] < sizes[1], "{sizes:?}");
+        let mut buf = Vec::new();
+        for i in [5, 0, 7, 2, 3, 6, 1, 4] {
+            reader.read_frame_bytes(i as u64, &mut buf)?;
+            let expected: Vec<u8> = frame(i).iter().flat_map(|v| v.to_le_bytes()).collect();
+            assert_eq!(buf, expected, "frame {i}");
+        }
+
+        std::fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn opens_files_written_before_playback_was_recorded() -> Result<()> {
+        // The checked-in recording predates `playback`, keeping `dt` in `config`.
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../sim_output.evo");
+        let reader = EvoReader::open(&path)?;
+        assert_eq!(reader.header.version, 1);
+        assert_eq!(reader.header.playback, PlaybackMeta::legacy(Some(0.1)));
+        assert_eq!(reader.total_frames(), 10);
+
+        let header = EvoHeader::from_json(
+            br#"{"version":1,"timestamp":"t","config":{"n_agents":1,"state_dims":1,"state_labels":["x"]}}"#,
+        )?;
+        assert_eq!(header.playback.dt, DEFAULT_DT);
+        assert_eq!(header.playback.total_frames, None);
+        Ok(())
+    }
+
+    #[test]
+    fn reports_why_a_file_is_unreadable() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_reader_error_test.evo");
+        let open_bytes = |bytes: &[u8]| -> Result<EvoError> {
+            std::fs::write(&tmp_path, bytes)?;
+            Ok(EvoReader::open(&tmp_path).err().expect("open should fail"))
+        };
+
+        assert!(matches!(open_bytes(b"EVO")?, EvoError::Truncated { .. }));
+        assert!(matches!(open_bytes(b"NOPE\0\0\0\0")?, EvoError::BadMagic));
+        let mut short_header = MAGIC_BYTES.to_vec();
+        short_header.extend_from_slice(&100u32.to_le_bytes());
+        assert!(matches!(
+            open_bytes(&short_header)?,
+            EvoError::Truncated { what: "header" }
+        ));
+
+        let header = EvoHeader {
+            version: FORMAT_VERSION + 1,
+            ..test_header(&["pos_x"], 1)
+        };
+        EvoRecorder::create(&tmp_path, header)?.finish()?;
+        assert!(matches!(
+            EvoReader::open(&tmp_path),
+            Err(EvoError::UnsupportedVersion { found, .. }) if found == FORMAT_VERSION + 1
+        ));
+
+        std::fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+}
@@ -1,128 +1,1186 @@
 use std::{
-    fs::File,
-    io::{BufWriter, Write},
-    path::Path,
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
 };
 
-use anyhow::{bail, Result};
-use candle_core::Tensor;
+use candle_core::{Device, Tensor};
 use chrono::{DateTime, Utc};
+use flate2::write::DeflateEncoder;
+use half::f16;
 use serde::{Deserialize, Serialize};
 
+use crate::error::{EvoError, Result};
+use crate::reader::EvoReader;
+use crate::simulation::DEFAULT_DT;
+
 pub const MAGIC_BYTES: &[u8; 4] = b"EVO1";
+/// `version` written to headers. Version 2 files end with a frame index (see
+/// [`INDEX_MAGIC`]); readers accept versions 1 and 2 and reject any other.
+pub const FORMAT_VERSION: u32 = 2;
+/// Oldest `version` readers still accept.
+pub const MIN_FORMAT_VERSION: u32 = 1;
 pub const MAX_HEADER_BYTES: u32 = 1_048_576; // 1 MB
+/// Marks the end of an optional trailer of tagged sections after the body:
+/// `[tag: [u8; 4], len: u64, payload]*`, then `trailer_len: u64` and this magic.
+pub const TRAILER_MAGIC: &[u8; 4] = b"EVOT";
+/// Trailer section holding one little-endian f32 per frame: seconds spent
+/// computing and recording it.
+pub const COMPUTE_TIME_TAG: &[u8; 4] = b"CTIM";
+/// Trailer section holding one little-endian u64 per frame: the simulation step
+/// (counted from 0) it was captured at. Present when frames are not uniformly
+/// `save_interval` steps apart, e.g. with `--record-on-change`.
+pub const SIM_STEP_TAG: &[u8; 4] = b"STEP";
+/// Trailer section holding one little-endian u64 per frame: the generation (counted
+/// from 0) it belongs to. Present when the run evolves, with `--generation-length`.
+pub const GENERATION_TAG: &[u8; 4] = b"GENR";
+/// Ends the frame index written after the trailer: one little-endian u64 per frame,
+/// the file offset it starts at, then `index_len: u32` (in bytes) and this magic.
+/// Lets readers seek straight to frames of any size, e.g. compressed ones.
+pub const INDEX_MAGIC: &[u8; 4] = b"EVO2";
+/// Whitespace reserved after the header JSON so it can be rewritten in place
+/// without moving the body. It must fit both what finishing adds: a
+/// `total_frames` of up to 20 digits in place of `null`, and `body_crc32`.
+const HEADER_SLACK_BYTES: usize = 64;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct EvoConfig {
     pub n_agents: usize,
     pub state_dims: usize,
     pub state_labels: Vec<String>,
+    /// `[min, max)` of state variables that wrap around a torus, keyed by label.
+    #[serde(default)]
+    pub torus_ranges: BTreeMap<String, [f32; 2]>,
+    /// Display metadata for state labels; labels without an entry show as-is.
+    #[serde(default)]
+    pub label_meta: BTreeMap<String, LabelMeta>,
+    /// `[min, max]` that recorded values of these labels were clamped to.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub clamps: BTreeMap<String, [f32; 2]>,
+}
+
+impl EvoConfig {
+    /// The config of a recording holding only state columns `columns`, in that
+    /// order: their labels, and the ranges, metadata and clamps of those labels.
+    pub fn subset(&self, columns: &[usize]) -> Self {
+        let state_labels: Vec<String> = columns
+            .iter()
+            .map(|&c| self.state_labels[c].clone())
+            .collect();
+        let kept = |label: &String| state_labels.contains(label);
+        let mut config = self.clone();
+        config.state_dims = state_labels.len();
+        config.torus_ranges.retain(|label, _| kept(label));
+        config.label_meta.retain(|label, _| kept(label));
+        config.clamps.retain(|label, _| kept(label));
+        config.state_labels = state_labels;
+        config
+    }
+}
+
+/// Human-friendly name and unit for a state label.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LabelMeta {
+    pub display: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+}
+
+/// Timing metadata used by readers to reconstruct simulated time.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlaybackMeta {
+    /// Simulated seconds per dynamics update.
+    pub dt: f64,
+    /// Dynamics updates per simulation step.
+    pub substeps: u32,
+    /// Simulation steps between recorded frames.
+    pub save_interval: u64,
+    /// Number of recorded frames, if known when the header is written.
+    pub total_frames: Option<u64>,
+}
+
+impl PlaybackMeta {
+    /// Playback of a file written before headers recorded it: the `config.dt` the
+    /// oldest files kept, or [`DEFAULT_DT`], with one update per step, every step
+    /// recorded and no frame count.
+    pub fn legacy(dt: Option<f64>) -> Self {
+        Self {
+            dt: dt.unwrap_or(DEFAULT_DT),
+            substeps: 1,
+            save_interval: 1,
+            total_frames: None,
+        }
+    }
+
+    /// Frames recorded over a run of `sim_frames` simulation steps, counting the
+    /// first step and every `save_interval`-th one after it.
+    pub fn frames_for_sim_frames(&self, sim_frames: u64) -> u64 {
+        sim_frames.div_ceil(self.save_interval.max(1))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct EvoHeader {
     pub version: u32,
     pub timestamp: String,
+    /// Definition the recording was produced from (e.g. `universal_gravitation`).
+    /// Empty in files written before it was recorded.
+    #[serde(default)]
+    pub def_name: String,
     pub config: EvoConfig,
+    /// Absent in older files; [`Self::from_json`] fills in [`PlaybackMeta::legacy`].
+    pub playback: PlaybackMeta,
+    /// Where this file sits in a sharded recording; absent for a standalone file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<ShardMeta>,
+    /// The backend the frames were computed on; absent in older files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<RuntimeMeta>,
+    /// The `--seed` the simulation was initialized from; absent in older files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Order of the values within each frame; absent (agent by agent) in older files.
+    #[serde(default, skip_serializing_if = "FrameLayout::is_aos")]
+    pub layout: FrameLayout,
+    /// How each frame is stored in the body; absent (raw) in older files.
+    #[serde(default, skip_serializing_if = "FrameCompression::is_none")]
+    pub compression: FrameCompression,
+    /// Precision of each stored value; absent (f32) in older files.
+    #[serde(default, skip_serializing_if = "FrameDtype::is_f32")]
+    pub dtype: FrameDtype,
+    /// CRC-32 of the body as stored (length prefixes of compressed frames
+    /// included), set when the recording is finished alongside
+    /// `playback.total_frames`; absent in unfinished and older files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_crc32: Option<u32>,
+}
+
+/// How the `n_agents x state_dims` values of a frame are ordered in the body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameLayout {
+    /// Agent by agent (`[n_agents, state_dims]`), so one agent's state is
+    /// contiguous. Suits per-agent drawing.
+    #[default]
+    Aos,
+    /// State variable by state variable (`[state_dims, n_agents]`), so one column
+    /// is contiguous. Suits analysis that reads a few columns.
+    Soa,
+}
+
+impl FrameLayout {
+    pub fn is_aos(&self) -> bool {
+        *self == Self::Aos
+    }
+}
+
+impl std::str::FromStr for FrameLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        match s {
+            "aos" => Ok(Self::Aos),
+            "soa" => Ok(Self::Soa),
+            _ => Err(format!("unknown frame layout {s:?} (expected aos or soa)")),
+        }
+    }
+}
+
+/// How frames are stored in the body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameCompression {
+    /// Raw little-endian f32s, every frame the same size, so frame `i` starts at
+    /// `i * frame_bytes` into the body.
+    #[default]
+    None,
+    /// Each frame deflated on its own and stored as `[len: u32, bytes]`, so frames
+    /// must be walked from the start of the body to be located.
+    Deflate,
+}
+
+impl FrameCompression {
+    pub fn is_none(&self) -> bool {
+        *self == Self::None
+    }
+}
+
+impl std::str::FromStr for FrameCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        match s {
+            "none" => Ok(Self::None),
+            "deflate" => Ok(Self::Deflate),
+            _ => Err(format!(
+                "unknown frame compression {s:?} (expected none or deflate)"
+            )),
+        }
+    }
+}
+
+/// Precision of the values stored in the body. Frames are handed to the
+/// recorder and read back as f32 either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameDtype {
+    /// Little-endian f32s.
+    #[default]
+    F32,
+    /// Little-endian IEEE half-precision floats: half the size, about three
+    /// significant digits, and magnitudes up to 65504 (larger ones become
+    /// infinite). Enough for viewing a run.
+    F16,
+}
+
+impl FrameDtype {
+    pub fn is_f32(&self) -> bool {
+        *self == Self::F32
+    }
+
+    /// Bytes per stored value.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::F32 => 4,
+            Self::F16 => 2,
+        }
+    }
+
+    /// Appends `value` to `out` in this precision.
+    pub fn encode(&self, value: f32, out: &mut Vec<u8>) {
+        match self {
+            Self::F32 => out.extend_from_slice(&value.to_le_bytes()),
+            Self::F16 => out.extend_from_slice(&f16::from_f32(value).to_le_bytes()),
+        }
+    }
+
+    /// The value stored in `bytes`, [`Self::size`] of them.
+    pub fn decode(&self, bytes: &[u8]) -> f32 {
+        match self {
+            Self::F32 => f32::from_le_bytes(bytes.try_into().unwrap()),
+            Self::F16 => f16::from_le_bytes(bytes.try_into().unwrap()).to_f32(),
+        }
+    }
+}
+
+impl std::str::FromStr for FrameDtype {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        match s {
+            "f32" => Ok(Self::F32),
+            "f16" => Ok(Self::F16),
+            _ => Err(format!("unknown frame dtype {s:?} (expected f32 or f16)")),
+        }
+    }
+}
+
+/// Device and library versions a recording was computed with, since backends
+/// can disagree in the last bits (e.g. Metal vs CPU).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuntimeMeta {
+    /// `cpu`, `cuda` or `metal`.
+    pub device: String,
+    /// candle-core version the simulator was built with.
+    pub backend_version: String,
+    /// Backend features (`cuda`, `metal`) the simulator was built with.
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+impl RuntimeMeta {
+    pub fn new(device: &Device) -> Self {
+        let kind = if device.is_cuda() {
+            "cuda"
+        } else if device.is_metal() {
+            "metal"
+        } else {
+            "cpu"
+        };
+        let mut features = Vec::new();
+        if cfg!(feature = "cuda") {
+            features.push("cuda".to_string());
+        }
+        if cfg!(feature = "metal") {
+            features.push("metal".to_string());
+        }
+        Self {
+            device: kind.to_string(),
+            backend_version: env!("CANDLE_VERSION").to_string(),
+            features,
+        }
+    }
+}
+
+/// Position of one shard in a recording split across files by [`ShardedRecorder`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShardMeta {
+    /// Shards are numbered from 0 in timeline order.
+    pub shard_index: u32,
+    /// Frames recorded in earlier shards: this shard's frame 0 is frame
+    /// `frame_offset` of the whole recording.
+    pub frame_offset: u64,
+    /// Simulation step of this shard's first frame. The shard's sim-step track,
+    /// if any, counts from it.
+    pub step_offset: u64,
 }
 
 impl EvoHeader {
-    pub fn new(config: EvoConfig) -> Self {
+    pub fn new(def_name: &str, config: EvoConfig, playback: PlaybackMeta) -> Self {
         let now: DateTime<Utc> = Utc::now();
         Self {
-            version: 1,
+            version: FORMAT_VERSION,
             timestamp: now.to_rfc3339(),
+            def_name: def_name.to_string(),
             config,
+            playback,
+            shard: None,
+            runtime: None,
+            seed: None,
+            layout: FrameLayout::Aos,
+            compression: FrameCompression::None,
+            dtype: FrameDtype::F32,
+            body_crc32: None,
+        }
+    }
+
+    /// Parses a header as stored in a file, giving files written before playback
+    /// metadata was recorded [`PlaybackMeta::legacy`] playback.
+    pub fn from_json(json: &[u8]) -> serde_json::Result<Self> {
+        let mut value: serde_json::Value = serde_json::from_slice(json)?;
+        if let Some(header) = value.as_object_mut() {
+            if !header.contains_key("playback") {
+                let dt = header
+                    .get("config")
+                    .and_then(|config| config.get("dt"))
+                    .and_then(serde_json::Value::as_f64);
+                let playback = serde_json::to_value(PlaybackMeta::legacy(dt))?;
+                header.insert("playback".to_string(), playback);
+            }
         }
+        serde_json::from_value(value)
+    }
+
+    /// Bytes of one uncompressed frame.
+    pub fn frame_bytes(&self) -> u64 {
+        (self.config.n_agents * self.config.state_dims * self.dtype.size()) as u64
     }
 }
 
+/// Header of a `"test"` recording of `n_agents` agents with state `labels`, one
+/// frame per unit step and no frame count, for tests to adjust.
+#[cfg(test)]
+pub(crate) fn test_header(labels: &[&str], n_agents: usize) -> EvoHeader {
+    EvoHeader::new(
+        "test",
+        EvoConfig {
+            n_agents,
+            state_dims: labels.len(),
+            state_labels: labels.iter().map(|s| s.to_string()).collect(),
+            torus_ranges: BTreeMap::new(),
+            label_meta: BTreeMap::new(),
+            clamps: BTreeMap::new(),
+        },
+        PlaybackMeta {
+            dt: 1.0,
+            substeps: 1,
+            save_interval: 1,
+            total_frames: None,
+        },
+    )
+}
+
 pub struct EvoRecorder {
-    writer: BufWriter<File>,
     header: EvoHeader,
-    frame_buffer: Vec<u8>,
+    header_len: usize,
+    frame_bytes: u64,
+    /// The file, while no background writer holds it.
+    body: Option<BodyWriter>,
+    /// The background writer frames are queued for, once one is running.
+    queue: Option<FrameQueue>,
     frames_written: u64,
+    compute_times: Vec<f32>,
+    sim_steps: Vec<u64>,
+    generations: Vec<u64>,
+    /// The state columns recorded from each tensor given to [`Self::write_frame`];
+    /// all of them when `None`.
+    columns: Option<Vec<u32>>,
+    finished: bool,
+}
+
+/// Frames waiting for the background writer, at most; a simulation that gets
+/// this far ahead of the disk waits for it, so a slow disk cannot pile up
+/// frames in memory.
+const FRAME_QUEUE_LEN: usize = 8;
+
+/// The file a recording's body is written to, and where its frames went.
+struct BodyWriter {
+    writer: BufWriter<File>,
+    compression: FrameCompression,
+    body_offset: u64,
+    /// Bytes of body written so far, length prefixes included.
+    body_bytes: u64,
+    /// File offset of each frame written, for the frame index.
+    frame_offsets: Vec<u64>,
+    /// Scratch space for a compressed frame.
+    compressed: Vec<u8>,
+    /// Running checksum of the body written so far.
+    crc: crc32fast::Hasher,
+}
+
+impl BodyWriter {
+    fn body_end(&self) -> u64 {
+        self.body_offset + self.body_bytes
+    }
+
+    /// Writes the encoded `frame` to the body, compressing it if the header asks to.
+    fn append_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.frame_offsets.push(self.body_end());
+        match self.compression {
+            FrameCompression::None => {
+                self.writer.write_all(frame)?;
+                self.crc.update(frame);
+                self.body_bytes += frame.len() as u64;
+            }
+            FrameCompression::Deflate => {
+                self.compressed.clear();
+                // The fastest level, so compression keeps up with the simulation.
+                let mut encoder =
+                    DeflateEncoder::new(&mut self.compressed, flate2::Compression::fast());
+                encoder.write_all(frame)?;
+                encoder.finish()?;
+                let prefix = (self.compressed.len() as u32).to_le_bytes();
+                self.writer.write_all(&prefix)?;
+                self.writer.write_all(&self.compressed)?;
+                self.crc.update(&prefix);
+                self.crc.update(&self.compressed);
+                self.body_bytes += 4 + self.compressed.len() as u64;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_index(&mut self) -> Result<()> {
+        let index_len = self.frame_offsets.len() as u64 * 8;
+        let index_len = u32::try_from(index_len).map_err(|_| EvoError::IndexTooLarge {
+            frames: self.frame_offsets.len() as u64,
+        })?;
+        for offset in &self.frame_offsets {
+            self.writer.write_all(&offset.to_le_bytes())?;
+        }
+        self.writer.write_all(&index_len.to_le_bytes())?;
+        self.writer.write_all(INDEX_MAGIC)?;
+        Ok(())
+    }
+
+    /// Writes `header_json` over the header and returns to the end of the body.
+    fn rewrite_header(&mut self, header_json: &[u8]) -> io::Result<()> {
+        self.writer.flush()?;
+        let file = self.writer.get_mut();
+        file.seek(SeekFrom::Start((MAGIC_BYTES.len() + 4) as u64))?;
+        file.write_all(header_json)?;
+        let body_end = self.body_end();
+        self.writer.get_mut().seek(SeekFrom::Start(body_end))?;
+        Ok(())
+    }
+}
+
+/// A thread appending the encoded frames sent to it, so neither the disk nor
+/// compression holds up the simulation. Frame buffers it is done with come
+/// back on `free` for reuse.
+struct FrameQueue {
+    frames: SyncSender<Vec<u8>>,
+    free: Receiver<Vec<u8>>,
+    /// How far the writer has got, readable without waiting for it.
+    progress: Arc<WriterProgress>,
+    thread: JoinHandle<io::Result<BodyWriter>>,
+}
+
+/// Frames a [`FrameQueue`]'s writer has appended and where the body then ended.
+#[derive(Default)]
+struct WriterProgress {
+    frames: AtomicU64,
+    body_end: AtomicU64,
+}
+
+impl FrameQueue {
+    fn spawn(mut body: BodyWriter) -> io::Result<Self> {
+        let (frames, queued) = mpsc::sync_channel::<Vec<u8>>(FRAME_QUEUE_LEN);
+        let (recycle, free) = mpsc::sync_channel(FRAME_QUEUE_LEN);
+        let progress = Arc::new(WriterProgress::default());
+        progress.body_end.store(body.body_end(), Ordering::Release);
+        let thread = thread::Builder::new()
+            .name("evo-recorder".to_string())
+            .spawn({
+                let progress = Arc::clone(&progress);
+                move || {
+                    for frame in queued {
+                        body.append_frame(&frame)?;
+                        // The end first, so it never lags the frame count.
+                        progress.body_end.store(body.body_end(), Ordering::Release);
+                        progress.frames.fetch_add(1, Ordering::Release);
+                        // Spare buffers beyond the queue's length are dropped.
+                        let _ = recycle.try_send(frame);
+                    }
+                    Ok(body)
+                }
+            })?;
+        Ok(Self {
+            frames,
+            free,
+            progress,
+            thread,
+        })
+    }
+
+    /// Writes the frames still queued and hands the file back.
+    fn join(self) -> io::Result<BodyWriter> {
+        drop(self.frames);
+        self.thread
+            .join()
+            .map_err(|_| io::Error::other("the recording thread panicked"))?
+    }
 }
 
 impl EvoRecorder {
-    pub fn create<P: AsRef<Path>>(path: P, header: EvoHeader) -> Result<Self> {
+    /// Creates the file and writes the header. The file is not pre-sized to
+    /// `playback.total_frames`: a run that crashes before [`Self::finish`] trims
+    /// the space would leave zero-filled frames that read back as recorded ones.
+    pub fn create<P: AsRef<Path>>(path: P, mut header: EvoHeader) -> Result<Self> {
+        // A checksum copied from another file's header describes that body.
+        header.body_crc32 = None;
         let file = File::create(path)?;
         let mut writer = BufWriter::new(file);
-        let header_json = serde_json::to_vec(&header)?;
+        let mut header_json = serde_json::to_vec(&header)?;
+        header_json.resize(header_json.len() + HEADER_SLACK_BYTES, b' ');
         if header_json.len() > MAX_HEADER_BYTES as usize {
-            bail!(
-                "Header too large to encode length (max {} bytes)",
-                MAX_HEADER_BYTES
-            );
+            return Err(EvoError::HeaderTooLarge {
+                len: header_json.len(),
+                max: MAX_HEADER_BYTES as usize,
+            });
         }
-        let header_len = header_json.len() as u32;
+        let header_len = header_json.len();
 
         writer.write_all(MAGIC_BYTES)?;
-        writer.write_all(&header_len.to_le_bytes())?;
+        writer.write_all(&(header_len as u32).to_le_bytes())?;
         writer.write_all(&header_json)?;
 
-        let capacity =
-            header.config.n_agents * header.config.state_dims * std::mem::size_of::<f32>();
-
-        Ok(Self {
+        let frame_bytes = header.frame_bytes();
+        let body_offset = (MAGIC_BYTES.len() + 4 + header_len) as u64;
+        let body = BodyWriter {
             writer,
+            compression: header.compression,
+            body_offset,
+            body_bytes: 0,
+            frame_offsets: Vec::new(),
+            compressed: Vec::new(),
+            crc: crc32fast::Hasher::new(),
+        };
+        Ok(Self {
             header,
-            frame_buffer: Vec::with_capacity(capacity),
+            header_len,
+            frame_bytes,
+            body: Some(body),
+            queue: None,
             frames_written: 0,
+            compute_times: Vec::new(),
+            sim_steps: Vec::new(),
+            generations: Vec::new(),
+            columns: None,
+            finished: false,
+        })
+    }
+
+    /// Reopens the recording at `path` to continue it after its first `frames`
+    /// frames, e.g. those a checkpoint was taken at. Later frames, left by a run
+    /// that went on past the checkpoint or crashed, are dropped with the trailer
+    /// and frame index; their compute times
+    /// and sim steps too. The header is kept as written, except that its frame
+    /// count is left for [`Self::finish`] to fill in.
+    pub fn append<P: AsRef<Path>>(path: P, frames: u64) -> Result<Self> {
+        let path = path.as_ref();
+        let mut reader = EvoReader::open(path)?;
+        if frames > reader.total_frames() {
+            return Err(EvoError::MissingFrames {
+                frames,
+                total_frames: reader.total_frames(),
+            });
+        }
+        let body_offset = reader.body_offset();
+        let body_end = reader.frame_offset(frames);
+        let frame_offsets = (0..frames).map(|i| reader.frame_offset(i)).collect();
+        let crc = reader.body_hasher(body_end)?;
+        let mut compute_times = reader.compute_times().unwrap_or_default();
+        compute_times.truncate(frames as usize);
+        let mut sim_steps = reader.sim_steps().unwrap_or_default();
+        sim_steps.truncate(frames as usize);
+        let mut generations = reader.generations().unwrap_or_default();
+        generations.truncate(frames as usize);
+        let mut header = reader.header;
+        // The file gets a frame index when it is finished.
+        header.version = FORMAT_VERSION;
+        header.playback.total_frames = None;
+        header.body_crc32 = None;
+
+        let file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(body_end)?;
+        let mut writer = BufWriter::new(file);
+        writer.seek(SeekFrom::Start(body_end))?;
+        let frame_bytes = header.frame_bytes();
+        let body = BodyWriter {
+            writer,
+            compression: header.compression,
+            body_offset,
+            body_bytes: body_end - body_offset,
+            frame_offsets,
+            compressed: Vec::new(),
+            crc,
+        };
+        Ok(Self {
+            header,
+            header_len: (body_offset - MAGIC_BYTES.len() as u64 - 4) as usize,
+            frame_bytes,
+            body: Some(body),
+            queue: None,
+            frames_written: frames,
+            compute_times,
+            sim_steps,
+            generations,
+            columns: None,
+            finished: false,
         })
     }
 
+    fn body_offset(&self) -> u64 {
+        (MAGIC_BYTES.len() + 4 + self.header_len) as u64
+    }
+
+    /// An upper bound on where the body ends once the frames written so far
+    /// reach the file, found without waiting for them: exact for uncompressed
+    /// frames; for compressed ones, where the writer has got to plus the most
+    /// deflate could make of each frame still queued.
+    fn body_end_bound(&self) -> u64 {
+        let Some(queue) = &self.queue else {
+            return self.body.as_ref().map_or(0, BodyWriter::body_end);
+        };
+        if self.header.compression == FrameCompression::None {
+            return self.body_offset() + self.frames_written * self.frame_bytes;
+        }
+        let appended = queue.progress.frames.load(Ordering::Acquire);
+        let body_end = queue.progress.body_end.load(Ordering::Acquire);
+        let queued = self.frames_written - appended;
+        body_end + queued * (4 + deflate_bound(self.frame_bytes))
+    }
+
+    /// Where the body ends once the frames written so far reach the file.
+    /// Compressed frames are only sized as they are written, so for those this
+    /// waits for the queue to drain.
+    fn body_end(&mut self) -> Result<u64> {
+        match self.header.compression {
+            FrameCompression::None => {
+                Ok(self.body_offset() + self.frames_written * self.frame_bytes)
+            }
+            FrameCompression::Deflate => Ok(self.drain()?.body_end()),
+        }
+    }
+
+    /// Waits for the queued frames to be written and takes the file back from
+    /// the background writer, surfacing the error it stopped on, if any.
+    fn drain(&mut self) -> Result<&mut BodyWriter> {
+        if let Some(queue) = self.queue.take() {
+            self.body = Some(queue.join()?);
+        }
+        self.body.as_mut().ok_or_else(stopped)
+    }
+
+    /// An empty buffer for the next frame, reusing one the writer is done with.
+    fn frame_buffer(&self) -> Vec<u8> {
+        let mut frame = self
+            .queue
+            .as_ref()
+            .and_then(|queue| queue.free.try_recv().ok())
+            .unwrap_or_else(|| Vec::with_capacity(self.frame_bytes as usize));
+        frame.clear();
+        frame
+    }
+
+    /// Hands the encoded `frame` to the background writer, starting it if need
+    /// be. Only waits while [`FRAME_QUEUE_LEN`] frames are already queued.
+    fn queue_frame(&mut self, frame: Vec<u8>) -> Result<()> {
+        if self.queue.is_none() {
+            let body = self.body.take().ok_or_else(stopped)?;
+            self.queue = Some(FrameQueue::spawn(body)?);
+        }
+        let queue = self.queue.as_ref().expect("started above");
+        if queue.frames.send(frame).is_err() {
+            // The writer only hangs up after a failed write; joining it returns
+            // that error.
+            self.drain()?;
+            return Err(stopped());
+        }
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    /// Records only state columns `columns` of the tensors given to
+    /// [`Self::write_frame`] from now on, in that order: those the header's
+    /// labels name. See [`EvoConfig::subset`].
+    pub fn select_columns(&mut self, columns: &[usize]) {
+        self.columns = Some(columns.iter().map(|&c| c as u32).collect());
+    }
+
+    /// Copies `state` (just the [selected](Self::select_columns) columns, if
+    /// any) to the host and queues it; see [`Self::write_frame_f32`].
     pub fn write_frame(&mut self, state: &Tensor) -> Result<()> {
+        let selected;
+        let state = match &self.columns {
+            Some(columns) => {
+                let columns = Tensor::from_slice(columns, columns.len(), state.device())?;
+                selected = state.contiguous()?.index_select(&columns, 1)?;
+                &selected
+            }
+            None => state,
+        };
         let dims = state.dims();
         if dims.len() != 2
             || dims[0] != self.header.config.n_agents
             || dims[1] != self.header.config.state_dims
         {
-            bail!(
-                "Shape mismatch: expected ({}, {}), got {:?}",
-                self.header.config.n_agents,
-                self.header.config.state_dims,
-                dims
-            );
+            return Err(EvoError::ShapeMismatch {
+                expected: [self.header.config.n_agents, self.header.config.state_dims],
+                found: dims.to_vec(),
+            });
         }
 
-        let frame = state.to_vec2::<f32>()?;
-        let flat: Vec<f32> = frame.into_iter().flatten().collect();
+        let flat = state.flatten_all()?.to_vec1::<f32>()?;
         self.write_frame_f32(&flat)
     }
 
+    /// Appends a frame given agent by agent (`[n_agents, state_dims]`, row-major),
+    /// transposing it first if the header asks for [`FrameLayout::Soa`] and
+    /// rounding it if it asks for [`FrameDtype::F16`].
+    ///
+    /// The frame is encoded here and written (and compressed) on a background
+    /// thread; [`Self::flush`] and [`Self::finish`] wait for it to catch up.
     pub fn write_frame_f32(&mut self, flat: &[f32]) -> Result<()> {
-        let expected = self.header.config.n_agents * self.header.config.state_dims;
+        let (n_agents, state_dims) = (self.header.config.n_agents, self.header.config.state_dims);
+        let expected = n_agents * state_dims;
         if flat.len() != expected {
-            bail!(
-                "Frame length mismatch: expected {}, got {}",
+            return Err(EvoError::FrameLength {
+                expected,
+                found: flat.len(),
+            });
+        }
+
+        let mut frame = self.frame_buffer();
+        let dtype = self.header.dtype;
+        match self.header.layout {
+            FrameLayout::Aos => {
+                for &value in flat {
+                    dtype.encode(value, &mut frame);
+                }
+            }
+            FrameLayout::Soa => {
+                for dim in 0..state_dims {
+                    for agent in 0..n_agents {
+                        dtype.encode(flat[agent * state_dims + dim], &mut frame);
+                    }
+                }
+            }
+        }
+        self.queue_frame(frame)
+    }
+
+    /// Appends an already-encoded frame (little-endian values in the header's
+    /// layout and dtype, uncompressed), e.g. one copied from another file by
+    /// [`crate::reader::EvoReader::read_frame_bytes`].
+    pub fn write_frame_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let expected = self.frame_bytes as usize;
+        if bytes.len() != expected {
+            return Err(EvoError::FrameLength {
                 expected,
-                flat.len()
-            );
+                found: bytes.len(),
+            });
         }
+        let mut frame = self.frame_buffer();
+        frame.extend_from_slice(bytes);
+        self.queue_frame(frame)
+    }
+
+    /// Waits for the queued frames to be written, then flushes them to the file.
+    pub fn flush(&mut self) -> Result<()> {
+        self.drain()?.writer.flush()?;
+        Ok(())
+    }
+
+    /// Frames handed to the recorder, including any still queued for the disk.
+    pub fn frames_written(&self) -> u64 {
+        self.frames_written
+    }
 
-        let byte_slice = unsafe {
-            std::slice::from_raw_parts(
-                flat.as_ptr() as *const u8,
-                flat.len() * std::mem::size_of::<f32>(),
-            )
+    /// Records the wall-clock seconds spent producing the most recent frame.
+    /// Opt-in: the timing track is only written if this is called.
+    pub fn record_compute_time(&mut self, seconds: f32) {
+        self.compute_times.push(seconds);
+    }
+
+    /// Records the simulation step the most recent frame was captured at. Opt-in:
+    /// only needed when frames are not uniformly spaced.
+    pub fn record_sim_step(&mut self, step: u64) {
+        self.sim_steps.push(step);
+    }
+
+    /// Records the generation the most recent frame belongs to. Opt-in: only
+    /// evolving runs have generations.
+    pub fn record_generation(&mut self, generation: u64) {
+        self.generations.push(generation);
+    }
+
+    /// Waits for the queued frames, appends the trailer (if any sections were
+    /// recorded) and the frame index, and rewrites the header so
+    /// `playback.total_frames` matches the frames actually written, warning if it
+    /// had declared otherwise, and `body_crc32` holds the body's checksum. Call
+    /// once, after the last frame; later calls do nothing. Dropping an unfinished
+    /// recorder finishes it.
+    pub fn finish(&mut self) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        if let Some(declared) = self.header.playback.total_frames {
+            if declared != self.frames_written {
+                eprintln!(
+                    "⚠️  Header declared {} frames but {} were written; patching header",
+                    declared, self.frames_written
+                );
+            }
+        }
+        self.header.playback.total_frames = Some(self.frames_written);
+        self.header.body_crc32 = Some(self.drain()?.crc.clone().finalize());
+        let header_json = self.header_json()?;
+        let trailer = self.trailer();
+        let body = self.drain()?;
+        body.rewrite_header(&header_json)?;
+        body.writer.write_all(&trailer)?;
+        body.write_index()?;
+        body.writer.flush()?;
+        Ok(())
+    }
+
+    /// The trailer's sections, length and magic; empty if no track was recorded.
+    fn trailer(&self) -> Vec<u8> {
+        let frames_written = self.frames_written;
+        let mut trailer = Vec::new();
+        let mut push_section = |tag: &[u8; 4], name: &str, count: usize, payload: Vec<u8>| {
+            if count == 0 {
+                return;
+            }
+            if count as u64 != frames_written {
+                eprintln!(
+                    "⚠️  Recorded {} for {} of {} frames",
+                    name, count, frames_written
+                );
+            }
+            trailer.extend_from_slice(tag);
+            trailer.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+            trailer.extend_from_slice(&payload);
         };
+        push_section(
+            COMPUTE_TIME_TAG,
+            "compute times",
+            self.compute_times.len(),
+            self.compute_times
+                .iter()
+                .flat_map(|t| t.to_le_bytes())
+                .collect(),
+        );
+        push_section(
+            SIM_STEP_TAG,
+            "sim steps",
+            self.sim_steps.len(),
+            self.sim_steps
+                .iter()
+                .flat_map(|s| s.to_le_bytes())
+                .collect(),
+        );
+        push_section(
+            GENERATION_TAG,
+            "generations",
+            self.generations.len(),
+            self.generations
+                .iter()
+                .flat_map(|g| g.to_le_bytes())
+                .collect(),
+        );
+        if trailer.is_empty() {
+            return trailer;
+        }
 
-        self.frame_buffer.clear();
-        self.frame_buffer.extend_from_slice(byte_slice);
-        self.writer.write_all(&self.frame_buffer)?;
+        let trailer_len = trailer.len() as u64;
+        trailer.extend_from_slice(&trailer_len.to_le_bytes());
+        trailer.extend_from_slice(TRAILER_MAGIC);
+        trailer
+    }
 
-        self.frames_written += 1;
+    /// The header as JSON, padded to the space reserved for it.
+    fn header_json(&self) -> Result<Vec<u8>> {
+        let mut header_json = serde_json::to_vec(&self.header)?;
+        if header_json.len() > self.header_len {
+            return Err(EvoError::HeaderTooLarge {
+                len: header_json.len(),
+                max: self.header_len,
+            });
+        }
+        header_json.resize(self.header_len, b' ');
+        Ok(header_json)
+    }
+}
+
+/// What a recorder whose background writer failed returns from then on.
+fn stopped() -> EvoError {
+    io::Error::other("the recording stopped at an earlier write error").into()
+}
+
+/// Size of the frame index of a file holding `frames` frames, footer included.
+fn index_bytes(frames: u64) -> u64 {
+    frames * 8 + 4 + INDEX_MAGIC.len() as u64
+}
+
+/// Most bytes deflating `len` bytes can take: zlib's conservative `deflateBound`,
+/// which covers data that does not compress and goes out in stored blocks.
+fn deflate_bound(len: u64) -> u64 {
+    len + len.div_ceil(8) + len.div_ceil(64) + 5
+}
+
+impl Drop for EvoRecorder {
+    /// Finishes a recording cut short by an early return, a `?`-propagated error or
+    /// a panic, so the partial file keeps its header count and trailer.
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            eprintln!("⚠️  Failed to finish recording on drop: {e:#}");
+        }
+    }
+}
+
+/// Shards of a recording and the frames each holds, written next to them as
+/// `<name>.shards.json`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShardManifest {
+    pub shards: Vec<ShardEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShardEntry {
+    /// File name, relative to the manifest's directory.
+    pub path: String,
+    pub frame_offset: u64,
+    /// See [`ShardMeta::step_offset`].
+    pub step_offset: u64,
+    pub frames: u64,
+}
+
+/// Records one timeline into shards of at most `shard_bytes` each (plus their
+/// trailers): `name.evo` is written as `name.000.evo`, `name.001.evo`, and so
+/// on, each with the full header and its [`ShardMeta`]. A shard always holds at
+/// least one frame, however small the limit.
+pub struct ShardedRecorder {
+    path: PathBuf,
+    header: EvoHeader,
+    shard_bytes: u64,
+    current: EvoRecorder,
+    /// See [`EvoRecorder::select_columns`].
+    columns: Option<Vec<usize>>,
+    manifest: ShardManifest,
+    finished: bool,
+}
+
+impl ShardedRecorder {
+    /// Creates the first shard. `header.playback.total_frames` is ignored, since
+    /// a shard's length is only known once it is full.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        mut header: EvoHeader,
+        shard_bytes: u64,
+    ) -> Result<Self> {
+        header.playback.total_frames = None;
+        let path = path.as_ref().to_path_buf();
+        let current = Self::create_shard(&path, &header, 0, 0)?;
+        Ok(Self {
+            path,
+            header,
+            shard_bytes,
+            current,
+            columns: None,
+            manifest: ShardManifest { shards: Vec::new() },
+            finished: false,
+        })
+    }
+
+    /// `dir/name.evo` -> `dir/name.007.evo`
+    pub fn shard_path(path: &Path, shard_index: u32) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(ext) => format!("{stem}.{shard_index:03}.{}", ext.to_string_lossy()),
+            None => format!("{stem}.{shard_index:03}"),
+        };
+        path.with_file_name(name)
+    }
+
+    /// `dir/name.evo` -> `dir/name.shards.json`
+    pub fn manifest_path(path: &Path) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!("{stem}.shards.json"))
+    }
+
+    fn create_shard(
+        path: &Path,
+        header: &EvoHeader,
+        shard_index: u32,
+        frame_offset: u64,
+    ) -> Result<EvoRecorder> {
+        let mut header = header.clone();
+        header.shard = Some(ShardMeta {
+            shard_index,
+            frame_offset,
+            step_offset: frame_offset * header.playback.save_interval,
+        });
+        EvoRecorder::create(Self::shard_path(path, shard_index), header)
+    }
+
+    fn meta(&self) -> &ShardMeta {
+        self.current
+            .header
+            .shard
+            .as_ref()
+            .expect("shards carry ShardMeta")
+    }
+
+    /// Finishes the current shard and notes it in the manifest.
+    fn finish_shard(&mut self) -> Result<()> {
+        self.current.finish()?;
+        let meta = self.meta();
+        let path = Self::shard_path(&self.path, meta.shard_index);
+        self.manifest.shards.push(ShardEntry {
+            path: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            frame_offset: meta.frame_offset,
+            step_offset: meta.step_offset,
+            frames: self.current.frames_written,
+        });
         Ok(())
     }
 
-    pub fn flush(&mut self) -> Result<()> {
-        self.writer.flush()?;
+    /// Moves on to the next shard if one more frame would overflow this one.
+    /// Only waits on the writer for the exact size of compressed frames once the
+    /// shard might be that full.
+    fn rotate_if_full(&mut self) -> Result<()> {
+        let frames = self.current.frames_written;
+        let next = self.current.frame_bytes + index_bytes(frames + 1);
+        let full = frames > 0
+            && self.current.body_end_bound() + next > self.shard_bytes
+            && self.current.body_end()? + next > self.shard_bytes;
+        if !full {
+            return Ok(());
+        }
+        self.finish_shard()?;
+        let meta = self.meta();
+        let (shard_index, frame_offset) = (
+            meta.shard_index + 1,
+            meta.frame_offset + self.current.frames_written,
+        );
+        self.current = Self::create_shard(&self.path, &self.header, shard_index, frame_offset)?;
+        if let Some(columns) = &self.columns {
+            self.current.select_columns(columns);
+        }
         Ok(())
     }
 
+    /// See [`EvoRecorder::select_columns`]; applies to every shard.
+    pub fn select_columns(&mut self, columns: &[usize]) {
+        self.current.select_columns(columns);
+        self.columns = Some(columns.to_vec());
+    }
+
+    pub fn write_frame(&mut self, state: &Tensor) -> Result<()> {
+        self.rotate_if_full()?;
+        self.current.write_frame(state)
+    }
+
+    pub fn write_frame_f32(&mut self, flat: &[f32]) -> Result<()> {
+        self.rotate_if_full()?;
+        self.current.write_frame_f32(flat)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.current.flush()
+    }
+
+    /// Frames written across all shards.
     pub fn frames_written(&self) -> u64 {
-        self.frames_written
+        self.meta().frame_offset + self.current.frames_written
+    }
+
+    /// Shards created so far.
+    pub fn shard_count(&self) -> u32 {
+        self.meta().shard_index + 1
+    }
+
+    /// See [`EvoRecorder::record_compute_time`].
+    pub fn record_compute_time(&mut self, seconds: f32) {
+        self.current.record_compute_time(seconds);
+    }
+
+    /// See [`EvoRecorder::record_sim_step`]. `step` counts from the start of the
+    /// run; it is stored relative to the current shard's first frame.
+    pub fn record_sim_step(&mut self, step: u64) {
+        let first = self.current.sim_steps.is_empty();
+        let meta = self
+            .current
+            .header
+            .shard
+            .as_mut()
+            .expect("shards carry ShardMeta");
+        if first {
+            meta.step_offset = step;
+        }
+        let step_offset = meta.step_offset;
+        self.current.record_sim_step(step - step_offset);
+    }
+
+    /// See [`EvoRecorder::record_generation`].
+    pub fn record_generation(&mut self, generation: u64) {
+        self.current.record_generation(generation);
+    }
+
+    /// Finishes the last shard and writes the manifest. Later calls do nothing;
+    /// dropping an unfinished recorder finishes it.
+    pub fn finish(&mut self) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        self.finish_shard()?;
+        let manifest = serde_json::to_vec_pretty(&self.manifest)?;
+        std::fs::write(Self::manifest_path(&self.path), manifest)?;
+        Ok(())
+    }
+}
+
+impl Drop for ShardedRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            eprintln!("⚠️  Failed to finish sharded recording on drop: {e:#}");
+        }
     }
 }
 
@@ -139,15 +1197,10 @@ mod tests {
             fs::remove_file(&tmp_path)?;
         }
 
-        let header = EvoHeader::new(EvoConfig {
-            n_agents: 2,
-            state_dims: 3,
-            state_labels: vec![
-                "pos_x".to_string(),
-                "vel_x".to_string(),
-                "energy".to_string(),
-            ],
-        });
+        let mut header = test_header(&["pos_x", "vel_x", "energy"], 2);
+        header.config.torus_ranges = BTreeMap::from([("pos_x".to_string(), [-10.0, 10.0])]);
+        header.playback.dt = 1.0 / 60.0;
+        header.playback.total_frames = Some(1);
 
         let mut recorder = EvoRecorder::create(&tmp_path, header.clone())?;
         let device = Device::Cpu;
@@ -173,4 +1226,520 @@ mod tests {
         fs::remove_file(&tmp_path)?;
         Ok(())
     }
+
+    /// `bytes` up to the frame index, checking it lists `frames` frames.
+    fn without_index(bytes: &[u8], frames: u64) -> &[u8] {
+        let (rest, footer) = bytes.split_at(bytes.len() - 8);
+        assert_eq!(&footer[4..], INDEX_MAGIC);
+        assert_eq!(
+            u32::from_le_bytes(footer[..4].try_into().unwrap()) as u64,
+            frames * 8
+        );
+        &rest[..rest.len() - frames as usize * 8]
+    }
+
+    fn read_header(bytes: &[u8]) -> (EvoHeader, usize) {
+        let header_len = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let header: EvoHeader = serde_json::from_slice(&bytes[8..8 + header_len]).unwrap();
+        (header, header_len)
+    }
+
+    #[test]
+    fn finish_patches_total_frames_to_frames_written() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_finish_test.evo");
+
+        // 10 sim frames at save_interval 3 record frames 0, 3, 6 and 9, but a naive
+        // header would declare 10 / 3 = 3.
+        let (max_sim_frames, save_interval) = (10u64, 3u64);
+        let playback = PlaybackMeta {
+            dt: 1.0,
+            substeps: 1,
+            save_interval,
+            total_frames: None,
+        };
+        assert_eq!(playback.frames_for_sim_frames(max_sim_frames), 4);
+        assert_eq!(playback.frames_for_sim_frames(9), 3);
+
+        let mut header = test_header(&["pos_x", "pos_y"], 1);
+        header.playback.save_interval = save_interval;
+        header.playback.total_frames = Some(max_sim_frames / save_interval);
+
+        let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+        for sim_frame in 0..max_sim_frames {
+            if sim_frame % save_interval == 0 {
+                recorder.write_frame_f32(&[sim_frame as f32, 0.0])?;
+            }
+        }
+        recorder.finish()?;
+        drop(recorder);
+
+        let bytes = fs::read(&tmp_path)?;
+        let bytes = without_index(&bytes, 4);
+        let (parsed, header_len) = read_header(bytes);
+        assert_eq!(parsed.playback.total_frames, Some(4));
+
+        // The body is untouched by the header rewrite.
+        let body: Vec<f32> = bytes[8 + header_len..]
+            .chunks_exact(4)
+            .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        assert_eq!(body, vec![0., 0., 3., 0., 6., 0., 9., 0.]);
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn soa_layout_stores_each_state_variable_contiguously() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_soa_test.evo");
+        let mut header = test_header(&["pos_x", "pos_y"], 3);
+        header.playback.total_frames = Some(1);
+        header.layout = FrameLayout::Soa;
+
+        let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+        let state = Tensor::new(&[[0f32, 10.], [1., 11.], [2., 12.]], &Device::Cpu)?;
+        recorder.write_frame(&state)?;
+        recorder.finish()?;
+        drop(recorder);
+
+        let bytes = fs::read(&tmp_path)?;
+        let bytes = without_index(&bytes, 1);
+        let (parsed, header_len) = read_header(bytes);
+        assert_eq!(parsed.layout, FrameLayout::Soa);
+        let body: Vec<f32> = bytes[8 + header_len..]
+            .chunks_exact(4)
+            .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        assert_eq!(body, vec![0., 1., 2., 10., 11., 12.]);
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn deflated_frames_read_back_exactly() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_deflate_test.evo");
+        let (n_agents, state_dims) = (50, 3);
+        let mut header = test_header(&["pos_x", "pos_y", "energy"], n_agents);
+        header.playback.total_frames = Some(10);
+        header.compression = FrameCompression::Deflate;
+
+        let frames: Vec<Vec<f32>> = (0..10)
+            .map(|frame| {
+                (0..n_agents * state_dims)
+                    .map(|k| match k % 7 {
+                        0 => -0.0,
+                        1 => f32::MIN_POSITIVE / 3.0,
+                        _ => (frame * 1000 + k) as f32 * 0.1,
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+        for frame in &frames {
+            recorder.write_frame_f32(frame)?;
+            recorder.record_compute_time(0.5);
+        }
+        recorder.finish()?;
+        drop(recorder);
+
+        let raw_len = (10 * n_agents * state_dims * 4) as u64;
+        assert!(fs::metadata(&tmp_path)?.len() < raw_len);
+
+        let mut reader = crate::reader::EvoReader::open(&tmp_path)?;
+        assert_eq!(reader.header.compression, FrameCompression::Deflate);
+        assert_eq!(reader.total_frames(), 10);
+        assert_eq!(reader.trailing_partial_bytes(), 0);
+        assert_eq!(reader.compute_times(), Some(vec![0.5; 10]));
+        assert!(reader.verify_checksum()?);
+        let mut buf = Vec::new();
+        for (i, frame) in frames.iter().enumerate().rev() {
+            reader.read_frame_bytes(i as u64, &mut buf)?;
+            let bits: Vec<u32> = buf
+                .chunks_exact(4)
+                .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+                .collect();
+            let expected: Vec<u32> = frame.iter().map(|v| v.to_bits()).collect();
+            assert_eq!(bits, expected, "frame {i}");
+        }
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn finish_declares_the_frames_written_after_an_early_stop() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_early_stop_test.evo");
+        let mut header = test_header(&["pos_x"], 1);
+        header.playback.total_frames = Some(5);
+
+        let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+        let body_offset = recorder.body_offset();
+        // No room is held for the declared frames, so a crash leaves no empty ones.
+        recorder.flush()?;
+        assert_eq!(fs::metadata(&tmp_path)?.len(), body_offset);
+
+        recorder.write_frame_f32(&[1.0])?;
+        recorder.write_frame_f32(&[2.0])?;
+        recorder.finish()?;
+        drop(recorder);
+
+        let bytes = fs::read(&tmp_path)?;
+        let bytes = without_index(&bytes, 2);
+        assert_eq!(bytes.len() as u64, body_offset + 2 * 4);
+        assert_eq!(read_header(bytes).0.playback.total_frames, Some(2));
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn append_continues_after_the_kept_frames() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_append_test.evo");
+        let mut header = test_header(&["pos_x", "pos_y"], 1);
+        for compression in [FrameCompression::None, FrameCompression::Deflate] {
+            header.compression = compression;
+            let mut recorder = EvoRecorder::create(&tmp_path, header.clone())?;
+            for i in 0..3 {
+                recorder.write_frame_f32(&[i as f32, 0.0])?;
+                recorder.record_compute_time(0.5);
+            }
+            recorder.finish()?;
+            drop(recorder);
+
+            // Resume after frame 2 of 3, as if from a checkpoint taken there.
+            let mut recorder = EvoRecorder::append(&tmp_path, 2)?;
+            assert_eq!(recorder.frames_written(), 2);
+            recorder.write_frame_f32(&[9.0, 0.0])?;
+            recorder.record_compute_time(0.25);
+            recorder.finish()?;
+            drop(recorder);
+
+            let mut reader = EvoReader::open(&tmp_path)?;
+            assert_eq!(reader.header.timestamp, header.timestamp);
+            assert_eq!(reader.header.playback.total_frames, Some(3));
+            assert_eq!(reader.compute_times(), Some(vec![0.5, 0.5, 0.25]));
+            // The checksum covers the kept frames and the appended one.
+            assert!(reader.verify_checksum()?, "{compression:?}");
+            let mut buf = Vec::new();
+            for (i, first) in [0.0f32, 1.0, 9.0].into_iter().enumerate() {
+                reader.read_frame_bytes(i as u64, &mut buf)?;
+                assert_eq!(buf[..4], first.to_le_bytes(), "{compression:?} frame {i}");
+            }
+            assert!(matches!(
+                EvoRecorder::append(&tmp_path, 4),
+                Err(EvoError::MissingFrames { .. })
+            ));
+        }
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn finish_appends_compute_time_trailer() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_trailer_test.evo");
+        let header = test_header(&["pos_x"], 1);
+
+        let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+        for (i, seconds) in [0.5f32, 0.25].into_iter().enumerate() {
+            recorder.write_frame_f32(&[i as f32])?;
+            recorder.record_compute_time(seconds);
+        }
+        recorder.finish()?;
+        drop(recorder);
+
+        let bytes = fs::read(&tmp_path)?;
+        let bytes = without_index(&bytes, 2);
+        let (_, header_len) = read_header(bytes);
+        let (body, trailer) = bytes[8 + header_len..].split_at(2 * 4);
+        assert_eq!(body, [0.0f32.to_le_bytes(), 1.0f32.to_le_bytes()].concat());
+
+        let (sections, footer) = trailer.split_at(trailer.len() - 12);
+        assert_eq!(&footer[8..], TRAILER_MAGIC);
+        assert_eq!(
+            u64::from_le_bytes(footer[..8].try_into().unwrap()),
+            sections.len() as u64
+        );
+        assert_eq!(&sections[..4], COMPUTE_TIME_TAG);
+        assert_eq!(u64::from_le_bytes(sections[4..12].try_into().unwrap()), 8);
+        assert_eq!(
+            &sections[12..],
+            [0.5f32.to_le_bytes(), 0.25f32.to_le_bytes()].concat()
+        );
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unwinding_finishes_a_partial_recording() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_panic_test.evo");
+        let mut header = test_header(&["pos_x"], 1);
+        header.playback.total_frames = Some(10);
+
+        let result = std::panic::catch_unwind(|| {
+            let mut recorder = EvoRecorder::create(&tmp_path, header).unwrap();
+            for i in 0..3 {
+                recorder.write_frame_f32(&[i as f32]).unwrap();
+                recorder.record_compute_time(0.5);
+            }
+            panic!("simulated crash mid-run");
+        });
+        assert!(result.is_err());
+
+        let reader = crate::reader::EvoReader::open(&tmp_path)?;
+        assert_eq!(reader.header.playback.total_frames, Some(3));
+        assert_eq!(reader.total_frames(), 3);
+        assert_eq!(reader.compute_times(), Some(vec![0.5; 3]));
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn early_return_keeps_every_reported_frame() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_drop_test.evo");
+        let header = test_header(&["pos_x"], 2);
+
+        // Stands in for a record loop that bails out through `?` without finishing.
+        let run = |frames_written: &mut u64| -> Result<()> {
+            let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+            for i in 0..5 {
+                recorder.write_frame_f32(&[i as f32, -(i as f32)])?;
+            }
+            *frames_written = recorder.frames_written();
+            recorder.write_frame_f32(&[0.0])?;
+            unreachable!("the short frame is rejected");
+        };
+        let mut frames_written = 0;
+        assert!(matches!(
+            run(&mut frames_written),
+            Err(EvoError::FrameLength {
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert_eq!(frames_written, 5);
+
+        let mut reader = crate::reader::EvoReader::open(&tmp_path)?;
+        assert_eq!(reader.total_frames(), frames_written);
+        assert_eq!(reader.header.playback.total_frames, Some(frames_written));
+        let mut buf = Vec::new();
+        reader.read_frame_bytes(4, &mut buf)?;
+        assert_eq!(
+            buf,
+            [4.0f32.to_le_bytes(), (-4.0f32).to_le_bytes()].concat()
+        );
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn frames_are_little_endian_on_any_host() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_endian_test.evo");
+        let header = test_header(&["pos_x", "pos_y"], 1);
+        let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+        let body_offset = recorder.body_offset() as usize;
+        recorder.write_frame_f32(&[1.5, -2.0])?;
+        recorder.finish()?;
+
+        // 1.5 is 0x3FC00000 and -2.0 is 0xC0000000, lowest byte first.
+        let bytes = fs::read(&tmp_path)?;
+        assert_eq!(
+            bytes[body_offset..body_offset + 8],
+            [0x00, 0x00, 0xC0, 0x3F, 0x00, 0x00, 0x00, 0xC0]
+        );
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn f16_frames_take_two_bytes_per_value() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_f16_test.evo");
+        let mut header = test_header(&["pos_x"], 3);
+        header.dtype = FrameDtype::F16;
+        let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+        recorder.write_frame_f32(&[1.5, 70000.0, 0.1])?;
+        recorder.finish()?;
+
+        let mut reader = crate::reader::EvoReader::open(&tmp_path)?;
+        assert_eq!(reader.header.dtype, FrameDtype::F16);
+        let mut buf = Vec::new();
+        reader.read_frame_bytes(0, &mut buf)?;
+        assert_eq!(buf.len(), 3 * 2);
+        let values: Vec<f32> = buf
+            .chunks_exact(2)
+            .map(|c| FrameDtype::F16.decode(c))
+            .collect();
+        // Exact where f16 can hold it, infinite past its range, rounded otherwise.
+        assert_eq!(values[..2], [1.5, f32::INFINITY]);
+        assert!((values[2] - 0.1).abs() < 1e-4);
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn frames_queued_for_the_writer_all_reach_the_file() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_queue_test.evo");
+        let header = test_header(&["pos_x"], 1);
+
+        // Several queues' worth, so writing waits on the disk along the way.
+        let frames = 3 * FRAME_QUEUE_LEN as u64;
+        let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+        for i in 0..frames {
+            recorder.write_frame_f32(&[i as f32])?;
+        }
+        recorder.flush()?;
+        let body_end = recorder.body_offset() + frames * 4;
+        assert_eq!(fs::metadata(&tmp_path)?.len(), body_end);
+
+        recorder.write_frame_f32(&[-1.0])?;
+        recorder.finish()?;
+        let mut reader = crate::reader::EvoReader::open(&tmp_path)?;
+        assert_eq!(reader.total_frames(), frames + 1);
+        let mut buf = Vec::new();
+        for i in 0..frames {
+            reader.read_frame_bytes(i, &mut buf)?;
+            assert_eq!(buf, (i as f32).to_le_bytes());
+        }
+        reader.read_frame_bytes(frames, &mut buf)?;
+        assert_eq!(buf, (-1.0f32).to_le_bytes());
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn selected_columns_are_the_only_ones_recorded() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_columns_test.evo");
+        let labels = ["pos_x", "pos_y", "energy"];
+        let config = EvoConfig {
+            n_agents: 2,
+            state_dims: 3,
+            state_labels: labels.map(String::from).to_vec(),
+            torus_ranges: BTreeMap::from([
+                ("pos_x".to_string(), [0.0, 10.0]),
+                ("pos_y".to_string(), [0.0, 10.0]),
+            ]),
+            label_meta: BTreeMap::new(),
+            clamps: BTreeMap::from([("energy".to_string(), [0.0, 1.0])]),
+        };
+        let config = config.subset(&[2, 0]);
+        assert_eq!(config.state_labels, ["energy", "pos_x"]);
+        assert_eq!(config.state_dims, 2);
+        assert_eq!(config.torus_ranges.keys().collect::<Vec<_>>(), ["pos_x"]);
+        assert_eq!(config.clamps.len(), 1);
+
+        let header = EvoHeader::new(
+            "test",
+            config,
+            PlaybackMeta {
+                dt: 1.0,
+                substeps: 1,
+                save_interval: 1,
+                total_frames: None,
+            },
+        );
+        let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+        recorder.select_columns(&[2, 0]);
+        let state = Tensor::new(&[[1f32, 2., 3.], [4., 5., 6.]], &Device::Cpu)?;
+        recorder.write_frame(&state)?;
+        recorder.finish()?;
+
+        let mut reader = crate::reader::EvoReader::open(&tmp_path)?;
+        let mut buf = Vec::new();
+        reader.read_frame_bytes(0, &mut buf)?;
+        let values: Vec<f32> = buf
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(values, [3.0, 1.0, 6.0, 4.0]);
+
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn body_end_bound_covers_frames_still_queued() -> Result<()> {
+        let tmp_path = std::env::temp_dir().join("evo_recorder_bound_test.evo");
+        let mut header = test_header(&["pos_x"], 256);
+        header.compression = FrameCompression::Deflate;
+        let mut recorder = EvoRecorder::create(&tmp_path, header)?;
+        // Scrambled frames hardly compress, so they are the closest to the bound.
+        for i in 0..3 * FRAME_QUEUE_LEN as u32 {
+            let frame: Vec<f32> = (0..256u32)
+                .map(|k| f32::from_bits(k.wrapping_mul(2_654_435_761) ^ i))
+                .collect();
+            recorder.write_frame_f32(&frame)?;
+            let bound = recorder.body_end_bound();
+            if i % 10 == 0 {
+                // Draining leaves nothing queued, so the bound is exact again.
+                assert!(bound >= recorder.body_end()?);
+                assert_eq!(recorder.body_end_bound(), recorder.body_end()?);
+            }
+        }
+        recorder.finish()?;
+        fs::remove_file(&tmp_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn sharded_recording_splits_at_the_byte_limit() -> Result<()> {
+        let dir = std::env::temp_dir().join("evo_recorder_shard_test");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("run.evo");
+        let mut header = test_header(&["pos_x", "pos_y"], 1);
+        header.playback.save_interval = 2;
+        header.playback.total_frames = Some(5);
+
+        let mut recorder = ShardedRecorder::create(&path, header, u64::MAX)?;
+        // Room for the header, two 8-byte frames and their index per shard.
+        let shard_bytes = recorder.current.body_offset() + 2 * 8 + index_bytes(2) + 4;
+        recorder.shard_bytes = shard_bytes;
+        for i in 0..5 {
+            recorder.write_frame_f32(&[i as f32, 0.0])?;
+        }
+        assert_eq!(recorder.frames_written(), 5);
+        assert_eq!(recorder.shard_count(), 3);
+        recorder.finish()?;
+        drop(recorder);
+
+        let manifest: ShardManifest =
+            serde_json::from_slice(&fs::read(ShardedRecorder::manifest_path(&path))?).unwrap();
+        let entries: Vec<_> = manifest
+            .shards
+            .iter()
+            .map(|s| (s.path.as_str(), s.frame_offset, s.frames))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("run.000.evo", 0, 2),
+                ("run.001.evo", 2, 2),
+                ("run.002.evo", 4, 1)
+            ]
+        );
+
+        let mut reader = crate::reader::EvoReader::open(dir.join("run.001.evo"))?;
+        assert!(fs::metadata(dir.join("run.001.evo"))?.len() <= shard_bytes);
+        assert_eq!(
+            reader.header.shard,
+            Some(ShardMeta {
+                shard_index: 1,
+                frame_offset: 2,
+                step_offset: 4,
+            })
+        );
+        assert_eq!(reader.header.playback.total_frames, Some(2));
+        let mut buf = Vec::new();
+        reader.read_frame_bytes(1, &mut buf)?;
+        assert_eq!(buf, [3.0f32.to_le_bytes(), 0.0f32.to_le_bytes()].concat());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,98 @@
+use std::sync::Mutex;
+
+use candle_core::{DType, Device, Shape, Tensor};
+use candle_nn::init::{Init, NormalOrUniform};
+use candle_nn::var_builder::SimpleBackend;
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, Normal, Uniform};
+
+/// `shape` values drawn uniformly from `[low, high)` on the host, so a seeded
+/// `rng` gives the same tensor on every device. Used by generated `init_state`
+/// and `init_genes`.
+pub fn uniform(
+    rng: &mut StdRng,
+    low: f32,
+    high: f32,
+    shape: impl Into<Shape>,
+    device: &Device,
+) -> candle_core::Result<Tensor> {
+    let dist = Uniform::new(low, high).map_err(candle_core::Error::wrap)?;
+    sample(rng, dist, shape.into(), device)
+}
+
+/// `shape` values drawn from a normal distribution on the host; see [`uniform`].
+pub fn normal(
+    rng: &mut StdRng,
+    mean: f32,
+    std: f32,
+    shape: impl Into<Shape>,
+    device: &Device,
+) -> candle_core::Result<Tensor> {
+    let dist = Normal::new(mean, std).map_err(candle_core::Error::wrap)?;
+    sample(rng, dist, shape.into(), device)
+}
+
+fn sample(
+    rng: &mut StdRng,
+    dist: impl Distribution<f32>,
+    shape: Shape,
+    device: &Device,
+) -> candle_core::Result<Tensor> {
+    let values: Vec<f32> = dist.sample_iter(rng).take(shape.elem_count()).collect();
+    Tensor::from_vec(values, shape, device)
+}
+
+/// Weights for a `VarBuilder`, drawn from `rng` with each variable's own
+/// initialization, in the order the network asks for them. Candle's CPU
+/// generator cannot be seeded, so a `VarMap` would give every run new weights.
+pub struct SeededWeights(Mutex<StdRng>);
+
+impl SeededWeights {
+    pub fn new(rng: StdRng) -> Self {
+        Self(Mutex::new(rng))
+    }
+}
+
+impl SimpleBackend for SeededWeights {
+    fn get(
+        &self,
+        shape: Shape,
+        _name: &str,
+        init: Init,
+        dtype: DType,
+        device: &Device,
+    ) -> candle_core::Result<Tensor> {
+        let mut rng = self.0.lock().unwrap();
+        let tensor = match init {
+            Init::Const(value) => Tensor::full(value as f32, shape, device)?,
+            Init::Uniform { lo, up } => uniform(&mut rng, lo as f32, up as f32, shape, device)?,
+            Init::Randn { mean, stdev } => {
+                normal(&mut rng, mean as f32, stdev as f32, shape, device)?
+            }
+            Init::Kaiming {
+                dist,
+                fan,
+                non_linearity,
+            } => {
+                // As `Init::var` computes it.
+                let std = non_linearity.gain() / (fan.for_shape(&shape) as f64).sqrt();
+                match dist {
+                    NormalOrUniform::Uniform => {
+                        let bound = (3f64.sqrt() * std) as f32;
+                        uniform(&mut rng, -bound, bound, shape, device)?
+                    }
+                    NormalOrUniform::Normal => normal(&mut rng, 0.0, std as f32, shape, device)?,
+                }
+            }
+        };
+        tensor.to_dtype(dtype)
+    }
+
+    fn get_unchecked(&self, name: &str, _: DType, _: &Device) -> candle_core::Result<Tensor> {
+        candle_core::bail!("no stored weight {name}: seeded weights are drawn on request")
+    }
+
+    fn contains_tensor(&self, _name: &str) -> bool {
+        false
+    }
+}
@@ -0,0 +1,798 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::checkpoint::Checkpoint;
+use crate::grid::SpatialGrid;
+use crate::lifecycle::Generation;
+use crate::recorder::{EvoConfig, EvoHeader, LabelMeta, PlaybackMeta};
+use crate::sampling::{self, SeededWeights};
+
+/// Default simulated time per step. Recorded in the header so that playback at
+/// `1 / dt` frames per second runs in real time.
+pub const DEFAULT_DT: f64 = 1.0 / 60.0;
+
+/// Settings for [`Simulation::new`].
+#[derive(Debug, Clone)]
+pub struct SimulationOptions {
+    /// Population size; the definition's default if `None`.
+    pub n_agents: Option<usize>,
+    /// Hidden layer width of the phenotype network; the definition's
+    /// `HIDDEN_LEN` if `None`.
+    pub hidden_len: Option<usize>,
+    /// Simulated seconds per dynamics update.
+    pub dt: f64,
+    /// Dynamics updates per simulation step.
+    pub substeps: u32,
+    /// Distribution to draw genes from; the definition's `init_genes` if `None`.
+    pub gene_init: Option<GeneInit>,
+    /// Seed for everything drawn at initialization (genes, phenotype weights and
+    /// the initial state), so a run can be reproduced. Drawn from the OS if `None`.
+    pub seed: Option<u64>,
+}
+
+impl Default for SimulationOptions {
+    /// The definition's defaults, one `DEFAULT_DT` update per step and an OS seed.
+    fn default() -> Self {
+        Self {
+            n_agents: None,
+            hidden_len: None,
+            dt: DEFAULT_DT,
+            substeps: 1,
+            gene_init: None,
+            seed: None,
+        }
+    }
+}
+
+/// A gene initialization distribution, written `uniform:LOW:HIGH` or
+/// `normal:MEAN:STD`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeneInit {
+    Uniform { low: f32, high: f32 },
+    Normal { mean: f32, std: f32 },
+}
+
+impl FromStr for GeneInit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let [kind, a, b] = parts[..] else {
+            bail!("expected uniform:LOW:HIGH or normal:MEAN:STD, got {s:?}");
+        };
+        let parse = |v: &str| {
+            v.parse::<f32>()
+                .with_context(|| format!("invalid gene init parameter {v:?}"))
+        };
+        let (a, b) = (parse(a)?, parse(b)?);
+        let init = match kind {
+            "uniform" => Self::Uniform { low: a, high: b },
+            "normal" => Self::Normal { mean: a, std: b },
+            _ => bail!("unknown gene init distribution {kind:?} (expected uniform or normal)"),
+        };
+        init.validate()?;
+        Ok(init)
+    }
+}
+
+impl GeneInit {
+    fn validate(&self) -> Result<()> {
+        match *self {
+            Self::Uniform { low, high } => {
+                if !(low.is_finite() && high.is_finite()) || low >= high {
+                    bail!(
+                        "uniform gene init needs finite bounds with LOW < HIGH, got {low}:{high}"
+                    );
+                }
+            }
+            Self::Normal { mean, std } => {
+                if !(mean.is_finite() && std.is_finite()) || std < 0.0 {
+                    bail!("normal gene init needs a finite MEAN and STD >= 0, got {mean}:{std}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `[n_agents, gene_len]` genes sampled on the host from `rng`, so the same
+    /// seed gives the same genes on every device.
+    pub fn sample(
+        &self,
+        n_agents: usize,
+        gene_len: usize,
+        rng: &mut StdRng,
+        device: &Device,
+    ) -> Result<Tensor> {
+        self.validate()?;
+        let shape = (n_agents, gene_len);
+        Ok(match *self {
+            Self::Uniform { low, high } => sampling::uniform(rng, low, high, shape, device)?,
+            Self::Normal { mean, std } => sampling::normal(rng, mean, std, shape, device)?,
+        })
+    }
+}
+
+/// A definition's `update_dynamics`: state, physics and attribute parameters, dt,
+/// and the count of particles left out of full grid cells to add to.
+type StepFn = fn(&Tensor, &Tensor, &Tensor, f32, &mut u64) -> candle_core::Result<Tensor>;
+/// A definition's phenotype expression: genes, weight seed, hidden width to the
+/// physics and attribute parameters.
+type ExpressFn = fn(&Tensor, u64, usize) -> candle_core::Result<(Tensor, Tensor)>;
+/// A definition's `init_state`.
+type InitStateFn = fn(usize, &mut StdRng, &Device) -> candle_core::Result<Tensor>;
+
+/// What [`Simulation::new`] takes from a generated definition.
+struct Generated {
+    config: EvoConfig,
+    fitness: Option<&'static str>,
+    gene_len: usize,
+    hidden_len: usize,
+    stencil: Option<(SpatialGrid, usize)>,
+    genes: Tensor,
+    step_fn: StepFn,
+    express_fn: ExpressFn,
+    init_state_fn: InitStateFn,
+}
+
+/// One population of a generated definition, advanced a simulation step at a time.
+///
+/// This is the step loop shared by the recording binary and in-process viewers;
+/// where frames go is up to the caller.
+pub struct Simulation {
+    def_name: String,
+    config: EvoConfig,
+    /// The definition's `FITNESS` state variable, if it declares one.
+    fitness: Option<&'static str>,
+    gene_len: usize,
+    hidden_len: usize,
+    /// Grid and stencil range (cells) of grid interactions, if any.
+    stencil: Option<(SpatialGrid, usize)>,
+    options: SimulationOptions,
+    state: Tensor,
+    /// Phenotype parameters expressed from the genes, fixed for a generation.
+    physics: Tensor,
+    attributes: Tensor,
+    /// `[n_agents, gene_len]` genes of the current generation.
+    genes: Tensor,
+    generation: u64,
+    /// Seed of the phenotype network's weights, shared by every generation.
+    weight_seed: u64,
+    step_fn: StepFn,
+    express_fn: ExpressFn,
+    init_state_fn: InitStateFn,
+    sim_frame: u64,
+    /// Particle placements left out of full grid cells by this simulation's steps.
+    grid_overflow: u64,
+}
+
+impl Simulation {
+    /// Initializes genes, phenotypes and state for definition `def`, all drawn
+    /// from one generator seeded with `options.seed`.
+    ///
+    /// Fails if `def` is not a generated definition.
+    pub fn new(def: &str, device: &Device, options: SimulationOptions) -> Result<Self> {
+        if !(options.dt.is_finite() && options.dt > 0.0) {
+            bail!("dt must be a positive finite number, got {}", options.dt);
+        }
+        if options.substeps == 0 {
+            bail!("substeps must be at least 1");
+        }
+        if options.hidden_len == Some(0) {
+            bail!("the phenotype hidden layer needs at least 1 unit");
+        }
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        macro_rules! build {
+            ($module:path) => {{
+                use def::dynamics::{
+                    init_state, update_dynamics, FITNESS, GENE_LEN, HIDDEN_LEN, N_AGENTS,
+                    STATE_DIMS, STATE_LABEL_META, STATE_VARS, STENCIL, TORUS_RANGES,
+                };
+                use def::phenotype::{init_genes, PhenotypeEngine};
+                use $module as def;
+
+                /// Phenotype expression (Genes -> Parameters) through the network
+                /// whose weights `weight_seed` draws.
+                fn express(
+                    genes: &Tensor,
+                    weight_seed: u64,
+                    hidden_len: usize,
+                ) -> candle_core::Result<(Tensor, Tensor)> {
+                    let weights = SeededWeights::new(StdRng::seed_from_u64(weight_seed));
+                    let device = genes.device().clone();
+                    let vs = VarBuilder::from_backend(Box::new(weights), DType::F32, device);
+                    let params = PhenotypeEngine::new(vs, GENE_LEN, hidden_len)?.forward(genes)?;
+                    Ok((params.physics, params.attributes))
+                }
+
+                let n_agents = options.n_agents.unwrap_or(N_AGENTS);
+                // Weights are freshly initialized, so any width fits the generated layers.
+                let hidden_len = options.hidden_len.unwrap_or(HIDDEN_LEN);
+
+                let config = EvoConfig {
+                    n_agents,
+                    state_dims: STATE_DIMS,
+                    state_labels: STATE_VARS.iter().map(|s| (*s).to_string()).collect(),
+                    torus_ranges: TORUS_RANGES
+                        .iter()
+                        .map(|&(label, min, max)| (label.to_string(), [min, max]))
+                        .collect(),
+                    label_meta: STATE_LABEL_META
+                        .iter()
+                        .map(|&(label, display, unit)| {
+                            (
+                                label.to_string(),
+                                LabelMeta {
+                                    display: display.to_string(),
+                                    unit: unit.map(str::to_string),
+                                },
+                            )
+                        })
+                        .collect(),
+                    clamps: BTreeMap::new(),
+                };
+                let genes = match &options.gene_init {
+                    Some(init) => init.sample(n_agents, GENE_LEN, &mut rng, device)?,
+                    None => init_genes(n_agents, GENE_LEN, &mut rng, device)?,
+                };
+                Generated {
+                    config,
+                    fitness: FITNESS,
+                    gene_len: GENE_LEN,
+                    hidden_len,
+                    stencil: STENCIL,
+                    genes,
+                    step_fn: update_dynamics,
+                    express_fn: express,
+                    init_state_fn: init_state,
+                }
+            }};
+        }
+        // The phenotype weights are drawn first, by their own generator, so every
+        // generation is expressed through the same network.
+        let weight_seed: u64 = rng.random();
+        let generated =
+            crate::with_definition!(def.to_string(), build, bail!(unknown_definition(def)));
+        let n_agents = generated.config.n_agents;
+        let (physics, attributes) =
+            (generated.express_fn)(&generated.genes, weight_seed, generated.hidden_len)?;
+        let state = (generated.init_state_fn)(n_agents, &mut rng, device)?;
+
+        Ok(Self {
+            def_name: def.to_string(),
+            config: generated.config,
+            fitness: generated.fitness,
+            gene_len: generated.gene_len,
+            hidden_len: generated.hidden_len,
+            stencil: generated.stencil,
+            options: SimulationOptions {
+                seed: Some(seed),
+                ..options
+            },
+            state,
+            physics,
+            attributes,
+            genes: generated.genes,
+            generation: 0,
+            weight_seed,
+            step_fn: generated.step_fn,
+            express_fn: generated.express_fn,
+            init_state_fn: generated.init_state_fn,
+            sim_frame: 0,
+            grid_overflow: 0,
+        })
+    }
+
+    /// Everything needed to continue this simulation later with
+    /// [`Self::restore`], taken when the recording held `frames_written` frames.
+    pub fn checkpoint(&self, frames_written: u64) -> Checkpoint {
+        Checkpoint {
+            def_name: self.def_name.clone(),
+            sim_frame: self.sim_frame,
+            seed: self.seed(),
+            frames_written,
+            generation: self.generation,
+            state: self.state.clone(),
+            physics: self.physics.clone(),
+            attributes: self.attributes.clone(),
+            genes: self.genes.clone(),
+        }
+    }
+
+    /// Continues from `checkpoint` instead of the freshly initialized population:
+    /// its state, phenotype parameters, genes and sim frame replace this
+    /// simulation's. Fails unless it was taken from the same definition, seed
+    /// (which draws the phenotype network later generations are expressed
+    /// through) and population size.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) -> Result<()> {
+        if checkpoint.def_name != self.def_name {
+            bail!(
+                "checkpoint is of definition {:?}, not {:?}",
+                checkpoint.def_name,
+                self.def_name
+            );
+        }
+        if checkpoint.seed != self.seed() {
+            bail!(
+                "checkpoint was taken from seed {}, not {}",
+                checkpoint.seed,
+                self.seed()
+            );
+        }
+        let restored = [
+            (&checkpoint.state, &self.state, "state"),
+            (&checkpoint.physics, &self.physics, "physics"),
+            (&checkpoint.attributes, &self.attributes, "attributes"),
+            (&checkpoint.genes, &self.genes, "genes"),
+        ];
+        for (restored, current, what) in restored {
+            if restored.dims() != current.dims() {
+                bail!(
+                    "checkpoint {what} has shape {:?}, expected {:?}",
+                    restored.dims(),
+                    current.dims()
+                );
+            }
+        }
+        let device = self.state.device().clone();
+        self.state = checkpoint.state.to_device(&device)?;
+        self.physics = checkpoint.physics.to_device(&device)?;
+        self.attributes = checkpoint.attributes.to_device(&device)?;
+        self.genes = checkpoint.genes.to_device(&device)?;
+        self.generation = checkpoint.generation;
+        self.sim_frame = checkpoint.sim_frame;
+        Ok(())
+    }
+
+    /// The current population's genes and generation index.
+    pub fn generation(&self) -> Generation {
+        Generation::new(self.generation, self.genes.clone())
+    }
+
+    /// Replaces the population with `next`: its genes are expressed through the
+    /// same phenotype network and every agent restarts from an initial state
+    /// drawn from `rng`. The sim frame keeps counting across generations.
+    pub fn start_generation(&mut self, next: Generation, rng: &mut StdRng) -> Result<()> {
+        if next.genes.dims() != self.genes.dims() {
+            bail!(
+                "generation {} has genes of shape {:?}, expected {:?}",
+                next.index,
+                next.genes.dims(),
+                self.genes.dims()
+            );
+        }
+        let (physics, attributes) =
+            (self.express_fn)(&next.genes, self.weight_seed, self.hidden_len)?;
+        self.state = (self.init_state_fn)(self.config.n_agents, rng, self.state.device())?;
+        self.physics = physics;
+        self.attributes = attributes;
+        self.genes = next.genes;
+        self.generation = next.index;
+        Ok(())
+    }
+
+    pub fn config(&self) -> &EvoConfig {
+        &self.config
+    }
+
+    pub fn gene_len(&self) -> usize {
+        self.gene_len
+    }
+
+    pub fn hidden_len(&self) -> usize {
+        self.hidden_len
+    }
+
+    /// The seed the run was initialized from; passing it again reproduces the run.
+    pub fn seed(&self) -> u64 {
+        self.options.seed.expect("set by Simulation::new")
+    }
+
+    /// The state variable the definition scores generations by, if any.
+    pub fn fitness_label(&self) -> Option<&'static str> {
+        self.fitness
+    }
+
+    /// A header for recording this simulation one frame per step. `total_frames`
+    /// is left unknown.
+    pub fn header(&self) -> EvoHeader {
+        let mut header = EvoHeader::new(
+            &self.def_name,
+            self.config.clone(),
+            PlaybackMeta {
+                dt: self.options.dt,
+                substeps: self.options.substeps,
+                save_interval: 1,
+                total_frames: None,
+            },
+        );
+        header.seed = self.options.seed;
+        header
+    }
+
+    /// The current `[n_agents, state_dims]` state.
+    pub fn state(&self) -> &Tensor {
+        &self.state
+    }
+
+    /// Simulation steps taken so far.
+    pub fn sim_frame(&self) -> u64 {
+        self.sim_frame
+    }
+
+    /// Advances one simulation step (`substeps` dynamics updates) and returns the
+    /// new state.
+    pub fn step(&mut self) -> Result<&Tensor> {
+        // Internal dynamics update (State + Parameters -> New State)
+        for _ in 0..self.options.substeps {
+            let dt = self.options.dt as f32;
+            self.state = (self.step_fn)(
+                &self.state,
+                &self.physics,
+                &self.attributes,
+                dt,
+                &mut self.grid_overflow,
+            )?;
+        }
+        self.sim_frame += 1;
+        Ok(&self.state)
+    }
+
+    /// How many times a step has left an agent out of its full grid cell, and so
+    /// out of that update's grid interactions.
+    pub fn grid_overflow(&self) -> u64 {
+        self.grid_overflow
+    }
+
+    /// The current state as row-major f32s, the layout of a recorded frame.
+    pub fn state_f32(&self) -> Result<Vec<f32>> {
+        Ok(self.state.flatten_all()?.to_vec1::<f32>()?)
+    }
+
+    /// Compares the largest per-update displacement along each grid axis with
+    /// how far the stencil sees (`range * cell_size`). Agents that move farther
+    /// skip cells, so their interactions are silently missed.
+    ///
+    /// `None` for definitions without grid interactions or `vel_x`/`vel_y` state.
+    pub fn stencil_reach(&self) -> Result<Option<StencilReach>> {
+        let Some((grid, range)) = &self.stencil else {
+            return Ok(None);
+        };
+        let label_index = |name: &str| self.config.state_labels.iter().position(|l| l == name);
+        let (Some(vx), Some(vy)) = (label_index("vel_x"), label_index("vel_y")) else {
+            return Ok(None);
+        };
+        // Velocities documented as per step are added to positions once per update;
+        // others are taken as per second.
+        let per_update = match self.config.label_meta.get("vel_x") {
+            Some(LabelMeta {
+                unit: Some(unit), ..
+            }) if unit == "per step" => 1.0,
+            _ => self.options.dt as f32,
+        };
+        let max_speed = |index: usize| -> Result<f32> {
+            let column = self.state.narrow(1, index, 1)?;
+            Ok(column.abs()?.max_all()?.to_scalar::<f32>()?)
+        };
+        let range = *range as f32;
+        Ok(Some(StencilReach {
+            max_displacement: [max_speed(vx)? * per_update, max_speed(vy)? * per_update],
+            reach: [range * grid.cell_size.0, range * grid.cell_size.1],
+        }))
+    }
+}
+
+/// State labels of definition `def`, in column order, without initializing it.
+///
+/// Fails if `def` is not a generated definition.
+pub fn state_labels(def: &str) -> Result<Vec<String>> {
+    macro_rules! labels {
+        ($module:path) => {{
+            use $module as def;
+            Ok(def::dynamics::STATE_VARS
+                .iter()
+                .map(|s| (*s).to_string())
+                .collect())
+        }};
+    }
+    crate::with_definition!(def.to_string(), labels, bail!(unknown_definition(def)))
+}
+
+fn unknown_definition(def: &str) -> String {
+    format!(
+        "unknown definition {def:?}; expected one of {}",
+        crate::_gen::DEFINITIONS.join(", ")
+    )
+}
+
+/// Result of [`Simulation::stencil_reach`], in world units along x and y.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StencilReach {
+    pub max_displacement: [f32; 2],
+    pub reach: [f32; 2],
+}
+
+impl StencilReach {
+    /// Whether some agent moves farther in one update than the stencil sees.
+    pub fn exceeded(&self) -> bool {
+        (0..2).any(|axis| {
+            let moved = self.max_displacement[axis];
+            moved.is_nan() || moved > self.reach[axis]
+        })
+    }
+}
+
+/// Per-column `[min, max]` bounds applied to states before they are recorded.
+pub struct StateClamp {
+    min: Tensor,
+    max: Tensor,
+}
+
+impl StateClamp {
+    /// Bounds for the labels in `clamps`; other columns pass through unchanged.
+    pub fn new(
+        config: &EvoConfig,
+        clamps: &BTreeMap<String, [f32; 2]>,
+        device: &Device,
+    ) -> Result<Self> {
+        let mut min = vec![f32::NEG_INFINITY; config.state_dims];
+        let mut max = vec![f32::INFINITY; config.state_dims];
+        for (label, &[lo, hi]) in clamps {
+            let index = config
+                .state_labels
+                .iter()
+                .position(|l| l == label)
+                .with_context(|| format!("cannot clamp unknown state label {label:?}"))?;
+            if !(lo.is_finite() && hi.is_finite()) || lo > hi {
+                bail!("clamp for {label:?} needs finite bounds with min <= max, got [{lo}, {hi}]");
+            }
+            min[index] = lo;
+            max[index] = hi;
+        }
+        Ok(Self {
+            min: Tensor::from_vec(min, (1, config.state_dims), device)?,
+            max: Tensor::from_vec(max, (1, config.state_dims), device)?,
+        })
+    }
+
+    /// `state` (`[n_agents, state_dims]`) with each column clamped to its bounds.
+    pub fn apply(&self, state: &Tensor) -> Result<Tensor> {
+        let shape = state.shape();
+        Ok(state.clamp(
+            &self.min.broadcast_as(shape)?,
+            &self.max.broadcast_as(shape)?,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_a_generated_definition() -> Result<()> {
+        let options = SimulationOptions {
+            n_agents: Some(8),
+            dt: 0.1,
+            substeps: 2,
+            ..Default::default()
+        };
+        let mut sim = Simulation::new("universal_gravitation", &Device::Cpu, options)?;
+        assert_eq!(sim.config().n_agents, 8);
+        assert_eq!(sim.header().def_name, "universal_gravitation");
+
+        let before = sim.state_f32()?;
+        assert_eq!(before.len(), 8 * sim.config().state_dims);
+        sim.step()?;
+        assert_eq!(sim.sim_frame(), 1);
+        assert_eq!(sim.state().dims(), [8, sim.config().state_dims]);
+        assert_ne!(sim.state_f32()?, before);
+
+        let invalid = SimulationOptions {
+            n_agents: Some(1),
+            dt: 0.0,
+            ..Default::default()
+        };
+        assert!(Simulation::new("universal_gravitation", &Device::Cpu, invalid).is_err());
+
+        let narrow = SimulationOptions {
+            n_agents: Some(2),
+            hidden_len: Some(3),
+            dt: 0.1,
+            ..Default::default()
+        };
+        Simulation::new("universal_gravitation", &Device::Cpu, narrow.clone())?.step()?;
+        let empty = SimulationOptions {
+            hidden_len: Some(0),
+            ..narrow
+        };
+        assert!(Simulation::new("universal_gravitation", &Device::Cpu, empty).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn halving_dt_halves_each_definitions_displacement() -> Result<()> {
+        for def in [
+            "example_conditional",
+            "example_predation",
+            "universal_gravitation",
+            "universal_gravitation_fixed_capacity_grid",
+        ] {
+            let options = SimulationOptions {
+                n_agents: Some(8),
+                dt: 0.1,
+                seed: Some(5),
+                ..Default::default()
+            };
+            let sim = Simulation::new(def, &Device::Cpu, options)?;
+            let labels = &sim.config().state_labels;
+            let moved = |dt: f32| -> Result<Vec<f32>> {
+                let next = (sim.step_fn)(&sim.state, &sim.physics, &sim.attributes, dt, &mut 0)?;
+                let mut moved = Vec::new();
+                for axis in ["pos_x", "pos_y"] {
+                    let column = labels.iter().position(|l| l == axis).unwrap();
+                    let delta = (next.narrow(1, column, 1)? - sim.state.narrow(1, column, 1)?)?;
+                    moved.extend(delta.flatten_all()?.to_vec1::<f32>()?);
+                }
+                Ok(moved)
+            };
+            let (full, half) = (moved(8.0)?, moved(4.0)?);
+            assert!(full.iter().any(|&d| d.abs() > 0.1), "{def} barely moved");
+            // Torus wrapping rounds positions to the ulp of the world's width.
+            for (full, half) in full.iter().zip(&half) {
+                assert!(
+                    (full - 2.0 * half).abs() < 2e-3,
+                    "{def}: {full} vs 2 * {half}"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn runs_with_the_same_seed_match() -> Result<()> {
+        let run = |seed| -> Result<(Vec<f32>, EvoHeader)> {
+            let options = SimulationOptions {
+                n_agents: Some(8),
+                dt: 0.1,
+                substeps: 2,
+                seed: Some(seed),
+                ..Default::default()
+            };
+            let mut sim = Simulation::new("universal_gravitation", &Device::Cpu, options)?;
+            for _ in 0..3 {
+                sim.step()?;
+            }
+            Ok((sim.state_f32()?, sim.header()))
+        };
+        let (state, header) = run(42)?;
+        assert_eq!(header.seed, Some(42));
+        let bits = |state: &[f32]| state.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&state), bits(&run(42)?.0));
+        assert_ne!(state, run(43)?.0);
+        Ok(())
+    }
+
+    #[test]
+    fn gene_init_parses_and_reproduces_from_a_seed() -> Result<()> {
+        let init: GeneInit = "normal:0:0.5".parse()?;
+        assert_eq!(
+            init,
+            GeneInit::Normal {
+                mean: 0.0,
+                std: 0.5
+            }
+        );
+        let uniform: GeneInit = "uniform:-1:1".parse()?;
+        assert_eq!(
+            uniform,
+            GeneInit::Uniform {
+                low: -1.0,
+                high: 1.0
+            }
+        );
+        for invalid in ["uniform:1:-1", "normal:0:-1", "poisson:0:1", "normal:0"] {
+            assert!(invalid.parse::<GeneInit>().is_err(), "{invalid}");
+        }
+
+        let sample = |seed| -> Result<Vec<f32>> {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let genes = uniform.sample(4, 3, &mut rng, &Device::Cpu)?;
+            Ok(genes.flatten_all()?.to_vec1::<f32>()?)
+        };
+        let genes = sample(7)?;
+        assert_eq!(genes.len(), 12);
+        assert!(genes.iter().all(|g| (-1.0..1.0).contains(g)));
+        assert_eq!(genes, sample(7)?);
+        assert_ne!(genes, sample(8)?);
+        Ok(())
+    }
+
+    #[test]
+    fn stencil_reach_covers_grid_definitions_only() -> Result<()> {
+        let options = SimulationOptions {
+            n_agents: Some(4),
+            dt: 0.1,
+            ..Default::default()
+        };
+        let sim = Simulation::new("universal_gravitation", &Device::Cpu, options.clone())?;
+        assert_eq!(sim.stencil_reach()?, None);
+
+        let sim = Simulation::new(
+            "universal_gravitation_fixed_capacity_grid",
+            &Device::Cpu,
+            options,
+        )?;
+        let reach = sim
+            .stencil_reach()?
+            .expect("grid definitions have a stencil");
+        assert_eq!(reach.reach, [128.0, 125.0]);
+
+        let fast = StencilReach {
+            max_displacement: [10.0, 200.0],
+            ..reach
+        };
+        assert!(fast.exceeded());
+        let slow = StencilReach {
+            max_displacement: [10.0, 10.0],
+            ..reach
+        };
+        assert!(!slow.exceeded());
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_definitions_are_errors() {
+        let options = SimulationOptions {
+            n_agents: Some(2),
+            dt: 0.1,
+            ..Default::default()
+        };
+        let err = Simulation::new("no_such_def", &Device::Cpu, options)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("universal_gravitation"), "{err}");
+        assert!(state_labels("no_such_def").is_err());
+    }
+
+    #[test]
+    fn clamp_bounds_only_the_named_columns() -> Result<()> {
+        let sim = Simulation::new(
+            "universal_gravitation",
+            &Device::Cpu,
+            SimulationOptions {
+                n_agents: Some(2),
+                dt: 0.1,
+                ..Default::default()
+            },
+        )?;
+        let config = sim.config();
+        let label = config.state_labels[0].clone();
+        let clamp = StateClamp::new(
+            config,
+            &BTreeMap::from([(label.clone(), [-1.0, 1.0])]),
+            &Device::Cpu,
+        )?;
+
+        let dims = config.state_dims;
+        let mut values = vec![1e9f32; 2 * dims];
+        values[dims] = -1e9;
+        let state = Tensor::from_vec(values, (2, dims), &Device::Cpu)?;
+        let clamped = clamp.apply(&state)?.to_vec2::<f32>()?;
+        assert_eq!(clamped[0][0], 1.0);
+        assert_eq!(clamped[1][0], -1.0);
+        assert!(clamped[0][1..].iter().all(|&v| v == 1e9));
+
+        let unknown = BTreeMap::from([("no_such_label".to_string(), [0.0, 1.0])]);
+        assert!(StateClamp::new(config, &unknown, &Device::Cpu).is_err());
+        let inverted = BTreeMap::from([(label, [1.0, -1.0])]);
+        assert!(StateClamp::new(config, &inverted, &Device::Cpu).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use candle_core::{bail, Result, Tensor};
+
+/// Uniform spatial hash over agent positions.
+///
+/// Unlike `SpatialGrid`, cells are unbounded and hold any number of agents, so a
+/// query returns a variable-length candidate list instead of fixed-capacity slots.
+/// The hash is built on the CPU; positions are not wrapped.
+#[derive(Debug, Clone)]
+pub struct SpatialHash {
+    pub cell_size: f32,
+    positions: Vec<(f32, f32)>,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl SpatialHash {
+    /// Builds a hash from host-side coordinates.
+    pub fn from_positions(pos_x: &[f32], pos_y: &[f32], cell_size: f32) -> Result<Self> {
+        if !(cell_size.is_finite() && cell_size > 0.0) {
+            bail!("spatial hash cell_size must be positive and finite, got {cell_size}");
+        }
+        if pos_x.len() != pos_y.len() {
+            bail!(
+                "spatial hash position length mismatch: {} x vs {} y",
+                pos_x.len(),
+                pos_y.len()
+            );
+        }
+
+        let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        let positions: Vec<(f32, f32)> = pos_x.iter().copied().zip(pos_y.iter().copied()).collect();
+        for (i, &(x, y)) in positions.iter().enumerate() {
+            cells.entry(cell_of(x, y, cell_size)).or_default().push(i);
+        }
+
+        Ok(Self {
+            cell_size,
+            positions,
+            cells,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+}
+
+fn cell_of(x: f32, y: f32, cell_size: f32) -> (i64, i64) {
+    (
+        (x / cell_size).floor() as i64,
+        (y / cell_size).floor() as i64,
+    )
+}
+
+/// Builds a spatial hash from `[N, 1]` position tensors.
+pub fn build_hash(pos_x: &Tensor, pos_y: &Tensor, cell_size: f32) -> Result<SpatialHash> {
+    let xs = pos_x.flatten_all()?.to_vec1::<f32>()?;
+    let ys = pos_y.flatten_all()?.to_vec1::<f32>()?;
+    SpatialHash::from_positions(&xs, &ys, cell_size)
+}
+
+/// Returns candidate neighbours of agent `i` within `range`.
+///
+/// Candidates are every other agent in the cells overlapping the query square, so
+/// the list is a superset of the agents within `range`; callers apply the exact
+/// distance test. Agent `i` itself is never included. A square covering more
+/// cells than are occupied (up to an infinite `range`) is answered by walking
+/// the occupied cells instead; a NaN `range` is an error.
+pub fn query_neighbors(hash: &SpatialHash, i: usize, range: f32) -> Result<Vec<usize>> {
+    if range.is_nan() {
+        bail!("spatial hash query range must be a number, got {range}");
+    }
+    let Some(&(x, y)) = hash.positions.get(i) else {
+        return Ok(Vec::new());
+    };
+    let range = range.max(0.0);
+    // Saturating casts keep the cells of an infinite square in range.
+    let (min_cx, min_cy) = cell_of(x - range, y - range, hash.cell_size);
+    let (max_cx, max_cy) = cell_of(x + range, y + range, hash.cell_size);
+
+    let mut out = Vec::new();
+    let mut add = |agents: &Vec<usize>| out.extend(agents.iter().copied().filter(|&j| j != i));
+    let span = |min: i64, max: i64| (max as i128 - min as i128 + 1).max(0);
+    if span(min_cx, max_cx).saturating_mul(span(min_cy, max_cy)) > hash.cells.len() as i128 {
+        let mut cells: Vec<_> = hash
+            .cells
+            .iter()
+            .filter(|(&(cx, cy), _)| {
+                (min_cx..=max_cx).contains(&cx) && (min_cy..=max_cy).contains(&cy)
+            })
+            .collect();
+        // In the order the cell walk below visits them.
+        cells.sort_unstable_by_key(|(&(cx, cy), _)| (cy, cx));
+        cells.into_iter().for_each(|(_, agents)| add(agents));
+    } else {
+        for cy in min_cy..=max_cy {
+            for cx in min_cx..=max_cx {
+                if let Some(agents) = hash.cells.get(&(cx, cy)) {
+                    add(agents);
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::Device;
+
+    fn brute_force(xs: &[f32], ys: &[f32], i: usize, range: f32) -> Vec<usize> {
+        (0..xs.len())
+            .filter(|&j| j != i)
+            .filter(|&j| {
+                let (dx, dy) = (xs[j] - xs[i], ys[j] - ys[i]);
+                dx * dx + dy * dy <= range * range
+            })
+            .collect()
+    }
+
+    #[test]
+    fn candidates_cover_brute_force_neighbors() -> Result<()> {
+        // Deterministic scatter including negative coordinates and cell boundaries.
+        let n = 200;
+        let xs: Vec<f32> = (0..n)
+            .map(|i| ((i * 37) % 101) as f32 * 1.7 - 80.0)
+            .collect();
+        let ys: Vec<f32> = (0..n)
+            .map(|i| ((i * 53) % 97) as f32 * 1.3 - 60.0)
+            .collect();
+        let device = Device::Cpu;
+        let pos_x = Tensor::from_slice(&xs, (n, 1), &device)?;
+        let pos_y = Tensor::from_slice(&ys, (n, 1), &device)?;
+
+        for (cell_size, range) in [(10.0, 10.0), (4.0, 12.5), (25.0, 3.0)] {
+            let hash = build_hash(&pos_x, &pos_y, cell_size)?;
+            assert_eq!(hash.len(), n);
+            for i in 0..n {
+                let candidates = query_neighbors(&hash, i, range)?;
+                assert!(!candidates.contains(&i));
+                for j in brute_force(&xs, &ys, i, range) {
+                    assert!(
+                        candidates.contains(&j),
+                        "agent {j} within {range} of {i} missing (cell_size {cell_size})"
+                    );
+                }
+                let mut sorted = candidates.clone();
+                sorted.sort_unstable();
+                sorted.dedup();
+                assert_eq!(sorted.len(), candidates.len(), "duplicate candidates");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unbounded_ranges_return_every_other_agent() -> Result<()> {
+        let hash = SpatialHash::from_positions(&[0.0, 1e6, -3e7], &[0.0, -2e6, 5.0], 1.0)?;
+        for range in [f32::INFINITY, 1e30] {
+            let mut candidates = query_neighbors(&hash, 0, range)?;
+            candidates.sort_unstable();
+            assert_eq!(candidates, [1, 2]);
+        }
+        assert!(query_neighbors(&hash, 0, f32::NAN).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_cell_size() {
+        assert!(SpatialHash::from_positions(&[0.0], &[0.0], 0.0).is_err());
+        assert!(SpatialHash::from_positions(&[0.0], &[0.0], f32::NAN).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::evo::Frame;
+use crate::mapping::VelocityMapping;
+use crate::renderer::{Instance, OverlayRect};
+
+/// Length of each barb of the arrowhead, as a share of the arrow's length.
+const HEAD_LENGTH: f32 = 0.3;
+
+/// Angle between the shaft and each barb, in radians (25 degrees).
+const HEAD_ANGLE: f32 = 0.436;
+
+/// Lines drawing a velocity arrow from each of `agents`: a shaft along the
+/// agent's velocity and two barbs at its tip, in the agent's color.
+///
+/// `agents` are every `stride`th agent of `frame`, in order, and `columns` the
+/// state columns of the velocity along screen x and y. Arrows are `scale` world
+/// units long per unit of speed up to `max_length`; agents at or below
+/// `min_speed`, or with a non-finite velocity, get none.
+pub fn arrows(
+    frame: &Frame,
+    columns: [usize; 2],
+    mapping: &VelocityMapping,
+    stride: usize,
+    agents: &[Instance],
+) -> Vec<OverlayRect> {
+    let (sin, cos) = HEAD_ANGLE.sin_cos();
+    let mut lines = Vec::with_capacity(agents.len() * 3);
+    for (k, agent) in agents.iter().enumerate() {
+        let state = frame.agent(k * stride);
+        let [vx, vy] = columns.map(|column| state[column]);
+        let speed = vx.hypot(vy);
+        if !speed.is_finite() || speed <= mapping.min_speed {
+            continue;
+        }
+        let length = (speed * mapping.scale).min(mapping.max_length);
+        let dir = [vx / speed, vy / speed];
+        let from = agent.center_px;
+        let tip = [from[0] + dir[0] * length, from[1] + dir[1] * length];
+        let line = |min_px, max_px| OverlayRect {
+            min_px,
+            max_px,
+            color: agent.color,
+        };
+
+        lines.push(line(from, tip));
+        let barb = length * HEAD_LENGTH;
+        for side in [-1.0, 1.0] {
+            // The shaft turned by the head angle either way, pointing back.
+            let back = [
+                -(dir[0] * cos - side * dir[1] * sin),
+                -(side * dir[0] * sin + dir[1] * cos),
+            ];
+            lines.push(line(
+                tip,
+                [tip[0] + back[0] * barb, tip[1] + back[1] * barb],
+            ));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evo::{tests::write_test_file, EvoFile};
+
+    #[test]
+    fn arrows_follow_velocity_and_clamp_their_length() {
+        // Two agents whose two state columns stand in for vel_x and vel_y.
+        let path = write_test_file("arrow_test.evo", "", 1);
+        let mut frame = EvoFile::open(&path).unwrap().empty_frame();
+        std::fs::remove_file(&path).unwrap();
+        let mapping: VelocityMapping =
+            serde_json::from_str(r#"{"x":"pos_x","y":"pos_y","maxLength":2}"#).unwrap();
+        let agent = |x| Instance {
+            center_px: [x, 0.0],
+            radius_px: 1.0,
+            _pad0: 0.0,
+            color: [1.0; 4],
+        };
+        let agents = [agent(0.0), agent(10.0)];
+
+        frame.data = vec![0.3, 0.4, 0.0, 0.0];
+        let lines = arrows(&frame, [0, 1], &mapping, 1, &agents);
+        assert_eq!(lines.len(), 3, "the resting agent gets no arrow");
+        let [x, y] = lines[0].max_px;
+        assert!((x - 0.3).abs() < 1e-6 && (y - 0.4).abs() < 1e-6);
+        for barb in &lines[1..] {
+            assert_eq!(barb.min_px, lines[0].max_px);
+        }
+
+        // Fast agents are clamped to maxLength; NaN velocities draw nothing.
+        frame.data = vec![30.0, 40.0, f32::NAN, 1.0];
+        let lines = arrows(&frame, [0, 1], &mapping, 1, &agents);
+        assert_eq!(lines.len(), 3);
+        let [x, y] = lines[0].max_px;
+        assert!((x - 1.2).abs() < 1e-5 && (y - 1.6).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,228 @@
+use anyhow::Result;
+use clap::ValueEnum;
+
+use crate::evo::Frame;
+use crate::mapping::{eval_source, ColorSpec, ValueRange, VisualMapping, VisualSource};
+use crate::source::FrameSource;
+
+/// What an `"auto"` mapping range spans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AutoRangeMode {
+    /// The values in the frame being drawn; adapts at once but can flicker
+    Frame,
+    /// The values in every recorded frame, so a color means the same value
+    /// throughout playback
+    Run,
+}
+
+/// Min/max of a source's raw values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObservedRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl ObservedRange {
+    pub const EMPTY: Self = Self {
+        min: f32::INFINITY,
+        max: f32::NEG_INFINITY,
+    };
+
+    pub fn add(&mut self, v: f32) {
+        if !v.is_nan() {
+            self.min = self.min.min(v);
+            self.max = self.max.max(v);
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// The range to normalize with: `range` if it is fixed, these values if it is
+    /// `"auto"` (unset while none were seen).
+    pub fn resolve(self, range: Option<ValueRange>) -> Option<[f32; 2]> {
+        match range? {
+            ValueRange::Fixed(range) => Some(range),
+            ValueRange::Auto => (self.min <= self.max).then_some([self.min, self.max]),
+        }
+    }
+}
+
+/// Raw values seen for each mapped channel that has an `"auto"` range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelRanges {
+    pub size: ObservedRange,
+    pub opacity: ObservedRange,
+    /// The colormap source, or the bivariate x and y sources.
+    pub color: [ObservedRange; 2],
+}
+
+impl ChannelRanges {
+    pub const EMPTY: Self = Self {
+        size: ObservedRange::EMPTY,
+        opacity: ObservedRange::EMPTY,
+        color: [ObservedRange::EMPTY; 2],
+    };
+
+    /// The finite values of every auto-ranged source of `mapping`, over every
+    /// `stride`th agent of `frame`. Non-finite values from a diverged agent would
+    /// stretch the range until every other agent maps to one end.
+    pub fn observe(frame: &Frame, mapping: &VisualMapping, stride: usize) -> Self {
+        let mut observed = Self::EMPTY;
+        let sources = auto_sources(mapping);
+        if sources.iter().all(Option::is_none) {
+            return observed;
+        }
+        for i in (0..frame.n_agents()).step_by(stride) {
+            let lookup = |label: &str| frame.get(i, label);
+            for (source, range) in sources.iter().zip(observed.channels_mut()) {
+                let Some(source) = source else { continue };
+                if let Ok(v) = eval_source(source, &lookup) {
+                    if v.is_finite() {
+                        range.add(v);
+                    }
+                }
+            }
+        }
+        observed
+    }
+
+    fn merge(&mut self, other: &Self) {
+        let other = [other.size, other.opacity, other.color[0], other.color[1]];
+        for (range, other) in self.channels_mut().into_iter().zip(other) {
+            range.merge(other);
+        }
+    }
+
+    /// Size, opacity, color x, color y, in the order of [`auto_sources`].
+    fn channels_mut(&mut self) -> [&mut ObservedRange; 4] {
+        let [color_x, color_y] = &mut self.color;
+        [&mut self.size, &mut self.opacity, color_x, color_y]
+    }
+}
+
+/// The sources of `mapping` whose range is `"auto"`: size, opacity, color x,
+/// color y.
+fn auto_sources(mapping: &VisualMapping) -> [Option<&VisualSource>; 4] {
+    let auto = |range: Option<ValueRange>| range == Some(ValueRange::Auto);
+    let size = mapping.size.as_ref().filter(|size| auto(size.value_range));
+    let opacity = mapping.opacity.as_ref().filter(|op| auto(op.value_range));
+    let color = match &mapping.color {
+        Some(ColorSpec::Colormap(color)) if auto(color.range) => [Some(&color.source), None],
+        Some(ColorSpec::Bivariate(bivariate)) => {
+            [0, 1].map(|axis| auto(bivariate.ranges[axis]).then(|| &bivariate.sources[axis]))
+        }
+        _ => [None; 2],
+    };
+    [
+        size.map(|size| &size.source),
+        opacity.map(|op| &op.source),
+        color[0],
+        color[1],
+    ]
+}
+
+/// The ranges `"auto"` mapping ranges resolve to, as `--auto-range` picks.
+pub struct AutoRanges {
+    mode: AutoRangeMode,
+    /// Everything seen so far, in `run` mode.
+    run: ChannelRanges,
+    current: ChannelRanges,
+}
+
+impl AutoRanges {
+    pub fn new(mode: AutoRangeMode) -> Self {
+        Self {
+            mode,
+            run: ChannelRanges::EMPTY,
+            current: ChannelRanges::EMPTY,
+        }
+    }
+
+    /// In `run` mode, reads every frame `source` has once, so the first frame
+    /// drawn already uses the range of the whole recording. Frames a live source
+    /// adds later widen it as they are drawn.
+    pub fn scan(&mut self, source: &dyn FrameSource, mapping: &VisualMapping) -> Result<()> {
+        if self.mode != AutoRangeMode::Run || auto_sources(mapping).iter().all(Option::is_none) {
+            return Ok(());
+        }
+        let mut frame = source.empty_frame();
+        for frame_index in 0..source.total_frames() {
+            source.read_frame_f32(frame_index, &mut frame.data)?;
+            self.run.merge(&ChannelRanges::observe(&frame, mapping, 1));
+        }
+        Ok(())
+    }
+
+    /// The ranges to draw every `stride`th agent of `frame` with.
+    pub fn update(
+        &mut self,
+        frame: &Frame,
+        mapping: &VisualMapping,
+        stride: usize,
+    ) -> ChannelRanges {
+        let seen = ChannelRanges::observe(frame, mapping, stride);
+        self.current = match self.mode {
+            AutoRangeMode::Frame => seen,
+            AutoRangeMode::Run => {
+                self.run.merge(&seen);
+                self.run
+            }
+        };
+        self.current
+    }
+
+    /// The ranges the last frame was drawn with.
+    pub fn current(&self) -> &ChannelRanges {
+        &self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evo::{tests::write_test_file, EvoFile};
+
+    #[test]
+    fn auto_ranges_follow_the_frame_or_the_whole_run() {
+        // The recorded frames are all zeros; the drawn ones are built below.
+        let path = write_test_file("autorange_test.evo", "", 3);
+        let evo = EvoFile::open(&path).unwrap();
+        let mapping: VisualMapping = serde_json::from_str(
+            r#"{"position":{"x":"pos_x","y":"pos_y"},
+                "size":{"source":"pos_x","valueRange":"auto","range":[1,5]},
+                "opacity":{"source":"pos_y","valueRange":[0,1],"range":[0,1]}}"#,
+        )
+        .unwrap();
+        let frame = |data: [f32; 4]| {
+            let mut frame = evo.empty_frame();
+            frame.data = data.to_vec();
+            frame
+        };
+        let (a, b) = (
+            frame([2.0, 0.0, 3.0, 0.0]),
+            frame([5.0, 0.0, f32::INFINITY, 0.0]),
+        );
+        let range = |ranges: ChannelRanges| ranges.size.resolve(Some(ValueRange::Auto));
+
+        let mut per_frame = AutoRanges::new(AutoRangeMode::Frame);
+        per_frame.scan(&evo, &mapping).unwrap();
+        assert_eq!(range(per_frame.update(&a, &mapping, 1)), Some([2.0, 3.0]));
+        assert_eq!(range(per_frame.update(&b, &mapping, 1)), Some([5.0, 5.0]));
+        assert_eq!(per_frame.current().opacity, ObservedRange::EMPTY);
+
+        let mut run = AutoRanges::new(AutoRangeMode::Run);
+        run.scan(&evo, &mapping).unwrap();
+        assert_eq!(range(run.update(&a, &mapping, 1)), Some([0.0, 3.0]));
+        assert_eq!(range(run.update(&b, &mapping, 1)), Some([0.0, 5.0]));
+        assert_eq!(range(run.update(&a, &mapping, 1)), Some([0.0, 5.0]));
+
+        assert_eq!(
+            ObservedRange::EMPTY.resolve(Some(ValueRange::Fixed([0.0, 2.0]))),
+            Some([0.0, 2.0])
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
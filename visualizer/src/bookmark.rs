@@ -0,0 +1,83 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+
+/// Frames on either side of a bookmark over which playback eases down to its
+/// slowdown and back up.
+const EASE_FRAMES: f64 = 30.0;
+
+/// A frame to dwell on, written `FRAME:SLOWDOWN`: playback runs `SLOWDOWN` times
+/// slower at the frame itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bookmark {
+    pub frame: usize,
+    pub slowdown: f64,
+}
+
+impl FromStr for Bookmark {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((frame, slowdown)) = s.split_once(':') else {
+            bail!("expected FRAME:SLOWDOWN, got {s:?}");
+        };
+        let frame = frame
+            .parse()
+            .with_context(|| format!("invalid bookmark frame {frame:?}"))?;
+        let slowdown: f64 = slowdown
+            .parse()
+            .with_context(|| format!("invalid bookmark slowdown {slowdown:?}"))?;
+        if !(slowdown.is_finite() && slowdown > 0.0) {
+            bail!("bookmark slowdown must be a positive finite number, got {slowdown}");
+        }
+        Ok(Self { frame, slowdown })
+    }
+}
+
+/// Playback speed (1 = normal) at fractional frame `position`. Each bookmark
+/// blends from 1 to `1 / slowdown` with a smoothstep over [`EASE_FRAMES`];
+/// where bookmarks overlap, the slowest wins.
+pub fn speed_at(bookmarks: &[Bookmark], position: f64) -> f64 {
+    bookmarks
+        .iter()
+        .filter_map(|bookmark| {
+            let distance = (position - bookmark.frame as f64).abs();
+            if distance >= EASE_FRAMES {
+                return None;
+            }
+            let t = 1.0 - distance / EASE_FRAMES;
+            let weight = t * t * (3.0 - 2.0 * t);
+            Some(1.0 / (1.0 + (bookmark.slowdown - 1.0) * weight))
+        })
+        .reduce(f64::min)
+        .unwrap_or(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playback_eases_down_to_each_bookmark() {
+        let bookmarks: Vec<Bookmark> = ["100:4", "110:2"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert!("100".parse::<Bookmark>().is_err());
+        assert!("100:0".parse::<Bookmark>().is_err());
+
+        assert_eq!(speed_at(&bookmarks, 0.0), 1.0);
+        assert_eq!(speed_at(&bookmarks, 100.0), 0.25);
+        // Approaching the bookmark slows down smoothly.
+        let approach: Vec<f64> = [70.0, 80.0, 90.0, 100.0]
+            .iter()
+            .map(|&p| speed_at(&bookmarks, p))
+            .collect();
+        assert!(approach.windows(2).all(|w| w[1] < w[0]));
+        // The slower bookmark wins where both apply.
+        assert_eq!(
+            speed_at(&bookmarks, 110.0),
+            speed_at(&bookmarks[..1], 110.0)
+        );
+    }
+}
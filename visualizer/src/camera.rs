@@ -0,0 +1,219 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+/// Zoom range the mouse wheel and touchpad pinch stay within.
+pub const MIN_ZOOM: f32 = 0.01;
+pub const MAX_ZOOM: f32 = 1000.0;
+
+/// Share of the window left empty on each side by [`fit`].
+const FIT_MARGIN: f32 = 0.05;
+
+/// A camera keyframe pinned to a recorded frame.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CameraKeyframe {
+    pub frame: f64,
+    pub camera_pos: [f32; 2],
+    pub zoom: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Easing {
+    #[default]
+    Linear,
+    Smoothstep,
+}
+
+/// Scripted camera motion loaded from `--camera-path`.
+///
+/// Accepts either a bare array of keyframes or
+/// `{ "easing": "smoothstep", "keyframes": [...] }`.
+#[derive(Debug, Clone)]
+pub struct CameraPath {
+    pub easing: Easing,
+    keyframes: Vec<CameraKeyframe>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CameraPathFile {
+    Keyframes(Vec<CameraKeyframe>),
+    Full {
+        #[serde(default)]
+        easing: Easing,
+        keyframes: Vec<CameraKeyframe>,
+    },
+}
+
+impl CameraPath {
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes =
+            fs::read(path).with_context(|| format!("failed to read camera path: {:?}", path))?;
+        let file: CameraPathFile =
+            serde_json::from_slice(&bytes).context("failed to parse camera path JSON")?;
+        let (easing, keyframes) = match file {
+            CameraPathFile::Keyframes(keyframes) => (Easing::default(), keyframes),
+            CameraPathFile::Full { easing, keyframes } => (easing, keyframes),
+        };
+        Self::new(easing, keyframes)
+    }
+
+    pub fn new(easing: Easing, mut keyframes: Vec<CameraKeyframe>) -> Result<Self> {
+        if keyframes.is_empty() {
+            bail!("camera path has no keyframes");
+        }
+        for k in &keyframes {
+            if !k.frame.is_finite() {
+                bail!("camera keyframe has non-finite frame {}", k.frame);
+            }
+            if !(k.zoom.is_finite() && k.zoom > 0.0) {
+                bail!(
+                    "camera keyframe at frame {} has invalid zoom {}",
+                    k.frame,
+                    k.zoom
+                );
+            }
+        }
+        keyframes.sort_by(|a, b| a.frame.total_cmp(&b.frame));
+        Ok(Self { easing, keyframes })
+    }
+
+    /// Camera position and zoom at a (fractional) frame. Holds the first and last
+    /// keyframes outside the scripted range.
+    pub fn sample(&self, frame: f64) -> ([f32; 2], f32) {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        if frame <= first.frame {
+            return (first.camera_pos, first.zoom);
+        }
+        if frame >= last.frame {
+            return (last.camera_pos, last.zoom);
+        }
+
+        // First keyframe strictly after `frame`; `frame > first.frame` keeps this >= 1.
+        let next = self.keyframes.partition_point(|k| k.frame <= frame);
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let mut t = ((frame - a.frame) / (b.frame - a.frame)) as f32;
+        if self.easing == Easing::Smoothstep {
+            t = t * t * (3.0 - 2.0 * t);
+        }
+        let lerp = |x: f32, y: f32| x + (y - x) * t;
+        (
+            [
+                lerp(a.camera_pos[0], b.camera_pos[0]),
+                lerp(a.camera_pos[1], b.camera_pos[1]),
+            ],
+            lerp(a.zoom, b.zoom),
+        )
+    }
+}
+
+/// World point drawn at window pixel `px` by a camera at `pos` and `zoom`,
+/// inverting the shader's world-to-screen transform (y points up in the world).
+pub fn screen_to_world(px: [f64; 2], window: [u32; 2], pos: [f32; 2], zoom: f32) -> [f32; 2] {
+    [
+        pos[0] + (px[0] as f32 - window[0] as f32 * 0.5) / zoom,
+        pos[1] - (px[1] as f32 - window[1] as f32 * 0.5) / zoom,
+    ]
+}
+
+/// Camera position after dragging the view by `delta_px` window pixels, so the
+/// world follows the mouse.
+pub fn pan(pos: [f32; 2], zoom: f32, delta_px: [f64; 2]) -> [f32; 2] {
+    [
+        pos[0] - delta_px[0] as f32 / zoom,
+        pos[1] + delta_px[1] as f32 / zoom,
+    ]
+}
+
+/// Camera position and zoom after multiplying `zoom` by `factor` (clamped to
+/// [`MIN_ZOOM`]..=[`MAX_ZOOM`]), keeping the world point under `px` in place.
+pub fn zoom_about(
+    px: [f64; 2],
+    window: [u32; 2],
+    pos: [f32; 2],
+    zoom: f32,
+    factor: f32,
+) -> ([f32; 2], f32) {
+    let anchor = screen_to_world(px, window, pos, zoom);
+    let zoom = (zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    let offset = screen_to_world(px, window, [0.0, 0.0], zoom);
+    ([anchor[0] - offset[0], anchor[1] - offset[1]], zoom)
+}
+
+/// Camera position and zoom that show the world rectangle `min`..`max` whole and
+/// centered in `window`, with a [`FIT_MARGIN`] border along its tighter axis.
+/// One zoom serves both axes, so the world keeps its aspect ratio.
+pub fn fit(min: [f32; 2], max: [f32; 2], window: [u32; 2]) -> ([f32; 2], f32) {
+    let center = [0, 1].map(|axis| (min[axis] + max[axis]) * 0.5);
+    let zoom = [0, 1]
+        .map(|axis| window[axis] as f32 * (1.0 - 2.0 * FIT_MARGIN) / (max[axis] - min[axis]))
+        .into_iter()
+        .fold(f32::INFINITY, f32::min);
+    (center, zoom.clamp(MIN_ZOOM, MAX_ZOOM))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(frame: f64, x: f32, zoom: f32) -> CameraKeyframe {
+        CameraKeyframe {
+            frame,
+            camera_pos: [x, 0.0],
+            zoom,
+        }
+    }
+
+    #[test]
+    fn interpolates_between_sorted_keyframes() {
+        let path = CameraPath::new(
+            Easing::Linear,
+            vec![key(10.0, 100.0, 3.0), key(0.0, 0.0, 1.0)],
+        )
+        .unwrap();
+        assert_eq!(path.sample(-5.0), ([0.0, 0.0], 1.0));
+        assert_eq!(path.sample(5.0), ([50.0, 0.0], 2.0));
+        assert_eq!(path.sample(20.0), ([100.0, 0.0], 3.0));
+
+        let eased = CameraPath::new(Easing::Smoothstep, path.keyframes.clone()).unwrap();
+        let (pos, _) = eased.sample(2.5);
+        assert!(pos[0] < 25.0);
+        assert_eq!(eased.sample(5.0).0, [50.0, 0.0]);
+
+        assert!(CameraPath::new(Easing::Linear, vec![key(0.0, 0.0, 0.0)]).is_err());
+        assert!(CameraPath::new(Easing::Linear, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn zooming_keeps_the_point_under_the_cursor() {
+        let window = [800, 600];
+        let cursor = [100.0, 450.0];
+        let pos = [5.0, -3.0];
+        let under = screen_to_world(cursor, window, pos, 2.0);
+
+        let (zoomed_pos, zoom) = zoom_about(cursor, window, pos, 2.0, 4.0);
+        assert_eq!(zoom, 8.0);
+        assert_eq!(screen_to_world(cursor, window, zoomed_pos, zoom), under);
+
+        assert_eq!(zoom_about(cursor, window, pos, 500.0, 10.0).1, MAX_ZOOM);
+        assert_eq!(zoom_about(cursor, window, pos, 0.02, 0.1).1, MIN_ZOOM);
+
+        // Dragging right by 20 px at zoom 2 moves the camera 10 world units left.
+        assert_eq!(pan(pos, 2.0, [20.0, 0.0]), [-5.0, -3.0]);
+    }
+
+    #[test]
+    fn fitting_shows_the_whole_world_at_one_zoom() {
+        // A 20x10 world in a 1000x1000 window is bounded by its width.
+        let (pos, zoom) = fit([-10.0, 0.0], [10.0, 10.0], [1000, 1000]);
+        assert_eq!(pos, [0.0, 5.0]);
+        assert!((zoom - 45.0).abs() < 1e-4);
+        // In a wide window the height binds instead.
+        let (_, zoom) = fit([-10.0, 0.0], [10.0, 10.0], [4000, 200]);
+        assert!((zoom - 18.0).abs() < 1e-4);
+        assert_eq!(fit([0.0; 2], [1e-9; 2], [800, 600]).1, MAX_ZOOM);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use anyhow::{bail, Context, Result};
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+
+use crate::renderer::{OverlayRect, Renderer};
+
+/// Caption text height in pixels.
+const TEXT_PX: f32 = 28.0;
+/// Space between the text and the edge of its backdrop.
+const PADDING_PX: u32 = 10;
+/// Gap between the bottom of the caption and the bottom of the window, leaving
+/// the timeline bar and its ticks uncovered.
+const BOTTOM_MARGIN_PX: f32 = 24.0;
+/// Fraction of the window width a caption wraps to.
+const MAX_WIDTH_FRACTION: f32 = 0.8;
+const BACKDROP: Rgba<u8> = Rgba([0, 0, 0, 160]);
+
+/// Fonts tried, in order, when --caption-font is not given. Ones with Japanese
+/// glyphs come first.
+const SYSTEM_FONTS: &[&str] = &[
+    "/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+    "/System/Library/Fonts/Supplemental/Arial Unicode.ttf",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "C:\\Windows\\Fonts\\meiryo.ttc",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+/// Text shown from `from_frame` up to, but not including, `to_frame`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Caption {
+    pub from_frame: usize,
+    pub to_frame: usize,
+    pub text: String,
+}
+
+/// Timed captions loaded from `--captions`, a JSON array of [`Caption`]s.
+#[derive(Debug, Clone)]
+pub struct Captions {
+    /// Ordered by `from_frame`, so overlapping captions stack in the order they
+    /// appeared.
+    captions: Vec<Caption>,
+}
+
+impl Captions {
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes =
+            fs::read(path).with_context(|| format!("failed to read captions: {:?}", path))?;
+        let captions: Vec<Caption> =
+            serde_json::from_slice(&bytes).context("failed to parse captions JSON")?;
+        Self::new(captions)
+    }
+
+    pub fn new(mut captions: Vec<Caption>) -> Result<Self> {
+        for caption in &captions {
+            if caption.from_frame >= caption.to_frame {
+                bail!(
+                    "caption {:?} must end after it starts, got frames {}..{}",
+                    caption.text,
+                    caption.from_frame,
+                    caption.to_frame
+                );
+            }
+        }
+        captions.sort_by_key(|caption| caption.from_frame);
+        Ok(Self { captions })
+    }
+
+    /// Indices of the captions showing at `frame`, top to bottom.
+    pub fn active(&self, frame: usize) -> Vec<usize> {
+        (0..self.captions.len())
+            .filter(|&i| (self.captions[i].from_frame..self.captions[i].to_frame).contains(&frame))
+            .collect()
+    }
+}
+
+/// Draws the active [`Captions`] at the bottom of the window, rasterized with
+/// their font whenever the captions showing or the window size change.
+pub struct CaptionOverlay {
+    captions: Captions,
+    font: FontVec,
+    /// Captions and window size of the caption image the renderer holds.
+    shown: Option<(Vec<usize>, [u32; 2])>,
+}
+
+impl CaptionOverlay {
+    pub fn new(captions: Captions, font_path: Option<&Path>) -> Result<Self> {
+        Ok(Self {
+            captions,
+            font: load_font(font_path)?,
+            shown: None,
+        })
+    }
+
+    /// Shows the captions active at `frame` from the next render on.
+    pub fn update(&mut self, frame: usize, renderer: &mut Renderer) {
+        let active = self.captions.active(frame);
+        let window = [renderer.config.width, renderer.config.height];
+        let shown = Some((active, window));
+        if self.shown == shown {
+            return;
+        }
+        let (active, _) = shown.as_ref().unwrap();
+        if active.is_empty() {
+            renderer.set_caption(None);
+        } else {
+            let texts: Vec<&str> = active
+                .iter()
+                .map(|&i| self.captions.captions[i].text.as_str())
+                .collect();
+            let image = self.rasterize(&texts, window[0] as f32 * MAX_WIDTH_FRACTION);
+            let rect = placement(image.dimensions(), window);
+            renderer.set_caption(Some((&image, rect)));
+        }
+        self.shown = shown;
+    }
+
+    /// `texts`, one after another and wrapped to `max_width` pixels, centered in
+    /// white on a translucent backdrop.
+    fn rasterize(&self, texts: &[&str], max_width: f32) -> RgbaImage {
+        let font = self.font.as_scaled(PxScale::from(TEXT_PX));
+        let wrap_width = (max_width - 2.0 * PADDING_PX as f32).max(TEXT_PX);
+        let lines: Vec<String> = texts
+            .iter()
+            .flat_map(|text| wrap(text, wrap_width, |c| font.h_advance(font.glyph_id(c))))
+            .collect();
+        let line_width = |line: &str| text_width(&self.font, TEXT_PX, line);
+        let widest = lines.iter().map(|l| line_width(l)).fold(0.0, f32::max);
+        let line_height = font.height() + font.line_gap();
+
+        let width = widest.ceil() as u32 + 2 * PADDING_PX;
+        let height = (line_height * lines.len() as f32).ceil() as u32 + 2 * PADDING_PX;
+        let mut image = RgbaImage::from_pixel(width, height, BACKDROP);
+        for (row, line) in lines.iter().enumerate() {
+            let baseline = PADDING_PX as f32 + row as f32 * line_height + font.ascent();
+            let x = PADDING_PX as f32 + (widest - line_width(line)) / 2.0;
+            draw_text(&mut image, &self.font, TEXT_PX, [x, baseline], line);
+        }
+        image
+    }
+}
+
+/// Width of `line` set in `font` at `px` pixels, kerning included.
+pub fn text_width(font: &FontVec, px: f32, line: &str) -> f32 {
+    let font = font.as_scaled(PxScale::from(px));
+    let mut width = 0.0;
+    let mut prev = None;
+    for c in line.chars() {
+        let id = font.glyph_id(c);
+        if let Some(prev) = prev {
+            width += font.kern(prev, id);
+        }
+        width += font.h_advance(id);
+        prev = Some(id);
+    }
+    width
+}
+
+/// Blends `line` in white into `image` at `px` pixels, starting from `origin`
+/// (x, baseline). Glyphs past the image edges are clipped.
+pub fn draw_text(image: &mut RgbaImage, font: &FontVec, px: f32, origin: [f32; 2], line: &str) {
+    let scaled = font.as_scaled(PxScale::from(px));
+    let [mut x, baseline] = origin;
+    let mut prev = None;
+    for c in line.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(prev) = prev {
+            x += scaled.kern(prev, id);
+        }
+        prev = Some(id);
+        let glyph = id.with_scale_and_position(px, ab_glyph::point(x, baseline));
+        x += scaled.h_advance(id);
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            let (Ok(px), Ok(py)) = (u32::try_from(px), u32::try_from(py)) else {
+                return;
+            };
+            if let Some(pixel) = image.get_pixel_mut_checked(px, py) {
+                // Blend white over the backdrop by the glyph's coverage.
+                for channel in pixel.0.iter_mut() {
+                    let c = *channel as f32;
+                    *channel = (c + (255.0 - c) * coverage.min(1.0)).round() as u8;
+                }
+            }
+        });
+    }
+}
+
+pub fn load_font(path: Option<&Path>) -> Result<FontVec> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => match SYSTEM_FONTS.iter().map(PathBuf::from).find(|p| p.exists()) {
+            Some(path) => path,
+            None => bail!("no font found; pass a TTF/OTF with --caption-font"),
+        },
+    };
+    let bytes = fs::read(&path).with_context(|| format!("failed to read font: {:?}", path))?;
+    FontVec::try_from_vec(bytes).with_context(|| format!("failed to parse font: {:?}", path))
+}
+
+/// Splits `text` into lines at most `max_width` wide, measuring each character
+/// with `advance`. Breaks at newlines, then at the last space that fits, or
+/// between any two characters in text without spaces (e.g. Japanese).
+fn wrap(text: &str, max_width: f32, advance: impl Fn(char) -> f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut width = 0.0;
+        for c in paragraph.chars() {
+            let w = advance(c);
+            if width + w > max_width && !line.is_empty() {
+                // Carry the word in progress over, if an earlier space can break.
+                let carry = match line.rfind(' ') {
+                    Some(i) if c != ' ' => line.split_off(i + 1),
+                    _ => String::new(),
+                };
+                lines.push(line.trim_end().to_string());
+                width = carry.chars().map(&advance).sum();
+                line = carry;
+                if c == ' ' {
+                    continue;
+                }
+            }
+            line.push(c);
+            width += w;
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    lines
+}
+
+/// Where a caption image of `size` goes in a `window`-sized window: centered
+/// horizontally, just above the timeline bar.
+fn placement((width, height): (u32, u32), window: [u32; 2]) -> OverlayRect {
+    let left = ((window[0] as f32 - width as f32) / 2.0).floor();
+    let top = window[1] as f32 - BOTTOM_MARGIN_PX - height as f32;
+    OverlayRect {
+        min_px: [left, top],
+        max_px: [left + width as f32, top + height as f32],
+        color: [1.0; 4],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_captions_stack_in_start_order() -> Result<()> {
+        let caption = |from_frame, to_frame, text: &str| Caption {
+            from_frame,
+            to_frame,
+            text: text.to_string(),
+        };
+        let captions = Captions::new(vec![
+            caption(50, 80, "second"),
+            caption(0, 60, "first"),
+            caption(80, 90, "third"),
+        ])?;
+        assert_eq!(captions.active(10), [0]);
+        assert_eq!(captions.active(55), [0, 1]);
+        // `to_frame` is exclusive, so sequential captions do not overlap.
+        assert_eq!(captions.active(80), [2]);
+        assert!(captions.active(90).is_empty());
+        assert!(Captions::new(vec![caption(5, 5, "empty")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn wraps_at_spaces_and_anywhere_without_them() {
+        let wrap5 = |text| wrap(text, 5.0, |_| 1.0);
+        assert_eq!(wrap5("hello world foo"), ["hello", "world", "foo"]);
+        assert_eq!(wrap5("ab cdefg"), ["ab", "cdefg"]);
+        assert_eq!(
+            wrap5("捕食者が増えると被食者が減る"),
+            ["捕食者が増", "えると被食", "者が減る"]
+        );
+        assert_eq!(wrap5("one\ntwo"), ["one", "two"]);
+    }
+}
@@ -1,27 +1,323 @@
 use std::{
+    borrow::Cow,
     collections::HashMap,
     fs::File,
+    io::Read,
+    ops::Range,
     path::{Path, PathBuf},
 };
 
 use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::DeflateDecoder;
+use half::f16;
 use memmap2::Mmap;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+/// Marks the end of an optional trailer of tagged sections after the body:
+/// `[tag: [u8; 4], len: u64, payload]*`, then `trailer_len: u64` and this magic.
+const TRAILER_MAGIC: &[u8; 4] = b"EVOT";
+/// Trailer section holding one little-endian f32 compute time (seconds) per frame.
+const COMPUTE_TIME_TAG: &[u8; 4] = b"CTIM";
+/// Trailer section holding one little-endian u64 simulation step per frame, for
+/// recordings whose frames are not uniformly spaced.
+const SIM_STEP_TAG: &[u8; 4] = b"STEP";
+/// Trailer section holding one little-endian u64 generation per frame, for runs
+/// that evolve.
+const GENERATION_TAG: &[u8; 4] = b"GENR";
+/// Ends the frame index of version 2 files, written after the trailer: one
+/// little-endian u64 file offset per frame, then `index_len: u32` and this magic.
+const INDEX_MAGIC: &[u8; 4] = b"EVO2";
+
+/// Payload byte range of each trailer section, keyed by tag.
+type TrailerSections = HashMap<[u8; 4], Range<usize>>;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct EvoConfig {
     pub n_agents: usize,
     pub state_dims: usize,
     pub state_labels: Vec<String>,
+    /// `[min, max)` of state variables that wrap around a torus, keyed by label.
+    #[serde(default)]
+    pub torus_ranges: HashMap<String, [f32; 2]>,
+    /// Display metadata for state labels; absent in older files.
+    #[serde(default)]
+    pub label_meta: HashMap<String, LabelMeta>,
 }
 
+/// Human-friendly name and unit for a state label.
 #[derive(Debug, Clone, Deserialize)]
-pub struct EvoHeader {
+pub struct LabelMeta {
+    pub display: String,
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaybackMeta {
+    pub dt: f64,
+    #[serde(default = "default_substeps")]
+    pub substeps: u32,
+    pub save_interval: u64,
     #[allow(dead_code)]
+    pub total_frames: Option<u64>,
+}
+
+fn default_substeps() -> u32 {
+    1
+}
+
+impl PlaybackMeta {
+    /// Simulated seconds per simulation step.
+    pub fn step_duration(&self) -> f64 {
+        self.dt * self.substeps as f64
+    }
+
+    /// Simulated seconds between consecutive recorded frames.
+    pub fn frame_duration(&self) -> f64 {
+        self.step_duration() * self.save_interval as f64
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvoHeader {
     pub version: u32,
     #[allow(dead_code)]
     pub timestamp: String,
+    /// Definition the recording was produced from; absent in older files.
+    #[serde(default)]
+    pub def_name: Option<String>,
     pub config: EvoConfig,
+    /// Absent in files written before playback metadata was recorded.
+    #[serde(default)]
+    pub playback: Option<PlaybackMeta>,
+    /// Where this file sits in a sharded recording; absent for a standalone file.
+    #[serde(default)]
+    pub shard: Option<ShardMeta>,
+    /// The backend the frames were computed on; absent in older files.
+    #[serde(default)]
+    pub runtime: Option<RuntimeMeta>,
+    /// Order of the values within each frame; absent (agent by agent) in older files.
+    #[serde(default)]
+    pub layout: FrameLayout,
+    /// How each frame is stored in the body; absent (raw) in older files.
+    #[serde(default)]
+    pub compression: FrameCompression,
+    /// Precision of each stored value; absent (f32) in older files.
+    #[serde(default)]
+    pub dtype: FrameDtype,
+    /// CRC-32 of the body as stored, written when the recording finished; absent
+    /// in unfinished and older files.
+    #[serde(default)]
+    pub body_crc32: Option<u32>,
+}
+
+/// How the values of a frame are ordered in the body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameLayout {
+    /// `[n_agents, state_dims]`: one agent's state is contiguous.
+    #[default]
+    Aos,
+    /// `[state_dims, n_agents]`: one state variable of every agent is contiguous.
+    Soa,
+}
+
+/// How frames are stored in the body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameCompression {
+    /// Raw frames of a fixed size.
+    #[default]
+    None,
+    /// Each frame deflated on its own, stored as `[len: u32, bytes]`.
+    Deflate,
+}
+
+/// Precision of the values stored in the body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameDtype {
+    /// Little-endian f32s.
+    #[default]
+    F32,
+    /// Little-endian half-precision floats, read back as f32.
+    F16,
+}
+
+impl FrameDtype {
+    /// Bytes per stored value.
+    pub fn size(self) -> usize {
+        match self {
+            Self::F32 => 4,
+            Self::F16 => 2,
+        }
+    }
+
+    /// The value stored in `bytes`, [`Self::size`] of them.
+    fn decode(self, bytes: &[u8]) -> f32 {
+        match self {
+            Self::F32 => f32::from_le_bytes(bytes.try_into().unwrap()),
+            Self::F16 => f16::from_le_bytes(bytes.try_into().unwrap()).to_f32(),
+        }
+    }
+}
+
+/// Device and library versions a recording was computed with.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuntimeMeta {
+    /// `cpu`, `cuda` or `metal`.
+    pub device: String,
+    /// candle-core version of the simulator.
+    pub backend_version: String,
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+impl std::fmt::Display for RuntimeMeta {
+    /// e.g. "metal (candle 0.9.1, features: metal)"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (candle {}", self.device, self.backend_version)?;
+        if !self.features.is_empty() {
+            write!(f, ", features: {}", self.features.join(", "))?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Position of one shard in a recording split across files.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShardMeta {
+    pub shard_index: u32,
+    /// Frames in earlier shards.
+    pub frame_offset: u64,
+    /// Simulation step of the shard's first frame; its sim step track counts
+    /// from here.
+    pub step_offset: u64,
+}
+
+impl EvoHeader {
+    /// The recorded definition name, if the header carries a non-empty one.
+    pub fn def_name(&self) -> Option<&str> {
+        self.def_name.as_deref().filter(|name| !name.is_empty())
+    }
+
+    /// `label` as shown to viewers, e.g. "Velocity X (per step)", falling back to
+    /// the raw label when the header has no metadata for it.
+    pub fn display_label(&self, label: &str) -> String {
+        match self.config.label_meta.get(label) {
+            Some(LabelMeta {
+                display,
+                unit: Some(unit),
+            }) => format!("{display} ({unit})"),
+            Some(meta) => meta.display.clone(),
+            None => label.to_string(),
+        }
+    }
+}
+
+/// One decoded frame: `n_agents` rows of `state_dims` values, addressable by label.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Frame {
+    /// Row-major `[n_agents, state_dims]` values.
+    pub data: Vec<f32>,
+    state_dims: usize,
+    label_to_index: HashMap<String, usize>,
+}
+
+impl Frame {
+    /// An empty frame with the layout `header` describes.
+    pub fn for_header(header: &EvoHeader) -> Self {
+        let label_to_index = header
+            .config
+            .state_labels
+            .iter()
+            .enumerate()
+            .map(|(idx, label)| (label.clone(), idx))
+            .collect();
+        Self {
+            data: Vec::new(),
+            state_dims: header.config.state_dims,
+            label_to_index,
+        }
+    }
+
+    pub fn n_agents(&self) -> usize {
+        self.data.len().checked_div(self.state_dims).unwrap_or(0)
+    }
+
+    /// The state row of agent `i`.
+    pub fn agent(&self, i: usize) -> &[f32] {
+        &self.data[i * self.state_dims..(i + 1) * self.state_dims]
+    }
+
+    /// The value of state `label` for agent `i`, if the label exists.
+    pub fn get(&self, i: usize, label: &str) -> Option<f32> {
+        let j = *self.label_to_index.get(label)?;
+        Some(self.agent(i)[j])
+    }
+}
+
+/// Axis-aligned rectangle in world coordinates, bounds inclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Rect {
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        (self.min[0]..=self.max[0]).contains(&x) && (self.min[1]..=self.max[1]).contains(&y)
+    }
+}
+
+/// Summary of one state variable over the agents of a frame, ignoring NaNs
+/// (all NaN when every value is).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColumnStats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+/// How much of a file's body holds whole frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileIntegrity {
+    pub complete_frames: usize,
+    /// Bytes after the last complete frame, e.g. from an interrupted write.
+    /// Playback ignores them.
+    pub trailing_partial_bytes: usize,
+    /// Frames the header says the file holds, if it says.
+    pub declared_frames: Option<usize>,
+}
+
+impl FileIntegrity {
+    /// Whether frames seem to be missing: a partial frame at the end, or fewer
+    /// complete frames than the header declares.
+    pub fn looks_truncated(&self) -> bool {
+        self.trailing_partial_bytes > 0
+            || self
+                .declared_frames
+                .is_some_and(|declared| declared > self.complete_frames)
+    }
+}
+
+impl std::fmt::Display for FileIntegrity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} complete frames", self.complete_frames)?;
+        if let Some(declared) = self.declared_frames.filter(|&d| d != self.complete_frames) {
+            write!(f, " of {declared} declared")?;
+        }
+        if self.trailing_partial_bytes > 0 {
+            let bytes = self.trailing_partial_bytes as f64;
+            match bytes {
+                b if b >= 1e6 => write!(f, ", {:.1}MB", b / 1e6)?,
+                b if b >= 1e3 => write!(f, ", {:.1}KB", b / 1e3)?,
+                b => write!(f, ", {b}B")?,
+            }
+            write!(f, " partial tail discarded")?;
+        }
+        Ok(())
+    }
 }
 
 pub struct EvoFile {
@@ -29,8 +325,18 @@ pub struct EvoFile {
     mmap: Mmap,
     pub header: EvoHeader,
     body_offset: usize,
+    body_end: usize,
     frame_bytes: usize,
+    /// Byte range of each stored frame (after its length prefix, if compressed),
+    /// from the frame index or a walk of a compressed body. `None` for raw bodies
+    /// without an index, whose frames sit `frame_bytes` apart.
+    frames: Option<Vec<Range<usize>>>,
+    trailer_sections: TrailerSections,
     label_to_index: HashMap<String, usize>,
+    /// Torus range per state dimension, for wrap-aware interpolation.
+    dim_torus: Vec<Option<[f32; 2]>>,
+    /// Capture time of each frame, for recordings with a sim step track.
+    frame_times: Option<Vec<f64>>,
 }
 
 impl EvoFile {
@@ -38,28 +344,13 @@ impl EvoFile {
         let path = path.as_ref().to_path_buf();
         let file = File::open(&path).with_context(|| format!("failed to open {:?}", path))?;
         let mmap = unsafe { Mmap::map(&file).context("failed to mmap file")? };
+        let (header, header_end) = parse_header(&mmap)?;
 
-        if mmap.len() < 8 {
-            bail!("file too small");
-        }
-        if &mmap[0..4] != b"EVO1" {
-            bail!("invalid magic bytes (expected EVO1)");
-        }
-        let header_len = u32::from_le_bytes(mmap[4..8].try_into().unwrap()) as usize;
-        let header_start: usize = 8;
-        let header_end = header_start
-            .checked_add(header_len)
-            .ok_or_else(|| anyhow!("header length overflow"))?;
-        if header_end > mmap.len() {
-            bail!("header exceeds file length");
-        }
-        let header: EvoHeader =
-            serde_json::from_slice(&mmap[header_start..header_end]).context("invalid header JSON")?;
-
-        let frame_bytes = header.config
+        let frame_bytes = header
+            .config
             .n_agents
             .checked_mul(header.config.state_dims)
-            .and_then(|n| n.checked_mul(std::mem::size_of::<f32>()))
+            .and_then(|n| n.checked_mul(header.dtype.size()))
             .ok_or_else(|| anyhow!("frame size overflow"))?;
         if frame_bytes == 0 {
             bail!("invalid frame size (0)");
@@ -69,19 +360,81 @@ impl EvoFile {
         for (idx, label) in header.config.state_labels.iter().enumerate() {
             label_to_index.insert(label.clone(), idx);
         }
+        let index = match header.version {
+            1 => None,
+            _ => parse_index(&mmap, header_end),
+        };
+        let index_start = index.as_ref().map_or(mmap.len(), |(start, _)| *start);
+        let (body_end, trailer_sections) = parse_trailer(&mmap[..index_start], header_end)
+            .unwrap_or_else(|| (index_start, HashMap::new()));
+        let compressed = header.compression == FrameCompression::Deflate;
+        let frames = match index {
+            Some((_, offsets)) => Some(
+                frame_ranges(&offsets, header_end, body_end, frame_bytes, compressed)
+                    .ok_or_else(|| anyhow!("invalid frame index"))?,
+            ),
+            None if compressed => Some(walk_compressed_frames(&mmap[..body_end], header_end)),
+            None => None,
+        };
+
+        let dim_torus = (0..header.config.state_dims)
+            .map(|idx| {
+                let label = header.config.state_labels.get(idx)?;
+                let range = *header.config.torus_ranges.get(label)?;
+                (range[1] > range[0]).then_some(range)
+            })
+            .collect();
 
-        Ok(Self {
+        let mut evo = Self {
             _path: path,
             mmap,
             header,
             body_offset: header_end,
+            body_end,
             frame_bytes,
+            frames,
+            trailer_sections,
             label_to_index,
-        })
+            dim_torus,
+            frame_times: None,
+        };
+        evo.frame_times = evo.read_frame_times();
+        Ok(evo)
+    }
+
+    /// Capture times from the sim step track, if it covers every frame.
+    fn read_frame_times(&self) -> Option<Vec<f64>> {
+        let range = self.trailer_sections.get(SIM_STEP_TAG)?.clone();
+        if self.total_frames() == 0 || range.len() != self.total_frames() * 8 {
+            return None;
+        }
+        let step_duration = self
+            .header
+            .playback
+            .as_ref()
+            .map(PlaybackMeta::step_duration)
+            .filter(|d| d.is_finite() && *d > 0.0)
+            .unwrap_or(1.0);
+        Some(
+            self.mmap[range]
+                .chunks_exact(8)
+                .map(|c| u64::from_le_bytes(c.try_into().unwrap()) as f64 * step_duration)
+                .collect(),
+        )
+    }
+
+    /// The generation frame `frame_index` belongs to, if the run evolved.
+    pub fn generation(&self, frame_index: usize) -> Option<u64> {
+        let track = &self.mmap[self.trailer_sections.get(GENERATION_TAG)?.clone()];
+        let bytes = track.get(frame_index * 8..frame_index * 8 + 8)?;
+        Some(u64::from_le_bytes(bytes.try_into().unwrap()))
     }
 
     pub fn total_frames_available(&self) -> usize {
-        let body_len = self.mmap.len().saturating_sub(self.body_offset);
+        if let Some(frames) = &self.frames {
+            return frames.len();
+        }
+        let body_len = self.body_end.saturating_sub(self.body_offset);
         body_len / self.frame_bytes
     }
 
@@ -89,33 +442,1135 @@ impl EvoFile {
         self.total_frames_available()
     }
 
+    /// Complete frames, the size of any partial frame after them, and the frame
+    /// count the header declares to check them against.
+    pub fn integrity(&self) -> FileIntegrity {
+        let declared_frames = self
+            .header
+            .playback
+            .as_ref()
+            .and_then(|playback| playback.total_frames)
+            .map(|n| n as usize);
+        if let Some(frames) = &self.frames {
+            let frames_end = frames.last().map_or(self.body_offset, |range| range.end);
+            return FileIntegrity {
+                complete_frames: frames.len(),
+                trailing_partial_bytes: self.body_end.saturating_sub(frames_end),
+                declared_frames,
+            };
+        }
+        let body_len = self.body_end.saturating_sub(self.body_offset);
+        FileIntegrity {
+            complete_frames: body_len / self.frame_bytes,
+            trailing_partial_bytes: body_len % self.frame_bytes,
+            declared_frames,
+        }
+    }
+
+    /// Hashes the body and compares it with the checksum the header recorded,
+    /// failing if any stored byte changed. `Ok(false)` if the file has none to
+    /// check against (an unfinished or older recording).
+    pub fn verify(&self) -> Result<bool> {
+        let Some(expected) = self.header.body_crc32 else {
+            return Ok(false);
+        };
+        let found = crc32fast::hash(&self.mmap[self.body_offset..self.body_end]);
+        if found != expected {
+            bail!(
+                "body checksum mismatch: header records {expected:#010x}, body hashes to {found:#010x}"
+            );
+        }
+        Ok(true)
+    }
+
+    /// Nominal simulated seconds per recorded frame; frames of a recording with a
+    /// sim step track may be further apart. Files without usable playback
+    /// metadata count one time unit per frame.
+    pub fn frame_duration(&self) -> f64 {
+        self.header
+            .playback
+            .as_ref()
+            .map(PlaybackMeta::frame_duration)
+            .filter(|d| d.is_finite() && *d > 0.0)
+            .unwrap_or(1.0)
+    }
+
+    /// Simulated time at which recorded frame `frame_index` was captured.
+    pub fn sim_time_of_frame(&self, frame_index: usize) -> f64 {
+        match &self.frame_times {
+            Some(times) => times[frame_index.min(times.len() - 1)],
+            None => frame_index as f64 * self.frame_duration(),
+        }
+    }
+
+    /// The last recorded frame captured at or before simulated time `t`,
+    /// clamped to the available frames.
+    pub fn frame_at_sim_time(&self, t: f64) -> usize {
+        let last = self.total_frames().saturating_sub(1);
+        if t.is_nan() || t <= 0.0 {
+            return 0;
+        }
+        if let Some(times) = &self.frame_times {
+            let after = times.partition_point(|&ft| ft <= t * (1.0 + 1e-12));
+            return after.saturating_sub(1).min(last);
+        }
+        // Nudge by a relative epsilon so `frame_at_sim_time(sim_time_of_frame(i)) == i`
+        // despite rounding in the division.
+        let frame = (t / self.frame_duration() * (1.0 + 1e-12)).floor();
+        if frame >= last as f64 {
+            last
+        } else {
+            frame as usize
+        }
+    }
+
+    /// Fractional frame position of simulated time `t`, clamped to
+    /// `[0, total_frames - 1]`.
+    pub fn frame_position_at_sim_time(&self, t: f64) -> f64 {
+        let last = self.total_frames().saturating_sub(1) as f64;
+        if t.is_nan() || t <= 0.0 {
+            return 0.0;
+        }
+        if let Some(times) = &self.frame_times {
+            let i = self.frame_at_sim_time(t);
+            let Some(&next) = times.get(i + 1) else {
+                return i as f64;
+            };
+            let span = next - times[i];
+            let frac = if span > 0.0 {
+                (t - times[i]) / span
+            } else {
+                0.0
+            };
+            return i as f64 + frac.clamp(0.0, 1.0);
+        }
+        (t / self.frame_duration()).min(last)
+    }
+
+    /// Wall-clock seconds the simulator spent computing frame `frame_index`, if the
+    /// file was recorded with `--record-compute-time`.
+    #[allow(dead_code)]
+    pub fn frame_compute_time(&self, frame_index: usize) -> Option<f32> {
+        let range = self.trailer_sections.get(COMPUTE_TIME_TAG)?;
+        let start = range.start.checked_add(frame_index.checked_mul(4)?)?;
+        let bytes = self
+            .mmap
+            .get(start..start + 4)
+            .filter(|_| start + 4 <= range.end)?;
+        Some(f32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
     pub fn state_index(&self, label: &str) -> Option<usize> {
         self.label_to_index.get(label).copied()
     }
 
-    /// Returns a freshly decoded frame as little-endian f32 values.
-    pub fn read_frame_f32(&self, frame_index: usize, out: &mut Vec<f32>) -> Result<()> {
+    /// An empty frame carrying this file's layout, to be filled by the `read_*` methods
+    /// through [`Frame::data`].
+    pub fn empty_frame(&self) -> Frame {
+        Frame::for_header(&self.header)
+    }
+
+    #[allow(dead_code)]
+    pub fn read_frame(&self, frame_index: usize) -> Result<Frame> {
+        let mut frame = self.empty_frame();
+        self.read_frame_f32(frame_index, &mut frame.data)?;
+        Ok(frame)
+    }
+
+    /// Decodes state dimension `dim` of every agent in `frame_index`, reading only
+    /// those (strided) values from the mapping. Compressed frames are inflated whole.
+    pub fn read_column_f32(
+        &self,
+        frame_index: usize,
+        dim: usize,
+        out: &mut Vec<f32>,
+    ) -> Result<()> {
         let total = self.total_frames();
-        if total == 0 {
-            bail!("no frames available");
+        if frame_index >= total {
+            bail!("frame_index out of range: {frame_index} >= {total}");
+        }
+        let state_dims = self.header.config.state_dims;
+        if dim >= state_dims {
+            bail!("state dimension out of range: {dim} >= {state_dims}");
+        }
+
+        let bytes = self.frame_body(frame_index)?;
+        let dtype = self.header.dtype;
+        let size = dtype.size();
+        match self.header.layout {
+            FrameLayout::Aos => {
+                out.clear();
+                let value = |row: &[u8]| dtype.decode(&row[size * dim..size * (dim + 1)]);
+                out.extend(bytes.chunks_exact(size * state_dims).map(value));
+            }
+            FrameLayout::Soa => {
+                let column_bytes = size * self.header.config.n_agents;
+                let column = &bytes[dim * column_bytes..(dim + 1) * column_bytes];
+                decode_values(column, dtype, out);
+            }
+        }
+        Ok(())
+    }
+
+    /// [`ColumnStats`] of state dimension `dim` in frame `frame_index`, from one
+    /// column read (contiguous in [`FrameLayout::Soa`] files).
+    #[allow(dead_code)]
+    pub fn column_stats(&self, frame_index: usize, dim: usize) -> Result<ColumnStats> {
+        let mut column = Vec::new();
+        self.read_column_f32(frame_index, dim, &mut column)?;
+        let (mut min, mut max, mut sum, mut count) = (f32::INFINITY, f32::NEG_INFINITY, 0.0f64, 0);
+        for &v in column.iter().filter(|v| !v.is_nan()) {
+            min = min.min(v);
+            max = max.max(v);
+            sum += v as f64;
+            count += 1;
+        }
+        if count == 0 {
+            return Ok(ColumnStats {
+                min: f32::NAN,
+                max: f32::NAN,
+                mean: f32::NAN,
+            });
+        }
+        Ok(ColumnStats {
+            min,
+            max,
+            mean: (sum / count as f64) as f32,
+        })
+    }
+
+    /// The first frame after `from` whose positions differ from those in `from` by
+    /// more than `eps` in L2 norm over all agents, for skipping static stretches.
+    /// Positions are `pos_x`/`pos_y`, or every state dimension if those are absent.
+    pub fn next_changed_frame(&self, from: usize, eps: f32) -> Option<usize> {
+        let dims: Vec<usize> = match (self.state_index("pos_x"), self.state_index("pos_y")) {
+            (Some(x), Some(y)) => vec![x, y],
+            _ => (0..self.header.config.state_dims).collect(),
+        };
+
+        let mut reference = Vec::with_capacity(dims.len());
+        for &dim in &dims {
+            let mut column = Vec::new();
+            self.read_column_f32(from, dim, &mut column).ok()?;
+            reference.push(column);
         }
+
+        let eps_sq = eps.max(0.0) * eps.max(0.0);
+        let mut column = Vec::new();
+        (from + 1..self.total_frames()).find(|&frame_index| {
+            let mut dist_sq = 0.0f32;
+            for (&dim, base) in dims.iter().zip(&reference) {
+                if self.read_column_f32(frame_index, dim, &mut column).is_err() {
+                    return false;
+                }
+                dist_sq += column
+                    .iter()
+                    .zip(base)
+                    .map(|(a, b)| (a - b) * (a - b))
+                    .sum::<f32>();
+                if dist_sq > eps_sq {
+                    return true;
+                }
+            }
+            false
+        })
+    }
+
+    /// Returns a freshly decoded frame as f32 values, agent by agent whatever the
+    /// file's layout and dtype.
+    pub fn read_frame_f32(&self, frame_index: usize, out: &mut Vec<f32>) -> Result<()> {
+        let bytes = self.frame_body(frame_index)?;
+        let dtype = self.header.dtype;
+        match self.header.layout {
+            FrameLayout::Aos => decode_values(&bytes, dtype, out),
+            FrameLayout::Soa => {
+                let config = &self.header.config;
+                decode_transposed(&bytes, config.n_agents, config.state_dims, dtype, out);
+            }
+        }
+        Ok(())
+    }
+
+    /// The raw bytes of frame `frame_index`: borrowed from the mapping, or inflated
+    /// for compressed files.
+    fn frame_body(&self, frame_index: usize) -> Result<Cow<'_, [u8]>> {
+        let total = self.total_frames();
         if frame_index >= total {
+            let partial = self.integrity().trailing_partial_bytes;
+            if frame_index == total && partial > 0 {
+                bail!("frame {frame_index} is incomplete: the file ends {partial} bytes into it");
+            }
+            if total == 0 {
+                bail!("no frames available");
+            }
             bail!("frame_index out of range: {frame_index} >= {total}");
         }
 
-        let start = self
-            .body_offset
-            .checked_add(frame_index * self.frame_bytes)
-            .ok_or_else(|| anyhow!("frame offset overflow"))?;
-        let end = start + self.frame_bytes;
-        let bytes = &self.mmap[start..end];
+        let Some(frames) = &self.frames else {
+            let start = self
+                .body_offset
+                .checked_add(frame_index * self.frame_bytes)
+                .ok_or_else(|| anyhow!("frame offset overflow"))?;
+            let end = start + self.frame_bytes;
+            return Ok(Cow::Borrowed(&self.mmap[start..end]));
+        };
+        let stored = &self.mmap[frames[frame_index].clone()];
+        if self.header.compression == FrameCompression::Deflate {
+            let mut bytes = Vec::with_capacity(self.frame_bytes);
+            // One byte past a whole frame is enough to catch an oversized one.
+            DeflateDecoder::new(stored)
+                .take(self.frame_bytes as u64 + 1)
+                .read_to_end(&mut bytes)
+                .with_context(|| format!("failed to inflate frame {frame_index}"))?;
+            if bytes.len() != self.frame_bytes {
+                bail!(
+                    "compressed frame {frame_index} holds {} bytes (expected {})",
+                    bytes.len(),
+                    self.frame_bytes
+                );
+            }
+            return Ok(Cow::Owned(bytes));
+        }
+        Ok(Cow::Borrowed(stored))
+    }
+
+    /// Frames in which at least one agent's (`idx_x`, `idx_y`) position lies inside
+    /// `rect`. Reads only the two position values of each agent.
+    #[allow(dead_code)]
+    pub fn frames_with_agent_in_rect(
+        &self,
+        idx_x: usize,
+        idx_y: usize,
+        rect: Rect,
+    ) -> Result<Vec<usize>> {
+        let state_dims = self.header.config.state_dims;
+        if idx_x >= state_dims || idx_y >= state_dims {
+            bail!("state dimension out of range: ({idx_x}, {idx_y}) with {state_dims} dims");
+        }
+        let n_agents = self.header.config.n_agents;
+        // Index of state `dim` of `agent` within a frame.
+        let index = |agent: usize, dim: usize| match self.header.layout {
+            FrameLayout::Aos => agent * state_dims + dim,
+            FrameLayout::Soa => dim * n_agents + agent,
+        };
+        let (dtype, size) = (self.header.dtype, self.header.dtype.size());
+        let value = |frame: &[u8], agent: usize, dim: usize| {
+            let k = size * index(agent, dim);
+            dtype.decode(&frame[k..k + size])
+        };
+        let mut frames = Vec::new();
+        for frame_index in 0..self.total_frames() {
+            let frame = self.frame_body(frame_index)?;
+            if (0..n_agents).any(|agent| {
+                rect.contains(value(&frame, agent, idx_x), value(&frame, agent, idx_y))
+            }) {
+                frames.push(frame_index);
+            }
+        }
+        Ok(frames)
+    }
+
+    /// Decodes the state at fractional frame `position` by linearly blending the
+    /// two bracketing recorded frames. `next` is scratch space for the later frame.
+    /// Toroidal dimensions blend along the shortest path around the torus.
+    pub fn read_frame_interpolated(
+        &self,
+        position: f64,
+        out: &mut Vec<f32>,
+        next: &mut Vec<f32>,
+    ) -> Result<()> {
+        let position = position.max(0.0);
+        let frame_index = position.floor() as usize;
+        self.read_frame_f32(frame_index, out)?;
 
-        let n_f32 = self.header.config.n_agents * self.header.config.state_dims;
-        out.clear();
-        out.reserve(n_f32);
-        for chunk in bytes.chunks_exact(4) {
-            out.push(f32::from_le_bytes(chunk.try_into().unwrap()));
+        let t = (position - frame_index as f64) as f32;
+        if t <= 0.0 || frame_index + 1 >= self.total_frames() {
+            return Ok(());
         }
+        self.read_frame_f32(frame_index + 1, next)?;
+        self.blend(out, next, t);
         Ok(())
     }
+
+    /// Blends frame `a` a fraction `t` of the way towards frame `b`, in place,
+    /// taking toroidal dimensions the short way around.
+    pub fn blend(&self, a: &mut [f32], b: &[f32], t: f32) {
+        let state_dims = self.header.config.state_dims;
+        for (k, (a, &b)) in a.iter_mut().zip(b).enumerate() {
+            *a = match self.dim_torus[k % state_dims] {
+                Some(range) => lerp_wrapped(*a, b, t, range),
+                None => *a + (b - *a) * t,
+            };
+        }
+    }
+}
+
+/// Decodes little-endian f32 `bytes` into `out`. Little-endian hosts copy them
+/// in bulk; big-endian hosts also swap each value.
+pub fn decode_frame(bytes: &[u8], out: &mut Vec<f32>) {
+    #[cfg(target_endian = "little")]
+    copy_frame(bytes, out);
+    #[cfg(target_endian = "big")]
+    decode_frame_swapped(bytes, out);
+}
+
+/// Decodes little-endian `dtype` values `bytes` into `out`.
+fn decode_values(bytes: &[u8], dtype: FrameDtype, out: &mut Vec<f32>) {
+    match dtype {
+        FrameDtype::F32 => decode_frame(bytes, out),
+        FrameDtype::F16 => {
+            out.clear();
+            out.extend(bytes.chunks_exact(2).map(|c| dtype.decode(c)));
+        }
+    }
+}
+
+/// Copies `bytes` into `out` as host-order f32 values. The copy goes through
+/// `out`'s own (aligned) memory, so `bytes` need not be aligned.
+fn copy_frame(bytes: &[u8], out: &mut Vec<f32>) {
+    out.clear();
+    out.resize(bytes.len() / 4, 0.0);
+    bytemuck::cast_slice_mut(out).copy_from_slice(&bytes[..bytes.len() / 4 * 4]);
+}
+
+/// Decodes a `[state_dims, n_agents]` frame of `dtype` values into `out` agent
+/// by agent.
+fn decode_transposed(
+    bytes: &[u8],
+    n_agents: usize,
+    state_dims: usize,
+    dtype: FrameDtype,
+    out: &mut Vec<f32>,
+) {
+    out.clear();
+    out.resize(n_agents * state_dims, 0.0);
+    for (k, chunk) in bytes.chunks_exact(dtype.size()).enumerate() {
+        let (dim, agent) = (k / n_agents, k % n_agents);
+        out[agent * state_dims + dim] = dtype.decode(chunk);
+    }
+}
+
+/// Decodes `bytes` stored in the opposite of host order: the big-endian host
+/// path, kept compiled everywhere so it is tested on little-endian hosts too.
+#[cfg_attr(target_endian = "little", allow(dead_code))]
+fn decode_frame_swapped(bytes: &[u8], out: &mut Vec<f32>) {
+    copy_frame(bytes, out);
+    for value in out.iter_mut() {
+        *value = f32::from_bits(value.to_bits().swap_bytes());
+    }
+}
+
+/// Reads just the header of the file at `path`, without mapping its body.
+pub fn read_header(path: impl AsRef<Path>) -> Result<EvoHeader> {
+    let path = path.as_ref();
+    let mut file = File::open(path).with_context(|| format!("failed to open {:?}", path))?;
+    let mut prefix = [0u8; 8];
+    file.read_exact(&mut prefix)
+        .with_context(|| format!("{:?} is too small", path))?;
+    let header_len = u32::from_le_bytes(prefix[4..8].try_into().unwrap()) as usize;
+    let mut bytes = prefix.to_vec();
+    bytes.resize(8 + header_len, 0);
+    file.read_exact(&mut bytes[8..])
+        .with_context(|| format!("{:?}: header exceeds file length", path))?;
+    Ok(parse_header(&bytes)?.0)
+}
+
+/// Validates the magic bytes and parses the header JSON, returning the header
+/// and the offset where the body starts.
+fn parse_header(bytes: &[u8]) -> Result<(EvoHeader, usize)> {
+    if bytes.len() < 8 {
+        bail!("file too small");
+    }
+    if &bytes[0..4] != b"EVO1" {
+        bail!("invalid magic bytes (expected EVO1)");
+    }
+    let header_len = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let header_start: usize = 8;
+    let header_end = header_start
+        .checked_add(header_len)
+        .ok_or_else(|| anyhow!("header length overflow"))?;
+    if header_end > bytes.len() {
+        bail!("header exceeds file length");
+    }
+    let header =
+        serde_json::from_slice(&bytes[header_start..header_end]).context("invalid header JSON")?;
+    Ok((header, header_end))
+}
+
+/// Byte ranges of the `[len: u32, bytes]` frames of a compressed body without an
+/// index, running from `body_offset` to the end of `bytes`. Stops at the first
+/// frame that runs past the end, as a truncated write leaves.
+fn walk_compressed_frames(bytes: &[u8], body_offset: usize) -> Vec<Range<usize>> {
+    let mut frames = Vec::new();
+    let mut pos = body_offset;
+    while let Some(prefix) = bytes.get(pos..pos + 4) {
+        let len = u32::from_le_bytes(prefix.try_into().unwrap()) as usize;
+        let start = pos + 4;
+        if start + len > bytes.len() {
+            break;
+        }
+        frames.push(start..start + len);
+        pos = start + len;
+    }
+    frames
+}
+
+/// Locates the frame index at the end of `bytes`, returning where it starts and
+/// the offset of each frame. Files without one (e.g. an unfinished recording)
+/// yield `None`.
+fn parse_index(bytes: &[u8], body_offset: usize) -> Option<(usize, Vec<usize>)> {
+    let footer_start = bytes.len().checked_sub(8)?;
+    if footer_start < body_offset || &bytes[footer_start + 4..] != INDEX_MAGIC {
+        return None;
+    }
+    let index_len = u32::from_le_bytes(bytes[footer_start..footer_start + 4].try_into().unwrap());
+    let index_start = footer_start.checked_sub(index_len as usize)?;
+    if index_start < body_offset || !index_len.is_multiple_of(8) {
+        return None;
+    }
+    let offsets = bytes[index_start..footer_start]
+        .chunks_exact(8)
+        .map(|c| usize::try_from(u64::from_le_bytes(c.try_into().unwrap())).ok())
+        .collect::<Option<_>>()?;
+    Some((index_start, offsets))
+}
+
+/// The byte range of each frame given the `offsets` they start at, skipping the
+/// length prefix of compressed frames. `None` unless the frames are in order,
+/// inside the body and (uncompressed) exactly `frame_bytes` long.
+fn frame_ranges(
+    offsets: &[usize],
+    body_offset: usize,
+    body_end: usize,
+    frame_bytes: usize,
+    compressed: bool,
+) -> Option<Vec<Range<usize>>> {
+    let mut ranges = Vec::with_capacity(offsets.len());
+    let mut prev_end = body_offset;
+    for (i, &start) in offsets.iter().enumerate() {
+        let next = offsets.get(i + 1).copied().unwrap_or(body_end);
+        let range = if compressed {
+            start.checked_add(4)?..next
+        } else {
+            start..start.checked_add(frame_bytes)?
+        };
+        if start < prev_end || range.start > range.end || range.end > next.min(body_end) {
+            return None;
+        }
+        prev_end = range.end;
+        ranges.push(range);
+    }
+    Some(ranges)
+}
+
+/// Locates the trailer, returning where the body ends and the payload range of each
+/// section. Files without a well-formed trailer yield `None` and are read as all body.
+fn parse_trailer(bytes: &[u8], body_offset: usize) -> Option<(usize, TrailerSections)> {
+    let footer_start = bytes.len().checked_sub(12)?;
+    if footer_start < body_offset || &bytes[footer_start + 8..] != TRAILER_MAGIC {
+        return None;
+    }
+    let trailer_len = u64::from_le_bytes(bytes[footer_start..footer_start + 8].try_into().unwrap());
+    let trailer_start = footer_start.checked_sub(usize::try_from(trailer_len).ok()?)?;
+    if trailer_start < body_offset {
+        return None;
+    }
+
+    let mut sections = HashMap::new();
+    let mut pos = trailer_start;
+    while pos < footer_start {
+        let tag: [u8; 4] = bytes.get(pos..pos + 4)?.try_into().unwrap();
+        let len = u64::from_le_bytes(bytes.get(pos + 4..pos + 12)?.try_into().unwrap());
+        let payload_start = pos + 12;
+        let payload_end = payload_start.checked_add(usize::try_from(len).ok()?)?;
+        if payload_end > footer_start {
+            return None;
+        }
+        sections.insert(tag, payload_start..payload_end);
+        pos = payload_end;
+    }
+    Some((trailer_start, sections))
+}
+
+/// Interpolates from `a` to `b` the short way around a torus spanning `[min, max)`,
+/// wrapping the result back into range.
+fn lerp_wrapped(a: f32, b: f32, t: f32, [min, max]: [f32; 2]) -> f32 {
+    let period = max - min;
+    let mut d = b - a;
+    d -= period * (d / period).round();
+    (a + d * t - min).rem_euclid(period) + min
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Writes a minimal `.evo` file with `n_frames` zeroed frames of 2 agents x 2 dims.
+    pub(crate) fn write_test_file(name: &str, playback: &str, n_frames: usize) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        let header = format!(
+            r#"{{"version":1,"timestamp":"t","config":{{"n_agents":2,"state_dims":2,"state_labels":["pos_x","pos_y"]}}{playback}}}"#
+        );
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"EVO1").unwrap();
+        file.write_all(&(header.len() as u32).to_le_bytes())
+            .unwrap();
+        file.write_all(header.as_bytes()).unwrap();
+        file.write_all(&vec![0u8; n_frames * 2 * 2 * 4]).unwrap();
+        path
+    }
+
+    #[test]
+    fn converts_between_frames_and_sim_time() {
+        let path = write_test_file(
+            "evo_sim_time_test.evo",
+            r#","playback":{"dt":0.01,"substeps":4,"save_interval":5,"total_frames":10}"#,
+            10,
+        );
+        let evo = EvoFile::open(&path).unwrap();
+
+        // Each recorded frame spans dt * substeps * save_interval = 0.2s.
+        assert!((evo.sim_time_of_frame(1) - 0.2).abs() < 1e-12);
+        assert!((evo.sim_time_of_frame(7) - 1.4).abs() < 1e-12);
+        for i in 0..10 {
+            assert_eq!(evo.frame_at_sim_time(evo.sim_time_of_frame(i)), i);
+        }
+        assert_eq!(evo.frame_at_sim_time(0.39), 1);
+        assert_eq!(evo.frame_at_sim_time(-1.0), 0);
+        assert_eq!(evo.frame_at_sim_time(f64::NAN), 0);
+        assert_eq!(evo.frame_at_sim_time(100.0), 9);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn wrapped_lerp_takes_the_short_way_around() {
+        let range = [-10.0, 10.0];
+        // 9 -> -9 is 2 units across the seam, not 18 units back through the middle.
+        assert!((lerp_wrapped(9.0, -9.0, 0.25, range) - 9.5).abs() < 1e-5);
+        assert!((lerp_wrapped(9.0, -9.0, 0.75, range) + 9.5).abs() < 1e-5);
+        // Moves that do not cross the seam match a plain lerp.
+        assert!((lerp_wrapped(-2.0, 4.0, 0.5, range) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn reads_compute_times_from_trailer() {
+        let path = write_test_file("evo_trailer_test.evo", "", 3);
+        let payload: Vec<u8> = [0.5f32, 0.25, 2.0]
+            .iter()
+            .flat_map(|t| t.to_le_bytes())
+            .collect();
+        append_trailer(&path, COMPUTE_TIME_TAG, &payload);
+
+        let evo = EvoFile::open(&path).unwrap();
+        assert_eq!(evo.total_frames(), 3);
+        assert_eq!(evo.frame_compute_time(0), Some(0.5));
+        assert_eq!(evo.frame_compute_time(2), Some(2.0));
+        assert_eq!(evo.frame_compute_time(3), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn integrity_reports_a_partial_final_frame() {
+        let path = write_test_file("evo_integrity_test.evo", "", 3);
+        let evo = EvoFile::open(&path).unwrap();
+        let integrity = evo.integrity();
+        assert_eq!(integrity.complete_frames, 3);
+        assert_eq!(integrity.trailing_partial_bytes, 0);
+        assert_eq!(integrity.to_string(), "3 complete frames");
+        drop(evo);
+
+        // An interrupted write leaves 5 of a frame's 16 bytes.
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&[0u8; 5]).unwrap();
+        let evo = EvoFile::open(&path).unwrap();
+        assert_eq!(evo.total_frames(), 3);
+        assert_eq!(
+            evo.integrity(),
+            FileIntegrity {
+                complete_frames: 3,
+                trailing_partial_bytes: 5,
+                declared_frames: None,
+            }
+        );
+        assert_eq!(
+            evo.integrity().to_string(),
+            "3 complete frames, 5B partial tail discarded"
+        );
+        assert!(evo.integrity().looks_truncated());
+
+        // A run stopped before the frames its header declared, on a frame boundary.
+        let path = write_test_file(
+            "evo_integrity_test.evo",
+            r#","playback":{"dt":1.0,"save_interval":1,"total_frames":10}"#,
+            3,
+        );
+        let evo = EvoFile::open(&path).unwrap();
+        assert_eq!(evo.integrity().trailing_partial_bytes, 0);
+        assert!(evo.integrity().looks_truncated());
+        assert_eq!(
+            evo.integrity().to_string(),
+            "3 complete frames of 10 declared"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_half_written_last_frame_is_reported_not_read() {
+        // Two frames of 16 bytes, then half of a third.
+        let path = write_test_file("evo_half_frame_test.evo", "", 2);
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&[0u8; 8]).unwrap();
+        drop(file);
+
+        let evo = EvoFile::open(&path).unwrap();
+        assert_eq!(evo.total_frames(), 2);
+        let integrity = evo.integrity();
+        assert_eq!(integrity.trailing_partial_bytes, 8);
+        assert!(integrity.looks_truncated());
+        let mut values = Vec::new();
+        evo.read_frame_f32(1, &mut values).unwrap();
+        let err = evo.read_frame_f32(2, &mut values).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "frame 2 is incomplete: the file ends 8 bytes into it"
+        );
+        assert!(evo.read_frame_f32(3, &mut values).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn verify_catches_a_changed_body_byte() {
+        let body_crc32 = crc32fast::hash(&[0u8; 3 * 16]);
+        let path = write_test_file(
+            "evo_checksum_test.evo",
+            &format!(r#","body_crc32":{body_crc32}"#),
+            3,
+        );
+        assert!(EvoFile::open(&path).unwrap().verify().unwrap());
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x80;
+        std::fs::write(&path, &bytes).unwrap();
+        let evo = EvoFile::open(&path).unwrap();
+        // The frame still decodes; only the checksum tells it changed.
+        assert_eq!(evo.integrity().complete_frames, 3);
+        let err = evo.verify().unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"), "{err}");
+
+        let path_without = write_test_file("evo_checksum_none_test.evo", "", 3);
+        assert!(!EvoFile::open(&path_without).unwrap().verify().unwrap());
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&path_without).unwrap();
+    }
+
+    /// Appends a trailer holding the single section `tag`.
+    fn append_trailer(path: &Path, tag: &[u8; 4], payload: &[u8]) {
+        let mut trailer = tag.to_vec();
+        trailer.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        trailer.extend_from_slice(payload);
+        let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(&trailer).unwrap();
+        file.write_all(&(trailer.len() as u64).to_le_bytes())
+            .unwrap();
+        file.write_all(TRAILER_MAGIC).unwrap();
+    }
+
+    #[test]
+    fn sim_step_track_places_unevenly_spaced_frames() {
+        let path = write_test_file(
+            "evo_sim_step_test.evo",
+            r#","playback":{"dt":0.5,"substeps":1,"save_interval":1,"total_frames":3}"#,
+            3,
+        );
+        let payload: Vec<u8> = [0u64, 1, 10].iter().flat_map(|s| s.to_le_bytes()).collect();
+        append_trailer(&path, SIM_STEP_TAG, &payload);
+
+        let evo = EvoFile::open(&path).unwrap();
+        assert_eq!(evo.total_frames(), 3);
+        assert_eq!(evo.sim_time_of_frame(2), 5.0);
+        assert_eq!(evo.frame_at_sim_time(4.9), 1);
+        assert_eq!(evo.frame_at_sim_time(5.0), 2);
+        assert_eq!(evo.frame_position_at_sim_time(2.75), 1.5);
+        assert_eq!(evo.frame_position_at_sim_time(100.0), 2.0);
+        // Playback still advances at the nominal rate.
+        assert_eq!(evo.frame_duration(), 0.5);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn generation_track_labels_each_frame() {
+        let path = write_test_file("evo_generation_test.evo", "", 3);
+        let payload: Vec<u8> = [0u64, 0, 1].iter().flat_map(|g| g.to_le_bytes()).collect();
+        append_trailer(&path, GENERATION_TAG, &payload);
+
+        let evo = EvoFile::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(evo.generation(1), Some(0));
+        assert_eq!(evo.generation(2), Some(1));
+        assert_eq!(evo.generation(3), None);
+    }
+
+    #[test]
+    fn frame_views_agents_by_row_and_label() {
+        let frame = Frame {
+            data: vec![1.0, 2.0, 3.0, 4.0],
+            state_dims: 2,
+            label_to_index: HashMap::from([("pos_x".to_string(), 0), ("pos_y".to_string(), 1)]),
+        };
+        assert_eq!(frame.n_agents(), 2);
+        assert_eq!(frame.agent(1), &[3.0, 4.0]);
+        assert_eq!(frame.get(0, "pos_y"), Some(2.0));
+        assert_eq!(frame.get(0, "energy"), None);
+
+        let json = serde_json::to_string(&frame).unwrap();
+        let back: Frame = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.get(1, "pos_x"), Some(3.0));
+    }
+
+    #[test]
+    fn finds_next_frame_with_moved_positions() {
+        let path = write_test_file("evo_next_changed_test.evo", "", 4);
+        // Frames are 2 agents x (pos_x, pos_y); nudge agent 1's pos_y in frame 3 only.
+        let mut bytes = std::fs::read(&path).unwrap();
+        let frame3_agent1_y = bytes.len() - 4;
+        bytes[frame3_agent1_y..].copy_from_slice(&0.5f32.to_le_bytes());
+        std::fs::write(&path, bytes).unwrap();
+
+        let evo = EvoFile::open(&path).unwrap();
+        let mut column = Vec::new();
+        evo.read_column_f32(3, 1, &mut column).unwrap();
+        assert_eq!(column, vec![0.0, 0.5]);
+
+        assert_eq!(evo.next_changed_frame(0, 0.1), Some(3));
+        assert_eq!(evo.next_changed_frame(0, 1.0), None);
+        assert_eq!(evo.next_changed_frame(3, 0.1), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn finds_frames_with_an_agent_inside_a_rect() {
+        let path = write_test_file("evo_rect_test.evo", "", 3);
+        // Frames are 2 agents x (pos_x, pos_y); move agent 1 to (5, 5), then (5, 20).
+        let mut bytes = std::fs::read(&path).unwrap();
+        let frame_bytes = 2 * 2 * 4;
+        let body = bytes.len() - 3 * frame_bytes;
+        for (frame, y) in [(1, 5.0f32), (2, 20.0)] {
+            let agent1 = body + frame * frame_bytes + 8;
+            bytes[agent1..agent1 + 4].copy_from_slice(&5.0f32.to_le_bytes());
+            bytes[agent1 + 4..agent1 + 8].copy_from_slice(&y.to_le_bytes());
+        }
+        std::fs::write(&path, bytes).unwrap();
+
+        let evo = EvoFile::open(&path).unwrap();
+        let zone = Rect {
+            min: [4.0, 4.0],
+            max: [6.0, 6.0],
+        };
+        assert_eq!(evo.frames_with_agent_in_rect(0, 1, zone).unwrap(), vec![1]);
+        let around_origin = Rect {
+            min: [-1.0, -1.0],
+            max: [0.0, 0.0],
+        };
+        assert_eq!(
+            evo.frames_with_agent_in_rect(0, 1, around_origin).unwrap(),
+            vec![0, 1, 2]
+        );
+        assert!(evo.frames_with_agent_in_rect(0, 2, zone).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn display_labels_fall_back_to_raw_labels() {
+        let path = write_test_file("evo_label_meta_test.evo", "", 1);
+        let mut evo = EvoFile::open(&path).unwrap();
+        evo.header.config.label_meta = HashMap::from([
+            (
+                "pos_x".to_string(),
+                LabelMeta {
+                    display: "Position X".to_string(),
+                    unit: Some("m".to_string()),
+                },
+            ),
+            (
+                "pos_y".to_string(),
+                LabelMeta {
+                    display: "Position Y".to_string(),
+                    unit: None,
+                },
+            ),
+        ]);
+        assert_eq!(evo.header.display_label("pos_x"), "Position X (m)");
+        assert_eq!(evo.header.display_label("pos_y"), "Position Y");
+        assert_eq!(evo.header.display_label("energy"), "energy");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn legacy_files_count_one_unit_per_frame() {
+        let path = write_test_file("evo_sim_time_legacy_test.evo", "", 3);
+        let evo = EvoFile::open(&path).unwrap();
+        assert_eq!(evo.sim_time_of_frame(2), 2.0);
+        assert_eq!(evo.frame_at_sim_time(1.5), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn frames_decode_the_same_on_either_path() {
+        let values = [1.5f32, -0.25, f32::MAX, 3.0e-7];
+        let le: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let mut out = Vec::new();
+        decode_frame(&le, &mut out);
+        assert_eq!(out, values);
+
+        // The slow path reads values stored in the opposite of host order.
+        let swapped: Vec<u8> = values
+            .iter()
+            .flat_map(|v| {
+                if cfg!(target_endian = "little") {
+                    v.to_be_bytes()
+                } else {
+                    v.to_le_bytes()
+                }
+            })
+            .collect();
+        decode_frame_swapped(&swapped, &mut out);
+        assert_eq!(out, values);
+
+        // Unaligned bytes still decode.
+        let mut shifted = vec![0u8];
+        shifted.extend_from_slice(&le);
+        decode_frame(&shifted[1..], &mut out);
+        assert_eq!(out, values);
+    }
+
+    #[test]
+    fn deflated_frames_read_back_exactly() {
+        use flate2::{write::DeflateEncoder, Compression};
+
+        let path = write_test_file("evo_deflate_test.evo", r#","compression":"deflate""#, 0);
+        let frames: Vec<[f32; 4]> = (0..10)
+            .map(|i| {
+                let tiny = f32::MIN_POSITIVE / 3.0;
+                [i as f32 * 0.1, -0.0, tiny, 1e30 / i as f32]
+            })
+            .collect();
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        for frame in &frames {
+            let bytes: Vec<u8> = frame.iter().flat_map(|v| v.to_le_bytes()).collect();
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(&bytes).unwrap();
+            let compressed = encoder.finish().unwrap();
+            file.write_all(&(compressed.len() as u32).to_le_bytes())
+                .unwrap();
+            file.write_all(&compressed).unwrap();
+        }
+        // An interrupted write of an 11th frame.
+        file.write_all(&[100, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+
+        let evo = EvoFile::open(&path).unwrap();
+        assert_eq!(evo.total_frames(), 10);
+        assert_eq!(
+            evo.integrity(),
+            FileIntegrity {
+                complete_frames: 10,
+                trailing_partial_bytes: 6,
+                declared_frames: None,
+            }
+        );
+        let mut values = Vec::new();
+        for (i, frame) in frames.iter().enumerate() {
+            evo.read_frame_f32(i, &mut values).unwrap();
+            let bits = |v: &[f32]| v.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(&values), bits(frame), "frame {i}");
+        }
+        evo.read_column_f32(3, 0, &mut values).unwrap();
+        assert_eq!(values, [frames[3][0], frames[3][2]]);
+        let rect = Rect {
+            min: [0.75, -1.0],
+            max: [1.0, 1.0],
+        };
+        assert_eq!(evo.frames_with_agent_in_rect(0, 1, rect).unwrap(), [8, 9]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn index_locates_frames_of_any_size() {
+        use flate2::{write::DeflateEncoder, Compression};
+
+        let path = std::env::temp_dir().join("evo_index_test.evo");
+        let header = r#"{"version":2,"timestamp":"t","config":{"n_agents":2,"state_dims":2,"state_labels":["pos_x","pos_y"]},"compression":"deflate"}"#;
+        let mut bytes = b"EVO1".to_vec();
+        bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        let frames: Vec<[f32; 4]> = (0..6)
+            .map(|i| match i % 2 {
+                0 => [0.0; 4],
+                _ => [i as f32, 1.0 / i as f32, -(i as f32), 1e-3 * i as f32],
+            })
+            .collect();
+        let mut offsets = Vec::new();
+        for frame in &frames {
+            offsets.push(bytes.len() as u64);
+            let raw: Vec<u8> = frame.iter().flat_map(|v| v.to_le_bytes()).collect();
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(&raw).unwrap();
+            let compressed = encoder.finish().unwrap();
+            bytes.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&compressed);
+        }
+        for offset in &offsets {
+            bytes.extend_from_slice(&offset.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(offsets.len() as u32 * 8).to_le_bytes());
+        bytes.extend_from_slice(INDEX_MAGIC);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let evo = EvoFile::open(&path).unwrap();
+        let stored = evo.frames.as_ref().unwrap();
+        assert!(stored[0].len() < stored[1].len());
+        assert_eq!(evo.integrity().trailing_partial_bytes, 0);
+        let mut values = Vec::new();
+        for i in [3, 0, 5, 1, 4, 2] {
+            evo.read_frame_f32(i, &mut values).unwrap();
+            assert_eq!(values, frames[i], "frame {i}");
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn f16_frames_read_back_as_f32() {
+        // Frames of 2 x 2 f16s are 8 bytes, so the 16-byte body holds two.
+        let path = write_test_file("evo_dtype_f16_test.evo", r#","dtype":"f16""#, 1);
+        let values = [0.5f32, 10.0, 1.0, -2.0];
+        let mut bytes = std::fs::read(&path).unwrap();
+        let body = bytes.len() - 16;
+        for (k, v) in values.iter().enumerate() {
+            let v = f16::from_f32(*v).to_le_bytes();
+            bytes[body + 2 * k..body + 2 * k + 2].copy_from_slice(&v);
+        }
+        std::fs::write(&path, &bytes).unwrap();
+        let evo = EvoFile::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(evo.header.dtype, FrameDtype::F16);
+        assert_eq!(evo.total_frames(), 2);
+
+        let mut frame = Vec::new();
+        evo.read_frame_f32(0, &mut frame).unwrap();
+        assert_eq!(frame, values);
+        evo.read_column_f32(0, 1, &mut frame).unwrap();
+        assert_eq!(frame, [10.0, -2.0]);
+        let rect = Rect {
+            min: [0.0, 5.0],
+            max: [1.0, 20.0],
+        };
+        assert_eq!(evo.frames_with_agent_in_rect(0, 1, rect).unwrap(), [0]);
+        evo.read_frame_f32(1, &mut frame).unwrap();
+        assert_eq!(frame, [0.0; 4]);
+    }
+
+    #[test]
+    fn soa_files_read_like_aos_files() {
+        // The same two agents, (0, 10) and (1, 11), in both layouts.
+        let aos = write_test_file("evo_layout_aos_test.evo", "", 1);
+        let soa = write_test_file("evo_layout_soa_test.evo", r#","layout":"soa""#, 1);
+        for (path, values) in [
+            (&aos, [0.0f32, 10.0, 1.0, 11.0]),
+            (&soa, [0.0, 1.0, 10.0, 11.0]),
+        ] {
+            let mut bytes = std::fs::read(path).unwrap();
+            let body = bytes.len() - 16;
+            for (k, v) in values.iter().enumerate() {
+                bytes[body + 4 * k..body + 4 * k + 4].copy_from_slice(&v.to_le_bytes());
+            }
+            std::fs::write(path, &bytes).unwrap();
+        }
+        let aos = EvoFile::open(&aos).unwrap();
+        let soa = EvoFile::open(&soa).unwrap();
+        assert_eq!(soa.header.layout, FrameLayout::Soa);
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        aos.read_frame_f32(0, &mut a).unwrap();
+        soa.read_frame_f32(0, &mut b).unwrap();
+        assert_eq!(a, [0.0, 10.0, 1.0, 11.0]);
+        assert_eq!(a, b);
+        soa.read_column_f32(0, 1, &mut b).unwrap();
+        assert_eq!(b, [10.0, 11.0]);
+        assert_eq!(
+            aos.column_stats(0, 1).unwrap(),
+            soa.column_stats(0, 1).unwrap()
+        );
+        assert_eq!(
+            soa.column_stats(0, 1).unwrap(),
+            ColumnStats {
+                min: 10.0,
+                max: 11.0,
+                mean: 10.5
+            }
+        );
+        let rect = Rect {
+            min: [0.5, 10.5],
+            max: [2.0, 12.0],
+        };
+        assert_eq!(soa.frames_with_agent_in_rect(0, 1, rect).unwrap(), [0]);
+    }
+
+    /// Times `column_stats` over every frame of the same recording in both
+    /// layouts. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn column_stats_benchmark() {
+        let (n_agents, state_dims, n_frames) = (100_000, 8, 20);
+        for layout in ["aos", "soa"] {
+            let path = std::env::temp_dir().join(format!("evo_column_stats_{layout}.evo"));
+            let header = format!(
+                r#"{{"version":1,"timestamp":"t","config":{{"n_agents":{n_agents},"state_dims":{state_dims},"state_labels":[]}},"layout":"{layout}"}}"#
+            );
+            let mut file = File::create(&path).unwrap();
+            file.write_all(b"EVO1").unwrap();
+            file.write_all(&(header.len() as u32).to_le_bytes())
+                .unwrap();
+            file.write_all(header.as_bytes()).unwrap();
+            let frame: Vec<u8> = (0..n_agents * state_dims)
+                .flat_map(|k| (k as f32).to_le_bytes())
+                .collect();
+            for _ in 0..n_frames {
+                file.write_all(&frame).unwrap();
+            }
+            drop(file);
+
+            let evo = EvoFile::open(&path).unwrap();
+            let start = std::time::Instant::now();
+            for frame_index in 0..n_frames {
+                evo.column_stats(frame_index, 3).unwrap();
+            }
+            println!(
+                "{layout}: column_stats over {n_frames} frames of {n_agents} agents in {:?}",
+                start.elapsed()
+            );
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
 }
@@ -0,0 +1,207 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    ops::Range,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::source::FrameSource;
+
+const NPY_MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// Frames or agents to export, written `A..B` with `B` excluded. Either end may
+/// be left out: `..B` starts at the first, `A..` runs to the last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexRange {
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+impl IndexRange {
+    /// The part of `0..len` the range covers.
+    pub fn within(self, len: usize) -> Range<usize> {
+        let end = self.end.map_or(len, |end| end.min(len));
+        self.start.min(end)..end
+    }
+}
+
+impl FromStr for IndexRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((start, end)) = s.split_once("..") else {
+            bail!("expected A..B, got {s:?}");
+        };
+        let bound = |v: &str| {
+            v.parse::<usize>()
+                .with_context(|| format!("invalid range bound {v:?}"))
+        };
+        let start = if start.is_empty() { 0 } else { bound(start)? };
+        let end = (!end.is_empty()).then(|| bound(end)).transpose()?;
+        if end.is_some_and(|end| end < start) {
+            bail!("range {s:?} ends before it starts");
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// Writes `frames` of `source` to `path` as CSV: a `frame,agent_id,<state
+/// labels>` header, then one row per agent in `agents` per frame. Frames are
+/// read and written one at a time, so memory stays bounded by a frame. Returns
+/// the rows written.
+pub fn export_csv(
+    source: &dyn FrameSource,
+    path: &Path,
+    frames: IndexRange,
+    agents: IndexRange,
+) -> Result<u64> {
+    let config = &source.header().config;
+    let (n_agents, state_dims) = (config.n_agents, config.state_dims);
+    let file = File::create(path).with_context(|| format!("failed to create {:?}", path))?;
+    let mut out = BufWriter::new(file);
+
+    write!(out, "frame,agent_id")?;
+    for label in &config.state_labels {
+        write!(out, ",{label}")?;
+    }
+    writeln!(out)?;
+
+    let agents = agents.within(n_agents);
+    let mut values = Vec::new();
+    let mut rows = 0;
+    for frame_index in frames.within(source.total_frames()) {
+        source.read_frame_f32(frame_index, &mut values)?;
+        for agent in agents.clone() {
+            write!(out, "{frame_index},{agent}")?;
+            for value in &values[agent * state_dims..(agent + 1) * state_dims] {
+                write!(out, ",{value}")?;
+            }
+            writeln!(out)?;
+            rows += 1;
+        }
+    }
+    out.flush()?;
+    Ok(rows)
+}
+
+/// Writes `frames` of `source` to `path` as one NumPy `.npy` array of
+/// little-endian f32s shaped `[frames, agents, state_dims]`, keeping only
+/// `agents`. Streamed a frame at a time like [`export_csv`]. Returns the array's
+/// shape.
+pub fn export_npy(
+    source: &dyn FrameSource,
+    path: &Path,
+    frames: IndexRange,
+    agents: IndexRange,
+) -> Result<[usize; 3]> {
+    let config = &source.header().config;
+    let state_dims = config.state_dims;
+    let frames = frames.within(source.total_frames());
+    let agents = agents.within(config.n_agents);
+    let shape = [frames.len(), agents.len(), state_dims];
+    let file = File::create(path).with_context(|| format!("failed to create {:?}", path))?;
+    let mut out = BufWriter::new(file);
+    out.write_all(&npy_header(shape))?;
+
+    let mut values = Vec::new();
+    for frame_index in frames {
+        source.read_frame_f32(frame_index, &mut values)?;
+        for value in &values[agents.start * state_dims..agents.end * state_dims] {
+            out.write_all(&value.to_le_bytes())?;
+        }
+    }
+    out.flush()?;
+    Ok(shape)
+}
+
+/// The `.npy` (version 1.0) preamble of a C-ordered little-endian f32 array of
+/// `shape`: magic, version, the header's length as a little-endian u16, then the
+/// header itself, a Python dict literal padded with spaces and ending in a newline
+/// so the data starts 64-byte aligned.
+fn npy_header(shape: [usize; 3]) -> Vec<u8> {
+    let [frames, agents, dims] = shape;
+    let mut dict = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({frames}, {agents}, {dims}), }}"
+    );
+    let preamble_len = NPY_MAGIC.len() + 2 + 2;
+    let padded = (preamble_len + dict.len() + 1).next_multiple_of(64);
+    let padding = padded - preamble_len - dict.len() - 1;
+    dict.push_str(&" ".repeat(padding));
+    dict.push('\n');
+
+    let mut header = NPY_MAGIC.to_vec();
+    header.extend_from_slice(&[1, 0]);
+    // Three dimensions of at most 20 digits each keep it well under 64KiB.
+    header.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::Frames;
+
+    #[test]
+    fn parses_ranges_with_open_ends() {
+        let range = |s: &str| s.parse::<IndexRange>();
+        assert_eq!(range("2..5").unwrap().within(10), 2..5);
+        assert_eq!(range("..5").unwrap().within(3), 0..3);
+        assert_eq!(range("7..").unwrap().within(10), 7..10);
+        assert_eq!(range("..").unwrap().within(4), 0..4);
+        assert_eq!(range("12..").unwrap().within(10), 10..10);
+        assert!(range("5..2").is_err());
+        assert!(range("5").is_err());
+        assert!(range("a..2").is_err());
+    }
+
+    #[test]
+    fn exports_selected_frames_and_agents() -> Result<()> {
+        let source = Frames::new(4, &["pos_x", "energy"], 5);
+        let path = std::env::temp_dir().join("evo_export_csv_test.csv");
+
+        let rows = export_csv(&source, &path, "3..".parse()?, "1..3".parse()?)?;
+        assert_eq!(rows, 4);
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "frame,agent_id,pos_x,energy\n\
+             3,1,310,311\n\
+             3,2,320,321\n\
+             4,1,410,411\n\
+             4,2,420,421\n"
+        );
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn npy_export_has_a_numpy_header_and_c_ordered_data() -> Result<()> {
+        let source = Frames::new(3, &["pos_x", "pos_y"], 4);
+        let path = std::env::temp_dir().join("evo_export_npy_test.npy");
+
+        let shape = export_npy(&source, &path, "1..3".parse()?, "1..".parse()?)?;
+        assert_eq!(shape, [2, 2, 2]);
+        let bytes = std::fs::read(&path)?;
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let dict = std::str::from_utf8(&bytes[10..10 + header_len])?;
+        assert_eq!(
+            dict.trim_end(),
+            "{'descr': '<f4', 'fortran_order': False, 'shape': (2, 2, 2), }"
+        );
+        assert!(dict.ends_with('\n'));
+        let data: Vec<f32> = bytes[10 + header_len..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(data, [110., 111., 120., 121., 210., 211., 220., 221.]);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
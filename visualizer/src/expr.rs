@@ -0,0 +1,342 @@
+use anyhow::{bail, Result};
+
+/// A function an expression can call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Func {
+    Sqrt,
+    Abs,
+    Min,
+    Max,
+}
+
+impl Func {
+    fn named(name: &str) -> Option<Self> {
+        match name {
+            "sqrt" => Some(Func::Sqrt),
+            "abs" => Some(Func::Abs),
+            "min" => Some(Func::Min),
+            "max" => Some(Func::Max),
+            _ => None,
+        }
+    }
+
+    /// Whether the function takes `n` arguments: one for `sqrt` and `abs`, two or
+    /// more for `min` and `max`.
+    fn accepts(self, n: usize) -> bool {
+        match self {
+            Func::Sqrt | Func::Abs => n == 1,
+            Func::Min | Func::Max => n >= 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+/// An arithmetic expression over state labels, e.g. `sqrt(vel_x^2 + vel_y^2)`,
+/// parsed once when the mapping loads.
+///
+/// Supports `+ - * /`, right-associative `^`, unary minus, parentheses, number
+/// literals, and calls to `sqrt`, `abs`, `min` and `max`. Any other identifier
+/// is a state label.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    src: String,
+    root: Node,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Num(f32),
+    Label(String),
+    Neg(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(Func, Vec<Node>),
+}
+
+impl Expr {
+    pub fn parse(src: &str) -> Result<Self> {
+        let mut parser = Parser { src, pos: 0 };
+        let root = parser.expr()?;
+        parser.skip_whitespace();
+        if parser.pos < src.len() {
+            return parser.error("expected an operator");
+        }
+        Ok(Self {
+            src: src.to_string(),
+            root,
+        })
+    }
+
+    /// The text the expression was parsed from.
+    pub fn as_str(&self) -> &str {
+        &self.src
+    }
+
+    /// The expression's value for one agent. Fails on a label `lookup` does not
+    /// know; non-finite results are returned as is.
+    pub fn eval(&self, lookup: &impl Fn(&str) -> Option<f32>) -> Result<f32> {
+        self.root.eval(lookup)
+    }
+
+    /// Every state label the expression reads, in order of appearance.
+    pub fn labels(&self) -> Vec<&str> {
+        let mut labels = Vec::new();
+        self.root.collect_labels(&mut labels);
+        labels
+    }
+}
+
+impl Node {
+    fn eval(&self, lookup: &impl Fn(&str) -> Option<f32>) -> Result<f32> {
+        Ok(match self {
+            Node::Num(v) => *v,
+            Node::Label(label) => match lookup(label) {
+                Some(v) => v,
+                None => bail!("unknown state label {label:?} in expression"),
+            },
+            Node::Neg(e) => -e.eval(lookup)?,
+            Node::Binary(op, a, b) => {
+                let (a, b) = (a.eval(lookup)?, b.eval(lookup)?);
+                match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Pow => a.powf(b),
+                }
+            }
+            Node::Call(func, args) => {
+                let mut values = args.iter().map(|arg| arg.eval(lookup));
+                match func {
+                    Func::Sqrt => values.next().unwrap_or(Ok(0.0))?.sqrt(),
+                    Func::Abs => values.next().unwrap_or(Ok(0.0))?.abs(),
+                    Func::Min => values.try_fold(f32::INFINITY, |m, v| v.map(|v| m.min(v)))?,
+                    Func::Max => values.try_fold(f32::NEG_INFINITY, |m, v| v.map(|v| m.max(v)))?,
+                }
+            }
+        })
+    }
+
+    fn collect_labels<'a>(&'a self, labels: &mut Vec<&'a str>) {
+        match self {
+            Node::Num(_) => {}
+            Node::Label(label) => labels.push(label),
+            Node::Neg(e) => e.collect_labels(labels),
+            Node::Binary(_, a, b) => {
+                a.collect_labels(labels);
+                b.collect_labels(labels);
+            }
+            Node::Call(_, args) => args.iter().for_each(|arg| arg.collect_labels(labels)),
+        }
+    }
+}
+
+/// Recursive-descent parser over `src`, lowest precedence first.
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error<T>(&self, message: &str) -> Result<T> {
+        bail!(
+            "{message} at column {} of expression {:?}",
+            self.pos + 1,
+            self.src
+        )
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.src[self.pos..].chars().next()
+    }
+
+    /// Consumes `c` if it is the next character.
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+        found
+    }
+
+    /// `term (('+' | '-') term)*`
+    fn expr(&mut self) -> Result<Node> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                BinaryOp::Add
+            } else if self.eat('-') {
+                BinaryOp::Sub
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+    }
+
+    /// `unary (('*' | '/') unary)*`
+    fn term(&mut self) -> Result<Node> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                BinaryOp::Mul
+            } else if self.eat('/') {
+                BinaryOp::Div
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    /// `'-' unary | atom ('^' unary)?`, so `-x^2` is `-(x^2)` and `2^-1` parses.
+    fn unary(&mut self) -> Result<Node> {
+        if self.eat('-') {
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        let base = self.atom()?;
+        if self.eat('^') {
+            return Ok(Node::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(self.unary()?),
+            ));
+        }
+        Ok(base)
+    }
+
+    /// A number, a label, a call, or a parenthesized expression.
+    fn atom(&mut self) -> Result<Node> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                if !self.eat(')') {
+                    return self.error("expected ')'");
+                }
+                Ok(inner)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => self.identifier(),
+            Some(_) => self.error("expected a number, label or '('"),
+            None => self.error("unexpected end"),
+        }
+    }
+
+    fn number(&mut self) -> Result<Node> {
+        let start = self.pos;
+        let rest = &self.src[start..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        match rest[..len].parse() {
+            Ok(v) => {
+                self.pos += len;
+                Ok(Node::Num(v))
+            }
+            Err(_) => self.error("invalid number"),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<Node> {
+        let start = self.pos;
+        let rest = &self.src[start..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let name = &rest[..len];
+        self.pos += len;
+        if !self.eat('(') {
+            return Ok(Node::Label(name.to_string()));
+        }
+
+        let Some(func) = Func::named(name) else {
+            self.pos = start;
+            return self.error(&format!("unknown function {name:?}"));
+        };
+        let mut args = Vec::new();
+        if !self.eat(')') {
+            loop {
+                args.push(self.expr()?);
+                if self.eat(')') {
+                    break;
+                }
+                if !self.eat(',') {
+                    return self.error("expected ',' or ')'");
+                }
+            }
+        }
+        if !func.accepts(args.len()) {
+            self.pos = start;
+            return self.error(&format!("wrong number of arguments to {name}"));
+        }
+        Ok(Node::Call(func, args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(src: &str) -> Result<f32> {
+        let lookup = |label: &str| match label {
+            "vel_x" => Some(3.0),
+            "vel_y" => Some(4.0),
+            _ => None,
+        };
+        Expr::parse(src)?.eval(&lookup)
+    }
+
+    #[test]
+    fn evaluates_with_precedence_and_nested_parens() {
+        assert_eq!(eval("sqrt(vel_x^2 + vel_y^2)").unwrap(), 5.0);
+        assert_eq!(eval("1 + 2 * 3").unwrap(), 7.0);
+        assert_eq!(eval("((1 + 2) * (3 - (4 / 2)))").unwrap(), 3.0);
+        assert_eq!(eval("2 ^ 3 ^ 2").unwrap(), 512.0);
+        assert_eq!(eval("-vel_x^2").unwrap(), -9.0);
+        assert_eq!(eval("2^-1").unwrap(), 0.5);
+        assert_eq!(eval("max(abs(-vel_x), min(vel_y, 10, 2.5))").unwrap(), 3.0);
+        assert_eq!(
+            Expr::parse("vel_x * (vel_y + vel_x)").unwrap().labels(),
+            ["vel_x", "vel_y", "vel_x"]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_expressions_and_unknown_names() {
+        for src in [
+            "",
+            "1 +",
+            "(vel_x",
+            "vel_x)",
+            "vel_x vel_y",
+            "1..2",
+            "sqrt(1, 2)",
+            "max(1)",
+        ] {
+            assert!(Expr::parse(src).is_err(), "{src:?} should not parse");
+        }
+        let err = Expr::parse("cbrt(vel_x)").unwrap_err().to_string();
+        assert!(
+            err.contains("unknown function \"cbrt\" at column 1"),
+            "{err}"
+        );
+
+        // Unknown labels parse, but fail to evaluate.
+        let err = eval("vel_x + speed").unwrap_err().to_string();
+        assert!(err.contains("\"speed\""), "{err}");
+    }
+}
@@ -0,0 +1,399 @@
+use std::{collections::HashMap, io::Write, ops::Range};
+
+use anyhow::{bail, Result};
+use image::RgbaImage;
+
+/// Colors a GIF palette holds.
+const MAX_COLORS: usize = 256;
+/// Widest LZW code GIF allows; the dictionary restarts once it is full.
+const MAX_CODE_BITS: u32 = 12;
+/// Bits per channel of the cache that maps colors to palette entries.
+const LOOKUP_BITS: u32 = 5;
+
+/// Up to 256 colors chosen by median cut, shared by every frame of an animation
+/// so colors stay put from frame to frame.
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+    /// Nearest palette entry per `LOOKUP_BITS`-quantized color, filled on first use.
+    lookup: Vec<Option<u8>>,
+}
+
+impl Palette {
+    /// Splits the box of `samples` with the widest channel at its median until
+    /// there are 256 boxes (or none left to split), and takes each box's mean.
+    pub fn median_cut(samples: &mut [[u8; 3]]) -> Self {
+        let mut boxes: Vec<Range<usize>> = std::iter::once(0..samples.len()).collect();
+        while boxes.len() < MAX_COLORS {
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .map(|(i, range)| {
+                    let (channel, extent) = widest_channel(&samples[range.clone()]);
+                    (extent, i, channel)
+                })
+                .max();
+            let Some((extent, i, channel)) = widest else {
+                break;
+            };
+            if extent == 0 {
+                break;
+            }
+            let range = boxes.swap_remove(i);
+            let slice = &mut samples[range.clone()];
+            slice.sort_unstable_by_key(|rgb| rgb[channel]);
+            let mid = range.start + slice.len() / 2;
+            boxes.push(range.start..mid);
+            boxes.push(mid..range.end);
+        }
+
+        let mut colors: Vec<[u8; 3]> = boxes
+            .iter()
+            .filter(|range| !range.is_empty())
+            .map(|range| {
+                let mut sum = [0u64; 3];
+                for rgb in &samples[range.clone()] {
+                    for (s, &c) in sum.iter_mut().zip(rgb) {
+                        *s += c as u64;
+                    }
+                }
+                let n = range.len() as u64;
+                sum.map(|s| ((s + n / 2) / n) as u8)
+            })
+            .collect();
+        // Splitting at the median can leave a dominant color (the background) in
+        // several boxes.
+        colors.sort_unstable();
+        colors.dedup();
+        if colors.is_empty() {
+            colors.push([0, 0, 0]);
+        }
+        Self {
+            colors,
+            lookup: vec![None; 1 << (3 * LOOKUP_BITS)],
+        }
+    }
+
+    /// Index of the palette entry nearest to `rgb`.
+    fn index(&mut self, rgb: [u8; 3]) -> u8 {
+        let shift = 8 - LOOKUP_BITS;
+        let key = rgb
+            .iter()
+            .fold(0, |key, &c| (key << LOOKUP_BITS) | (c >> shift) as usize);
+        if let Some(index) = self.lookup[key] {
+            return index;
+        }
+        // Match against the center of the cache cell so the cached answer suits
+        // every color in it.
+        let center = rgb.map(|c| ((c >> shift) << shift) as i32 + (1 << shift >> 1));
+        let index = self
+            .colors
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, color)| {
+                color
+                    .iter()
+                    .zip(center)
+                    .map(|(&c, m)| (c as i32 - m).pow(2))
+                    .sum::<i32>()
+            })
+            .map_or(0, |(i, _)| i as u8);
+        self.lookup[key] = Some(index);
+        index
+    }
+
+    /// Bits per index in the color table, which holds `2^bits` entries.
+    fn table_bits(&self) -> u32 {
+        self.colors
+            .len()
+            .next_power_of_two()
+            .trailing_zeros()
+            .max(1)
+    }
+}
+
+/// The channel with the widest spread of values in `colors`, and that spread.
+fn widest_channel(colors: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (lo, hi) = colors.iter().fold((u8::MAX, 0), |(lo, hi), rgb| {
+                (lo.min(rgb[channel]), hi.max(rgb[channel]))
+            });
+            (channel, hi.saturating_sub(lo))
+        })
+        .max_by_key(|&(_, extent)| extent)
+        .unwrap_or((0, 0))
+}
+
+/// Writes a looping GIF89a animation, one full-size frame at a time. Encoded here
+/// rather than with the `gif` crate, which is not among the dependencies (`image`
+/// is built with PNG only); the tests check its LZW stream against a known-good
+/// GIF's.
+pub struct GifWriter<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+    palette: Palette,
+    /// Delay after each frame, in hundredths of a second.
+    delay: u16,
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Writes the header and global palette for `width` x `height` frames shown
+    /// at `fps`, looping forever.
+    pub fn new(mut out: W, width: u32, height: u32, palette: Palette, fps: f64) -> Result<Self> {
+        let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+            bail!("{width}x{height} frames are too large for a GIF");
+        };
+        if !(fps.is_finite() && fps > 0.0) {
+            bail!("GIF fps must be a positive finite number, got {fps}");
+        }
+        // Viewers treat delays under 2/100 s as "as fast as possible" or ignore them.
+        let delay = (100.0 / fps).round().clamp(2.0, u16::MAX as f64) as u16;
+
+        let bits = palette.table_bits();
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        // Global color table present, 8-bit color resolution, table size.
+        out.write_all(&[0xf0 | (bits - 1) as u8, 0, 0])?;
+        for i in 0..1 << bits {
+            out.write_all(palette.colors.get(i).unwrap_or(&[0, 0, 0]))?;
+        }
+        // NETSCAPE2.0 application extension: loop count 0 repeats forever.
+        out.write_all(&[0x21, 0xff, 0x0b])?;
+        out.write_all(b"NETSCAPE2.0")?;
+        out.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+        Ok(Self {
+            out,
+            width,
+            height,
+            palette,
+            delay,
+        })
+    }
+
+    pub fn write_frame(&mut self, image: &RgbaImage) -> Result<()> {
+        if image.dimensions() != (self.width as u32, self.height as u32) {
+            bail!(
+                "frame is {:?}, expected {}x{}",
+                image.dimensions(),
+                self.width,
+                self.height
+            );
+        }
+        let indices: Vec<u8> = image
+            .pixels()
+            .map(|p| self.palette.index([p[0], p[1], p[2]]))
+            .collect();
+
+        // Graphic control extension: no disposal or transparency, just the delay.
+        self.out.write_all(&[0x21, 0xf9, 0x04, 0x00])?;
+        self.out.write_all(&self.delay.to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+        // Image descriptor covering the whole canvas, using the global palette.
+        self.out.write_all(&[0x2c, 0, 0, 0, 0])?;
+        self.out.write_all(&self.width.to_le_bytes())?;
+        self.out.write_all(&self.height.to_le_bytes())?;
+        self.out.write_all(&[0x00])?;
+
+        let min_code_bits = self.palette.table_bits().max(2);
+        self.out.write_all(&[min_code_bits as u8])?;
+        for block in lzw_encode(&indices, min_code_bits).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0x00])?;
+        Ok(())
+    }
+
+    /// Writes the trailer and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.out.write_all(&[0x3b])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Packs variable-width codes least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    n_bits: u32,
+}
+
+impl BitWriter {
+    fn push(&mut self, code: u16, bits: u32) {
+        self.acc |= (code as u32) << self.n_bits;
+        self.n_bits += bits;
+        while self.n_bits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.n_bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.n_bits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+/// GIF-flavored LZW: starts with a clear code, widens codes as the dictionary
+/// grows, and clears it again once 12-bit codes run out.
+fn lzw_encode(indices: &[u8], min_code_bits: u32) -> Vec<u8> {
+    let clear = 1u16 << min_code_bits;
+    let end = clear + 1;
+    let mut out = BitWriter::default();
+    let mut dict: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end + 1;
+    let mut code_bits = min_code_bits + 1;
+    out.push(clear, code_bits);
+
+    let Some((&first, rest)) = indices.split_first() else {
+        out.push(end, code_bits);
+        return out.finish();
+    };
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = dict.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        out.push(prefix, code_bits);
+        if next_code < 1 << MAX_CODE_BITS {
+            dict.insert((prefix, index), next_code);
+            next_code += 1;
+            if next_code > 1 << code_bits && code_bits < MAX_CODE_BITS {
+                code_bits += 1;
+            }
+        } else {
+            out.push(clear, code_bits);
+            dict.clear();
+            next_code = end + 1;
+            code_bits = min_code_bits + 1;
+        }
+        prefix = index as u16;
+    }
+    out.push(prefix, code_bits);
+    out.push(end, code_bits);
+    out.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plain GIF LZW decoder, written the way readers do it.
+    fn lzw_decode(data: &[u8], min_code_bits: u32) -> Vec<u8> {
+        let clear = 1usize << min_code_bits;
+        let end = clear + 1;
+        let reset = || -> Vec<Vec<u8>> {
+            (0..clear)
+                .map(|i| vec![i as u8])
+                .chain([vec![], vec![]])
+                .collect()
+        };
+        let mut table = reset();
+        let mut code_bits = min_code_bits + 1;
+        let mut prev: Option<usize> = None;
+        let mut out = Vec::new();
+        let (mut acc, mut n_bits, mut bytes) = (0u32, 0, data.iter());
+        loop {
+            while n_bits < code_bits {
+                acc |= (*bytes.next().expect("missing end code") as u32) << n_bits;
+                n_bits += 8;
+            }
+            let code = (acc & ((1 << code_bits) - 1)) as usize;
+            acc >>= code_bits;
+            n_bits -= code_bits;
+            if code == clear {
+                table = reset();
+                code_bits = min_code_bits + 1;
+                prev = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = match (table.get(code), prev) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(prev)) => {
+                    let mut entry = table[prev].clone();
+                    entry.push(table[prev][0]);
+                    entry
+                }
+                (None, None) => panic!("undefined code {code}"),
+            };
+            if let Some(prev) = prev {
+                if table.len() < 1 << MAX_CODE_BITS {
+                    let mut added = table[prev].clone();
+                    added.push(entry[0]);
+                    table.push(added);
+                    if table.len() == 1 << code_bits && code_bits < MAX_CODE_BITS {
+                        code_bits += 1;
+                    }
+                }
+            }
+            out.extend_from_slice(&entry);
+            prev = Some(code);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trips_through_dictionary_resets() {
+        // Enough varied input to fill the 12-bit dictionary several times.
+        let mut state = 1u32;
+        let indices: Vec<u8> = (0..200_000)
+            .map(|i| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                if i % 7 == 0 {
+                    (state >> 16) as u8
+                } else {
+                    (i / 50) as u8
+                }
+            })
+            .collect();
+        assert_eq!(lzw_decode(&lzw_encode(&indices, 8), 8), indices);
+
+        let small: Vec<u8> = (0..1000).map(|i| (i % 3) as u8).collect();
+        assert_eq!(lzw_decode(&lzw_encode(&small, 2), 2), small);
+        assert_eq!(lzw_decode(&lzw_encode(&[], 2), 2), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn lzw_matches_a_known_good_gif() {
+        // The image data of the 3x5 sample GIF in Wikipedia's "GIF" article, an
+        // 8-bit code size and one 11-byte sub-block, and the indices it holds.
+        let data = [
+            0x00, 0x51, 0xfc, 0x1b, 0x28, 0x70, 0xa0, 0xc1, 0x83, 0x01, 0x01,
+        ];
+        let mut indices = vec![0xff; 15];
+        (indices[0], indices[4]) = (0x28, 0x28);
+        assert_eq!(lzw_decode(&data, 8), indices);
+        assert_eq!(lzw_encode(&indices, 8), data);
+    }
+
+    #[test]
+    fn palette_keeps_distinct_colors() {
+        let mut samples = vec![[0, 0, 0]; 1000];
+        samples.extend([[255, 0, 0]; 10]);
+        samples.extend([[0, 0, 255]; 10]);
+        let mut palette = Palette::median_cut(&mut samples);
+        assert_eq!(palette.colors.len(), 3);
+        for rgb in [[0, 0, 0], [255, 0, 0], [0, 0, 255]] {
+            let index = palette.index(rgb) as usize;
+            assert_eq!(palette.colors[index], rgb);
+        }
+
+        let mut gif = GifWriter::new(Vec::new(), 2, 1, palette, 10.0).unwrap();
+        gif.write_frame(&RgbaImage::from_raw(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 255]).unwrap())
+            .unwrap();
+        assert!(gif.write_frame(&RgbaImage::new(1, 1)).is_err());
+        let bytes = gif.finish().unwrap();
+        assert!(bytes.starts_with(b"GIF89a"));
+        assert_eq!(bytes.last(), Some(&0x3b));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{evo::Rect, renderer::OverlayRect};
+
+/// Opacity of the densest cell; emptier cells fade towards transparent.
+const MAX_ALPHA: f32 = 0.6;
+
+/// Side of a `--mode heatmap` cell, in window pixels.
+pub const DENSITY_CELL_PX: u32 = 4;
+
+/// The area the heatmap covers: each axis's torus range where it has one, else
+/// the extent of `positions` along it. `None` when that extent is empty.
+pub fn bounds(positions: &[[f32; 2]], torus_ranges: [Option<[f32; 2]>; 2]) -> Option<Rect> {
+    let mut min = [f32::INFINITY; 2];
+    let mut max = [f32::NEG_INFINITY; 2];
+    for position in positions {
+        for (axis, &v) in position.iter().enumerate() {
+            if v.is_finite() {
+                min[axis] = min[axis].min(v);
+                max[axis] = max[axis].max(v);
+            }
+        }
+    }
+    for (axis, range) in torus_ranges.into_iter().enumerate() {
+        if let Some([lo, hi]) = range {
+            (min[axis], max[axis]) = (lo, hi);
+        }
+    }
+    (min[0] < max[0] && min[1] < max[1]).then_some(Rect { min, max })
+}
+
+/// Log-scaled density of `positions` over a `bins` (`[columns, rows]`) grid
+/// spanning `bounds`, row by row from `bounds.min`: each cell's count against the
+/// densest cell's, in `[0, 1]`, so structure around a dense core stays visible.
+/// Empty cells are 0. Positions outside `bounds` are skipped; those on a max edge
+/// count towards the last cell.
+///
+/// The one binning of every heatmap: `--heatmap-bins`, the heatmap level of
+/// detail, and `--mode heatmap`.
+pub fn density(positions: &[[f32; 2]], bins: [usize; 2], bounds: Rect) -> Vec<f32> {
+    let mut counts = vec![0u32; bins[0] * bins[1]];
+    let cell = |value: f32, axis: usize| {
+        let t = (value - bounds.min[axis]) / (bounds.max[axis] - bounds.min[axis]);
+        ((t * bins[axis] as f32) as usize).min(bins[axis] - 1)
+    };
+    for &[x, y] in positions {
+        if bins[0] > 0 && bins[1] > 0 && bounds.contains(x, y) {
+            counts[cell(y, 1) * bins[0] + cell(x, 0)] += 1;
+        }
+    }
+    let densest = counts.iter().copied().max().unwrap_or(0);
+    let scale = 1.0 / (densest as f32).ln_1p().max(f32::MIN_POSITIVE);
+    counts
+        .into_iter()
+        .map(|count| (count as f32).ln_1p() * scale)
+        .collect()
+}
+
+/// World-space background cells of a `bins` x `bins` [`density`] grid over
+/// `bounds`, shaded with inferno. Empty cells are left out.
+pub fn rects(positions: &[[f32; 2]], bins: usize, bounds: Rect) -> Vec<OverlayRect> {
+    let cell = [0, 1].map(|axis| (bounds.max[axis] - bounds.min[axis]) / bins as f32);
+    density(positions, [bins, bins], bounds)
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, t)| t > 0.0)
+        .map(|(i, t)| {
+            let c = colorous::INFERNO.eval_continuous(t as f64);
+            let min = [
+                bounds.min[0] + (i % bins) as f32 * cell[0],
+                bounds.min[1] + (i / bins) as f32 * cell[1],
+            ];
+            OverlayRect {
+                min_px: min,
+                max_px: [min[0] + cell[0], min[1] + cell[1]],
+                color: [
+                    c.r as f32 / 255.0,
+                    c.g as f32 / 255.0,
+                    c.b as f32 / 255.0,
+                    MAX_ALPHA * t,
+                ],
+            }
+        })
+        .collect()
+}
+
+/// `--mode heatmap` [`density`] of `positions` over what a `window`-pixel view
+/// with the camera at `camera_pos` and `zoom` shows: a grid of `DENSITY_CELL_PX`
+/// cells, returned as its `[columns, rows]` and the cells row by row from the
+/// bottom.
+pub fn screen_density(
+    positions: &[[f32; 2]],
+    camera_pos: [f32; 2],
+    zoom: f32,
+    window: [u32; 2],
+) -> ([usize; 2], Vec<f32>) {
+    let bins = window.map(|px| px.div_ceil(DENSITY_CELL_PX).max(1) as usize);
+    let half = [0, 1].map(|axis| window[axis] as f32 * 0.5 / zoom);
+    let view = Rect {
+        min: [camera_pos[0] - half[0], camera_pos[1] - half[1]],
+        max: [camera_pos[0] + half[0], camera_pos[1] + half[1]],
+    };
+    (bins, density(positions, bins, view))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn density_bins_positions_by_cell() {
+        let bounds = Rect {
+            min: [0.0, 0.0],
+            max: [4.0, 2.0],
+        };
+        // (0, 0) lands in the first cell; (3.9, 1) and (4, 2), on the max edge, in
+        // the last. Positions outside are skipped.
+        let positions = [
+            [0.0, 0.0],
+            [3.9, 1.0],
+            [4.0, 2.0],
+            [4.1, 0.0],
+            [f32::NAN, 0.0],
+        ];
+        let density = density(&positions, [4, 2], bounds);
+        assert_eq!(density[7], 1.0);
+        assert!((density[0] - 2f32.ln() / 3f32.ln()).abs() < 1e-6);
+        assert_eq!(density.iter().filter(|&&d| d > 0.0).count(), 2);
+        assert!(super::density(&positions, [0, 2], bounds).is_empty());
+    }
+
+    #[test]
+    fn screen_density_bins_what_the_camera_shows() {
+        // A 16x8 px window at zoom 2 shows world x in [-4, 4] and y in [-2, 2].
+        let positions = [[-3.9, -1.9], [-3.8, -1.8], [3.9, 1.9], [100.0, 0.0]];
+        let (bins, density) = screen_density(&positions, [0.0, 0.0], 2.0, [16, 8]);
+        assert_eq!(bins, [4, 2]);
+        // Two agents in the bottom-left cell, one in the top-right, one offscreen.
+        assert_eq!(density[0], 1.0);
+        assert!((density[7] - 2f32.ln() / 3f32.ln()).abs() < 1e-6);
+        assert_eq!(density.iter().filter(|&&d| d > 0.0).count(), 2);
+
+        let (_, empty) = screen_density(&[], [0.0, 0.0], 1.0, [16, 8]);
+        assert!(empty.iter().all(|&d| d == 0.0));
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use anyhow::Result;
+use image::{Rgba, RgbaImage};
+
+use crate::captions::{draw_text, load_font, text_width};
+use crate::renderer::{OverlayRect, Renderer};
+
+/// HUD text height in pixels.
+const TEXT_PX: f32 = 16.0;
+/// Space between the HUD contents and the edge of its backdrop.
+const PADDING_PX: u32 = 8;
+/// Gap between the HUD and the top-left corner of the window.
+const MARGIN_PX: f32 = 10.0;
+/// Size of the legend's color bar.
+const BAR_WIDTH_PX: u32 = 200;
+const BAR_HEIGHT_PX: u32 = 12;
+/// Space between the color bar and the text above and below it.
+const BAR_GAP_PX: u32 = 4;
+const BACKDROP: Rgba<u8> = Rgba([0, 0, 0, 160]);
+
+/// A color bar: what the colors show and the colors from `range[0]` to
+/// `range[1]`, one per pixel of the bar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Legend {
+    pub title: String,
+    pub range: [f32; 2],
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl Legend {
+    /// Samples `color_at` across `range` for each pixel of the bar.
+    pub fn new(title: String, range: [f32; 2], color_at: impl Fn(f32) -> [u8; 3]) -> Self {
+        let [lo, hi] = range;
+        let last = (BAR_WIDTH_PX - 1) as f32;
+        let colors = (0..BAR_WIDTH_PX)
+            .map(|x| color_at(lo + (hi - lo) * x as f32 / last))
+            .collect();
+        Self {
+            title,
+            range,
+            colors,
+        }
+    }
+}
+
+/// Frame information and the color legend in the top-left corner of the
+/// window, toggled with H. Rasterized whenever its contents or the window size
+/// change.
+///
+/// Drawn with ab_glyph into one texture, the way captions are, rather than with
+/// egui: a few lines and a color bar do not need a UI toolkit, and egui-wgpu
+/// would tie the viewer's wgpu version to egui's releases.
+pub struct Hud {
+    font: FontVec,
+    visible: bool,
+    /// Lines, legend and window size of the image the renderer holds.
+    shown: Option<(Vec<String>, Option<Legend>, [u32; 2])>,
+}
+
+impl Hud {
+    pub fn new(font_path: Option<&Path>) -> Result<Self> {
+        Ok(Self {
+            font: load_font(font_path)?,
+            visible: true,
+            shown: None,
+        })
+    }
+
+    /// Shows or hides the HUD; hidden, it is left out of renders and screenshots.
+    pub fn toggle(&mut self, renderer: &mut Renderer) {
+        self.visible = !self.visible;
+        self.shown = None;
+        if !self.visible {
+            renderer.set_hud(None);
+        }
+    }
+
+    /// Shows `lines` above `legend` from the next render on, while visible.
+    pub fn update(&mut self, lines: Vec<String>, legend: Option<Legend>, renderer: &mut Renderer) {
+        if !self.visible {
+            return;
+        }
+        let window = [renderer.config.width, renderer.config.height];
+        let shown = Some((lines, legend, window));
+        if self.shown == shown {
+            return;
+        }
+        let (lines, legend, _) = shown.as_ref().unwrap();
+        let image = self.rasterize(lines, legend.as_ref());
+        let (width, height) = image.dimensions();
+        let rect = OverlayRect {
+            min_px: [MARGIN_PX, MARGIN_PX],
+            max_px: [MARGIN_PX + width as f32, MARGIN_PX + height as f32],
+            color: [1.0; 4],
+        };
+        renderer.set_hud(Some((&image, rect)));
+        self.shown = shown;
+    }
+
+    /// `lines` left-aligned in white on a translucent backdrop, then the legend's
+    /// title, its color bar, and the ends of its range under the bar's ends.
+    fn rasterize(&self, lines: &[String], legend: Option<&Legend>) -> RgbaImage {
+        let font = self.font.as_scaled(PxScale::from(TEXT_PX));
+        let line_height = font.height() + font.line_gap();
+        let range_labels = legend.map(|legend| legend.range.map(|v| format!("{v:.3}")));
+        let mut texts: Vec<&str> = lines.iter().map(String::as_str).collect();
+        if let Some(legend) = legend {
+            texts.push(&legend.title);
+        }
+        let widest = texts
+            .iter()
+            .map(|text| text_width(&self.font, TEXT_PX, text))
+            .fold(0.0, f32::max);
+        let content_width = match legend {
+            Some(_) => (widest.ceil() as u32).max(BAR_WIDTH_PX),
+            None => widest.ceil() as u32,
+        };
+        let mut content_height = (line_height * texts.len() as f32).ceil() as u32;
+        if legend.is_some() {
+            content_height += 2 * BAR_GAP_PX + BAR_HEIGHT_PX + line_height.ceil() as u32;
+        }
+
+        let mut image = RgbaImage::from_pixel(
+            content_width + 2 * PADDING_PX,
+            content_height + 2 * PADDING_PX,
+            BACKDROP,
+        );
+        let left = PADDING_PX as f32;
+        let mut top = PADDING_PX as f32;
+        for text in &texts {
+            let baseline = top + font.ascent();
+            draw_text(&mut image, &self.font, TEXT_PX, [left, baseline], text);
+            top += line_height;
+        }
+        if let (Some(legend), Some([min, max])) = (legend, range_labels) {
+            let bar_top = top.ceil() as u32 + BAR_GAP_PX;
+            for (x, &[r, g, b]) in legend.colors.iter().enumerate() {
+                for y in bar_top..bar_top + BAR_HEIGHT_PX {
+                    image.put_pixel(PADDING_PX + x as u32, y, Rgba([r, g, b, 255]));
+                }
+            }
+            let baseline = (bar_top + BAR_HEIGHT_PX + BAR_GAP_PX) as f32 + font.ascent();
+            draw_text(&mut image, &self.font, TEXT_PX, [left, baseline], &min);
+            let right = left + BAR_WIDTH_PX as f32 - text_width(&self.font, TEXT_PX, &max);
+            draw_text(&mut image, &self.font, TEXT_PX, [right, baseline], &max);
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legend_spans_its_range_across_the_bar() {
+        let legend = Legend::new("speed".to_string(), [-1.0, 3.0], |v| {
+            [((v + 1.0) * 50.0).round() as u8, 0, 0]
+        });
+        assert_eq!(legend.colors.len(), BAR_WIDTH_PX as usize);
+        assert_eq!(legend.colors[0], [0, 0, 0]);
+        assert_eq!(legend.colors[BAR_WIDTH_PX as usize - 1], [200, 0, 0]);
+        assert!(legend.colors.windows(2).all(|w| w[0][0] <= w[1][0]));
+    }
+}
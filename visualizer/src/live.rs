@@ -0,0 +1,125 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver},
+        Mutex,
+    },
+    thread,
+};
+
+use anyhow::{bail, Context, Result};
+use candle_core::Device;
+use evolimo_simulator::recorder::RuntimeMeta;
+use evolimo_simulator::simulation::{Simulation, SimulationOptions};
+
+use crate::evo::EvoHeader;
+use crate::source::FrameSource;
+
+/// Frames the simulation may run ahead of the viewer before it waits.
+const LIVE_FRAME_QUEUE: usize = 2;
+
+/// A simulation stepping on a background thread (on the CPU) that streams its
+/// frames to the viewer through a channel instead of a file. Only the newest
+/// frame is kept, and playback always shows it: earlier frames, as a seek asks
+/// for, read as the newest.
+pub struct LiveSource {
+    header: EvoHeader,
+    frame_duration: f64,
+    frames: Mutex<Receiver<Vec<f32>>>,
+    latest: Mutex<LatestFrame>,
+}
+
+struct LatestFrame {
+    /// Frames received so far, counting the initial state.
+    count: usize,
+    data: Vec<f32>,
+}
+
+impl LiveSource {
+    pub fn spawn(def: &str, options: SimulationOptions) -> Result<Self> {
+        let mut sim = Simulation::new(def, &Device::Cpu, options)?;
+        let mut sim_header = sim.header();
+        sim_header.runtime = Some(RuntimeMeta::new(&Device::Cpu));
+        // The simulator's header serializes to the JSON a recorded file carries.
+        let header: EvoHeader = serde_json::from_value(serde_json::to_value(sim_header)?)
+            .context("failed to read the simulation header")?;
+        let frame_duration = header
+            .playback
+            .as_ref()
+            .map_or(1.0, |playback| playback.frame_duration());
+        let initial = sim.state_f32()?;
+
+        let (tx, rx) = mpsc::sync_channel(LIVE_FRAME_QUEUE);
+        thread::spawn(move || loop {
+            match sim.step().map(drop).and_then(|()| sim.state_f32()) {
+                Ok(frame) => {
+                    // The viewer hung up.
+                    if tx.send(frame).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("live simulation stopped: {e:#}");
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            header,
+            frame_duration,
+            frames: Mutex::new(rx),
+            latest: Mutex::new(LatestFrame {
+                count: 1,
+                data: initial,
+            }),
+        })
+    }
+}
+
+impl FrameSource for LiveSource {
+    fn header(&self) -> &EvoHeader {
+        &self.header
+    }
+
+    fn total_frames(&self) -> usize {
+        self.latest.lock().unwrap().count
+    }
+
+    fn frame_duration(&self) -> f64 {
+        self.frame_duration
+    }
+
+    fn sim_time_of_frame(&self, frame_index: usize) -> f64 {
+        frame_index as f64 * self.frame_duration
+    }
+
+    fn frame_at_sim_time(&self, _t: f64) -> usize {
+        self.total_frames() - 1
+    }
+
+    fn frame_position_at_sim_time(&self, t: f64) -> f64 {
+        self.frame_at_sim_time(t) as f64
+    }
+
+    fn read_frame_f32(&self, frame_index: usize, out: &mut Vec<f32>) -> Result<()> {
+        let latest = self.latest.lock().unwrap();
+        if frame_index >= latest.count {
+            bail!(
+                "live frame {frame_index} is not available (newest is {})",
+                latest.count - 1
+            );
+        }
+        out.clear();
+        out.extend_from_slice(&latest.data);
+        Ok(())
+    }
+
+    fn poll(&self) {
+        let frames = self.frames.lock().unwrap();
+        let mut latest = self.latest.lock().unwrap();
+        while let Ok(frame) = frames.try_recv() {
+            latest.count += 1;
+            latest.data = frame;
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use clap::ValueEnum;
+
+/// Above this many agents per drawn instance, `auto` stops subsampling and
+/// shades the density instead: every 16th agent still reads as the population,
+/// sparser samples mostly show noise.
+const AUTO_MAX_STRIDE: usize = 16;
+
+/// How agents are drawn, as `--mode` picks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RenderMode {
+    /// One circle per agent, styled by the mapping
+    Agents,
+    /// A colormapped density of agent positions, binned in screen space; for
+    /// populations too large for their circles to show structure
+    Heatmap,
+}
+
+/// How to draw a frame with more agents than `--max-instances`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LodStrategy {
+    /// Subsample up to 16x, and shade density beyond that
+    Auto,
+    /// Draw every Kth agent, with K just large enough to fit
+    Subsample,
+    /// Draw no agents; shade the background by their density
+    Heatmap,
+}
+
+/// Level of detail a frame is drawn at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lod {
+    /// Every `stride`th agent (1 draws them all).
+    Subsample { stride: usize },
+    /// Only the density heatmap.
+    Heatmap,
+}
+
+impl Lod {
+    /// The level of detail that keeps `n_agents` within `max_instances`.
+    pub fn choose(n_agents: usize, max_instances: Option<usize>, strategy: LodStrategy) -> Self {
+        let Some(max) = max_instances.filter(|&max| n_agents > max) else {
+            return Lod::Subsample { stride: 1 };
+        };
+        let stride = n_agents.div_ceil(max.max(1));
+        match strategy {
+            LodStrategy::Auto if stride > AUTO_MAX_STRIDE => Lod::Heatmap,
+            LodStrategy::Auto | LodStrategy::Subsample => Lod::Subsample { stride },
+            LodStrategy::Heatmap => Lod::Heatmap,
+        }
+    }
+
+    /// How many of `n_agents` are drawn at this level of detail.
+    pub fn drawn(self, n_agents: usize) -> usize {
+        match self {
+            Lod::Subsample { stride } => n_agents.div_ceil(stride),
+            Lod::Heatmap => 0,
+        }
+    }
+
+    /// Window title suffix describing the level of detail, empty at full detail.
+    pub fn caption(self) -> String {
+        match self {
+            Lod::Subsample { stride: 1 } => String::new(),
+            Lod::Subsample { stride } => format!(" | drawing every {stride}th agent"),
+            Lod::Heatmap => " | drawing density".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lod_degrades_with_agent_count() {
+        let auto = |n| Lod::choose(n, Some(1000), LodStrategy::Auto);
+        assert_eq!(auto(1000), Lod::Subsample { stride: 1 });
+        assert_eq!(auto(1001), Lod::Subsample { stride: 2 });
+        assert_eq!(auto(16_000), Lod::Subsample { stride: 16 });
+        assert_eq!(auto(16_001), Lod::Heatmap);
+        assert_eq!(
+            Lod::choose(1_000_000, Some(1000), LodStrategy::Subsample),
+            Lod::Subsample { stride: 1000 }
+        );
+        assert_eq!(
+            Lod::choose(1001, Some(1000), LodStrategy::Heatmap),
+            Lod::Heatmap
+        );
+        assert_eq!(
+            Lod::choose(1_000_000, None, LodStrategy::Heatmap),
+            Lod::Subsample { stride: 1 }
+        );
+    }
+}
@@ -1,42 +1,298 @@
+mod arrow;
+mod autorange;
+mod bookmark;
+mod camera;
+mod captions;
 mod evo;
+mod export;
+mod expr;
+mod gif;
+mod heatmap;
+mod hud;
+#[cfg(feature = "live")]
+mod live;
+mod lod;
 mod mapping;
+mod playback;
+mod prefetch;
 mod renderer;
+mod series;
+mod source;
+mod stats;
+mod timeline;
+mod trail;
 
 use std::{
-    fs,
-    path::PathBuf,
-    time::{Duration, Instant},
+    fs::{self, File},
+    io::BufWriter,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{bail, Context, Result};
+use autorange::{AutoRangeMode, AutoRanges, ChannelRanges, ObservedRange};
+use bookmark::Bookmark;
+use camera::CameraPath;
+use captions::{CaptionOverlay, Captions};
 use clap::Parser;
-use evo::EvoFile;
-use mapping::{apply_scale, clamp01, eval_source, normalize, VisualMapping};
-use renderer::{Instance, Renderer};
+use evo::{EvoFile, Frame, PlaybackMeta};
+use export::IndexRange;
+use gif::{GifWriter, Palette};
+use hud::{Hud, Legend};
+use image::RgbaImage;
+use lod::{Lod, LodStrategy, RenderMode};
+use mapping::{
+    apply_scale, clamp01, eval_source, normalize, normalize_around, ColorMapping, ColorSpec,
+    Projection, VisualMapping, VisualSource,
+};
+use playback::Playback;
+use prefetch::FramePrefetcher;
+use renderer::{Instance, OverlayRect, RenderOptions, Renderer};
+use series::EvoSeries;
+use source::FrameSource;
+use trail::Trails;
 use winit::{
-    event::{Event, MouseScrollDelta, WindowEvent},
+    event::{ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
+    keyboard::{Key, NamedKey},
     window::WindowBuilder,
 };
 
 #[derive(Debug, Parser)]
 #[command(name = "evolimo-visualizer")]
 struct Args {
-    /// Definition name (used to set defaults for input and mapping)
+    /// Definition name (used to set defaults for input and mapping; without it the
+    /// mapping follows the definition recorded in the input)
     #[arg(long)]
     def: Option<String>,
 
-    /// Path to sim_output.evo
+    /// Path to sim_output.evo, or to the `.shards.json` manifest (or directory) of
+    /// a sharded recording
     #[arg(long)]
     input: Option<PathBuf>,
 
+    /// Check the input against the body checksum recorded when it was finished,
+    /// refusing to play a file whose frames changed since
+    #[arg(long, conflicts_with = "live")]
+    verify: bool,
+
+    /// Run the simulation in this process and show its newest frame instead of
+    /// reading --input (requires the `live` feature)
+    #[arg(long, conflicts_with = "input")]
+    live: bool,
+
     /// Path to visual_mapping.json
     #[arg(long)]
     mapping: Option<PathBuf>,
 
-    /// Simulation playback FPS
-    #[arg(long, default_value_t = 60.0)]
-    sim_fps: f64,
+    /// Simulation playback FPS (defaults to the rate recorded in the header)
+    #[arg(long)]
+    sim_fps: Option<f64>,
+
+    /// MSAA sample count: 1 (off), 4, or 8
+    #[arg(long, default_value_t = 1)]
+    msaa: u32,
+
+    /// PNG sprite drawn per agent (tinted by its color) instead of a disc
+    #[arg(long)]
+    sprite: Option<PathBuf>,
+
+    /// Blend between the two bracketing recorded frames for smooth slow motion
+    #[arg(long)]
+    interpolate: bool,
+
+    /// Draw agents as discs expanded in the vertex shader, at least a pixel
+    /// across, instead of indexed quads, for very large populations. Falls back
+    /// to indexed quads when --sprite is set
+    #[arg(long)]
+    point_mode: bool,
+
+    /// Draw agents as Gaussian splats (sigma set by their radius) blended
+    /// additively, for a smooth density field instead of discrete discs
+    #[arg(long, conflicts_with_all = ["sprite", "point_mode"])]
+    splat: bool,
+
+    /// JSON camera keyframes (`{frame, camera_pos, zoom}`) to fly through during playback
+    #[arg(long)]
+    camera_path: Option<PathBuf>,
+
+    /// JSON captions (`{from_frame, to_frame, text}`, `to_frame` exclusive) shown
+    /// at the bottom of the window while playback is within their frames
+    #[arg(long)]
+    captions: Option<PathBuf>,
+
+    /// TTF/OTF font for --captions and the HUD (defaults to a common system font)
+    #[arg(long)]
+    caption_font: Option<PathBuf>,
+
+    /// Position axes shown on screen; `xz` and `yz` need a `z` position in the
+    /// mapping. Press P to cycle through them
+    #[arg(long, value_enum, default_value_t = Projection::Xy)]
+    projection: Projection,
+
+    /// Also draw agents near the edge of a toroidal position axis one period over,
+    /// so structure spanning the boundary stays visible
+    #[arg(long)]
+    wrap_render: bool,
+
+    /// Slow playback down around a frame, easing to SLOWDOWN times slower at the
+    /// frame itself and back; may be repeated. Bookmarks show as ticks on the
+    /// timeline bar
+    #[arg(long = "bookmark", value_name = "FRAME:SLOWDOWN")]
+    bookmarks: Vec<Bookmark>,
+
+    /// Draw a line behind each agent through where it was over the last K recorded
+    /// frames, fading with age; 0 disables trails
+    #[arg(long, value_name = "K", default_value_t = 0)]
+    trail_length: usize,
+
+    /// Draw agents as circles, or as a density heatmap binned at screen resolution
+    #[arg(long, value_enum, default_value_t = RenderMode::Agents)]
+    mode: RenderMode,
+
+    /// Shade the background by agent density on an N x N grid, spanning the torus
+    /// along toroidal axes and the agents' extent otherwise
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    heatmap_bins: Option<u32>,
+
+    /// Most agents drawn per frame; larger frames fall back to a coarser level of
+    /// detail chosen by --lod
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_instances: Option<u64>,
+
+    /// Level of detail for frames with more than --max-instances agents
+    #[arg(long, value_enum, default_value_t = LodStrategy::Auto, requires = "max_instances")]
+    lod: LodStrategy,
+
+    /// What mapping ranges set to "auto" span
+    #[arg(long, value_enum, default_value_t = AutoRangeMode::Frame)]
+    auto_range: AutoRangeMode,
+
+    /// Render frames --from..--to to a looping GIF at this path and exit, without
+    /// showing the window
+    #[arg(long, value_name = "OUT.gif", conflicts_with = "live")]
+    export_gif: Option<PathBuf>,
+
+    /// First frame exported by --export-gif
+    #[arg(long, default_value_t = 0, requires = "export_gif")]
+    from: usize,
+
+    /// Frame --export-gif stops before (exclusive); defaults to the end of the input
+    #[arg(long, requires = "export_gif")]
+    to: Option<usize>,
+
+    /// Frame rate of the exported GIF (defaults to the playback rate)
+    #[arg(long, requires = "export_gif")]
+    fps: Option<f64>,
+
+    /// Write the recorded values to a CSV file (`frame,agent_id,<state labels>`,
+    /// one row per agent per frame) and exit, without showing the window
+    #[arg(long, value_name = "OUT.csv", group = "frame_dump",
+          conflicts_with_all = ["live", "export_gif"])]
+    export_csv: Option<PathBuf>,
+
+    /// Write the recorded values to a NumPy array file of shape
+    /// `[frames, agents, state_dims]` (little-endian f32) and exit
+    #[arg(long, value_name = "OUT.npy", group = "frame_dump",
+          conflicts_with_all = ["live", "export_gif"])]
+    export_npy: Option<PathBuf>,
+
+    // A flag of the viewer rather than a separate evo-stats binary: decoding
+    // frames to f32 and opening shard series live here, in `FrameSource`, while
+    // the simulator's reader only copies raw frame bytes.
+    /// Print per-variable min, max, mean, standard deviation and NaN/Inf counts
+    /// over the recording as JSON and exit, without showing the window
+    #[arg(long, group = "frame_dump", conflicts_with_all = ["live", "export_gif"])]
+    stats: bool,
+
+    /// With --stats, print one JSON line of statistics per frame instead
+    #[arg(long, requires = "stats")]
+    per_frame: bool,
+
+    /// Frames --export-csv, --export-npy and --stats read, as `A..B` (B excluded;
+    /// either end may be left out)
+    #[arg(long, default_value = "..", requires = "frame_dump")]
+    frame_range: IndexRange,
+
+    /// Agents --export-csv, --export-npy and --stats read, by index, as `A..B`
+    #[arg(long, default_value = "..", requires = "frame_dump")]
+    agents: IndexRange,
+
+    /// Render every frame offscreen to PNGs in --out-dir and exit, without a
+    /// window, so it runs on machines with no display
+    #[arg(long, requires = "out_dir", conflicts_with_all = ["live", "export_gif"])]
+    headless: bool,
+
+    /// Directory --headless writes frame_00000.png, frame_00001.png, ... to
+    #[arg(long, requires = "headless")]
+    out_dir: Option<PathBuf>,
+
+    /// Width of the frames --headless renders, in pixels
+    #[arg(long, default_value_t = 1280, value_parser = clap::value_parser!(u32).range(1..))]
+    width: u32,
+
+    /// Height of the frames --headless renders, in pixels
+    #[arg(long, default_value_t = 720, value_parser = clap::value_parser!(u32).range(1..))]
+    height: u32,
+}
+
+/// Playback rate used when neither the CLI nor the header provides a usable one.
+const DEFAULT_SIM_FPS: f64 = 60.0;
+/// Sane bounds for a playback rate derived from header timing metadata.
+const DERIVED_SIM_FPS_RANGE: std::ops::RangeInclusive<f64> = 0.01..=1000.0;
+
+/// Resolves the playback rate (recorded frames per second) from `--sim-fps`,
+/// falling back to real time as recorded in the header, then to [`DEFAULT_SIM_FPS`].
+fn resolve_sim_fps(cli: Option<f64>, playback: Option<&PlaybackMeta>) -> Result<f64> {
+    if let Some(sim_fps) = cli {
+        if !(sim_fps.is_finite() && sim_fps > 0.0) {
+            bail!("--sim-fps must be a positive finite number");
+        }
+        return Ok(sim_fps);
+    }
+    let Some(playback) = playback else {
+        return Ok(DEFAULT_SIM_FPS);
+    };
+    let derived = 1.0 / playback.frame_duration();
+    if derived.is_finite() && DERIVED_SIM_FPS_RANGE.contains(&derived) {
+        Ok(derived)
+    } else {
+        eprintln!(
+            "warning: header playback rate {derived} (dt = {}, substeps = {}, save_interval = {}) is out of range; using {DEFAULT_SIM_FPS} fps",
+            playback.dt, playback.substeps, playback.save_interval
+        );
+        Ok(DEFAULT_SIM_FPS)
+    }
+}
+
+/// Moreland's cool-to-warm diverging map, sampled at every eighth.
+const COOLWARM: [(f32, [u8; 3]); 9] = [
+    (0.0, [59, 76, 192]),
+    (0.125, [98, 130, 234]),
+    (0.25, [141, 176, 254]),
+    (0.375, [184, 208, 249]),
+    (0.5, [221, 221, 221]),
+    (0.625, [245, 196, 173]),
+    (0.75, [244, 154, 123]),
+    (0.875, [222, 96, 77]),
+    (1.0, [180, 4, 38]),
+];
+
+/// Linear interpolation between the two `(t, rgb)` stops around `t`. `stops` is
+/// sorted by `t` and spans `[0, 1]`.
+fn interpolate_stops(stops: &[(f32, [u8; 3])], t: f32) -> [u8; 3] {
+    let upper = stops
+        .partition_point(|&(at, _)| at < t)
+        .clamp(1, stops.len() - 1);
+    let ((t0, c0), (t1, c1)) = (stops[upper - 1], stops[upper]);
+    let f = if t1 > t0 {
+        clamp01((t - t0) / (t1 - t0))
+    } else {
+        0.0
+    };
+    std::array::from_fn(|i| (c0[i] as f32 + f * (c1[i] as f32 - c0[i] as f32)).round() as u8)
 }
 
 fn colormap_rgb(name: &str, t01: f32) -> Result<[u8; 3]> {
@@ -47,25 +303,654 @@ fn colormap_rgb(name: &str, t01: f32) -> Result<[u8; 3]> {
         // Approximate "heat" and "cool" with available gradients.
         "heat" => colorous::INFERNO.eval_continuous(t),
         "cool" => colorous::TURBO.eval_continuous(t),
+        "grayscale" => {
+            let v = (t * 255.0).round() as u8;
+            return Ok([v, v, v]);
+        }
+        // Diverging: blue below the midpoint, red above, white at it.
+        "rdbu" => colorous::RED_BLUE.eval_continuous(1.0 - t),
+        "coolwarm" => return Ok(interpolate_stops(&COOLWARM, t as f32)),
         other => bail!("unsupported colormap: {other}"),
     };
     Ok([c.r, c.g, c.b])
 }
 
+/// Color of raw value `raw` under `color`, normalized with `range`.
+fn colormap_color(color: &ColorMapping, raw: f32, range: Option<[f32; 2]>) -> [u8; 3] {
+    let t = match color.midpoint {
+        Some(midpoint) => normalize_around(raw, range, midpoint),
+        None => normalize(raw, range),
+    };
+    match (&color.stops, &color.colormap) {
+        (Some(stops), _) => interpolate_stops(stops, t),
+        (None, Some(name)) => colormap_rgb(name, t).unwrap_or([255; 3]),
+        (None, None) => [255; 3],
+    }
+}
+
+/// The HUD color bar of `color`, titled with its source as `source` and
+/// spanning `range` (`[0, 1]` when unset).
+fn color_legend(color: &ColorMapping, source: String, range: Option<[f32; 2]>) -> Legend {
+    let name = match (&color.stops, &color.colormap) {
+        (Some(_), _) => "custom stops",
+        (None, Some(name)) => name.as_str(),
+        (None, None) => "white",
+    };
+    let range = range.unwrap_or([0.0, 1.0]);
+    Legend::new(format!("{source} ({name})"), range, |raw| {
+        colormap_color(color, raw, Some(range))
+    })
+}
+
+/// Minimum L2 change in positions (world units, over all agents) that the
+/// skip key (`N`) treats as the next interesting frame.
+const SKIP_STATIC_EPS: f32 = 1e-3;
+
+/// Describes the range a color source is normalized with (unset ranges clamp
+/// to `[0, 1]`) next to the values actually seen in the frame.
+fn color_range_caption(active: Option<[f32; 2]>, observed: ObservedRange) -> String {
+    let [lo, hi] = active.unwrap_or([0.0, 1.0]);
+    if observed.min > observed.max {
+        return format!("[{lo:.3}, {hi:.3}]");
+    }
+    format!(
+        "[{lo:.3}, {hi:.3}] (frame {:.3}..{:.3})",
+        observed.min, observed.max
+    )
+}
+
+/// Side length of a baked bivariate palette.
+const BIVARIATE_SIZE: usize = 16;
+
+/// A 2D palette baked into a `BIVARIATE_SIZE` x `BIVARIATE_SIZE` grid.
+struct BivariatePalette {
+    colors: Vec<[u8; 3]>,
+}
+
+impl BivariatePalette {
+    /// Bakes a named palette by bilinear interpolation between its corner colors.
+    /// Corners are `[low/low, high x, high y, high/high]` (Joshua Stevens' schemes).
+    fn named(name: &str) -> Result<Self> {
+        let corners: [[u8; 3]; 4] = match name {
+            "bluered" => [
+                [0xe8, 0xe8, 0xe8],
+                [0xc8, 0x5a, 0x5a],
+                [0x64, 0xac, 0xbe],
+                [0x57, 0x42, 0x49],
+            ],
+            "greenblue" => [
+                [0xe8, 0xe8, 0xe8],
+                [0x73, 0xae, 0x80],
+                [0x6c, 0x83, 0xb5],
+                [0x2a, 0x5a, 0x5b],
+            ],
+            other => bail!("unsupported bivariate palette: {other}"),
+        };
+
+        let last = (BIVARIATE_SIZE - 1) as f32;
+        let mut colors = Vec::with_capacity(BIVARIATE_SIZE * BIVARIATE_SIZE);
+        for iy in 0..BIVARIATE_SIZE {
+            let ty = iy as f32 / last;
+            for ix in 0..BIVARIATE_SIZE {
+                let tx = ix as f32 / last;
+                let mut rgb = [0u8; 3];
+                for (c, out) in rgb.iter_mut().enumerate() {
+                    let low = corners[0][c] as f32 * (1.0 - tx) + corners[1][c] as f32 * tx;
+                    let high = corners[2][c] as f32 * (1.0 - tx) + corners[3][c] as f32 * tx;
+                    *out = (low * (1.0 - ty) + high * ty).round() as u8;
+                }
+                colors.push(rgb);
+            }
+        }
+        Ok(Self { colors })
+    }
+
+    fn eval(&self, tx: f32, ty: f32) -> [u8; 3] {
+        let last = (BIVARIATE_SIZE - 1) as f32;
+        let ix = (clamp01(tx) * last).round() as usize;
+        let iy = (clamp01(ty) * last).round() as usize;
+        self.colors[iy * BIVARIATE_SIZE + ix]
+    }
+}
+
+/// Share of a torus period next to each edge whose agents `--wrap-render` also
+/// draws on the opposite side.
+const WRAP_MARGIN: f32 = 0.1;
+
+/// Offsets along one position axis at which to draw an agent at `value`: itself,
+/// plus one period over when it lies within `radius` and [`WRAP_MARGIN`] of an
+/// edge of the torus `range`. Returns the offsets and how many are used.
+///
+/// Everything is in world units. That includes `radius`: the shader scales an
+/// instance's `radius_px` by the zoom, as it does its center, so it is a world
+/// radius despite the name.
+fn wrap_offsets(value: f32, radius: f32, range: Option<[f32; 2]>) -> ([f32; 2], usize) {
+    let Some([min, max]) = range else {
+        return ([0.0; 2], 1);
+    };
+    let period = max - min;
+    let margin = radius + WRAP_MARGIN * period;
+    if value - min < margin {
+        ([0.0, period], 2)
+    } else if max - value < margin {
+        ([0.0, -period], 2)
+    } else {
+        ([0.0; 2], 1)
+    }
+}
+
+/// `value` moved by whole periods into the torus `range`, if it has one.
+fn wrap_into(value: f32, range: Option<[f32; 2]>) -> f32 {
+    match range {
+        Some([min, max]) if max > min => min + (value - min).rem_euclid(max - min),
+        _ => value,
+    }
+}
+
+/// State columns shown along screen x and y, with their torus ranges.
+struct ScreenAxes {
+    indices: [usize; 2],
+    torus_ranges: [Option<[f32; 2]>; 2],
+    /// Velocity columns along screen x and y, when the mapping draws arrows and
+    /// has the labels this projection needs.
+    velocity: Option<[usize; 2]>,
+}
+
+impl ScreenAxes {
+    /// Where `agent` (one row of state) is on screen, wrapped into the torus along
+    /// toroidal axes so agents past an edge show up on the other side.
+    fn position(&self, agent: &[f32]) -> [f32; 2] {
+        [0, 1].map(|axis| wrap_into(agent[self.indices[axis]], self.torus_ranges[axis]))
+    }
+}
+
+fn screen_axes(
+    evo: &dyn FrameSource,
+    mapping: &VisualMapping,
+    projection: Projection,
+) -> Result<ScreenAxes> {
+    let axes = mapping.position.project(projection).with_context(|| {
+        format!(
+            "the {} projection needs position.z in the mapping",
+            projection.name()
+        )
+    })?;
+    let mut indices = [0; 2];
+    for (index, (axis, label)) in indices.iter_mut().zip(axes) {
+        *index = evo
+            .state_index(label)
+            .with_context(|| format!("missing state label for position.{axis}: {label}"))?;
+    }
+    let velocity_labels = mapping
+        .velocity
+        .as_ref()
+        .and_then(|v| v.project(projection));
+    let velocity = match velocity_labels {
+        Some(labels) => {
+            let mut columns = [0; 2];
+            for (column, label) in columns.iter_mut().zip(labels) {
+                *column = evo
+                    .state_index(label)
+                    .with_context(|| format!("missing state label for velocity: {label}"))?;
+            }
+            Some(columns)
+        }
+        None => None,
+    };
+    let torus_ranges = &evo.header().config.torus_ranges;
+    let torus_range = |(axis, label): (&str, &str)| {
+        let world_range = mapping.position.world_range(axis);
+        world_range.or_else(|| torus_ranges.get(label).copied())
+    };
+    Ok(ScreenAxes {
+        indices,
+        torus_ranges: axes.map(torus_range),
+        velocity,
+    })
+}
+
+/// How agents are drawn: the visual mapping, the palette of a bivariate color
+/// mapping, and which state variables place them on screen.
+struct AgentStyle<'a> {
+    mapping: &'a VisualMapping,
+    bivariate_palette: Option<&'a BivariatePalette>,
+    axes: &'a ScreenAxes,
+}
+
+/// Fills `instances` with every `stride`th agent of `frame` as `style` draws
+/// them, normalizing `"auto"` ranges to `auto`, and returns the raw color source
+/// values seen (per bivariate axis). Instances start with one per drawn agent,
+/// in agent order; the copies `wrap_render` adds across torus edges follow them.
+fn build_instances(
+    frame: &Frame,
+    style: &AgentStyle,
+    auto: &ChannelRanges,
+    wrap_render: bool,
+    stride: usize,
+    instances: &mut Vec<Instance>,
+) -> [ObservedRange; 2] {
+    instances.clear();
+    instances.reserve(frame.n_agents().div_ceil(stride));
+    let AgentStyle {
+        mapping,
+        bivariate_palette,
+        axes,
+    } = *style;
+    let mut observed = [ObservedRange::EMPTY; 2];
+    let mut wrapped = Vec::new();
+
+    for i in (0..frame.n_agents()).step_by(stride) {
+        let [pos_x, pos_y] = axes.position(frame.agent(i));
+
+        let lookup = |label: &str| frame.get(i, label);
+
+        let mut radius_px = 2.0;
+        if let Some(size_map) = &mapping.size {
+            let raw = eval_source(&size_map.source, &lookup).unwrap_or(0.0);
+            let t = normalize(raw, auto.size.resolve(size_map.value_range));
+            let t = apply_scale(t, size_map.scale.as_deref()).unwrap_or(t);
+            radius_px = size_map.range[0] + t * (size_map.range[1] - size_map.range[0]);
+        }
+
+        let mut opacity = 1.0;
+        if let Some(op_map) = &mapping.opacity {
+            let raw = eval_source(&op_map.source, &lookup).unwrap_or(0.0);
+            let t = normalize(raw, auto.opacity.resolve(op_map.value_range));
+            opacity = op_map.range[0] + t * (op_map.range[1] - op_map.range[0]);
+            opacity = opacity.clamp(0.0, 1.0);
+        }
+
+        let mut rgb = [255u8, 255u8, 255u8];
+        let mut direct_rgb = None;
+        match &mapping.color {
+            Some(ColorSpec::Colormap(color_map)) => {
+                let raw = eval_source(&color_map.source, &lookup).unwrap_or(0.0);
+                observed[0].add(raw);
+                let range = auto.color[0].resolve(color_map.range);
+                rgb = colormap_color(color_map, raw, range);
+            }
+            Some(ColorSpec::Bivariate(bivariate)) => {
+                let [tx, ty] = [0, 1].map(|axis| {
+                    let raw = eval_source(&bivariate.sources[axis], &lookup).unwrap_or(0.0);
+                    observed[axis].add(raw);
+                    normalize(raw, auto.color[axis].resolve(bivariate.ranges[axis]))
+                });
+                if let Some(palette) = bivariate_palette {
+                    rgb = palette.eval(tx, ty);
+                }
+            }
+            Some(ColorSpec::Solid(solid)) => rgb = solid.solid.0,
+            Some(ColorSpec::DirectRgb(direct)) => {
+                direct_rgb = Some(direct.eval(lookup));
+            }
+            None => {}
+        }
+
+        let center_px = [pos_x, pos_y];
+        let [r, g, b] = direct_rgb.unwrap_or_else(|| rgb.map(|c| c as f32 / 255.0));
+        let color = [r, g, b, opacity];
+
+        let wrap_ranges = if wrap_render {
+            axes.torus_ranges
+        } else {
+            [None; 2]
+        };
+        let (dxs, nx) = wrap_offsets(pos_x, radius_px, wrap_ranges[0]);
+        let (dys, ny) = wrap_offsets(pos_y, radius_px, wrap_ranges[1]);
+        for (i, dx) in dxs[..nx].iter().enumerate() {
+            for (j, dy) in dys[..ny].iter().enumerate() {
+                let instance = Instance {
+                    center_px: [center_px[0] + dx, center_px[1] + dy],
+                    radius_px,
+                    _pad0: 0.0,
+                    color,
+                };
+                // The unshifted copy (both offsets 0) is the agent itself.
+                if i == 0 && j == 0 {
+                    instances.push(instance);
+                } else {
+                    wrapped.push(instance);
+                }
+            }
+        }
+    }
+    instances.append(&mut wrapped);
+    observed
+}
+
+/// Background cells shading the density of `frame`'s agents on screen.
+fn heatmap_rects(frame: &Frame, axes: &ScreenAxes, bins: usize) -> Vec<OverlayRect> {
+    let positions: Vec<[f32; 2]> = (0..frame.n_agents())
+        .map(|i| axes.position(frame.agent(i)))
+        .collect();
+    match heatmap::bounds(&positions, axes.torus_ranges) {
+        Some(bounds) => heatmap::rects(&positions, bins, bounds),
+        None => Vec::new(),
+    }
+}
+
+/// Camera that shows every agent of `frame`, or the whole torus along toroidal
+/// axes, in a `window`-pixel view. The default camera when there is nothing to
+/// fit.
+fn fit_camera(frame: &Frame, axes: &ScreenAxes, window: [u32; 2]) -> ([f32; 2], f32) {
+    let positions: Vec<[f32; 2]> = (0..frame.n_agents())
+        .map(|i| axes.position(frame.agent(i)))
+        .collect();
+    match heatmap::bounds(&positions, axes.torus_ranges) {
+        Some(bounds) => camera::fit(bounds.min, bounds.max, window),
+        None => ([0.0, 0.0], 1.0),
+    }
+}
+
+/// Heatmap resolution drawn in place of the agents when --heatmap-bins is unset.
+const LOD_HEATMAP_BINS: usize = 128;
+
+/// Fills `instances` and the background for `frame` at the level of detail its
+/// agent count allows, returning that level and the color source values seen.
+/// Under `--mode heatmap` only the renderer's density is filled.
+fn build_frame(
+    frame: &Frame,
+    style: &AgentStyle,
+    auto_ranges: &mut AutoRanges,
+    args: &Args,
+    renderer: &mut Renderer,
+    instances: &mut Vec<Instance>,
+) -> (Lod, [ObservedRange; 2]) {
+    let (mapping, axes) = (style.mapping, style.axes);
+    if args.mode == RenderMode::Heatmap {
+        instances.clear();
+        let positions: Vec<[f32; 2]> = (0..frame.n_agents())
+            .map(|i| axes.position(frame.agent(i)))
+            .collect();
+        renderer.set_density(&positions);
+        return (Lod::Heatmap, [ObservedRange::EMPTY; 2]);
+    }
+    let max_instances = args.max_instances.map(|n| n as usize);
+    let lod = Lod::choose(frame.n_agents(), max_instances, args.lod);
+    let heatmap_bins = args.heatmap_bins.map(|bins| bins as usize);
+    let (observed, heatmap_bins) = match lod {
+        Lod::Subsample { stride } => (
+            build_instances(
+                frame,
+                style,
+                &auto_ranges.update(frame, mapping, stride),
+                args.wrap_render,
+                stride,
+                instances,
+            ),
+            heatmap_bins,
+        ),
+        Lod::Heatmap => {
+            instances.clear();
+            (
+                [ObservedRange::EMPTY; 2],
+                Some(heatmap_bins.unwrap_or(LOD_HEATMAP_BINS)),
+            )
+        }
+    };
+    if let Some(velocity) = &mapping.velocity {
+        let arrows = match (lod, axes.velocity) {
+            (Lod::Subsample { stride }, Some(columns)) => {
+                let agents = &instances[..lod.drawn(frame.n_agents())];
+                arrow::arrows(frame, columns, velocity, stride, agents)
+            }
+            _ => Vec::new(),
+        };
+        renderer.set_arrows(&arrows);
+    }
+    match heatmap_bins {
+        Some(bins) => renderer.set_background(&heatmap_rects(frame, axes, bins)),
+        // Clear the density left from a frame drawn at the heatmap level.
+        None if max_instances.is_some() => renderer.set_background(&[]),
+        None => {}
+    }
+    (lod, observed)
+}
+
+/// Pixels sampled across all exported frames to choose the GIF palette.
+const GIF_PALETTE_SAMPLES: usize = 1 << 20;
+
+/// Renders `instances` offscreen at the window size, with the current camera and
+/// overlays, and writes them to a PNG in the working directory named after the
+/// definition, the frame, and the wall-clock time.
+fn save_screenshot(
+    renderer: &mut Renderer,
+    instances: &[Instance],
+    def_name: Option<&str>,
+    frame: usize,
+) -> Result<PathBuf> {
+    let image = renderer.capture_frame(instances)?;
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = PathBuf::from(format!(
+        "{}_frame{frame:06}_{secs}.png",
+        def_name.unwrap_or("evolimo")
+    ));
+    image
+        .save(&path)
+        .with_context(|| format!("failed to write screenshot {:?}", path))?;
+    Ok(path)
+}
+
+/// Reads frame `index` into `frame`, builds its `instances` with `build`, and
+/// renders them offscreen, moving the camera along `camera_path` if given.
+fn capture_offscreen(
+    evo: &dyn FrameSource,
+    renderer: &mut Renderer,
+    camera_path: Option<&CameraPath>,
+    index: usize,
+    frame: &mut Frame,
+    instances: &mut Vec<Instance>,
+    build: &mut impl FnMut(usize, &Frame, &mut Renderer, &mut Vec<Instance>),
+) -> Result<RgbaImage> {
+    evo.read_frame_f32(index, &mut frame.data)
+        .with_context(|| format!("failed to read frame {index}"))?;
+    if let Some(path) = camera_path {
+        let (pos, zoom) = path.sample(index as f64);
+        renderer.update_camera(pos, zoom);
+    }
+    build(index, frame, renderer, instances);
+    renderer.mark_instances_dirty();
+    renderer.capture_frame(instances)
+}
+
+/// Renders every frame offscreen to `frame_00000.png`, `frame_00001.png`, ...
+/// (numbered by recorded frame) in `out_dir`, returning how many were written.
+fn export_png_sequence(
+    evo: &dyn FrameSource,
+    renderer: &mut Renderer,
+    camera_path: Option<&CameraPath>,
+    out_dir: &Path,
+    mut build: impl FnMut(usize, &Frame, &mut Renderer, &mut Vec<Instance>),
+) -> Result<usize> {
+    fs::create_dir_all(out_dir).with_context(|| format!("failed to create {:?}", out_dir))?;
+    let mut frame = evo.empty_frame();
+    let mut instances = Vec::new();
+    let total = evo.total_frames();
+    for index in 0..total {
+        let image = capture_offscreen(
+            evo,
+            renderer,
+            camera_path,
+            index,
+            &mut frame,
+            &mut instances,
+            &mut build,
+        )?;
+        let path = out_dir.join(format!("frame_{index:05}.png"));
+        image
+            .save(&path)
+            .with_context(|| format!("failed to write {:?}", path))?;
+    }
+    Ok(total)
+}
+
+/// Renders `frames` offscreen and writes them to `out_path` as a looping GIF.
+/// Frames are rendered twice: once to pick one palette for the whole range, so
+/// colors do not flicker between frames, and once to encode them.
+fn export_gif(
+    evo: &dyn FrameSource,
+    renderer: &mut Renderer,
+    frames: Range<usize>,
+    fps: f64,
+    camera_path: Option<&CameraPath>,
+    out_path: &Path,
+    mut build: impl FnMut(usize, &Frame, &mut Renderer, &mut Vec<Instance>),
+) -> Result<()> {
+    let mut frame = evo.empty_frame();
+    let mut instances = Vec::new();
+    let mut capture = |index: usize, renderer: &mut Renderer| {
+        capture_offscreen(
+            evo,
+            renderer,
+            camera_path,
+            index,
+            &mut frame,
+            &mut instances,
+            &mut build,
+        )
+    };
+
+    let n_pixels = renderer.config.width as usize * renderer.config.height as usize;
+    let stride = (n_pixels * frames.len() / GIF_PALETTE_SAMPLES).max(1);
+    let mut samples = Vec::new();
+    for index in frames.clone() {
+        let image = capture(index, renderer)?;
+        samples.extend(image.pixels().step_by(stride).map(|p| [p[0], p[1], p[2]]));
+    }
+    let palette = Palette::median_cut(&mut samples);
+
+    let file =
+        File::create(out_path).with_context(|| format!("failed to create {:?}", out_path))?;
+    let mut gif = GifWriter::new(
+        BufWriter::new(file),
+        renderer.config.width,
+        renderer.config.height,
+        palette,
+        fps,
+    )?;
+    for index in frames {
+        gif.write_frame(&capture(index, renderer)?)?;
+    }
+    gif.finish()?;
+    Ok(())
+}
+
+/// Reports the outcome of --verify on `path`: a mismatch stops playback, a file
+/// without a checksum only gets a warning.
+fn report_verified(verified: Result<bool>, path: &Path) -> Result<()> {
+    if verified.with_context(|| format!("{:?} failed verification", path))? {
+        eprintln!("Verified {:?} against its body checksum", path);
+    } else {
+        eprintln!(
+            "warning: {:?} has no body checksum to verify (unfinished or older recording)",
+            path
+        );
+    }
+    Ok(())
+}
+
+/// Starts definition `def` on a background thread as the frame source.
+#[cfg(feature = "live")]
+fn open_live(def: &str) -> Result<Arc<dyn FrameSource>> {
+    let env_usize = |key: &str| std::env::var(key).ok().and_then(|v| v.parse().ok());
+    let options = evolimo_simulator::simulation::SimulationOptions {
+        n_agents: env_usize("EVO_N_AGENTS"),
+        hidden_len: env_usize("EVO_HIDDEN_LEN"),
+        dt: 1.0 / DEFAULT_SIM_FPS,
+        substeps: 1,
+        gene_init: None,
+        seed: None,
+    };
+    Ok(Arc::new(live::LiveSource::spawn(def, options)?))
+}
+
+#[cfg(not(feature = "live"))]
+fn open_live(_def: &str) -> Result<Arc<dyn FrameSource>> {
+    bail!("--live needs the visualizer built with `--features live`")
+}
+
 fn main() -> Result<()> {
     let args = Args::parse();
-    if !(args.sim_fps.is_finite() && args.sim_fps > 0.0) {
-        bail!("--sim-fps must be a positive finite number");
+    if ![1, 4, 8].contains(&args.msaa) {
+        bail!("--msaa must be 1, 4, or 8");
     }
 
     let def = args.def.as_deref().unwrap_or("universal_gravitation");
 
-    let input_path = args.input.unwrap_or_else(|| {
-        PathBuf::from(format!("../simulator/output/{}.evo", def))
-    });
+    let input_path = args
+        .input
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("../simulator/output/{}.evo", def)));
+
+    // A sharded recording has a manifest in place of the single file.
+    let manifest_path = input_path.with_file_name(format!(
+        "{}.shards.json",
+        input_path.file_stem().unwrap_or_default().to_string_lossy()
+    ));
+    let evo: Arc<dyn FrameSource> = if args.live {
+        open_live(def)?
+    } else if EvoSeries::is_series_path(&input_path)
+        || (!input_path.exists() && manifest_path.exists())
+    {
+        let series_path = if input_path.exists() {
+            &input_path
+        } else {
+            &manifest_path
+        };
+        let series = EvoSeries::open(series_path)?;
+        if args.verify {
+            report_verified(series.verify(), series_path)?;
+        }
+        eprintln!(
+            "Playing {} shards from {:?}",
+            series.shard_count(),
+            series_path
+        );
+        Arc::new(series)
+    } else {
+        let file = EvoFile::open(&input_path)?;
+        let integrity = file.integrity();
+        if integrity.looks_truncated() {
+            eprintln!("warning: {:?}: {integrity}", input_path);
+        }
+        if args.verify {
+            report_verified(file.verify(), &input_path)?;
+        }
+        Arc::new(file)
+    };
+    // Before anything else reaches stdout, so the JSON can be piped.
+    if args.stats {
+        let (frames, agents) = (args.frame_range, args.agents);
+        let out = std::io::stdout().lock();
+        stats::write_stats(evo.as_ref(), frames, agents, args.per_frame, out)?;
+        return Ok(());
+    }
+    if let Some(runtime) = &evo.header().runtime {
+        println!("Recorded on {runtime}");
+    }
+    if let Some(out_path) = &args.export_csv {
+        let rows = export::export_csv(evo.as_ref(), out_path, args.frame_range, args.agents)?;
+        println!("Exported {rows} rows to {:?}", out_path);
+        return Ok(());
+    }
+    if let Some(out_path) = &args.export_npy {
+        let shape = export::export_npy(evo.as_ref(), out_path, args.frame_range, args.agents)?;
+        println!("Exported an array of shape {shape:?} to {:?}", out_path);
+        return Ok(());
+    }
 
-    let mapping_path = args.mapping.unwrap_or_else(|| {
-        PathBuf::from(format!("../domain-model/_gen/{}/visual_mapping.json", def))
+    // An explicit --def wins; otherwise use the definition recorded in the file.
+    let mapping_def = match (&args.def, evo.header().def_name()) {
+        (None, Some(recorded)) => recorded,
+        _ => def,
+    };
+    let mapping_path = args.mapping.clone().unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "../domain-model/_gen/{}/visual_mapping.json",
+            mapping_def
+        ))
     });
 
     let mapping_bytes = fs::read(&mapping_path)
@@ -73,36 +958,215 @@ fn main() -> Result<()> {
     let mapping: VisualMapping =
         serde_json::from_slice(&mapping_bytes).context("failed to parse mapping JSON")?;
 
-    let evo = EvoFile::open(&input_path)?;
     let total_frames = evo.total_frames();
     if total_frames == 0 {
         bail!("no frames found in {:?}", input_path);
     }
+    let sim_fps = resolve_sim_fps(args.sim_fps, evo.header().playback.as_ref())?;
+
+    let bivariate_palette = match &mapping.color {
+        Some(ColorSpec::Bivariate(bivariate)) => Some(BivariatePalette::named(&bivariate.palette)?),
+        _ => None,
+    };
+    if let Some(ColorSpec::Colormap(color)) = &mapping.color {
+        color.validate().context("invalid color mapping")?;
+    }
+    if let Some(ColorSpec::DirectRgb(direct)) = &mapping.color {
+        for label in &direct.direct_rgb {
+            if evo.state_index(label).is_none() {
+                bail!("color.direct_rgb state {label:?} is not in the recording");
+            }
+        }
+    }
+    for source in mapping.sources() {
+        if let VisualSource::Expr(expr) = source {
+            for label in expr.labels() {
+                if evo.state_index(label).is_none() {
+                    bail!(
+                        "expression {:?} reads state {label:?}, which is not in the recording",
+                        expr.as_str()
+                    );
+                }
+            }
+        }
+    }
+
+    let mut projection = args.projection;
+    let mut axes = screen_axes(evo.as_ref(), &mapping, projection)?;
+    if args.wrap_render && axes.torus_ranges == [None, None] {
+        eprintln!("warning: --wrap-render has no effect: neither position axis is toroidal");
+    }
+
+    let sprite = match &args.sprite {
+        Some(path) => {
+            let sprite = image::open(path)
+                .with_context(|| format!("failed to load sprite: {:?}", path))?
+                .to_rgba8();
+            // A texture needs at least one texel.
+            if sprite.width() == 0 || sprite.height() == 0 {
+                bail!(
+                    "sprite {:?} is empty ({}x{})",
+                    path,
+                    sprite.width(),
+                    sprite.height()
+                );
+            }
+            Some(sprite)
+        }
+        None => None,
+    };
+
+    let camera_path = args
+        .camera_path
+        .as_deref()
+        .map(CameraPath::load)
+        .transpose()?;
+    let mut captions = match &args.captions {
+        Some(path) => Some(CaptionOverlay::new(
+            Captions::load(path)?,
+            args.caption_font.as_deref(),
+        )?),
+        None => None,
+    };
+
+    let mut trails = (args.trail_length > 0).then(|| Trails::new(args.trail_length));
+    let mut auto_ranges = AutoRanges::new(args.auto_range);
+    auto_ranges.scan(evo.as_ref(), &mapping)?;
+
+    // The camera starts out fitted to the first frame; live runs may not have one yet.
+    let mut first_frame = evo.empty_frame();
+    if evo.total_frames() > 0 {
+        evo.read_frame_f32(0, &mut first_frame.data)?;
+    }
 
-    let idx_x = evo
-        .state_index(&mapping.position.x)
-        .with_context(|| format!("missing state label for position.x: {}", mapping.position.x))?;
-    let idx_y = evo
-        .state_index(&mapping.position.y)
-        .with_context(|| format!("missing state label for position.y: {}", mapping.position.y))?;
+    let options = RenderOptions {
+        sample_count: args.msaa,
+        sprite,
+        point_mode: args.point_mode,
+        splat: args.splat,
+    };
+    if args.point_mode && options.sprite.is_some() {
+        eprintln!("--point-mode is ignored with --sprite; drawing sized sprites");
+    }
+
+    if let Some(out_dir) = &args.out_dir {
+        let mut renderer =
+            pollster::block_on(Renderer::new_offscreen(args.width, args.height, options))?;
+        let (pos, zoom) = fit_camera(&first_frame, &axes, [args.width, args.height]);
+        renderer.update_camera(pos, zoom);
+        let frames = export_png_sequence(
+            evo.as_ref(),
+            &mut renderer,
+            camera_path.as_ref(),
+            out_dir,
+            |index, frame, renderer, instances| {
+                if let Some(captions) = captions.as_mut() {
+                    captions.update(index, renderer);
+                }
+                let style = AgentStyle {
+                    mapping: &mapping,
+                    bivariate_palette: bivariate_palette.as_ref(),
+                    axes: &axes,
+                };
+                let (lod, _) =
+                    build_frame(frame, &style, &mut auto_ranges, &args, renderer, instances);
+                if let Some(trails) = trails.as_mut() {
+                    let agents = &instances[..lod.drawn(frame.n_agents())];
+                    trails.update(index, agents, axes.torus_ranges, renderer);
+                }
+            },
+        )?;
+        // Frames are rendered as fast as possible; the playback rate only matters
+        // once they are stitched together.
+        println!(
+            "Rendered {frames} frames to {:?}; at {sim_fps} fps: ffmpeg -framerate {sim_fps} -i {}/frame_%05d.png out.mp4",
+            out_dir,
+            out_dir.display()
+        );
+        return Ok(());
+    }
 
     let event_loop = EventLoop::new()?;
     let window = WindowBuilder::new()
         .with_title("Evolimo Visualizer")
+        .with_visible(args.export_gif.is_none())
         .build(&event_loop)?;
     let window: &'static winit::window::Window = Box::leak(Box::new(window));
 
-    let mut renderer = pollster::block_on(Renderer::new(window))?;
+    let mut renderer = pollster::block_on(Renderer::new(window, options))?;
+    let window_size = [renderer.config.width, renderer.config.height];
+    let (mut camera_pos, mut zoom) = fit_camera(&first_frame, &axes, window_size);
+    renderer.update_camera(camera_pos, zoom);
+    let mut hud = match Hud::new(args.caption_font.as_deref()) {
+        Ok(hud) => Some(hud),
+        Err(e) => {
+            eprintln!("no HUD: {e:#}");
+            None
+        }
+    };
 
-    let mut frame_buf: Vec<f32> = Vec::new();
+    if let Some(out_path) = &args.export_gif {
+        let to = args.to.unwrap_or(total_frames);
+        if args.from >= to || to > total_frames {
+            bail!(
+                "--from {} --to {to} is not a frame range in the {total_frames}-frame input",
+                args.from
+            );
+        }
+        let fps = args.fps.unwrap_or(sim_fps);
+        export_gif(
+            evo.as_ref(),
+            &mut renderer,
+            args.from..to,
+            fps,
+            camera_path.as_ref(),
+            out_path,
+            |index, frame, renderer, instances| {
+                if let Some(captions) = captions.as_mut() {
+                    captions.update(index, renderer);
+                }
+                let style = AgentStyle {
+                    mapping: &mapping,
+                    bivariate_palette: bivariate_palette.as_ref(),
+                    axes: &axes,
+                };
+                let (lod, _) =
+                    build_frame(frame, &style, &mut auto_ranges, &args, renderer, instances);
+                if let Some(trails) = trails.as_mut() {
+                    let agents = &instances[..lod.drawn(frame.n_agents())];
+                    trails.update(index, agents, axes.torus_ranges, renderer);
+                }
+            },
+        )?;
+        println!(
+            "Exported frames {}..{to} to {:?} at {fps} fps",
+            args.from, out_path
+        );
+        return Ok(());
+    }
+
+    let mut frame = evo.empty_frame();
+    let mut next_frame_buf: Vec<f32> = Vec::new();
     let mut instances: Vec<Instance> = Vec::new();
+    // Interpolated playback blends two frames per draw and reads them directly, and
+    // live playback has no next frame to read ahead.
+    let mut prefetch =
+        (!args.interpolate && !args.live).then(|| FramePrefetcher::new(Arc::clone(&evo)));
 
-    let n_agents = evo.header.config.n_agents;
-    let state_dims = evo.header.config.state_dims;
+    let n_agents = evo.header().config.n_agents;
 
-    let frame_dt = Duration::from_secs_f64(1.0 / args.sim_fps);
-    let start = Instant::now();
-    let mut next_tick = start;
+    let frame_dt = Duration::from_secs_f64(1.0 / sim_fps);
+    // Simulated seconds that elapse per wall-clock second of playback.
+    let time_scale = evo.frame_duration() * sim_fps;
+    let mut next_tick = Instant::now();
+    // The playhead advances by wall-clock time scaled by the bookmark speed.
+    let mut playback = Playback::new();
+    let mut last_advance = Instant::now();
+    let bookmark_frames: Vec<usize> = args.bookmarks.iter().map(|b| b.frame).collect();
+    let generation_starts = timeline::generation_starts(evo.as_ref());
+    let mut current_frame: usize = 0;
+    // Digits typed so far of a frame to jump to with Enter.
+    let mut goto_frame = String::new();
 
     let mut fps_window_start = Instant::now();
     let mut fps_frames: u32 = 0;
@@ -111,10 +1175,18 @@ fn main() -> Result<()> {
     let mut title_last_update = Instant::now();
     let title_update_dt = Duration::from_millis(250);
 
-    let mut last_drawn_frame: usize = usize::MAX;
+    let mut last_drawn_position: f64 = f64::NAN;
+    let mut color_caption = String::new();
+    let mut legend: Option<Legend> = None;
+    let mut lod_caption = String::new();
 
-    let mut camera_pos = [0.0, 0.0];
-    let mut zoom = 1.0;
+    // Window position of the mouse, whether the left button is dragging along the
+    // timeline bar or across the view, and the frame last picked by the timeline
+    // or the keyboard (applied at the next redraw).
+    let mut cursor = [0.0f64; 2];
+    let mut scrubbing = false;
+    let mut panning = false;
+    let mut seek_frame: Option<usize> = None;
 
     event_loop.run(move |event, elwt| {
         elwt.set_control_flow(ControlFlow::WaitUntil(next_tick));
@@ -133,27 +1205,246 @@ fn main() -> Result<()> {
                     renderer.resize(size.width, size.height);
                 }
                 WindowEvent::TouchpadMagnify { delta, .. } => {
-                    zoom *= 1.0 + delta as f32;
-                    zoom = zoom.max(0.01).min(1000.0);
+                    let window_size = [renderer.config.width, renderer.config.height];
+                    (camera_pos, zoom) = camera::zoom_about(
+                        cursor,
+                        window_size,
+                        camera_pos,
+                        zoom,
+                        1.0 + delta as f32,
+                    );
                     renderer.update_camera(camera_pos, zoom);
                     window.request_redraw();
                 }
                 WindowEvent::MouseWheel { delta, .. } => {
-                    match delta {
-                        MouseScrollDelta::PixelDelta(pos) => {
-                            camera_pos[0] -= pos.x as f32 / zoom;
-                            camera_pos[1] += pos.y as f32 / zoom;
-                        }
-                        MouseScrollDelta::LineDelta(x, y) => {
-                            let speed = 20.0;
-                            camera_pos[0] -= x * speed / zoom;
-                            camera_pos[1] += y * speed / zoom;
+                    // One wheel notch zooms by 10%; touchpads scroll in pixels.
+                    let notches = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 50.0,
+                    };
+                    let window_size = [renderer.config.width, renderer.config.height];
+                    (camera_pos, zoom) = camera::zoom_about(
+                        cursor,
+                        window_size,
+                        camera_pos,
+                        zoom,
+                        1.1f32.powf(notches),
+                    );
+                    renderer.update_camera(camera_pos, zoom);
+                    window.request_redraw();
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    let previous = cursor;
+                    cursor = [position.x, position.y];
+                    if scrubbing {
+                        seek_frame = Some(timeline::frame_at(
+                            cursor[0],
+                            renderer.config.width,
+                            evo.total_frames(),
+                        ));
+                        window.request_redraw();
+                    } else if panning {
+                        let delta = [cursor[0] - previous[0], cursor[1] - previous[1]];
+                        camera_pos = camera::pan(camera_pos, zoom, delta);
+                        renderer.update_camera(camera_pos, zoom);
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Left,
+                    ..
+                } => {
+                    let pressed = state == ElementState::Pressed;
+                    scrubbing = pressed && timeline::contains(cursor[1], renderer.config.height);
+                    panning = pressed && !scrubbing;
+                    if scrubbing {
+                        seek_frame = Some(timeline::frame_at(
+                            cursor[0],
+                            renderer.config.width,
+                            evo.total_frames(),
+                        ));
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            logical_key: Key::Character(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if key.eq_ignore_ascii_case("p") => {
+                    let next = mapping.position.next_projection(projection);
+                    if next == projection {
+                        eprintln!("no other projection: the mapping has no position.z");
+                    } else {
+                        match screen_axes(evo.as_ref(), &mapping, next) {
+                            Ok(next_axes) => {
+                                projection = next;
+                                axes = next_axes;
+                                if let Some(trails) = trails.as_mut() {
+                                    trails.clear();
+                                }
+                                last_drawn_position = f64::NAN;
+                                window.request_redraw();
+                            }
+                            Err(e) => eprintln!("cannot switch projection: {e:#}"),
                         }
                     }
+                }
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            logical_key: Key::Character(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if key.eq_ignore_ascii_case("h") => {
+                    if let Some(hud) = hud.as_mut() {
+                        hud.toggle(&mut renderer);
+                        // Redraw it at once rather than at the next title update.
+                        title_last_update -= title_update_dt;
+                        window.request_redraw();
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            logical_key: Key::Character(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if key.eq_ignore_ascii_case("f") => {
+                    let window_size = [renderer.config.width, renderer.config.height];
+                    (camera_pos, zoom) = fit_camera(&frame, &axes, window_size);
                     renderer.update_camera(camera_pos, zoom);
                     window.request_redraw();
                 }
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            logical_key: Key::Character(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if key.eq_ignore_ascii_case("n") => {
+                    match evo.next_changed_frame(current_frame, SKIP_STATIC_EPS) {
+                        Some(target) => {
+                            playback.sim_time +=
+                                evo.sim_time_of_frame(target) - evo.sim_time_of_frame(current_frame);
+                            if let Some(prefetch) = prefetch.as_mut() {
+                                prefetch.discard();
+                            }
+                            window.request_redraw();
+                        }
+                        None => eprintln!("no frame after {current_frame} changes positions"),
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            logical_key: Key::Character(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if key.eq_ignore_ascii_case("s") => {
+                    match save_screenshot(
+                        &mut renderer,
+                        &instances,
+                        evo.header().def_name(),
+                        current_frame,
+                    ) {
+                        Ok(path) => println!("Saved frame {current_frame} to {:?}", path),
+                        Err(e) => eprintln!("screenshot failed: {e:#}"),
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            logical_key: Key::Named(NamedKey::Space),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => {
+                    playback.paused = !playback.paused;
+                    window.request_redraw();
+                }
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            logical_key: Key::Character(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if playback.paused && matches!(key.as_str(), "." | ",") => {
+                    playback.step(evo.as_ref(), if key.as_str() == "." { 1 } else { -1 });
+                    if let Some(prefetch) = prefetch.as_mut() {
+                        prefetch.discard();
+                    }
+                    window.request_redraw();
+                }
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            logical_key: Key::Character(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if matches!(key.as_str(), "+" | "=" | "-") => {
+                    playback.scale_rate(key.as_str() != "-");
+                }
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            logical_key: Key::Character(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if key.chars().all(|c| c.is_ascii_digit()) => {
+                    goto_frame.push_str(&key);
+                }
+                WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            logical_key: Key::Named(named),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => {
+                    let last = evo.total_frames().saturating_sub(1);
+                    match named {
+                        NamedKey::ArrowRight => playback.step(evo.as_ref(), 1),
+                        NamedKey::ArrowLeft => playback.step(evo.as_ref(), -1),
+                        NamedKey::Home => seek_frame = Some(0),
+                        NamedKey::End => seek_frame = Some(last),
+                        NamedKey::Enter => match std::mem::take(&mut goto_frame).parse() {
+                            Ok(frame) => seek_frame = Some(frame),
+                            Err(_) => return,
+                        },
+                        NamedKey::Escape => {
+                            goto_frame.clear();
+                            return;
+                        }
+                        _ => return,
+                    }
+                    if let Some(prefetch) = prefetch.as_mut() {
+                        prefetch.discard();
+                    }
+                    window.request_redraw();
+                }
                 WindowEvent::RedrawRequested => {
+                    evo.poll();
                     fps_frames = fps_frames.saturating_add(1);
                     let now = Instant::now();
                     let fps_elapsed = now.duration_since(fps_window_start);
@@ -164,97 +1455,190 @@ fn main() -> Result<()> {
                         fps_window_start = now;
                     }
 
-                    let elapsed = start.elapsed().as_secs_f64();
-                    let desired = (elapsed * args.sim_fps) as usize;
-                    let frame_index = desired.min(total_frames.saturating_sub(1));
+                    let speed = bookmark::speed_at(
+                        &args.bookmarks,
+                        evo.frame_position_at_sim_time(playback.sim_time),
+                    );
+                    playback.advance(
+                        now.duration_since(last_advance).as_secs_f64(),
+                        time_scale * speed,
+                    );
+                    last_advance = now;
+                    if let Some(target) = seek_frame.take() {
+                        playback.seek(evo.as_ref(), target);
+                        if let Some(prefetch) = prefetch.as_mut() {
+                            prefetch.discard();
+                        }
+                    }
+                    let frame_index = playback.frame(evo.as_ref());
+                    current_frame = frame_index;
+                    renderer.set_overlay(&timeline::rects(
+                        renderer.config.width,
+                        renderer.config.height,
+                        frame_index,
+                        evo.total_frames(),
+                        &bookmark_frames,
+                        &generation_starts,
+                    ));
+                    if let Some(captions) = captions.as_mut() {
+                        captions.update(frame_index, &mut renderer);
+                    }
+                    // Fractional frame position, used for smooth camera paths and blending.
+                    let playhead = evo.frame_position_at_sim_time(playback.sim_time);
+                    let draw_position = if args.interpolate {
+                        playhead
+                    } else {
+                        frame_index as f64
+                    };
+
+                    if let Some(path) = &camera_path {
+                        (camera_pos, zoom) = path.sample(playhead);
+                        renderer.update_camera(camera_pos, zoom);
+                    }
 
+                    // HUD text due this tick, shown once the frame's legend is built.
+                    let mut hud_lines = None;
                     if now.duration_since(title_last_update) >= title_update_dt {
+                        // Only 3D mappings have a projection worth showing.
+                        let projection_caption = match mapping.position.z {
+                            Some(_) => format!(" | view: {}", projection.name()),
+                            None => String::new(),
+                        };
+                        let mut playback_caption = playback.caption();
+                        if !goto_frame.is_empty() {
+                            playback_caption += &format!(" | go to frame: {goto_frame}");
+                        }
                         window.set_title(&format!(
-                            "Evolimo Visualizer | agents: {} | sim frame: {}/{} | fps: {:.1}",
+                            "Evolimo Visualizer | {} | agents: {}{} | sim frame: {}/{} | t: {:.2} | fps: {:.1}{}{}{}",
+                            evo.header().def_name().unwrap_or("unknown definition"),
                             n_agents,
+                            lod_caption,
                             frame_index,
-                            total_frames.saturating_sub(1),
-                            fps_last
+                            evo.total_frames().saturating_sub(1),
+                            evo.sim_time_of_frame(frame_index),
+                            fps_last,
+                            playback_caption,
+                            projection_caption,
+                            color_caption
                         ));
+                        if hud.is_some() {
+                            let mut lines = vec![
+                                format!(
+                                    "frame {}/{}",
+                                    frame_index,
+                                    evo.total_frames().saturating_sub(1)
+                                ),
+                                format!("t {:.2}", evo.sim_time_of_frame(frame_index)),
+                            ];
+                            if let Some(generation) = evo.generation(frame_index) {
+                                lines.push(format!("generation {generation}"));
+                            }
+                            lines.push(format!("{fps_last:.1} fps"));
+                            lines.push(format!("{n_agents} agents{lod_caption}"));
+                            hud_lines = Some(lines);
+                        }
                         title_last_update = now;
                     }
 
-                    if frame_index != last_drawn_frame {
-                        if let Err(e) = evo.read_frame_f32(frame_index, &mut frame_buf) {
+                    if draw_position != last_drawn_position {
+                        let read = if args.interpolate {
+                            evo.read_frame_interpolated(
+                                draw_position,
+                                &mut frame.data,
+                                &mut next_frame_buf,
+                            )
+                        } else if prefetch
+                            .as_mut()
+                            .is_some_and(|p| p.take(frame_index, &mut frame.data))
+                        {
+                            Ok(())
+                        } else {
+                            evo.read_frame_f32(frame_index, &mut frame.data)
+                        };
+                        if let Err(e) = read {
                             eprintln!("failed to read frame {frame_index}: {e:#}");
-                            last_drawn_frame = frame_index;
+                            last_drawn_position = draw_position;
                             return;
                         }
 
+                        // Read the frame due at the next tick while this one is drawn.
+                        if let Some(prefetch) = prefetch.as_mut() {
+                            let next_index = evo.frame_at_sim_time(
+                                playback.sim_time
+                                    + frame_dt.as_secs_f64() * playback.speed(time_scale * speed),
+                            );
+                            if next_index != frame_index {
+                                prefetch.request(next_index);
+                            }
+                        }
+
                         // let w = renderer.config.width as f32;
                         // let h = renderer.config.height as f32;
                         // let cx = w * 0.5;
                         // let cy = h * 0.5;
 
-                        instances.clear();
-                        instances.reserve(n_agents);
-
-                        for i in 0..n_agents {
-                            let base = i * state_dims;
-                            let pos_x = frame_buf[base + idx_x];
-                            let pos_y = frame_buf[base + idx_y];
-
-                            let lookup = |label: &str| {
-                                evo.state_index(label)
-                                    .map(|j| frame_buf[base + j])
-                            };
-
-                            let mut radius_px = 2.0;
-                            if let Some(size_map) = &mapping.size {
-                                let raw = match eval_source(&size_map.source, &lookup) {
-                                    Ok(v) => v,
-                                    Err(_) => 0.0,
-                                };
-                                let t = normalize(raw, size_map.value_range);
-                                let t = apply_scale(t, size_map.scale.as_deref()).unwrap_or(t);
-                                radius_px = size_map.range[0]
-                                    + t * (size_map.range[1] - size_map.range[0]);
-                            }
-
-                            let mut opacity = 1.0;
-                            if let Some(op_map) = &mapping.opacity {
-                                let raw = match eval_source(&op_map.source, &lookup) {
-                                    Ok(v) => v,
-                                    Err(_) => 0.0,
-                                };
-                                let t = normalize(raw, op_map.value_range);
-                                opacity = op_map.range[0] + t * (op_map.range[1] - op_map.range[0]);
-                                opacity = opacity.max(0.0).min(1.0);
-                            }
-
-                            let mut rgb = [255u8, 255u8, 255u8];
-                            if let Some(color_map) = &mapping.color {
-                                let raw = match eval_source(&color_map.source, &lookup) {
-                                    Ok(v) => v,
-                                    Err(_) => 0.0,
-                                };
-                                let t = normalize(raw, color_map.range);
-                                rgb = colormap_rgb(&color_map.colormap, t).unwrap_or(rgb);
-                            }
+                        let style = AgentStyle {
+                            mapping: &mapping,
+                            bivariate_palette: bivariate_palette.as_ref(),
+                            axes: &axes,
+                        };
+                        let (lod, observed) = build_frame(
+                            &frame,
+                            &style,
+                            &mut auto_ranges,
+                            &args,
+                            &mut renderer,
+                            &mut instances,
+                        );
+                        lod_caption = lod.caption();
+                        if let Some(trails) = trails.as_mut() {
+                            let agents = &instances[..lod.drawn(frame.n_agents())];
+                            trails.update(frame_index, agents, axes.torus_ranges, &mut renderer);
+                        }
 
-                            // let center_px = [pos_x + cx, cy - pos_y];
-                            let center_px = [pos_x, pos_y];
-                            let color = [
-                                rgb[0] as f32 / 255.0,
-                                rgb[1] as f32 / 255.0,
-                                rgb[2] as f32 / 255.0,
-                                opacity,
-                            ];
+                        let source_caption = |source: &VisualSource| match source {
+                            VisualSource::Single(label) => evo.header().display_label(label),
+                            VisualSource::Multi { sources, .. } => sources
+                                .iter()
+                                .map(|label| evo.header().display_label(label))
+                                .collect::<Vec<_>>()
+                                .join(" + "),
+                            VisualSource::Expr(expr) => expr.as_str().to_string(),
+                        };
+                        let auto = auto_ranges.current().color;
+                        let range_caption = |axis: usize, range| {
+                            color_range_caption(auto[axis].resolve(range), observed[axis])
+                        };
+                        legend = match &mapping.color {
+                            Some(ColorSpec::Colormap(color_map)) => Some(color_legend(
+                                color_map,
+                                source_caption(&color_map.source),
+                                auto[0].resolve(color_map.range),
+                            )),
+                            _ => None,
+                        };
+                        color_caption = match &mapping.color {
+                            Some(ColorSpec::Colormap(color_map)) => format!(
+                                " | color {}: {}",
+                                source_caption(&color_map.source),
+                                range_caption(0, color_map.range)
+                            ),
+                            Some(ColorSpec::Bivariate(bivariate)) => format!(
+                                " | color x {}: {} y {}: {}",
+                                source_caption(&bivariate.sources[0]),
+                                range_caption(0, bivariate.ranges[0]),
+                                source_caption(&bivariate.sources[1]),
+                                range_caption(1, bivariate.ranges[1])
+                            ),
+                            _ => String::new(),
+                        };
 
-                            instances.push(Instance {
-                                center_px,
-                                radius_px,
-                                _pad0: 0.0,
-                                color,
-                            });
-                        }
+                        renderer.mark_instances_dirty();
+                        last_drawn_position = draw_position;
+                    }
 
-                        last_drawn_frame = frame_index;
+                    if let (Some(hud), Some(lines)) = (hud.as_mut(), hud_lines) {
+                        hud.update(lines, legend.clone(), &mut renderer);
                     }
 
                     if let Err(e) = renderer.render(&instances) {
@@ -269,3 +1653,45 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_offsets_copy_agents_near_an_edge_one_period_over() {
+        // A period of 100 with a margin of 10, plus the agent's radius of 5.
+        let range = Some([0.0, 100.0]);
+        assert_eq!(wrap_offsets(50.0, 5.0, range), ([0.0; 2], 1));
+        assert_eq!(wrap_offsets(14.0, 5.0, range), ([0.0, 100.0], 2));
+        assert_eq!(wrap_offsets(16.0, 5.0, range), ([0.0; 2], 1));
+        assert_eq!(wrap_offsets(86.0, 5.0, range), ([0.0, -100.0], 2));
+        assert_eq!(wrap_offsets(1.0, 5.0, None), ([0.0; 2], 1));
+    }
+
+    #[test]
+    fn sim_fps_falls_back_from_the_cli_to_the_header_to_the_default() -> Result<()> {
+        let playback = |dt: f64, substeps: u32, save_interval: u64| PlaybackMeta {
+            dt,
+            substeps,
+            save_interval,
+            total_frames: None,
+        };
+        // A frame every 2 steps of 2 updates of 1/120 s plays at 30 fps.
+        let recorded = playback(1.0 / 120.0, 2, 2);
+        assert_eq!(resolve_sim_fps(Some(12.5), Some(&recorded))?, 12.5);
+        assert_eq!(resolve_sim_fps(None, Some(&recorded))?, 30.0);
+        assert_eq!(resolve_sim_fps(None, None)?, DEFAULT_SIM_FPS);
+        for unusable in [
+            playback(0.0, 1, 1),
+            playback(1e-9, 1, 1),
+            playback(1000.0, 1, 1),
+        ] {
+            assert_eq!(resolve_sim_fps(None, Some(&unusable))?, DEFAULT_SIM_FPS);
+        }
+        for invalid in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(resolve_sim_fps(Some(invalid), Some(&recorded)).is_err());
+        }
+        Ok(())
+    }
+}
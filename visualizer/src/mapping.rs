@@ -1,6 +1,8 @@
 use anyhow::{bail, Result};
 use serde::Deserialize;
 
+use crate::expr::Expr;
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum BlendMode {
@@ -11,8 +13,24 @@ pub enum BlendMode {
 }
 
 #[derive(Debug, Clone, Deserialize)]
-#[serde(untagged)]
+#[serde(try_from = "RawVisualSource")]
 pub enum VisualSource {
+    Single(String),
+    Multi {
+        sources: Vec<String>,
+        weights: Option<Vec<f32>>,
+        blend: Option<BlendMode>,
+    },
+    /// `{"expr": "sqrt(vel_x^2 + vel_y^2)"}`, parsed when the mapping loads.
+    Expr(Expr),
+}
+
+/// [`VisualSource`] as written in the mapping, before expressions are parsed.
+/// Kept separate so a parse error is reported as is rather than as an untagged
+/// enum mismatch.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawVisualSource {
     Single(String),
     Multi {
         sources: Vec<String>,
@@ -21,13 +39,66 @@ pub enum VisualSource {
         #[serde(default)]
         blend: Option<BlendMode>,
     },
+    Expr {
+        expr: String,
+    },
+}
+
+impl TryFrom<RawVisualSource> for VisualSource {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawVisualSource) -> Result<Self> {
+        Ok(match raw {
+            RawVisualSource::Single(name) => VisualSource::Single(name),
+            RawVisualSource::Multi {
+                sources,
+                weights,
+                blend,
+            } => VisualSource::Multi {
+                sources,
+                weights,
+                blend,
+            },
+            RawVisualSource::Expr { expr } => VisualSource::Expr(Expr::parse(&expr)?),
+        })
+    }
+}
+
+/// The raw values a source is normalized against: fixed as `[min, max]`, or
+/// `"auto"` to follow the values actually recorded (see `--auto-range`).
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "RawValueRange")]
+pub enum ValueRange {
+    Fixed([f32; 2]),
+    Auto,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawValueRange {
+    Fixed([f32; 2]),
+    Keyword(String),
+}
+
+impl TryFrom<RawValueRange> for ValueRange {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawValueRange) -> Result<Self> {
+        match raw {
+            RawValueRange::Fixed(range) => Ok(ValueRange::Fixed(range)),
+            RawValueRange::Keyword(keyword) if keyword == "auto" => Ok(ValueRange::Auto),
+            RawValueRange::Keyword(other) => {
+                bail!("invalid range {other:?}, expected [min, max] or \"auto\"")
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct SizeMapping {
     pub source: VisualSource,
     #[serde(default, rename = "valueRange")]
-    pub value_range: Option<[f32; 2]>,
+    pub value_range: Option<ValueRange>,
     pub range: [f32; 2],
     #[serde(default)]
     pub scale: Option<String>,
@@ -36,16 +107,117 @@ pub struct SizeMapping {
 #[derive(Debug, Clone, Deserialize)]
 pub struct ColorMapping {
     pub source: VisualSource,
-    pub colormap: String,
+    /// A named colormap; may be omitted when `stops` is given.
+    #[serde(default)]
+    pub colormap: Option<String>,
+    /// A custom gradient as `[[t, [r, g, b]], ...]` with 0-255 channels,
+    /// interpolated linearly between neighboring stops. Takes precedence over
+    /// `colormap`.
+    #[serde(default)]
+    pub stops: Option<Vec<(f32, [u8; 3])>>,
+    #[serde(default)]
+    pub range: Option<ValueRange>,
+    /// Raw value placed at the middle of the colormap, e.g. 0 for a signed
+    /// quantity on a diverging map; each side of it spreads over half the map.
+    #[serde(default)]
+    pub midpoint: Option<f32>,
+}
+
+impl ColorMapping {
+    /// Checks that the mapping names a colormap or gives stops, and that the
+    /// stops are sorted by `t` and run from exactly 0 to exactly 1.
+    pub fn validate(&self) -> Result<()> {
+        let Some(stops) = &self.stops else {
+            if self.colormap.is_none() {
+                bail!("color needs a colormap or stops");
+            }
+            return Ok(());
+        };
+        if stops.len() < 2 {
+            bail!("color stops need at least 2 entries, got {}", stops.len());
+        }
+        if let Some(w) = stops.windows(2).find(|w| w[1].0 < w[0].0) {
+            let (t0, t1) = (w[0].0, w[1].0);
+            bail!("color stops are not sorted by t: {t0} before {t1}");
+        }
+        let (first, last) = (stops[0].0, stops[stops.len() - 1].0);
+        if first != 0.0 || last != 1.0 {
+            bail!("color stops must span t = 0 to t = 1, got {first} to {last}");
+        }
+        Ok(())
+    }
+}
+
+/// Two-variable color mapping: `sources[0]` drives the palette's x axis and
+/// `sources[1]` its y axis.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BivariateMapping {
+    pub sources: [VisualSource; 2],
+    pub palette: String,
     #[serde(default)]
-    pub range: Option<[f32; 2]>,
+    pub ranges: [Option<ValueRange>; 2],
+}
+
+/// An sRGB color written as `"#rrggbb"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct HexColor(pub [u8; 3]);
+
+impl TryFrom<String> for HexColor {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        let hex = s.strip_prefix('#').unwrap_or(&s);
+        if hex.len() != 6 || !hex.is_ascii() {
+            bail!("invalid color {s:?}, expected \"#rrggbb\"");
+        }
+        let mut rgb = [0u8; 3];
+        for (i, c) in rgb.iter_mut().enumerate() {
+            *c = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+                .map_err(|_| anyhow::anyhow!("invalid color {s:?}, expected \"#rrggbb\""))?;
+        }
+        Ok(Self(rgb))
+    }
+}
+
+/// A constant color for every agent, bypassing any colormap.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SolidColor {
+    pub solid: HexColor,
+}
+
+/// Color read straight from three state labels holding r, g and b in `[0, 1]`,
+/// e.g. computed by the dynamics. Each channel is clamped; no colormap or
+/// normalization applies.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DirectRgb {
+    pub direct_rgb: [String; 3],
+}
+
+impl DirectRgb {
+    /// The agent's color, with missing or non-finite channels as 0.
+    pub fn eval(&self, lookup: impl Fn(&str) -> Option<f32>) -> [f32; 3] {
+        self.direct_rgb
+            .each_ref()
+            .map(|label| lookup(label).filter(|v| v.is_finite()).map_or(0.0, clamp01))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ColorSpec {
+    // Tried first so that `solid` wins even if colormap fields are also present.
+    Solid(SolidColor),
+    DirectRgb(DirectRgb),
+    Bivariate(BivariateMapping),
+    Colormap(ColorMapping),
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct OpacityMapping {
     pub source: VisualSource,
     #[serde(default, rename = "valueRange")]
-    pub value_range: Option<[f32; 2]>,
+    pub value_range: Option<ValueRange>,
     pub range: [f32; 2],
 }
 
@@ -55,22 +227,157 @@ pub struct VisualMapping {
     #[serde(default)]
     pub size: Option<SizeMapping>,
     #[serde(default)]
-    pub color: Option<ColorMapping>,
+    pub color: Option<ColorSpec>,
     #[serde(default)]
     pub opacity: Option<OpacityMapping>,
+    #[serde(default)]
+    pub velocity: Option<VelocityMapping>,
+}
+
+impl VisualMapping {
+    /// Every source the size, color and opacity mappings read.
+    pub fn sources(&self) -> Vec<&VisualSource> {
+        let mut sources = Vec::new();
+        sources.extend(self.size.as_ref().map(|size| &size.source));
+        match &self.color {
+            Some(ColorSpec::Colormap(color)) => sources.push(&color.source),
+            Some(ColorSpec::Bivariate(bivariate)) => sources.extend(&bivariate.sources),
+            _ => {}
+        }
+        sources.extend(self.opacity.as_ref().map(|opacity| &opacity.source));
+        sources
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct PositionMapping {
     pub x: String,
     pub y: String,
+    /// Third position axis of 3D states, shown by the `xz` and `yz` projections.
+    #[serde(default)]
+    pub z: Option<String>,
+    /// `[width, height]` of a toroidal world centered on the origin, for
+    /// recordings whose header has no torus ranges for `x` and `y`; overrides
+    /// the header where it does.
+    #[serde(default, rename = "worldSize")]
+    pub world_size: Option<[f32; 2]>,
+}
+
+/// Which two position axes drive screen x and y.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Projection {
+    #[default]
+    Xy,
+    Xz,
+    Yz,
+}
+
+impl Projection {
+    const ALL: [Projection; 3] = [Projection::Xy, Projection::Xz, Projection::Yz];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Projection::Xy => "xy",
+            Projection::Xz => "xz",
+            Projection::Yz => "yz",
+        }
+    }
+}
+
+impl PositionMapping {
+    /// `(axis, label)` shown along screen x and y under `projection`, or `None`
+    /// if it needs a `z` axis the mapping does not have.
+    pub fn project(&self, projection: Projection) -> Option<[(&'static str, &str); 2]> {
+        let x = ("x", self.x.as_str());
+        let y = ("y", self.y.as_str());
+        let z = || self.z.as_deref().map(|z| ("z", z));
+        Some(match projection {
+            Projection::Xy => [x, y],
+            Projection::Xz => [x, z()?],
+            Projection::Yz => [y, z()?],
+        })
+    }
+
+    /// The `[min, max)` torus range `world_size` gives position `axis`, if any.
+    pub fn world_range(&self, axis: &str) -> Option<[f32; 2]> {
+        let [width, height] = self.world_size?;
+        let half = match axis {
+            "x" => width * 0.5,
+            "y" => height * 0.5,
+            _ => return None,
+        };
+        Some([-half, half])
+    }
+
+    /// The projection after `current` that this mapping can show, wrapping
+    /// around; `current` itself if it is the only one.
+    pub fn next_projection(&self, current: Projection) -> Projection {
+        // Variants are declared in `ALL` order.
+        let start = current as usize;
+        (1..=Projection::ALL.len())
+            .map(|offset| Projection::ALL[(start + offset) % Projection::ALL.len()])
+            .find(|&p| self.project(p).is_some())
+            .unwrap_or(current)
+    }
+}
+
+/// Velocity arrows: a line from each agent along the velocity in the `x` and `y`
+/// (and, for the 3D projections, `z`) state labels, `scale` world units long per
+/// unit of speed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct VelocityMapping {
+    pub x: String,
+    pub y: String,
+    #[serde(default)]
+    pub z: Option<String>,
+    #[serde(default = "default_velocity_scale")]
+    pub scale: f32,
+    /// Longest arrow in world units; faster agents are drawn at this length.
+    #[serde(default = "default_max_arrow_length", rename = "maxLength")]
+    pub max_length: f32,
+    /// Agents at or below this speed get no arrow.
+    #[serde(default = "default_min_speed", rename = "minSpeed")]
+    pub min_speed: f32,
+}
+
+fn default_velocity_scale() -> f32 {
+    1.0
+}
+
+fn default_max_arrow_length() -> f32 {
+    20.0
+}
+
+fn default_min_speed() -> f32 {
+    1e-6
+}
+
+impl VelocityMapping {
+    /// The velocity labels along screen x and y under `projection`, or `None` if
+    /// it needs a `z` label the mapping does not have.
+    pub fn project(&self, projection: Projection) -> Option<[&str; 2]> {
+        let z = || self.z.as_deref();
+        Some(match projection {
+            Projection::Xy => [&self.x, &self.y],
+            Projection::Xz => [&self.x, z()?],
+            Projection::Yz => [&self.y, z()?],
+        })
+    }
 }
 
 pub fn clamp01(v: f32) -> f32 {
-    v.max(0.0).min(1.0)
+    if v.is_nan() {
+        return 0.0;
+    }
+    v.clamp(0.0, 1.0)
 }
 
+/// Maps `v` into `[0, 1]`. Non-finite values (e.g. from a diverged run) map to
+/// 0 rather than to either end of the range.
 pub fn normalize(v: f32, range: Option<[f32; 2]>) -> f32 {
+    if !v.is_finite() {
+        return 0.0;
+    }
     let Some([min, max]) = range else {
         return clamp01(v);
     };
@@ -80,6 +387,29 @@ pub fn normalize(v: f32, range: Option<[f32; 2]>) -> f32 {
     clamp01((v - min) / (max - min))
 }
 
+/// Like [`normalize`], but maps `[min, midpoint]` to `[0, 0.5]` and
+/// `[midpoint, max]` to `[0.5, 1]`, so `midpoint` lands on the center of a
+/// diverging colormap even when the range is lopsided around it.
+pub fn normalize_around(v: f32, range: Option<[f32; 2]>, midpoint: f32) -> f32 {
+    if !v.is_finite() {
+        return 0.0;
+    }
+    let [min, max] = range.unwrap_or([0.0, 1.0]);
+    if v < midpoint {
+        if midpoint > min {
+            0.5 * clamp01((v - min) / (midpoint - min))
+        } else {
+            0.0
+        }
+    } else if max > midpoint {
+        0.5 + 0.5 * clamp01((v - midpoint) / (max - midpoint))
+    } else if v > midpoint {
+        1.0
+    } else {
+        0.5
+    }
+}
+
 pub fn apply_scale(mut t: f32, scale: Option<&str>) -> Result<f32> {
     t = clamp01(t);
     let Some(scale) = scale else {
@@ -97,12 +427,10 @@ pub fn apply_scale(mut t: f32, scale: Option<&str>) -> Result<f32> {
     }
 }
 
-pub fn eval_source(
-    source: &VisualSource,
-    lookup: &impl Fn(&str) -> Option<f32>,
-) -> Result<f32> {
+pub fn eval_source(source: &VisualSource, lookup: &impl Fn(&str) -> Option<f32>) -> Result<f32> {
     match source {
         VisualSource::Single(name) => Ok(lookup(name).unwrap_or(0.0)),
+        VisualSource::Expr(expr) => expr.eval(lookup),
         VisualSource::Multi {
             sources,
             weights,
@@ -111,16 +439,11 @@ pub fn eval_source(
             if sources.is_empty() {
                 return Ok(0.0);
             }
-            let vals: Vec<f32> = sources
-                .iter()
-                .map(|s| lookup(s).unwrap_or(0.0))
-                .collect();
+            let vals: Vec<f32> = sources.iter().map(|s| lookup(s).unwrap_or(0.0)).collect();
 
             let blend = blend.clone().unwrap_or(BlendMode::Average);
             match blend {
-                BlendMode::Max => Ok(vals
-                    .into_iter()
-                    .fold(f32::NEG_INFINITY, |a, b| a.max(b))),
+                BlendMode::Max => Ok(vals.into_iter().fold(f32::NEG_INFINITY, |a, b| a.max(b))),
                 BlendMode::Min => Ok(vals.into_iter().fold(f32::INFINITY, |a, b| a.min(b))),
                 BlendMode::Add | BlendMode::Average => {
                     let n = vals.len();
@@ -130,7 +453,7 @@ pub fn eval_source(
                     };
                     let mut sum = 0.0;
                     let mut wsum = 0.0;
-                    for (v, wi) in vals.into_iter().zip(w.into_iter()) {
+                    for (v, wi) in vals.into_iter().zip(w) {
                         sum += v * wi;
                         wsum += wi;
                     }
@@ -148,3 +471,183 @@ pub fn eval_source(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_accepts_colormap_and_bivariate_forms() {
+        let single: VisualMapping = serde_json::from_str(
+            r#"{"position":{"x":"pos_x","y":"pos_y"},
+                "color":{"source":"size","colormap":"viridis","range":[0,50]}}"#,
+        )
+        .unwrap();
+        assert!(matches!(single.color, Some(ColorSpec::Colormap(_))));
+
+        let stops = |stops: &str| -> Result<()> {
+            let mapping: VisualMapping = serde_json::from_str(&format!(
+                r#"{{"position":{{"x":"pos_x","y":"pos_y"}},"color":{{"source":"size",{stops}}}}}"#
+            ))?;
+            let Some(ColorSpec::Colormap(color)) = mapping.color else {
+                panic!("expected colormap color mapping");
+            };
+            color.validate()
+        };
+        stops(r#""stops":[[0,[0,0,0]],[0.3,[255,0,0]],[1,[255,255,255]]]"#).unwrap();
+        stops(r#""colormap":"viridis","stops":[[0,[0,0,0]],[1,[9,9,9]]]"#).unwrap();
+        let err = stops(r#""stops":[[0,[0,0,0]],[0.8,[1,1,1]],[0.5,[2,2,2]],[1,[3,3,3]]]"#);
+        assert!(err.unwrap_err().to_string().contains("not sorted"));
+        let err = stops(r#""stops":[[0,[0,0,0]],[0.9,[1,1,1]]]"#);
+        assert!(err.unwrap_err().to_string().contains("t = 0 to t = 1"));
+        assert!(stops(r#""stops":[[0,[0,0,0]]]"#).is_err());
+        assert!(stops(r#""range":[0,1]"#).is_err());
+
+        let bivariate: VisualMapping = serde_json::from_str(
+            r#"{"position":{"x":"pos_x","y":"pos_y"},
+                "color":{"sources":["size","vel_x"],"palette":"bluered","ranges":[[0,10],null]}}"#,
+        )
+        .unwrap();
+        let Some(ColorSpec::Bivariate(b)) = bivariate.color else {
+            panic!("expected bivariate color mapping");
+        };
+        assert_eq!(b.palette, "bluered");
+        assert_eq!(b.ranges, [Some(ValueRange::Fixed([0.0, 10.0])), None]);
+
+        let auto: VisualMapping = serde_json::from_str(
+            r#"{"position":{"x":"pos_x","y":"pos_y"},
+                "color":{"source":"energy","colormap":"viridis","range":"auto"}}"#,
+        )
+        .unwrap();
+        let Some(ColorSpec::Colormap(c)) = auto.color else {
+            panic!("expected colormap color mapping");
+        };
+        assert_eq!(c.range, Some(ValueRange::Auto));
+        assert!(serde_json::from_str::<ValueRange>(r#""automatic""#).is_err());
+
+        let solid: VisualMapping = serde_json::from_str(
+            r##"{"position":{"x":"pos_x","y":"pos_y"},"color":{"solid":"#00FfC0"}}"##,
+        )
+        .unwrap();
+        let Some(ColorSpec::Solid(c)) = solid.color else {
+            panic!("expected solid color");
+        };
+        assert_eq!(c.solid, HexColor([0x00, 0xff, 0xc0]));
+        assert!(serde_json::from_str::<HexColor>(r##""#12345""##).is_err());
+        assert!(serde_json::from_str::<HexColor>(r##""#12345g""##).is_err());
+
+        let direct: VisualMapping = serde_json::from_str(
+            r#"{"position":{"x":"pos_x","y":"pos_y"},"color":{"direct_rgb":["r","g","b"]}}"#,
+        )
+        .unwrap();
+        let Some(ColorSpec::DirectRgb(direct)) = direct.color else {
+            panic!("expected direct rgb color");
+        };
+        let lookup = |label: &str| match label {
+            "r" => Some(0.25),
+            "g" => Some(1.5),
+            _ => Some(f32::NAN),
+        };
+        assert_eq!(direct.eval(lookup), [0.25, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn expression_sources_parse_when_the_mapping_loads() {
+        let mapping: VisualMapping = serde_json::from_str(
+            r#"{"position":{"x":"pos_x","y":"pos_y"},
+                "size":{"source":{"expr":"sqrt(vel_x^2 + vel_y^2)"},"range":[1,5]}}"#,
+        )
+        .unwrap();
+        let lookup = |label: &str| match label {
+            "vel_x" => Some(-3.0),
+            "vel_y" => Some(4.0),
+            _ => None,
+        };
+        assert_eq!(eval_source(mapping.sources()[0], &lookup).unwrap(), 5.0);
+
+        let err = serde_json::from_str::<VisualMapping>(
+            r#"{"position":{"x":"pos_x","y":"pos_y"},
+                "size":{"source":{"expr":"sqrt(vel_x^2 +"},"range":[1,5]}}"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("unexpected end at column 15"), "{err}");
+    }
+
+    #[test]
+    fn projections_pick_screen_axes() {
+        let flat: PositionMapping = serde_json::from_str(r#"{"x":"pos_x","y":"pos_y"}"#).unwrap();
+        assert_eq!(
+            flat.project(Projection::Xy),
+            Some([("x", "pos_x"), ("y", "pos_y")])
+        );
+        assert_eq!(flat.project(Projection::Xz), None);
+        assert_eq!(flat.next_projection(Projection::Xy), Projection::Xy);
+
+        let solid: PositionMapping =
+            serde_json::from_str(r#"{"x":"pos_x","y":"pos_y","z":"pos_z"}"#).unwrap();
+        assert_eq!(
+            solid.project(Projection::Yz),
+            Some([("y", "pos_y"), ("z", "pos_z")])
+        );
+        assert_eq!(solid.next_projection(Projection::Xy), Projection::Xz);
+        assert_eq!(solid.next_projection(Projection::Yz), Projection::Xy);
+        assert_eq!(solid.world_range("x"), None);
+
+        let world: PositionMapping =
+            serde_json::from_str(r#"{"x":"pos_x","y":"pos_y","worldSize":[20,10]}"#).unwrap();
+        assert_eq!(world.world_range("x"), Some([-10.0, 10.0]));
+        assert_eq!(world.world_range("y"), Some([-5.0, 5.0]));
+        assert_eq!(world.world_range("z"), None);
+    }
+
+    #[test]
+    fn non_finite_values_map_to_zero() {
+        let lookup = |name: &str| match name {
+            "nan" => Some(f32::NAN),
+            "inf" => Some(f32::INFINITY),
+            "ninf" => Some(f32::NEG_INFINITY),
+            _ => Some(1.0),
+        };
+        for name in ["nan", "inf", "ninf"] {
+            let raw = eval_source(&VisualSource::Single(name.to_string()), &lookup).unwrap();
+            for range in [None, Some([0.0, 10.0]), Some([-10.0, 0.0])] {
+                let t = normalize(raw, range);
+                assert_eq!(t, 0.0, "{name} with {range:?}");
+                for scale in [None, Some("linear"), Some("sqrt"), Some("log")] {
+                    assert_eq!(apply_scale(t, scale).unwrap(), 0.0);
+                }
+            }
+        }
+
+        // A NaN among blended sources poisons the blend, which then maps to 0.
+        let blended = VisualSource::Multi {
+            sources: vec!["finite".to_string(), "nan".to_string()],
+            weights: None,
+            blend: None,
+        };
+        assert_eq!(
+            normalize(eval_source(&blended, &lookup).unwrap(), Some([0.0, 2.0])),
+            0.0
+        );
+
+        assert_eq!(clamp01(f32::NAN), 0.0);
+        assert_eq!(apply_scale(f32::NAN, Some("log")).unwrap(), 0.0);
+        assert_eq!(normalize(5.0, Some([0.0, 10.0])), 0.5);
+        assert_eq!(normalize_around(f32::NAN, Some([-1.0, 1.0]), 0.0), 0.0);
+    }
+
+    #[test]
+    fn midpoint_splits_the_range_into_halves() {
+        let range = Some([-2.0, 8.0]);
+        assert_eq!(normalize_around(0.0, range, 0.0), 0.5);
+        assert_eq!(normalize_around(-1.0, range, 0.0), 0.25);
+        assert_eq!(normalize_around(4.0, range, 0.0), 0.75);
+        assert_eq!(normalize_around(-5.0, range, 0.0), 0.0);
+        assert_eq!(normalize_around(20.0, range, 0.0), 1.0);
+        // A midpoint at an end of the range leaves that half empty.
+        assert_eq!(normalize_around(1.0, Some([1.0, 3.0]), 1.0), 0.5);
+        assert_eq!(normalize_around(2.0, Some([1.0, 3.0]), 1.0), 0.75);
+        assert_eq!(normalize_around(0.0, Some([1.0, 3.0]), 1.0), 0.0);
+    }
+}
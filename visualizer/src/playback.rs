@@ -0,0 +1,126 @@
+use std::ops::RangeInclusive;
+
+use crate::source::FrameSource;
+
+/// Bounds for the playback rate multiplier that `+`/`-` double and halve.
+const RATE_RANGE: RangeInclusive<f64> = (1.0 / 64.0)..=64.0;
+
+/// The playhead of interactive playback: where it is, how fast it moves, and
+/// whether it is held.
+///
+/// The position is kept in simulated seconds rather than a frame index, so
+/// playback advances smoothly through frames recorded at uneven sim steps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Playback {
+    /// Simulated seconds since the first recorded frame.
+    pub sim_time: f64,
+    /// Multiplier on top of `--sim-fps` and bookmark slowdowns.
+    pub rate: f64,
+    pub paused: bool,
+}
+
+impl Playback {
+    pub fn new() -> Self {
+        Self {
+            sim_time: 0.0,
+            rate: 1.0,
+            paused: false,
+        }
+    }
+
+    /// Moves the playhead by `wall_seconds` of playback at `time_scale` simulated
+    /// seconds per wall-clock second, times [`Self::rate`]. Holds it while paused.
+    pub fn advance(&mut self, wall_seconds: f64, time_scale: f64) {
+        self.sim_time += wall_seconds * self.speed(time_scale);
+    }
+
+    /// Simulated seconds per wall-clock second, 0 while paused.
+    pub fn speed(&self, time_scale: f64) -> f64 {
+        if self.paused {
+            0.0
+        } else {
+            time_scale * self.rate
+        }
+    }
+
+    /// Jumps to the start of `frame`, clamped to the frames `source` holds.
+    pub fn seek(&mut self, source: &dyn FrameSource, frame: usize) {
+        let last = source.total_frames().saturating_sub(1);
+        self.sim_time = source.sim_time_of_frame(frame.min(last));
+    }
+
+    /// Jumps `delta` frames from the current one, clamped like [`Self::seek`].
+    pub fn step(&mut self, source: &dyn FrameSource, delta: isize) {
+        let frame = self.frame(source).saturating_add_signed(delta);
+        self.seek(source, frame);
+    }
+
+    /// The recorded frame the playhead is on.
+    pub fn frame(&self, source: &dyn FrameSource) -> usize {
+        source.frame_at_sim_time(self.sim_time)
+    }
+
+    /// Doubles the rate when `faster`, halves it otherwise.
+    pub fn scale_rate(&mut self, faster: bool) {
+        let rate = if faster {
+            self.rate * 2.0
+        } else {
+            self.rate / 2.0
+        };
+        self.rate = rate.clamp(*RATE_RANGE.start(), *RATE_RANGE.end());
+    }
+
+    /// Window title suffix for a held or rescaled playhead, empty otherwise.
+    pub fn caption(&self) -> String {
+        if self.paused {
+            " | paused".to_string()
+        } else if self.rate != 1.0 {
+            format!(" | speed: {}x", self.rate)
+        } else {
+            String::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evo::{tests::write_test_file, EvoFile};
+
+    #[test]
+    fn seeks_and_steps_stay_within_the_recording() {
+        let path = write_test_file(
+            "playback_seek_test.evo",
+            r#","playback":{"dt":0.5,"substeps":1,"save_interval":1,"total_frames":10}"#,
+            10,
+        );
+        let evo = EvoFile::open(&path).unwrap();
+        let mut playback = Playback::new();
+
+        playback.advance(1.0, 1.0);
+        assert_eq!(playback.frame(&evo), 2);
+        playback.paused = true;
+        playback.advance(1.0, 1.0);
+        assert_eq!(playback.frame(&evo), 2);
+
+        playback.step(&evo, 3);
+        assert_eq!(playback.frame(&evo), 5);
+        playback.step(&evo, -10);
+        assert_eq!(playback.frame(&evo), 0);
+        playback.seek(&evo, 100);
+        assert_eq!(playback.frame(&evo), 9);
+        playback.step(&evo, 1);
+        assert_eq!(playback.frame(&evo), 9);
+
+        playback.paused = false;
+        playback.scale_rate(false);
+        playback.advance(2.0, 1.0);
+        assert_eq!(playback.sim_time, 4.5 + 1.0);
+        for _ in 0..20 {
+            playback.scale_rate(true);
+        }
+        assert_eq!(playback.rate, 64.0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+use anyhow::Result;
+
+use crate::source::FrameSource;
+
+/// Reads the frame playback is expected to show next on a background thread,
+/// so the mmap read and decode overlap with rendering the current one.
+///
+/// At most one read is in flight. A result for any other frame (e.g. after a
+/// scrub) is dropped and callers fall back to a synchronous read.
+pub struct FramePrefetcher {
+    requests: Sender<(usize, Vec<f32>)>,
+    results: Receiver<(usize, Result<Vec<f32>>)>,
+    in_flight: Option<usize>,
+    spare: Vec<f32>,
+}
+
+impl FramePrefetcher {
+    pub fn new(evo: Arc<dyn FrameSource>) -> Self {
+        let (requests, request_rx) = mpsc::channel::<(usize, Vec<f32>)>();
+        let (result_tx, results) = mpsc::channel();
+        thread::spawn(move || {
+            for (frame_index, mut buf) in request_rx {
+                let result = evo.read_frame_f32(frame_index, &mut buf).map(|()| buf);
+                if result_tx.send((frame_index, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            requests,
+            results,
+            in_flight: None,
+            spare: Vec::new(),
+        }
+    }
+
+    /// Starts reading `frame_index` unless a read is already in flight.
+    pub fn request(&mut self, frame_index: usize) {
+        if self.in_flight.is_some() {
+            return;
+        }
+        let buf = std::mem::take(&mut self.spare);
+        if self.requests.send((frame_index, buf)).is_ok() {
+            self.in_flight = Some(frame_index);
+        }
+    }
+
+    /// Swaps the prefetched `frame_index` into `out`, waiting for the read to
+    /// finish if needed. Returns `false` if that frame was not prefetched or
+    /// its read failed, leaving `out` untouched.
+    pub fn take(&mut self, frame_index: usize, out: &mut Vec<f32>) -> bool {
+        if self.in_flight != Some(frame_index) {
+            self.discard();
+            return false;
+        }
+        self.in_flight = None;
+        match self.results.recv() {
+            Ok((_, Ok(mut buf))) => {
+                std::mem::swap(out, &mut buf);
+                self.spare = buf;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Drops the in-flight read, e.g. after the playhead jumps. Does not wait:
+    /// an unfinished read keeps its slot until its result is collected here.
+    pub fn discard(&mut self) {
+        if self.in_flight.is_none() {
+            return;
+        }
+        match self.results.try_recv() {
+            Ok(_) | Err(TryRecvError::Disconnected) => self.in_flight = None,
+            Err(TryRecvError::Empty) => {}
+        }
+    }
+}
@@ -2,6 +2,8 @@ use anyhow::Result;
 use bytemuck::{Pod, Zeroable};
 use wgpu::util::DeviceExt;
 
+use crate::heatmap;
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
 pub struct Uniforms {
@@ -64,51 +66,132 @@ impl Instance {
     }
 }
 
+/// A rectangle drawn over the agents in screen space, e.g. part of the timeline
+/// bar, or under them in world space (see [`Renderer::set_background`]). Trails
+/// and velocity arrows reuse it as a line from `min_px` to `max_px` (see
+/// [`Renderer::set_trails`] and [`Renderer::set_arrows`]).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct OverlayRect {
+    /// Top-left corner in window pixels (y down); for the background, a corner
+    /// in world units.
+    pub min_px: [f32; 2],
+    pub max_px: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl OverlayRect {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        static ATTRIBS: [wgpu::VertexAttribute; 3] =
+            wgpu::vertex_attr_array![1 => Float32x2, 2 => Float32x2, 3 => Float32x4];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<OverlayRect>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBS,
+        }
+    }
+}
+
+/// Adds each fragment, weighted by its alpha, onto what is already drawn.
+const ADDITIVE_BLENDING: wgpu::BlendState = wgpu::BlendState {
+    color: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::SrcAlpha,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    },
+    alpha: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    },
+};
+
+/// Construction-time rendering options.
+pub struct RenderOptions {
+    /// MSAA sample count; 1 disables it. Must be supported for the surface format.
+    pub sample_count: u32,
+    /// Sprite drawn for each agent (tinted by its color) instead of a procedural disc.
+    pub sprite: Option<image::RgbaImage>,
+    /// Draw each agent as a quad expanded from its instance in the vertex shader,
+    /// with no vertex or index buffer, and at least a pixel across. WebGPU points
+    /// have no size, so these stand in for sized point primitives. Ignored,
+    /// falling back to indexed quads, when a sprite is set.
+    pub point_mode: bool,
+    /// Draw each agent as a Gaussian splat (sigma a third of its radius) blended
+    /// additively, so overlapping agents build up a smooth density field. Needs
+    /// the procedural disc: ignored with a sprite or in point mode.
+    pub splat: bool,
+}
+
 pub struct Renderer {
-    pub surface: wgpu::Surface<'static>,
+    /// The window's surface; `None` for an offscreen renderer, which can only
+    /// [`Self::capture_frame`].
+    pub surface: Option<wgpu::Surface<'static>>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
 
     pipeline: wgpu::RenderPipeline,
+    overlay_pipeline: wgpu::RenderPipeline,
+    background_pipeline: wgpu::RenderPipeline,
+    trail_pipeline: wgpu::RenderPipeline,
+    caption_pipeline: wgpu::RenderPipeline,
+    density_pipeline: wgpu::RenderPipeline,
     vertex_buf: wgpu::Buffer,
     index_buf: wgpu::Buffer,
     index_count: u32,
 
     uniform_buf: wgpu::Buffer,
     uniform_bind_group: wgpu::BindGroup,
+    sprite_bind_group: Option<wgpu::BindGroup>,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
 
     instance_buf: wgpu::Buffer,
     instance_capacity: usize,
+    /// Instances in `instance_buf` as of the last upload, and so drawn.
+    instance_count: usize,
+    /// Set when the caller rebuilt its instances since the last upload.
+    instances_dirty: bool,
+
+    overlay: RectLayer,
+    background: RectLayer,
+    trails: RectLayer,
+    arrows: RectLayer,
+    /// Where the caption image is drawn and the bind group sampling it, if any.
+    caption: RectLayer,
+    caption_bind_group: Option<wgpu::BindGroup>,
+    /// The HUD image, drawn like the caption but above it.
+    hud: RectLayer,
+    hud_bind_group: Option<wgpu::BindGroup>,
+
+    /// World positions drawn as a screen-space density in place of the agents;
+    /// see [`Self::set_density`].
+    density_points: Vec<[f32; 2]>,
+    density_bind_group_layout: wgpu::BindGroupLayout,
+    /// The colormap the density is shaded with, 256 texels wide.
+    density_colormap: wgpu::TextureView,
+    /// The density binned for the last render, when there are points.
+    density: Option<DensityLayer>,
+
+    sample_count: u32,
+    msaa_view: Option<wgpu::TextureView>,
+
+    /// Whether agents are drawn as points; see [`RenderOptions::point_mode`].
+    pub point_mode: bool,
 
     pub camera_pos: [f32; 2],
     pub zoom: f32,
 }
 
 impl Renderer {
-    pub async fn new(window: &'static winit::window::Window) -> Result<Self> {
+    pub async fn new(
+        window: &'static winit::window::Window,
+        options: RenderOptions,
+    ) -> Result<Self> {
         let instance = wgpu::Instance::default();
         let surface = instance.create_surface(window)?;
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .ok_or_else(|| anyhow::anyhow!("no suitable GPU adapters found"))?;
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: Some("device"),
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                },
-                None,
-            )
-            .await?;
+        let adapter = request_adapter(&instance, Some(&surface)).await?;
+        let (device, queue) = request_device(&adapter).await?;
 
         let caps = surface.get_capabilities(&adapter);
         let format = caps
@@ -131,6 +214,51 @@ impl Renderer {
         };
         surface.configure(&device, &config);
 
+        Self::with_target(Some(surface), &adapter, device, queue, config, options)
+    }
+
+    /// A renderer with no window, drawing `width` x `height` frames that are only
+    /// read back with [`Self::capture_frame`], e.g. on a machine without a display.
+    pub async fn new_offscreen(width: u32, height: u32, options: RenderOptions) -> Result<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = request_adapter(&instance, None).await?;
+        let (device, queue) = request_device(&adapter).await?;
+
+        // Never configured on a surface: only the size and format are used.
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+
+        Self::with_target(None, &adapter, device, queue, config, options)
+    }
+
+    /// Builds the pipelines and buffers for drawing into `config`-sized targets
+    /// of its format, shared by window and offscreen renderers.
+    fn with_target(
+        surface: Option<wgpu::Surface<'static>>,
+        adapter: &wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        options: RenderOptions,
+    ) -> Result<Self> {
+        let sample_count = options.sample_count;
+        let point_mode = options.point_mode && options.sprite.is_none();
+        let splat = options.splat && options.sprite.is_none() && !point_mode;
+
+        let format = config.format;
+        let format_features = adapter.get_texture_format_features(format);
+        if !format_features.flags.sample_count_supported(sample_count) {
+            anyhow::bail!("MSAA sample count {sample_count} is not supported for {format:?}");
+        }
+
         let shader_src = include_str!("shader.wgsl");
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("shader"),
@@ -173,33 +301,85 @@ impl Renderer {
             }],
         });
 
+        let sprite_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("sprite_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+        let sprite_bind_group = options.sprite.as_ref().map(|sprite| {
+            create_sprite_bind_group(&device, &queue, &sprite_bind_group_layout, sprite)
+        });
+
+        let mut bind_group_layouts = vec![&uniform_bind_group_layout];
+        if sprite_bind_group.is_some() {
+            bind_group_layouts.push(&sprite_bind_group_layout);
+        }
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("pipeline_layout"),
-            bind_group_layouts: &[&uniform_bind_group_layout],
+            bind_group_layouts: &bind_group_layouts,
             push_constant_ranges: &[],
         });
 
+        // Point mode builds its quads' corners from the vertex index, reading only
+        // the instance buffer.
+        let quad_buffers = [Vertex::desc(), Instance::desc()];
+        let vertex_buffers = if point_mode {
+            &quad_buffers[1..]
+        } else {
+            &quad_buffers[..]
+        };
         let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("pipeline"),
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &shader,
-                entry_point: "vs_main",
+                entry_point: if point_mode { "vs_point" } else { "vs_main" },
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: &[Vertex::desc(), Instance::desc()],
+                buffers: vertex_buffers,
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
-                entry_point: "fs_main",
+                entry_point: if sprite_bind_group.is_some() {
+                    "fs_sprite"
+                } else if splat {
+                    "fs_splat"
+                } else {
+                    "fs_main"
+                },
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
                 targets: &[Some(wgpu::ColorTargetState {
                     format: config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    blend: Some(if splat {
+                        ADDITIVE_BLENDING
+                    } else {
+                        wgpu::BlendState::ALPHA_BLENDING
+                    }),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
+                topology: if point_mode {
+                    wgpu::PrimitiveTopology::TriangleStrip
+                } else {
+                    wgpu::PrimitiveTopology::TriangleList
+                },
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
                 cull_mode: None,
@@ -208,10 +388,132 @@ impl Renderer {
                 conservative: false,
             },
             depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
             multiview: None,
         });
 
+        // Overlays are flat-colored quads in window pixels, drawn without sprites.
+        let overlay_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("overlay_pipeline_layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        // The caption is a rect textured like a sprite.
+        let caption_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("caption_pipeline_layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout, &sprite_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let rect_pipeline = |label, layout, vs_entry_point, fs_entry_point| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: vs_entry_point,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    buffers: &[Vertex::desc(), OverlayRect::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: fs_entry_point,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+        };
+        let overlay_pipeline = rect_pipeline(
+            "overlay_pipeline",
+            &overlay_pipeline_layout,
+            "vs_overlay",
+            "fs_main",
+        );
+        let background_pipeline = rect_pipeline(
+            "background_pipeline",
+            &overlay_pipeline_layout,
+            "vs_background",
+            "fs_main",
+        );
+        let trail_pipeline = rect_pipeline(
+            "trail_pipeline",
+            &overlay_pipeline_layout,
+            "vs_trail",
+            "fs_main",
+        );
+        let caption_pipeline = rect_pipeline(
+            "caption_pipeline",
+            &caption_pipeline_layout,
+            "vs_caption",
+            "fs_sprite",
+        );
+
+        let density_texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let density_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("density_bind_group_layout"),
+                entries: &[density_texture_entry(2), density_texture_entry(3)],
+            });
+        let density_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("density_pipeline_layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout, &density_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        // The density is one window-filling quad, so it takes no instance buffer.
+        let density_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("density_pipeline"),
+            layout: Some(&density_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_density",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[Vertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_density",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+        let density_colormap = create_density_colormap(&device, &queue);
+
         let vertices: &[Vertex] = &[
             Vertex { pos: [-1.0, -1.0] },
             Vertex { pos: [1.0, -1.0] },
@@ -239,19 +541,52 @@ impl Renderer {
             mapped_at_creation: false,
         });
 
+        let overlay = RectLayer::new(&device, "overlay_buf");
+        let background = RectLayer::new(&device, "background_buf");
+        let trails = RectLayer::new(&device, "trail_buf");
+        let arrows = RectLayer::new(&device, "arrow_buf");
+        let caption = RectLayer::new(&device, "caption_buf");
+        let hud = RectLayer::new(&device, "hud_buf");
+
+        let msaa_view = create_msaa_view(&device, &config, sample_count);
+
         let renderer = Self {
             surface,
             device,
             queue,
             config,
             pipeline,
+            overlay_pipeline,
+            background_pipeline,
+            trail_pipeline,
+            caption_pipeline,
+            density_pipeline,
             vertex_buf,
             index_buf,
             index_count: indices.len() as u32,
             uniform_buf,
             uniform_bind_group,
+            sprite_bind_group,
+            texture_bind_group_layout: sprite_bind_group_layout,
             instance_buf,
             instance_capacity,
+            instance_count: 0,
+            instances_dirty: true,
+            overlay,
+            background,
+            trails,
+            arrows,
+            caption,
+            caption_bind_group: None,
+            hud,
+            hud_bind_group: None,
+            density_points: Vec::new(),
+            density_bind_group_layout,
+            density_colormap,
+            density: None,
+            sample_count,
+            msaa_view,
+            point_mode,
             camera_pos: [0.0, 0.0],
             zoom: 1.0,
         };
@@ -262,7 +597,10 @@ impl Renderer {
     pub fn resize(&mut self, width: u32, height: u32) {
         self.config.width = width.max(1);
         self.config.height = height.max(1);
-        self.surface.configure(&self.device, &self.config);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+        self.msaa_view = create_msaa_view(&self.device, &self.config, self.sample_count);
         self.update_uniforms();
     }
 
@@ -283,59 +621,645 @@ impl Renderer {
             .write_buffer(&self.uniform_buf, 0, bytemuck::bytes_of(&uniforms));
     }
 
+    /// Marks the instances passed to the next [`Self::render`] as changed, so they are
+    /// uploaded again. Until then, redraws (e.g. after camera moves) reuse the
+    /// instance buffer as is.
+    pub fn mark_instances_dirty(&mut self) {
+        self.instances_dirty = true;
+    }
+
+    /// Replaces the overlay drawn over the agents from the next [`Self::render`] on.
+    pub fn set_overlay(&mut self, rects: &[OverlayRect]) {
+        self.overlay.set(&self.device, &self.queue, rects);
+    }
+
+    /// Replaces the rectangles drawn under the agents from the next [`Self::render`]
+    /// on. Unlike the overlay they are placed in world units, so they follow the camera.
+    pub fn set_background(&mut self, rects: &[OverlayRect]) {
+        self.background.set(&self.device, &self.queue, rects);
+    }
+
+    /// Replaces the trail lines drawn between the background and the agents from
+    /// the next [`Self::render`] on, each from `min_px` to `max_px` in world units.
+    pub fn set_trails(&mut self, lines: &[OverlayRect]) {
+        self.trails.set(&self.device, &self.queue, lines);
+    }
+
+    /// Replaces the velocity arrows drawn over the agents from the next
+    /// [`Self::render`] on, as lines like [`Self::set_trails`].
+    pub fn set_arrows(&mut self, lines: &[OverlayRect]) {
+        self.arrows.set(&self.device, &self.queue, lines);
+    }
+
+    /// Draws `points` (world positions) from the next [`Self::render`] on as a
+    /// density heatmap binned in screen space, under the agents; nothing when
+    /// empty. The points are binned again on every render, so the heatmap keeps
+    /// its resolution as the camera moves.
+    pub fn set_density(&mut self, points: &[[f32; 2]]) {
+        self.density_points.clear();
+        self.density_points.extend_from_slice(points);
+    }
+
+    /// Replaces the caption drawn over everything else from the next
+    /// [`Self::render`] on: `image` stretched over the window-pixel rect (whose
+    /// color tints it), or nothing.
+    pub fn set_caption(&mut self, caption: Option<(&image::RgbaImage, OverlayRect)>) {
+        let (device, queue) = (&self.device, &self.queue);
+        let layout = &self.texture_bind_group_layout;
+        self.caption_bind_group = set_image(device, queue, layout, &mut self.caption, caption);
+    }
+
+    /// Replaces the HUD, drawn over the caption, like [`Self::set_caption`].
+    pub fn set_hud(&mut self, hud: Option<(&image::RgbaImage, OverlayRect)>) {
+        let (device, queue) = (&self.device, &self.queue);
+        let layout = &self.texture_bind_group_layout;
+        self.hud_bind_group = set_image(device, queue, layout, &mut self.hud, hud);
+    }
+
     pub fn render(&mut self, instances: &[Instance]) -> Result<()> {
+        let Some(surface) = &self.surface else {
+            anyhow::bail!("an offscreen renderer has no window to present to");
+        };
+        let frame = surface.get_current_texture()?;
+        self.upload_instances(instances)?;
+        self.upload_density();
+
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("encoder"),
+            });
+        self.draw(&mut encoder, &view);
+
+        self.queue.submit(Some(encoder.finish()));
+        frame.present();
+
+        self.device.poll(wgpu::Maintain::Wait);
+
+        Ok(())
+    }
+
+    /// Draws `instances` like [`Self::render`], but into an offscreen texture the
+    /// size of the surface, and reads it back instead of presenting it.
+    pub fn capture_frame(&mut self, instances: &[Instance]) -> Result<image::RgbaImage> {
+        self.upload_instances(instances)?;
+        self.upload_density();
+
+        let (width, height) = (self.config.width, self.config.height);
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("capture_target"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Buffer rows must be padded to a multiple of 256 bytes.
+        let unpadded_row = width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row = unpadded_row.div_ceil(align) * align;
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("capture_readback"),
+            size: padded_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("capture_encoder"),
+            });
+        self.draw(&mut encoder, &view);
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            size,
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()??;
+
+        let bgra = matches!(
+            self.config.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        );
+        let mut pixels = Vec::with_capacity((unpadded_row * height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_row as usize]);
+        }
+        readback.unmap();
+        if bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        image::RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| anyhow::anyhow!("captured frame has the wrong size"))
+    }
+
+    /// Uploads `instances` if they were marked dirty since the last upload. Until
+    /// the next upload, only the instances uploaded here are drawn, so a caller's
+    /// longer slice never reads past the buffer.
+    fn upload_instances(&mut self, instances: &[Instance]) -> Result<()> {
+        if !self.instances_dirty {
+            return Ok(());
+        }
+        // An empty frame still clears and presents, but uploads and draws nothing.
+        self.instance_count = 0;
+        if instances.is_empty() {
+            self.instances_dirty = false;
+            return Ok(());
+        }
         if instances.len() > self.instance_capacity {
-            self.instance_capacity = instances.len().next_power_of_two().max(1);
+            let instance_bytes = std::mem::size_of::<Instance>() as u64;
+            let max_instances = self.device.limits().max_buffer_size / instance_bytes;
+            anyhow::ensure!(
+                instances.len() as u64 <= max_instances,
+                "{} instances exceed the device's limit of {max_instances}",
+                instances.len()
+            );
+            let capacity = (instances.len() as u64)
+                .checked_next_power_of_two()
+                .map_or(max_instances, |capacity| capacity.min(max_instances));
+            self.instance_capacity = capacity as usize;
             self.instance_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
                 label: Some("instance_buf"),
-                size: (self.instance_capacity * std::mem::size_of::<Instance>()) as u64,
+                size: capacity * instance_bytes,
                 usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
                 mapped_at_creation: false,
             });
         }
         self.queue
             .write_buffer(&self.instance_buf, 0, bytemuck::cast_slice(instances));
+        self.instance_count = instances.len();
+        self.instances_dirty = false;
+        Ok(())
+    }
 
-        let frame = self.surface.get_current_texture()?;
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    /// Bins the density points for the current camera and window size into the
+    /// density texture, resizing it with the window.
+    fn upload_density(&mut self) {
+        if self.density_points.is_empty() {
+            self.density = None;
+            return;
+        }
+        let window = [self.config.width, self.config.height];
+        let (bins, cells) =
+            heatmap::screen_density(&self.density_points, self.camera_pos, self.zoom, window);
+        let size = wgpu::Extent3d {
+            width: bins[0] as u32,
+            height: bins[1] as u32,
+            depth_or_array_layers: 1,
+        };
+        // A resized window needs a texture of the new grid size.
+        self.density = self.density.take().filter(|density| density.size == size);
+        let density = self.density.get_or_insert_with(|| {
+            DensityLayer::new(
+                &self.device,
+                &self.density_bind_group_layout,
+                &self.density_colormap,
+                size,
+            )
+        });
+        self.queue.write_texture(
+            density.texture.as_image_copy(),
+            bytemuck::cast_slice(&cells),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+    }
 
-        let mut encoder =
-            self.device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("encoder"),
-                });
-
-        {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("render_pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
+    /// Records the pass that clears `view` and draws the background, the trails, the
+    /// density, the uploaded instances, the arrows, the overlay and the caption,
+    /// resolving from the MSAA target when there is one.
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("render_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.msaa_view.as_ref().unwrap_or(view),
+                resolve_target: self.msaa_view.as_ref().map(|_| view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
 
-            rpass.set_pipeline(&self.pipeline);
+        self.draw_rects(&mut rpass, &self.background_pipeline, &self.background);
+        self.draw_rects(&mut rpass, &self.trail_pipeline, &self.trails);
+
+        if let Some(density) = &self.density {
+            rpass.set_pipeline(&self.density_pipeline);
             rpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            rpass.set_bind_group(1, &density.bind_group, &[]);
             rpass.set_vertex_buffer(0, self.vertex_buf.slice(..));
-            rpass.set_vertex_buffer(1, self.instance_buf.slice(..));
             rpass.set_index_buffer(self.index_buf.slice(..), wgpu::IndexFormat::Uint16);
-            rpass.draw_indexed(0..self.index_count, 0, 0..instances.len() as u32);
+            rpass.draw_indexed(0..self.index_count, 0, 0..1);
         }
 
-        self.queue.submit(Some(encoder.finish()));
-        frame.present();
+        let instance_count = self.instance_count;
+        if instance_count > 0 {
+            rpass.set_pipeline(&self.pipeline);
+            rpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            if let Some(sprite_bind_group) = &self.sprite_bind_group {
+                rpass.set_bind_group(1, sprite_bind_group, &[]);
+            }
+            if self.point_mode {
+                rpass.set_vertex_buffer(0, self.instance_buf.slice(..));
+                rpass.draw(0..4, 0..instance_count as u32);
+            } else {
+                rpass.set_vertex_buffer(0, self.vertex_buf.slice(..));
+                rpass.set_vertex_buffer(1, self.instance_buf.slice(..));
+                rpass.set_index_buffer(self.index_buf.slice(..), wgpu::IndexFormat::Uint16);
+                rpass.draw_indexed(0..self.index_count, 0, 0..instance_count as u32);
+            }
+        }
 
-        self.device.poll(wgpu::Maintain::Wait);
+        self.draw_rects(&mut rpass, &self.trail_pipeline, &self.arrows);
+        self.draw_rects(&mut rpass, &self.overlay_pipeline, &self.overlay);
 
+        if let Some(caption_bind_group) = &self.caption_bind_group {
+            rpass.set_bind_group(1, caption_bind_group, &[]);
+            self.draw_rects(&mut rpass, &self.caption_pipeline, &self.caption);
+        }
+        if let Some(hud_bind_group) = &self.hud_bind_group {
+            rpass.set_bind_group(1, hud_bind_group, &[]);
+            self.draw_rects(&mut rpass, &self.caption_pipeline, &self.hud);
+        }
+    }
+
+    fn draw_rects<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        pipeline: &'a wgpu::RenderPipeline,
+        layer: &'a RectLayer,
+    ) {
+        if layer.count == 0 {
+            return;
+        }
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buf.slice(..));
+        rpass.set_vertex_buffer(1, layer.buf.slice(..));
+        rpass.set_index_buffer(self.index_buf.slice(..), wgpu::IndexFormat::Uint16);
+        rpass.draw_indexed(0..self.index_count, 0, 0..layer.count);
+    }
+}
+
+/// Places `image` in `layer`, or empties it, returning the bind group that
+/// samples the image.
+fn set_image(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    layer: &mut RectLayer,
+    image: Option<(&image::RgbaImage, OverlayRect)>,
+) -> Option<wgpu::BindGroup> {
+    let Some((image, rect)) = image else {
+        layer.set(device, queue, &[]);
+        return None;
+    };
+    layer.set(device, queue, &[rect]);
+    Some(create_sprite_bind_group(device, queue, layout, image))
+}
+
+/// The `r32float` texture of binned density cells and its bind group.
+struct DensityLayer {
+    size: wgpu::Extent3d,
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+impl DensityLayer {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        colormap: &wgpu::TextureView,
+        size: wgpu::Extent3d,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("density"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("density_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(colormap),
+                },
+            ],
+        });
+        Self {
+            size,
+            texture,
+            bind_group,
+        }
+    }
+}
+
+/// Bakes inferno, the colormap of the `--heatmap-bins` background, into a
+/// 256x1 texture. Its bytes are used as is, like agent colors.
+fn create_density_colormap(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::TextureView {
+    let texels: Vec<u8> = (0..256)
+        .flat_map(|i| {
+            let c = colorous::INFERNO.eval_rational(i, 256);
+            [c.r, c.g, c.b, 255]
+        })
+        .collect();
+    let texture = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("density_colormap"),
+            size: wgpu::Extent3d {
+                width: 256,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        &texels,
+    );
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// A growable buffer of rectangles drawn by one of the rect pipelines.
+struct RectLayer {
+    label: &'static str,
+    buf: wgpu::Buffer,
+    capacity: usize,
+    count: u32,
+}
+
+impl RectLayer {
+    fn new(device: &wgpu::Device, label: &'static str) -> Self {
+        Self {
+            label,
+            buf: create_overlay_buf(device, label, 1),
+            capacity: 1,
+            count: 0,
+        }
+    }
+
+    fn set(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, rects: &[OverlayRect]) {
+        if rects.len() > self.capacity {
+            self.capacity = rects.len().next_power_of_two();
+            self.buf = create_overlay_buf(device, self.label, self.capacity);
+        }
+        queue.write_buffer(&self.buf, 0, bytemuck::cast_slice(rects));
+        self.count = rects.len() as u32;
+    }
+}
+
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+) -> Result<wgpu::Adapter> {
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface,
+            force_fallback_adapter: false,
+        })
+        .await
+        .ok_or_else(|| anyhow::anyhow!("no suitable GPU adapters found"))
+}
+
+async fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue)> {
+    Ok(adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("device"),
+                required_features: wgpu::Features::empty(),
+                required_limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await?)
+}
+
+fn create_overlay_buf(device: &wgpu::Device, label: &str, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: (capacity * std::mem::size_of::<OverlayRect>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Creates the multisampled color target resolved into the surface each frame, or
+/// `None` when MSAA is disabled.
+fn create_msaa_view(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count <= 1 {
+        return None;
+    }
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("msaa_target"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+/// Uploads `sprite` as an sRGB texture and binds it with a linear sampler.
+fn create_sprite_bind_group(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    sprite: &image::RgbaImage,
+) -> wgpu::BindGroup {
+    let (width, height) = sprite.dimensions();
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("sprite"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        sprite.as_raw(),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * width),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("sprite_sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("sprite_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(point_mode: bool) -> RenderOptions {
+        RenderOptions {
+            sample_count: 1,
+            sprite: None,
+            point_mode,
+            splat: false,
+        }
+    }
+
+    fn agent(center_px: [f32; 2], radius_px: f32) -> Instance {
+        Instance {
+            center_px,
+            radius_px,
+            _pad0: 0.0,
+            color: [1.0; 4],
+        }
+    }
+
+    fn lit_pixels(image: &image::RgbaImage) -> usize {
+        image.pixels().filter(|pixel| pixel[0] > 0).count()
+    }
+
+    #[test]
+    fn point_mode_draws_agents_at_their_mapped_size() -> Result<()> {
+        let mut quads = pollster::block_on(Renderer::new_offscreen(32, 32, options(false)))?;
+        let mut points = pollster::block_on(Renderer::new_offscreen(32, 32, options(true)))?;
+        let disc = [agent([0.5, 0.5], 8.0)];
+        assert_eq!(
+            lit_pixels(&points.capture_frame(&disc)?),
+            lit_pixels(&quads.capture_frame(&disc)?)
+        );
+
+        // An agent far smaller than a pixel still shows.
+        points.mark_instances_dirty();
+        assert!(lit_pixels(&points.capture_frame(&[agent([0.5, 0.5], 0.01)])?) >= 1);
+        Ok(())
+    }
+
+    /// Times drawing a million agents a pixel in radius with indexed quads and in point
+    /// mode. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn point_mode_benchmark() -> Result<()> {
+        let (n_agents, n_frames, size) = (1_000_000, 10, 1024);
+        let instances: Vec<Instance> = (0..n_agents)
+            .map(|k| {
+                let (x, y) = ((k % size) as f32, (k / size % size) as f32);
+                agent([x - size as f32 / 2.0, y - size as f32 / 2.0], 1.0)
+            })
+            .collect();
+        for point_mode in [false, true] {
+            let mut renderer = pollster::block_on(Renderer::new_offscreen(
+                size as u32,
+                size as u32,
+                options(point_mode),
+            ))?;
+            // The first frame uploads the instances; the timed ones only draw them.
+            renderer.capture_frame(&instances)?;
+            let start = std::time::Instant::now();
+            for _ in 0..n_frames {
+                renderer.capture_frame(&instances)?;
+            }
+            let mode = if point_mode { "point mode" } else { "quads" };
+            println!(
+                "{mode}: {n_frames} frames of {n_agents} agents in {:?}",
+                start.elapsed()
+            );
+        }
         Ok(())
     }
 }
@@ -0,0 +1,443 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::evo::{read_header, EvoFile, EvoHeader, PlaybackMeta, ShardMeta};
+use crate::source::FrameSource;
+
+/// `<name>.shards.json`, as written next to a sharded recording.
+#[derive(Debug, Deserialize)]
+struct ShardManifest {
+    shards: Vec<ShardEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ShardEntry {
+    /// Relative to the manifest's directory.
+    path: PathBuf,
+    frame_offset: u64,
+    step_offset: u64,
+}
+
+struct Shard {
+    path: PathBuf,
+    frame_offset: usize,
+    /// Simulated time of the shard's first frame.
+    start_time: f64,
+    file: OnceLock<EvoFile>,
+}
+
+/// The shards of a recording split by `--shard-bytes`, played back as one
+/// timeline. Only the first and last shard are mapped up front; the others are
+/// opened when a frame in them is first read.
+pub struct EvoSeries {
+    header: EvoHeader,
+    shards: Vec<Shard>,
+    total_frames: usize,
+}
+
+impl EvoSeries {
+    /// Whether `path` names a shard manifest or a directory of shards rather than
+    /// a single `.evo` file.
+    pub fn is_series_path(path: &Path) -> bool {
+        path.is_dir() || path.to_string_lossy().ends_with(".shards.json")
+    }
+
+    /// Opens the shards listed in manifest `path`, or every shard (file with a
+    /// shard header) in directory `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut entries = if path.is_dir() {
+            scan_shards(path)?
+        } else {
+            let manifest: ShardManifest = serde_json::from_slice(
+                &std::fs::read(path).with_context(|| format!("failed to read {:?}", path))?,
+            )
+            .with_context(|| format!("invalid shard manifest {:?}", path))?;
+            let dir = path.parent().unwrap_or(Path::new("."));
+            manifest
+                .shards
+                .into_iter()
+                .map(|entry| ShardEntry {
+                    path: dir.join(entry.path),
+                    ..entry
+                })
+                .collect()
+        };
+        entries.sort_by_key(|entry| entry.frame_offset);
+        if entries.is_empty() {
+            bail!("no shards found in {:?}", path);
+        }
+
+        let first = open_shard(&entries[0].path)?;
+        let header = first.header.clone();
+        let step_duration = header
+            .playback
+            .as_ref()
+            .map(PlaybackMeta::step_duration)
+            .filter(|d| d.is_finite() && *d > 0.0)
+            .unwrap_or(1.0);
+        let shards: Vec<Shard> = entries
+            .into_iter()
+            .map(|entry| Shard {
+                path: entry.path,
+                frame_offset: entry.frame_offset as usize,
+                start_time: entry.step_offset as f64 * step_duration,
+                file: OnceLock::new(),
+            })
+            .collect();
+        let _ = shards[0].file.set(first);
+
+        let mut series = Self {
+            header,
+            shards,
+            total_frames: 0,
+        };
+        // The last shard's frame count may still be growing, or cut short.
+        let last = series.shards.len() - 1;
+        series.total_frames = series.shards[last].frame_offset + series.shard(last)?.total_frames();
+        Ok(series)
+    }
+
+    /// Checks every shard against its body checksum; see [`EvoFile::verify`].
+    /// `Ok(false)` if any shard has none.
+    pub fn verify(&self) -> Result<bool> {
+        let mut verified = true;
+        for (i, shard) in self.shards.iter().enumerate() {
+            verified &= self
+                .shard(i)?
+                .verify()
+                .with_context(|| format!("shard {:?} is corrupt", shard.path))?;
+        }
+        Ok(verified)
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The shard `i`, mapping it on first use.
+    fn shard(&self, i: usize) -> Result<&EvoFile> {
+        let shard = &self.shards[i];
+        if let Some(file) = shard.file.get() {
+            return Ok(file);
+        }
+        let file = open_shard(&shard.path)?;
+        let (expected, found) = (&self.header.config, &file.header.config);
+        if found.n_agents != expected.n_agents || found.state_labels != expected.state_labels {
+            bail!(
+                "shard {:?} has {} agents with states {:?}, expected {} with {:?}",
+                shard.path,
+                found.n_agents,
+                found.state_labels,
+                expected.n_agents,
+                expected.state_labels
+            );
+        }
+        Ok(shard.file.get_or_init(|| file))
+    }
+
+    /// The shard holding series frame `frame_index`, and the frame's index in it.
+    fn locate(&self, frame_index: usize) -> (usize, usize) {
+        let i = self
+            .shards
+            .partition_point(|s| s.frame_offset <= frame_index)
+            .saturating_sub(1);
+        (i, frame_index - self.shards[i].frame_offset)
+    }
+
+    /// Frames shard `i` should hold, going by where the next one starts.
+    fn declared_frames(&self, i: usize) -> usize {
+        match self.shards.get(i + 1) {
+            Some(next) => next.frame_offset - self.shards[i].frame_offset,
+            None => self.total_frames - self.shards[i].frame_offset,
+        }
+    }
+
+    /// The shard playing at simulated time `t`.
+    fn shard_at_sim_time(&self, t: f64) -> usize {
+        self.shards
+            .partition_point(|s| s.start_time <= t)
+            .saturating_sub(1)
+    }
+}
+
+/// Maps a shard, warning about a partial frame left at its end.
+fn open_shard(path: &Path) -> Result<EvoFile> {
+    let file = EvoFile::open(path)?;
+    let integrity = file.integrity();
+    if integrity.looks_truncated() {
+        eprintln!("warning: {:?}: {integrity}", path);
+    }
+    Ok(file)
+}
+
+/// Shards in directory `dir`, found by their headers. Other files are skipped,
+/// with a warning for `.evo` files whose header cannot be read; shards of more
+/// than one recording are an error.
+fn scan_shards(dir: &Path) -> Result<Vec<ShardEntry>> {
+    let mut recordings: BTreeMap<String, Vec<(ShardMeta, PathBuf)>> = BTreeMap::new();
+    for dir_entry in std::fs::read_dir(dir).with_context(|| format!("failed to list {:?}", dir))? {
+        let path = dir_entry?.path();
+        if path.extension().is_none_or(|ext| ext != "evo") {
+            continue;
+        }
+        match read_header(&path) {
+            Ok(header) => {
+                if let Some(shard) = header.shard {
+                    let name = recording_name(&path);
+                    recordings.entry(name).or_default().push((shard, path));
+                }
+            }
+            Err(e) => eprintln!("warning: skipping {:?}: {e:#}", path),
+        }
+    }
+    if recordings.len() > 1 {
+        let names: Vec<&str> = recordings.keys().map(String::as_str).collect();
+        bail!(
+            "{:?} holds shards of {} recordings ({}); open one's .shards.json instead",
+            dir,
+            names.len(),
+            names.join(", ")
+        );
+    }
+    let mut found = recordings.into_values().next().unwrap_or_default();
+    found.sort_by_key(|(shard, _)| shard.shard_index);
+    let mut entries = Vec::with_capacity(found.len());
+    for (i, (shard, path)) in found.into_iter().enumerate() {
+        if shard.shard_index as usize != i {
+            bail!("shard {i} is missing from {:?}", dir);
+        }
+        entries.push(ShardEntry {
+            path,
+            frame_offset: shard.frame_offset,
+            step_offset: shard.step_offset,
+        });
+    }
+    Ok(entries)
+}
+
+/// The recording shard `path` belongs to: its file name without the `.NNN.evo`
+/// shards are numbered with.
+fn recording_name(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match stem.rsplit_once('.') {
+        Some((name, index)) if index.bytes().all(|b| b.is_ascii_digit()) => name.to_string(),
+        _ => stem.into_owned(),
+    }
+}
+
+impl FrameSource for EvoSeries {
+    fn header(&self) -> &EvoHeader {
+        &self.header
+    }
+
+    fn total_frames(&self) -> usize {
+        self.total_frames
+    }
+
+    fn frame_duration(&self) -> f64 {
+        self.header
+            .playback
+            .as_ref()
+            .map(PlaybackMeta::frame_duration)
+            .filter(|d| d.is_finite() && *d > 0.0)
+            .unwrap_or(1.0)
+    }
+
+    fn sim_time_of_frame(&self, frame_index: usize) -> f64 {
+        let (i, local) = self.locate(frame_index.min(self.total_frames.saturating_sub(1)));
+        match self.shard(i) {
+            Ok(file) => self.shards[i].start_time + file.sim_time_of_frame(local),
+            Err(_) => frame_index as f64 * self.frame_duration(),
+        }
+    }
+
+    fn frame_at_sim_time(&self, t: f64) -> usize {
+        let i = self.shard_at_sim_time(t);
+        let shard = &self.shards[i];
+        match self.shard(i) {
+            Ok(file) => shard.frame_offset + file.frame_at_sim_time(t - shard.start_time),
+            Err(_) => shard.frame_offset,
+        }
+    }
+
+    fn frame_position_at_sim_time(&self, t: f64) -> f64 {
+        let i = self.shard_at_sim_time(t);
+        let shard = &self.shards[i];
+        let Ok(file) = self.shard(i) else {
+            return shard.frame_offset as f64;
+        };
+        let local_t = t - shard.start_time;
+        let position = file.frame_position_at_sim_time(local_t);
+        // Past the shard's last frame, move on towards the next shard's first.
+        let last = file.total_frames().saturating_sub(1);
+        let Some(next) = self.shards.get(i + 1) else {
+            return shard.frame_offset as f64 + position;
+        };
+        if position < last as f64 {
+            return shard.frame_offset as f64 + position;
+        }
+        let last_time = file.sim_time_of_frame(last);
+        let span = next.start_time - shard.start_time - last_time;
+        let frac = if span > 0.0 {
+            ((local_t - last_time) / span).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        (shard.frame_offset + last) as f64 + frac
+    }
+
+    fn read_frame_f32(&self, frame_index: usize, out: &mut Vec<f32>) -> Result<()> {
+        if frame_index >= self.total_frames {
+            bail!(
+                "frame_index out of range: {frame_index} >= {}",
+                self.total_frames
+            );
+        }
+        let (i, local) = self.locate(frame_index);
+        let file = self.shard(i)?;
+        if local >= file.total_frames() {
+            bail!(
+                "frame {frame_index} is missing: shard {:?} holds {} of its {} frames",
+                self.shards[i].path,
+                file.total_frames(),
+                self.declared_frames(i)
+            );
+        }
+        file.read_frame_f32(local, out)
+    }
+
+    fn read_frame_interpolated(
+        &self,
+        position: f64,
+        out: &mut Vec<f32>,
+        next: &mut Vec<f32>,
+    ) -> Result<()> {
+        let position = position.max(0.0);
+        let frame_index = position.floor() as usize;
+        let (i, local) = self.locate(frame_index);
+        let file = self.shard(i)?;
+        if local + 1 < file.total_frames() || i + 1 == self.shards.len() {
+            return file.read_frame_interpolated(
+                position - self.shards[i].frame_offset as f64,
+                out,
+                next,
+            );
+        }
+        // Blend across the boundary into the next shard.
+        self.read_frame_f32(frame_index, out)?;
+        let t = (position - frame_index as f64) as f32;
+        if t > 0.0 && frame_index + 1 < self.total_frames {
+            self.read_frame_f32(frame_index + 1, next)?;
+            file.blend(out, next, t);
+        }
+        Ok(())
+    }
+
+    /// Searches the shard holding `from` only; a static stretch running into the
+    /// next shard ends at that shard's first frame.
+    fn next_changed_frame(&self, from: usize, eps: f32) -> Option<usize> {
+        let (i, local) = self.locate(from);
+        let shard = &self.shards[i];
+        match self.shard(i).ok()?.next_changed_frame(local, eps) {
+            Some(found) => Some(shard.frame_offset + found),
+            None => self.shards.get(i + 1).map(|next| next.frame_offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Writes shard `index` of a 1 agent x 1 dim recording `name` holding `values`.
+    fn write_shard(dir: &Path, name: &str, index: u32, frame_offset: u64, values: &[f32]) {
+        let header = format!(
+            r#"{{"version":1,"timestamp":"t","config":{{"n_agents":1,"state_dims":1,"state_labels":["pos_x"]}},"playback":{{"dt":0.5,"substeps":1,"save_interval":2,"total_frames":{}}},"shard":{{"shard_index":{index},"frame_offset":{frame_offset},"step_offset":{}}}}}"#,
+            values.len(),
+            frame_offset * 2
+        );
+        let mut file = std::fs::File::create(dir.join(format!("{name}.{index:03}.evo"))).unwrap();
+        file.write_all(b"EVO1").unwrap();
+        file.write_all(&(header.len() as u32).to_le_bytes())
+            .unwrap();
+        file.write_all(header.as_bytes()).unwrap();
+        for v in values {
+            file.write_all(&v.to_le_bytes()).unwrap();
+        }
+    }
+
+    #[test]
+    fn plays_shards_as_one_timeline() {
+        let dir = std::env::temp_dir().join("evo_series_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        write_shard(&dir, "run", 0, 0, &[0.0, 1.0]);
+        write_shard(&dir, "run", 1, 2, &[2.0, 3.0]);
+        write_shard(&dir, "run", 2, 4, &[4.0]);
+        let manifest = r#"{"shards":[
+            {"path":"run.000.evo","frame_offset":0,"step_offset":0,"frames":2},
+            {"path":"run.001.evo","frame_offset":2,"step_offset":4,"frames":2},
+            {"path":"run.002.evo","frame_offset":4,"step_offset":8,"frames":1}]}"#;
+        std::fs::write(dir.join("run.shards.json"), manifest).unwrap();
+
+        for path in [dir.join("run.shards.json"), dir.clone()] {
+            assert!(EvoSeries::is_series_path(&path));
+            let series = EvoSeries::open(&path).unwrap();
+            assert_eq!(series.shard_count(), 3);
+            assert_eq!(series.total_frames(), 5);
+            // Only the ends are mapped until a middle frame is read.
+            assert!(series.shards[1].file.get().is_none());
+
+            let mut out = Vec::new();
+            for i in 0..5 {
+                series.read_frame_f32(i, &mut out).unwrap();
+                assert_eq!(out, [i as f32]);
+                // Frames are 2 steps of 0.5s apart.
+                assert_eq!(series.sim_time_of_frame(i), i as f64);
+                assert_eq!(series.frame_at_sim_time(i as f64 + 0.5), i);
+            }
+            assert_eq!(series.frame_position_at_sim_time(1.5), 1.5);
+            let mut next = Vec::new();
+            series
+                .read_frame_interpolated(1.25, &mut out, &mut next)
+                .unwrap();
+            assert_eq!(out, [1.25]);
+            assert!(series.read_frame_f32(5, &mut out).is_err());
+        }
+
+        // A middle shard cut short reports its missing frames.
+        write_shard(&dir, "run", 1, 2, &[2.0]);
+        let series = EvoSeries::open(&dir).unwrap();
+        let mut out = Vec::new();
+        series.read_frame_f32(2, &mut out).unwrap();
+        assert!(series.read_frame_f32(3, &mut out).is_err());
+        series.read_frame_f32(4, &mut out).unwrap();
+        assert_eq!(out, [4.0]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn directory_scans_skip_other_files_and_keep_recordings_apart() {
+        let dir = std::env::temp_dir().join("evo_series_scan_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        write_shard(&dir, "run", 0, 0, &[0.0, 1.0]);
+        write_shard(&dir, "run", 1, 2, &[2.0]);
+        std::fs::write(dir.join("notes.evo"), b"not a recording").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"").unwrap();
+        assert_eq!(EvoSeries::open(&dir).unwrap().total_frames(), 3);
+
+        write_shard(&dir, "other", 0, 0, &[9.0]);
+        let err = EvoSeries::open(&dir).err().unwrap().to_string();
+        assert!(err.contains("2 recordings (other, run)"), "{err}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -9,6 +9,18 @@ struct Uniforms {
 @group(0) @binding(0)
 var<uniform> u: Uniforms;
 
+@group(1) @binding(0)
+var sprite_tex: texture_2d<f32>;
+@group(1) @binding(1)
+var sprite_sampler: sampler;
+
+// --mode heatmap: density cells in [0, 1], rows from the bottom of the window,
+// and the 256x1 colormap they are shaded with.
+@group(1) @binding(2)
+var density_tex: texture_2d<f32>;
+@group(1) @binding(3)
+var density_colormap: texture_2d<f32>;
+
 struct VsIn {
   @location(0) pos: vec2<f32>,
   @location(1) center_px: vec2<f32>,
@@ -16,32 +28,138 @@ struct VsIn {
   @location(3) color: vec4<f32>,
 };
 
+struct PointIn {
+  @location(1) center_px: vec2<f32>,
+  @location(2) radius_px: f32,
+  @location(3) color: vec4<f32>,
+};
+
+struct ScreenIn {
+  @location(0) pos: vec2<f32>,
+};
+
+struct OverlayIn {
+  @location(0) pos: vec2<f32>,
+  @location(1) min_px: vec2<f32>,
+  @location(2) max_px: vec2<f32>,
+  @location(3) color: vec4<f32>,
+};
+
 struct VsOut {
   @builtin(position) clip_pos: vec4<f32>,
   @location(0) local: vec2<f32>,
   @location(1) color: vec4<f32>,
 };
 
-@vertex
-fn vs_main(input: VsIn) -> VsOut {
-  let world_pos = input.center_px;
+fn world_to_screen(world_pos: vec2<f32>) -> vec2<f32> {
   let screen_x = (world_pos.x - u.camera_pos.x) * u.zoom + u.screen_size.x * 0.5;
   let screen_y = u.screen_size.y * 0.5 - (world_pos.y - u.camera_pos.y) * u.zoom;
-  let center_px = vec2<f32>(screen_x, screen_y);
-  
-  let radius = input.radius_px * u.zoom;
-  let pos_px = center_px + input.pos * radius;
+  return vec2<f32>(screen_x, screen_y);
+}
 
+fn screen_to_clip(pos_px: vec2<f32>) -> vec4<f32> {
   let ndc_x = (pos_px.x / u.screen_size.x) * 2.0 - 1.0;
   let ndc_y = 1.0 - (pos_px.y / u.screen_size.y) * 2.0;
+  return vec4<f32>(ndc_x, ndc_y, 0.0, 1.0);
+}
+
+@vertex
+fn vs_main(input: VsIn) -> VsOut {
+  let center_px = world_to_screen(input.center_px);
+  let radius = input.radius_px * u.zoom;
+  let pos_px = center_px + input.pos * radius;
 
   var out: VsOut;
-  out.clip_pos = vec4<f32>(ndc_x, ndc_y, 0.0, 1.0);
+  out.clip_pos = screen_to_clip(pos_px);
   out.local = input.pos;
   out.color = input.color;
   return out;
 }
 
+// Point mode: WebGPU points are always one pixel, so each instance is instead
+// expanded here into a screen-aligned quad, a 4-vertex strip built from the vertex
+// index with no vertex or index buffer. The quad is at least a pixel across, so
+// agents smaller than that still cover one.
+const POINT_MIN_RADIUS_PX: f32 = 1.0;
+
+@vertex
+fn vs_point(@builtin(vertex_index) vertex: u32, input: PointIn) -> VsOut {
+  let corner = vec2<f32>(f32(vertex & 1u), f32(vertex >> 1u)) * 2.0 - 1.0;
+  let radius = max(input.radius_px * u.zoom, POINT_MIN_RADIUS_PX);
+  var out: VsOut;
+  out.clip_pos = screen_to_clip(world_to_screen(input.center_px) + corner * radius);
+  out.local = corner;
+  out.color = input.color;
+  return out;
+}
+
+// Screen-space overlay rectangles (e.g. the timeline bar), unaffected by the camera.
+@vertex
+fn vs_overlay(input: OverlayIn) -> VsOut {
+  var out: VsOut;
+  out.clip_pos = screen_to_clip(mix(input.min_px, input.max_px, input.pos * 0.5 + 0.5));
+  out.local = vec2<f32>(0.0, 0.0);
+  out.color = input.color;
+  return out;
+}
+
+// World-space rectangles under the agents (e.g. the --heatmap-bins density),
+// following the camera.
+@vertex
+fn vs_background(input: OverlayIn) -> VsOut {
+  let min_px = world_to_screen(input.min_px);
+  let max_px = world_to_screen(input.max_px);
+  var out: VsOut;
+  out.clip_pos = screen_to_clip(mix(min_px, max_px, input.pos * 0.5 + 0.5));
+  out.local = vec2<f32>(0.0, 0.0);
+  out.color = input.color;
+  return out;
+}
+
+// Trail lines (--trail-length) from min_px to max_px in world units, a fixed
+// width in window pixels so they stay thin at any zoom.
+const TRAIL_HALF_WIDTH_PX: f32 = 1.0;
+
+@vertex
+fn vs_trail(input: OverlayIn) -> VsOut {
+  let a = world_to_screen(input.min_px);
+  let b = world_to_screen(input.max_px);
+  let along = b - a;
+  let len = length(along);
+  var normal = vec2<f32>(0.0, 0.0);
+  if (len > 0.0) {
+    normal = vec2<f32>(-along.y, along.x) / len;
+  }
+  let t = input.pos.x * 0.5 + 0.5;
+  var out: VsOut;
+  out.clip_pos = screen_to_clip(mix(a, b, t) + normal * input.pos.y * TRAIL_HALF_WIDTH_PX);
+  out.local = vec2<f32>(0.0, 0.0);
+  out.color = input.color;
+  return out;
+}
+
+// The window-filling quad of the density heatmap; `local` is its [0, 1] UV
+// with v up, matching the density rows.
+@vertex
+fn vs_density(input: ScreenIn) -> VsOut {
+  var out: VsOut;
+  out.clip_pos = vec4<f32>(input.pos, 0.0, 1.0);
+  out.local = input.pos * 0.5 + 0.5;
+  out.color = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+  return out;
+}
+
+// Screen-space caption image, sampled by fs_sprite. Its quad-local y is flipped
+// so the image's top row lands at min_px (y down).
+@vertex
+fn vs_caption(input: OverlayIn) -> VsOut {
+  var out: VsOut;
+  out.clip_pos = screen_to_clip(mix(input.min_px, input.max_px, input.pos * 0.5 + 0.5));
+  out.local = vec2<f32>(input.pos.x, -input.pos.y);
+  out.color = input.color;
+  return out;
+}
+
 @fragment
 fn fs_main(input: VsOut) -> @location(0) vec4<f32> {
   if (dot(input.local, input.local) > 1.0) {
@@ -49,3 +167,34 @@ fn fs_main(input: VsOut) -> @location(0) vec4<f32> {
   }
   return input.color;
 }
+
+// Gaussian splat for --splat: sigma is a third of the radius, so the falloff
+// reaches ~1% at the quad's edge, exp(-4.5 * r^2) with r in quad-local units.
+@fragment
+fn fs_splat(input: VsOut) -> @location(0) vec4<f32> {
+  let r2 = dot(input.local, input.local);
+  if (r2 > 1.0) {
+    discard;
+  }
+  return vec4<f32>(input.color.rgb, input.color.a * exp(-4.5 * r2));
+}
+
+// Empty cells show the background; others take their colormap entry.
+@fragment
+fn fs_density(input: VsOut) -> @location(0) vec4<f32> {
+  let dims = textureDimensions(density_tex);
+  let cell = min(vec2<u32>(input.local * vec2<f32>(dims)), dims - vec2<u32>(1u, 1u));
+  let t = textureLoad(density_tex, cell, 0).r;
+  if (t <= 0.0) {
+    discard;
+  }
+  let entry = u32(round(clamp(t, 0.0, 1.0) * 255.0));
+  return textureLoad(density_colormap, vec2<u32>(entry, 0u), 0);
+}
+
+@fragment
+fn fs_sprite(input: VsOut) -> @location(0) vec4<f32> {
+  // Quad-local [-1, 1] (y up) to texture UV [0, 1] (v down).
+  let uv = vec2<f32>(input.local.x * 0.5 + 0.5, 0.5 - input.local.y * 0.5);
+  return textureSample(sprite_tex, sprite_sampler, uv) * input.color;
+}
@@ -0,0 +1,183 @@
+use anyhow::Result;
+
+use crate::evo::{EvoFile, EvoHeader, Frame};
+
+/// Where the viewer gets frames from: a recorded `.evo` file, or (with the
+/// `live` feature) a simulation running in this process.
+pub trait FrameSource: Send + Sync {
+    fn header(&self) -> &EvoHeader;
+
+    /// Frames available so far; a live source grows as the simulation runs.
+    fn total_frames(&self) -> usize;
+
+    /// Nominal simulated seconds per frame.
+    fn frame_duration(&self) -> f64;
+
+    fn sim_time_of_frame(&self, frame_index: usize) -> f64;
+
+    /// The frame to show at simulated time `t`.
+    fn frame_at_sim_time(&self, t: f64) -> usize;
+
+    /// Fractional frame position of simulated time `t`.
+    fn frame_position_at_sim_time(&self, t: f64) -> f64;
+
+    fn read_frame_f32(&self, frame_index: usize, out: &mut Vec<f32>) -> Result<()>;
+
+    /// Decodes the state at fractional frame `position`. Sources that cannot blend
+    /// show the earlier frame.
+    fn read_frame_interpolated(
+        &self,
+        position: f64,
+        out: &mut Vec<f32>,
+        _next: &mut Vec<f32>,
+    ) -> Result<()> {
+        self.read_frame_f32(position.max(0.0) as usize, out)
+    }
+
+    /// The first frame after `from` whose positions moved by more than `eps`, if
+    /// the source can look ahead.
+    fn next_changed_frame(&self, _from: usize, _eps: f32) -> Option<usize> {
+        None
+    }
+
+    /// The generation `frame_index` belongs to, for sources of evolving runs.
+    fn generation(&self, _frame_index: usize) -> Option<u64> {
+        None
+    }
+
+    fn state_index(&self, label: &str) -> Option<usize> {
+        self.header()
+            .config
+            .state_labels
+            .iter()
+            .position(|l| l == label)
+    }
+
+    fn empty_frame(&self) -> Frame {
+        Frame::for_header(self.header())
+    }
+
+    /// Takes in frames produced since the last call. Recorded files are complete
+    /// up front, so this does nothing for them.
+    fn poll(&self) {}
+}
+
+impl FrameSource for EvoFile {
+    fn header(&self) -> &EvoHeader {
+        &self.header
+    }
+
+    fn total_frames(&self) -> usize {
+        self.total_frames()
+    }
+
+    fn frame_duration(&self) -> f64 {
+        self.frame_duration()
+    }
+
+    fn sim_time_of_frame(&self, frame_index: usize) -> f64 {
+        self.sim_time_of_frame(frame_index)
+    }
+
+    fn frame_at_sim_time(&self, t: f64) -> usize {
+        self.frame_at_sim_time(t)
+    }
+
+    fn frame_position_at_sim_time(&self, t: f64) -> f64 {
+        self.frame_position_at_sim_time(t)
+    }
+
+    fn read_frame_f32(&self, frame_index: usize, out: &mut Vec<f32>) -> Result<()> {
+        self.read_frame_f32(frame_index, out)
+    }
+
+    fn read_frame_interpolated(
+        &self,
+        position: f64,
+        out: &mut Vec<f32>,
+        next: &mut Vec<f32>,
+    ) -> Result<()> {
+        self.read_frame_interpolated(position, out, next)
+    }
+
+    fn next_changed_frame(&self, from: usize, eps: f32) -> Option<usize> {
+        self.next_changed_frame(from, eps)
+    }
+
+    fn generation(&self, frame_index: usize) -> Option<u64> {
+        self.generation(frame_index)
+    }
+
+    fn state_index(&self, label: &str) -> Option<usize> {
+        self.state_index(label)
+    }
+}
+
+/// Frames held in memory for tests, each value `frame * 100 + agent * 10 + dim`.
+#[cfg(test)]
+pub(crate) struct Frames {
+    pub header: EvoHeader,
+    pub total_frames: usize,
+    /// Each frame's generation; none when empty.
+    pub generations: Vec<u64>,
+}
+
+#[cfg(test)]
+impl Frames {
+    /// `total_frames` frames of `n_agents` agents with the given state labels.
+    pub fn new(n_agents: usize, labels: &[&str], total_frames: usize) -> Self {
+        let config = serde_json::json!({
+            "n_agents": n_agents,
+            "state_dims": labels.len(),
+            "state_labels": labels,
+        });
+        let header = serde_json::json!({"version": 2, "timestamp": "t", "config": config});
+        Self {
+            header: serde_json::from_value(header).unwrap(),
+            total_frames,
+            generations: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl FrameSource for Frames {
+    fn header(&self) -> &EvoHeader {
+        &self.header
+    }
+
+    fn total_frames(&self) -> usize {
+        self.total_frames
+    }
+
+    fn frame_duration(&self) -> f64 {
+        1.0
+    }
+
+    fn sim_time_of_frame(&self, frame_index: usize) -> f64 {
+        frame_index as f64
+    }
+
+    fn frame_at_sim_time(&self, t: f64) -> usize {
+        t as usize
+    }
+
+    fn frame_position_at_sim_time(&self, t: f64) -> f64 {
+        t
+    }
+
+    fn read_frame_f32(&self, frame_index: usize, out: &mut Vec<f32>) -> Result<()> {
+        let config = &self.header.config;
+        out.clear();
+        for agent in 0..config.n_agents {
+            for dim in 0..config.state_dims {
+                out.push((frame_index * 100 + agent * 10 + dim) as f32);
+            }
+        }
+        Ok(())
+    }
+
+    fn generation(&self, frame_index: usize) -> Option<u64> {
+        self.generations.get(frame_index).copied()
+    }
+}
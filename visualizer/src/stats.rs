@@ -0,0 +1,178 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::export::IndexRange;
+use crate::source::FrameSource;
+
+/// Summary of one state variable over the values folded into it: range, mean and
+/// (population) standard deviation of the finite values, and how many were NaN
+/// or infinite. Statistics of no finite values are NaN, written as `null`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VariableStats {
+    pub label: String,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f64,
+    pub std: f64,
+    pub nan: u64,
+    pub inf: u64,
+    #[serde(skip)]
+    count: u64,
+    /// Sum of squared differences from the mean, updated as in Welford's method.
+    #[serde(skip)]
+    m2: f64,
+}
+
+impl VariableStats {
+    fn new(label: String) -> Self {
+        Self {
+            label,
+            min: f32::NAN,
+            max: f32::NAN,
+            mean: f64::NAN,
+            std: f64::NAN,
+            nan: 0,
+            inf: 0,
+            count: 0,
+            m2: 0.0,
+        }
+    }
+
+    fn push(&mut self, value: f32) {
+        if value.is_nan() {
+            self.nan += 1;
+            return;
+        }
+        if value.is_infinite() {
+            self.inf += 1;
+            return;
+        }
+        if self.count == 0 {
+            (self.min, self.max, self.mean) = (value, value, 0.0);
+        }
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.count += 1;
+        let delta = value as f64 - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value as f64 - self.mean);
+        self.std = (self.m2 / self.count as f64).sqrt();
+    }
+}
+
+/// Statistics of every state variable over one frame or a whole run.
+#[derive(Debug, Serialize)]
+struct Stats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frame: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frames: Option<usize>,
+    variables: Vec<VariableStats>,
+}
+
+/// Folds the `agents` of `frames` into per-variable statistics and writes them to
+/// `out` as JSON: one object for the whole range, or with `per_frame`, one line
+/// per frame. Only one frame is held at a time.
+pub fn write_stats(
+    source: &dyn FrameSource,
+    frames: IndexRange,
+    agents: IndexRange,
+    per_frame: bool,
+    mut out: impl Write,
+) -> Result<()> {
+    let config = &source.header().config;
+    let state_dims = config.state_dims;
+    let fresh = || -> Vec<VariableStats> {
+        (0..state_dims)
+            .map(|dim| {
+                let label = config.state_labels.get(dim).cloned();
+                VariableStats::new(label.unwrap_or_else(|| format!("dim{dim}")))
+            })
+            .collect()
+    };
+
+    let frames = frames.within(source.total_frames());
+    let agents = agents.within(config.n_agents);
+    let n_frames = frames.len();
+    let mut variables = fresh();
+    let mut values = Vec::new();
+    for frame_index in frames {
+        source.read_frame_f32(frame_index, &mut values)?;
+        let selected = &values[agents.start * state_dims..agents.end * state_dims];
+        for agent in selected.chunks_exact(state_dims) {
+            for (stats, &value) in variables.iter_mut().zip(agent) {
+                stats.push(value);
+            }
+        }
+        if per_frame {
+            let stats = Stats {
+                frame: Some(frame_index),
+                frames: None,
+                variables: std::mem::replace(&mut variables, fresh()),
+            };
+            serde_json::to_writer(&mut out, &stats)?;
+            writeln!(out)?;
+        }
+    }
+    if !per_frame {
+        let stats = Stats {
+            frame: None,
+            frames: Some(n_frames),
+            variables,
+        };
+        serde_json::to_writer_pretty(&mut out, &stats)?;
+        writeln!(out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::Frames;
+
+    #[test]
+    fn folds_finite_values_and_counts_the_rest() {
+        let mut stats = VariableStats::new("energy".to_string());
+        let (nan, inf) = (f32::NAN, f32::INFINITY);
+        for value in [2.0, 4.0, nan, 4.0, inf, 5.0, 7.0, 4.0, 5.0, 9.0] {
+            stats.push(value);
+        }
+        assert_eq!((stats.min, stats.max), (2.0, 9.0));
+        assert_eq!((stats.nan, stats.inf), (1, 1));
+        assert!((stats.mean - 5.0).abs() < 1e-12);
+        assert!((stats.std - 2.0).abs() < 1e-12);
+
+        let empty = serde_json::to_string(&VariableStats::new("x".to_string())).unwrap();
+        assert_eq!(
+            empty,
+            r#"{"label":"x","min":null,"max":null,"mean":null,"std":null,"nan":0,"inf":0}"#
+        );
+    }
+
+    #[test]
+    fn per_frame_stats_cover_only_the_selected_agents() -> Result<()> {
+        // Agents 1 and 2 of frames 2 and 3: `frame * 100 + agent * 10 + dim`.
+        let source = Frames::new(4, &["pos_x", "energy"], 5);
+        let mut out = Vec::new();
+        write_stats(&source, "2..4".parse()?, "1..3".parse()?, true, &mut out)?;
+        let lines: Vec<serde_json::Value> = std::str::from_utf8(&out)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(lines.len(), 2);
+        for (line, frame) in lines.iter().zip([2.0, 3.0]) {
+            assert_eq!(line["frame"], frame as u64);
+            let energy = &line["variables"][1];
+            assert_eq!(energy["label"], "energy");
+            assert_eq!(energy["min"], frame * 100.0 + 11.0);
+            assert_eq!(energy["max"], frame * 100.0 + 21.0);
+            assert_eq!(energy["mean"], frame * 100.0 + 16.0);
+            assert_eq!(energy["std"], 5.0);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use crate::renderer::OverlayRect;
+use crate::source::FrameSource;
+
+/// Height of the timeline bar along the bottom of the window, in pixels.
+const BAR_HEIGHT_PX: f32 = 6.0;
+/// Height of the strip above the window's bottom edge that responds to clicks;
+/// taller than the bar so it is easy to hit.
+const HIT_HEIGHT_PX: f64 = 16.0;
+
+const TRACK_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.15];
+const PROGRESS_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.6];
+const TICK_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 0.9];
+const GENERATION_TICK_COLOR: [f32; 4] = [0.4, 0.8, 1.0, 0.9];
+
+/// Whether window position `y` (pixels, y down) is on the timeline bar.
+pub fn contains(y: f64, window_height: u32) -> bool {
+    (window_height as f64 - HIT_HEIGHT_PX..=window_height as f64).contains(&y)
+}
+
+/// The frame at window position `x` on the bar: `(x / width) * total_frames`,
+/// clamped to the recording.
+pub fn frame_at(x: f64, window_width: u32, total_frames: usize) -> usize {
+    if total_frames == 0 || window_width == 0 {
+        return 0;
+    }
+    let t = (x / window_width as f64).clamp(0.0, 1.0);
+    ((t * total_frames as f64) as usize).min(total_frames - 1)
+}
+
+/// The frames of `source` that start a new generation, for sources of evolving
+/// runs; empty for others.
+pub fn generation_starts(source: &dyn FrameSource) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut previous = source.generation(0);
+    for frame in 1..source.total_frames() {
+        let generation = source.generation(frame);
+        if generation.is_some() && generation != previous {
+            starts.push(frame);
+        }
+        previous = generation;
+    }
+    starts
+}
+
+/// Rectangles drawing the bar: the track, progress up to and including `frame`,
+/// a tick at each frame in `ticks` (e.g. bookmarks) and a differently colored
+/// one at each of `generations`.
+pub fn rects(
+    window_width: u32,
+    window_height: u32,
+    frame: usize,
+    total_frames: usize,
+    ticks: &[usize],
+    generations: &[usize],
+) -> Vec<OverlayRect> {
+    let (width, height) = (window_width as f32, window_height as f32);
+    let top = height - BAR_HEIGHT_PX;
+    let x_of = |frame: usize| width * frame as f32 / total_frames.max(1) as f32;
+
+    let mut rects = vec![
+        OverlayRect {
+            min_px: [0.0, top],
+            max_px: [width, height],
+            color: TRACK_COLOR,
+        },
+        OverlayRect {
+            min_px: [0.0, top],
+            max_px: [x_of((frame + 1).min(total_frames)), height],
+            color: PROGRESS_COLOR,
+        },
+    ];
+    let tick = |frame: usize, color: [f32; 4]| {
+        let x = x_of(frame);
+        OverlayRect {
+            min_px: [x, top - BAR_HEIGHT_PX],
+            max_px: [x + 1.0, height],
+            color,
+        }
+    };
+    rects.extend(ticks.iter().map(|&frame| tick(frame, TICK_COLOR)));
+    rects.extend(
+        generations
+            .iter()
+            .map(|&frame| tick(frame, GENERATION_TICK_COLOR)),
+    );
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_bar_positions_to_frames() {
+        assert_eq!(frame_at(0.0, 800, 100), 0);
+        assert_eq!(frame_at(400.0, 800, 100), 50);
+        assert_eq!(frame_at(799.0, 800, 100), 99);
+        // Drags past either end stay on the recording.
+        assert_eq!(frame_at(-20.0, 800, 100), 0);
+        assert_eq!(frame_at(900.0, 800, 100), 99);
+        assert_eq!(frame_at(400.0, 800, 0), 0);
+
+        assert!(contains(595.0, 600));
+        assert!(!contains(300.0, 600));
+    }
+
+    #[test]
+    fn ticks_the_first_frame_of_each_generation() {
+        let mut source = crate::source::Frames::new(1, &["x"], 6);
+        assert!(generation_starts(&source).is_empty());
+        source.generations = vec![0, 0, 1, 1, 1, 2];
+        assert_eq!(generation_starts(&source), [2, 5]);
+    }
+
+    #[test]
+    fn progress_fills_through_the_current_frame() {
+        let rects = rects(800, 600, 49, 100, &[25], &[50]);
+        assert_eq!(rects.len(), 4);
+        assert_eq!(rects[0].max_px, [800.0, 600.0]);
+        assert_eq!(rects[1].max_px[0], 400.0);
+        assert_eq!(rects[2].min_px[0], 200.0);
+        assert_eq!(rects[3].min_px[0], 400.0);
+        assert_eq!(rects[3].color, GENERATION_TICK_COLOR);
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::VecDeque;
+
+use crate::renderer::{Instance, OverlayRect, Renderer};
+
+/// Share of an agent's opacity its trail has right behind it.
+const TRAIL_OPACITY: f32 = 0.6;
+
+/// Where each drawn agent was over the last `--trail-length` recorded frames,
+/// drawn as lines behind it that fade with age.
+pub struct Trails {
+    length: usize,
+    /// Recorded frames and the agent positions at them, oldest first, ending at
+    /// `last_frame` and spanning at most `length` frames. Playback faster than
+    /// the display skips frames, so they need not be consecutive.
+    history: VecDeque<(usize, Vec<[f32; 2]>)>,
+    last_frame: Option<usize>,
+}
+
+impl Trails {
+    pub fn new(length: usize) -> Self {
+        Self {
+            length,
+            history: VecDeque::with_capacity(length),
+            last_frame: None,
+        }
+    }
+
+    /// Forgets all positions, e.g. after the positions change meaning.
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.last_frame = None;
+    }
+
+    /// Records `agents` at recorded frame `frame` and shows their trails from the
+    /// next render on. `agents` holds one instance per agent, in the same order
+    /// every frame.
+    pub fn update(
+        &mut self,
+        frame: usize,
+        agents: &[Instance],
+        torus_ranges: [Option<[f32; 2]>; 2],
+        renderer: &mut Renderer,
+    ) {
+        self.record(frame, agents);
+        renderer.set_trails(&self.lines(agents, torus_ranges));
+    }
+
+    /// Frames skipped on the way forward are bridged by a straight line. A step
+    /// back, a jump past the whole trail or a different set of agents starts the
+    /// trails over instead, so they never bridge a seek.
+    fn record(&mut self, frame: usize, agents: &[Instance]) {
+        if self.last_frame == Some(frame) {
+            return;
+        }
+        let follows = self
+            .last_frame
+            .is_some_and(|last| last < frame && frame - last < self.length);
+        if !follows
+            || self
+                .history
+                .back()
+                .is_some_and(|(_, h)| h.len() != agents.len())
+        {
+            self.history.clear();
+        }
+        while self
+            .history
+            .front()
+            .is_some_and(|&(oldest, _)| oldest + self.length <= frame)
+        {
+            self.history.pop_front();
+        }
+        self.history
+            .push_back((frame, agents.iter().map(|agent| agent.center_px).collect()));
+        self.last_frame = Some(frame);
+    }
+
+    /// Lines through each agent's recorded positions up to where it is drawn now,
+    /// in its color and dimmer the older they are. A step of more than half the
+    /// period along a toroidal axis is the agent wrapping around, and is skipped
+    /// rather than drawn across the world.
+    fn lines(&self, agents: &[Instance], torus_ranges: [Option<[f32; 2]>; 2]) -> Vec<OverlayRect> {
+        if self.history.iter().any(|(_, h)| h.len() != agents.len()) {
+            return Vec::new();
+        }
+        let Some(last_frame) = self.last_frame else {
+            return Vec::new();
+        };
+        // Frames behind the newest each point is, with the agent as drawn now at 0.
+        let ages: Vec<usize> = self
+            .history
+            .iter()
+            .map(|&(frame, _)| last_frame - frame + 1)
+            .chain(std::iter::once(0))
+            .collect();
+        let wraps = |a: [f32; 2], b: [f32; 2]| {
+            (0..2).any(|axis| {
+                torus_ranges[axis]
+                    .is_some_and(|[min, max]| (b[axis] - a[axis]).abs() > (max - min) * 0.5)
+            })
+        };
+        let mut lines = Vec::new();
+        for (i, agent) in agents.iter().enumerate() {
+            let points: Vec<[f32; 2]> = self
+                .history
+                .iter()
+                .map(|(_, positions)| positions[i])
+                .chain(std::iter::once(agent.center_px))
+                .collect();
+            for (k, pair) in points.windows(2).enumerate() {
+                let (from, to) = (pair[0], pair[1]);
+                if from == to || wraps(from, to) {
+                    continue;
+                }
+                let age = ages[k + 1] as f32;
+                let mut color = agent.color;
+                color[3] *= TRAIL_OPACITY * (1.0 - age / self.length as f32);
+                lines.push(OverlayRect {
+                    min_px: from,
+                    max_px: to,
+                    color,
+                });
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(x: f32) -> Instance {
+        Instance {
+            center_px: [x, 0.0],
+            radius_px: 1.0,
+            _pad0: 0.0,
+            color: [1.0; 4],
+        }
+    }
+
+    #[test]
+    fn trails_fade_and_break_at_jumps_and_wraps() {
+        let mut trails = Trails::new(2);
+        for (frame, x) in [(0, 0.0), (1, 1.0), (2, 2.0)] {
+            trails.record(frame, &[agent(x)]);
+        }
+        // Only the last two frames are kept; the newest line is the brightest.
+        let lines = trails.lines(&[agent(2.5)], [None; 2]);
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].min_px, lines[0].max_px), ([1.0, 0.0], [2.0, 0.0]));
+        assert_eq!((lines[1].min_px, lines[1].max_px), ([2.0, 0.0], [2.5, 0.0]));
+        assert!(lines[0].color[3] < lines[1].color[3]);
+
+        // On a [0, 10) torus, going from 2 to 9.5 is a wrap, not a long step.
+        assert_eq!(
+            trails.lines(&[agent(2.5)], [Some([0.0, 10.0]), None]).len(),
+            2
+        );
+        trails.record(3, &[agent(9.5)]);
+        let lines = trails.lines(&[agent(9.5)], [Some([0.0, 10.0]), None]);
+        assert_eq!(lines.len(), 0);
+
+        // A seek clears the history instead of drawing a line across it.
+        trails.record(40, &[agent(5.0)]);
+        assert!(trails.lines(&[agent(5.0)], [None; 2]).is_empty());
+    }
+
+    #[test]
+    fn skipped_frames_keep_the_trail_but_seeks_back_do_not() {
+        // Every other frame, as playback at twice the display rate records them:
+        // the older sample is one frame back, and fainter.
+        let mut trails = Trails::new(4);
+        for (frame, x) in [(0, 0.0), (2, 1.0), (4, 2.0)] {
+            trails.record(frame, &[agent(x)]);
+        }
+        let lines = trails.lines(&[agent(2.5)], [None; 2]);
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].min_px, lines[0].max_px), ([1.0, 0.0], [2.0, 0.0]));
+        assert!(lines[0].color[3] < lines[1].color[3]);
+
+        trails.record(3, &[agent(3.0)]);
+        assert!(trails.lines(&[agent(3.0)], [None; 2]).is_empty());
+    }
+}